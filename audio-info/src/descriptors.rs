@@ -0,0 +1,292 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+// Frames analysed for the spectral centroid
+const CENTROID_WINDOW: usize = 2048;
+// Windows for finding silence, in seconds
+const SILENCE_WINDOW_SEC: f64 = 0.05;
+// Windows quieter than this count as silence
+const SILENCE_DB: f64 = -60.0;
+// Floor for levels, so silence is a very quiet number rather than -inf
+const MIN_DB: f64 = -120.0;
+
+// Broad measurements of how a piece of audio sounds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Descriptors {
+    pub duration_sec: f64,
+    // Loudness over the whole file, in dBFS
+    pub rms_db: f64,
+    pub peak_db: f64,
+    // Peak to loudness ratio in dB. High for spiky, transient audio and low for dense, compressed audio
+    pub crest_db: f64,
+    // Brightness, the average frequency weighted by energy
+    pub centroid_hz: f64,
+    // Share of the audio, from 0 to 1, that is silent
+    pub silence_ratio: f64,
+}
+
+pub(crate) fn to_db(value: f64) -> f64 {
+    if value > 0.0 {
+        (20.0 * value.log10()).max(MIN_DB)
+    } else {
+        MIN_DB
+    }
+}
+
+pub(crate) fn rms(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+}
+
+pub(crate) fn hann(size: usize) -> Vec<f64> {
+    (0..size)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / size as f64).cos())
+        .collect()
+}
+
+fn spectral_centroid(mono: &[f64], sample_rate: usize) -> f64 {
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(CENTROID_WINDOW);
+    let hann = hann(CENTROID_WINDOW);
+    let bin_hz = sample_rate as f64 / CENTROID_WINDOW as f64;
+    let mut buffer = vec![Complex::new(0.0, 0.0); CENTROID_WINDOW];
+    let (mut weighted, mut total) = (0.0, 0.0);
+    for frame in mono.chunks_exact(CENTROID_WINDOW) {
+        for (b, (s, w)) in buffer.iter_mut().zip(frame.iter().zip(hann.iter())) {
+            *b = Complex::new(s * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (bin, b) in buffer[..CENTROID_WINDOW / 2].iter().enumerate() {
+            let magnitude = b.norm();
+            weighted += bin as f64 * bin_hz * magnitude;
+            total += magnitude;
+        }
+    }
+    if total > 0.0 {
+        weighted / total
+    } else {
+        0.0
+    }
+}
+
+pub fn describe(samples: &[f64], channels: usize, sample_rate: usize) -> Descriptors {
+    let channels = channels.max(1);
+    let mono: Vec<f64> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f64>() / channels as f64)
+        .collect();
+    let rms_db = to_db(rms(samples));
+    let peak_db = to_db(samples.iter().fold(0_f64, |acc, s| acc.max(s.abs())));
+
+    let silence_window = ((sample_rate as f64 * SILENCE_WINDOW_SEC) as usize).max(1);
+    let windows: Vec<f64> = mono.chunks(silence_window).map(|w| to_db(rms(w))).collect();
+    let silent = windows.iter().filter(|db| **db < SILENCE_DB).count();
+
+    Descriptors {
+        duration_sec: mono.len() as f64 / sample_rate.max(1) as f64,
+        rms_db,
+        peak_db,
+        crest_db: peak_db - rms_db,
+        centroid_hz: spectral_centroid(&mono, sample_rate),
+        silence_ratio: if windows.is_empty() { 1.0 } else { silent as f64 / windows.len() as f64 },
+    }
+}
+
+// Either end can be left open, e.g. only a max duration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DescriptorRange {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+// The descriptors an output has to match. Descriptors without a range aren't checked
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DescriptorTargets {
+    pub duration_sec: Option<DescriptorRange>,
+    pub rms_db: Option<DescriptorRange>,
+    pub peak_db: Option<DescriptorRange>,
+    pub crest_db: Option<DescriptorRange>,
+    pub centroid_hz: Option<DescriptorRange>,
+    pub silence_ratio: Option<DescriptorRange>,
+}
+
+impl DescriptorTargets {
+    fn ranges(&self) -> [(&'static str, Option<DescriptorRange>); 6] {
+        [
+            ("duration_sec", self.duration_sec),
+            ("rms_db", self.rms_db),
+            ("peak_db", self.peak_db),
+            ("crest_db", self.crest_db),
+            ("centroid_hz", self.centroid_hz),
+            ("silence_ratio", self.silence_ratio),
+        ]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges().iter().all(|(_, range)| range.is_none())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, range) in self.ranges() {
+            if let Some(DescriptorRange { min: Some(min), max: Some(max) }) = range {
+                if min > max {
+                    return Err(format!("{} target should have a min below its max", name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Why the descriptors miss the targets, empty if they pass
+    pub fn failures(&self, descriptors: &Descriptors) -> Vec<String> {
+        let values = [
+            descriptors.duration_sec,
+            descriptors.rms_db,
+            descriptors.peak_db,
+            descriptors.crest_db,
+            descriptors.centroid_hz,
+            descriptors.silence_ratio,
+        ];
+        self.ranges()
+            .iter()
+            .zip(values.iter())
+            .filter_map(|((name, range), value)| {
+                let range = (*range)?;
+                match (range.min, range.max) {
+                    (Some(min), _) if *value < min => Some(format!("{} {:.2} below {:.2}", name, value, min)),
+                    (_, Some(max)) if *value > max => Some(format!("{} {:.2} above {:.2}", name, value, max)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+// Parses targets, e.g. { "duration_sec": { "max": 4 }, "centroid_hz": { "min": 2000 }, "silence_ratio": { "max": 0.5 } }
+pub fn parse_descriptor_targets(json: &str) -> Result<DescriptorTargets, String> {
+    let targets: DescriptorTargets = serde_json::from_str(json).map_err(|e| format!("Invalid descriptor targets: {}", e))?;
+    targets.validate()?;
+    Ok(targets)
+}
+
+pub fn load_descriptor_targets(path: &str) -> Result<DescriptorTargets, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+    parse_descriptor_targets(&json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn sine(hz: f64, gain: f64, seconds: f64) -> Vec<f64> {
+        let length = (seconds * SAMPLE_RATE as f64) as usize;
+        (0..length)
+            .map(|i| (2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64).sin() * gain)
+            .collect()
+    }
+
+    fn close(value: f64, expected: f64, tolerance: f64) -> bool {
+        (value - expected).abs() <= tolerance
+    }
+
+    #[test]
+    fn describes_a_sine() {
+        let d = describe(&sine(1000.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        assert!(close(d.duration_sec, 1.0, 1e-9), "{:?}", d);
+        // A sine's rms is its peak over root 2, 3dB down
+        assert!(close(d.peak_db, to_db(0.5), 0.01), "{:?}", d);
+        assert!(close(d.rms_db, to_db(0.5 / 2_f64.sqrt()), 0.01), "{:?}", d);
+        assert!(close(d.crest_db, 3.01, 0.02), "{:?}", d);
+        assert!(close(d.centroid_hz, 1000.0, 50.0), "{:?}", d);
+        assert_eq!(d.silence_ratio, 0.0);
+    }
+
+    #[test]
+    fn brighter_audio_has_a_higher_centroid() {
+        let low = describe(&sine(200.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        let high = describe(&sine(5000.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        assert!(close(high.centroid_hz, 5000.0, 100.0), "{:?}", high);
+        assert!(low.centroid_hz < high.centroid_hz);
+    }
+
+    #[test]
+    fn describes_silence() {
+        let d = describe(&vec![0.0; SAMPLE_RATE], 1, SAMPLE_RATE);
+        assert_eq!(d.rms_db, MIN_DB);
+        assert_eq!(d.peak_db, MIN_DB);
+        assert_eq!(d.centroid_hz, 0.0);
+        assert_eq!(d.silence_ratio, 1.0);
+    }
+
+    #[test]
+    fn silence_ratio_is_the_share_of_silent_windows() {
+        let mut samples = sine(1000.0, 0.5, 1.0);
+        samples.extend(vec![0.0; SAMPLE_RATE]);
+        let d = describe(&samples, 1, SAMPLE_RATE);
+        assert!(close(d.duration_sec, 2.0, 1e-9));
+        assert!(close(d.silence_ratio, 0.5, 0.03), "{:?}", d);
+    }
+
+    #[test]
+    fn channels_are_frames_not_time() {
+        let mono = sine(1000.0, 0.5, 1.0);
+        let stereo: Vec<f64> = mono.iter().flat_map(|s| [*s, *s]).collect();
+        let d = describe(&stereo, 2, SAMPLE_RATE);
+        assert!(close(d.duration_sec, 1.0, 1e-9));
+        assert!(close(d.centroid_hz, 1000.0, 50.0), "{:?}", d);
+    }
+
+    fn range(min: Option<f64>, max: Option<f64>) -> Option<DescriptorRange> {
+        Some(DescriptorRange { min, max })
+    }
+
+    #[test]
+    fn failures_report_values_outside_their_range() {
+        let d = describe(&sine(1000.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        let targets = DescriptorTargets {
+            duration_sec: range(None, Some(0.5)),
+            centroid_hz: range(Some(2000.0), None),
+            silence_ratio: range(Some(0.0), Some(0.5)),
+            ..DescriptorTargets::default()
+        };
+        assert_eq!(
+            targets.failures(&d),
+            vec![
+                String::from("duration_sec 1.00 above 0.50"),
+                format!("centroid_hz {:.2} below 2000.00", d.centroid_hz)
+            ]
+        );
+    }
+
+    #[test]
+    fn values_within_their_range_pass() {
+        let d = describe(&sine(1000.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        let targets = DescriptorTargets {
+            duration_sec: range(Some(0.5), Some(2.0)),
+            rms_db: range(Some(-12.0), None),
+            peak_db: range(None, Some(0.0)),
+            ..DescriptorTargets::default()
+        };
+        assert!(targets.failures(&d).is_empty());
+        assert!(DescriptorTargets::default().failures(&d).is_empty());
+        assert!(DescriptorTargets::default().is_empty());
+        assert!(!targets.is_empty());
+    }
+
+    #[test]
+    fn parses_targets() {
+        let targets = parse_descriptor_targets(r#"{ "duration_sec": { "max": 4 }, "centroid_hz": { "min": 2000 } }"#).unwrap();
+        assert_eq!(targets.duration_sec, range(None, Some(4.0)));
+        assert_eq!(targets.centroid_hz, range(Some(2000.0), None));
+        assert_eq!(targets.rms_db, None);
+        assert!(parse_descriptor_targets(r#"{ "rms_db": { "min": -6, "max": -12 } }"#).is_err());
+        assert!(parse_descriptor_targets(r#"{ "loudness": { "max": 0 } }"#).is_err());
+    }
+}
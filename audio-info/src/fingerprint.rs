@@ -0,0 +1,172 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::descriptors::{hann, rms, to_db};
+
+// Frames analysed for the spectrum
+const FINGERPRINT_WINDOW: usize = 2048;
+// Bands the spectrum is summarised into, spaced evenly in log frequency up to nyquist
+const SPECTRUM_BANDS: usize = 24;
+const LOWEST_HZ: f64 = 40.0;
+// Segments the loudness over time is summarised into
+const ENVELOPE_SEGMENTS: usize = 16;
+// Levels are relative to the loudest band or segment, floored so quiet parts don't dominate
+const RELATIVE_FLOOR_DB: f64 = -60.0;
+// An average difference this large, in dB, counts as nothing alike
+const SPECTRUM_RANGE_DB: f64 = 24.0;
+const ENVELOPE_RANGE_DB: f64 = 30.0;
+
+// A compact summary of how audio sounds, for telling near duplicates apart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    // Level of each frequency band, in dB
+    pub spectrum: Vec<f64>,
+    // Level of each stretch of the audio from start to end, in dB
+    pub envelope: Vec<f64>,
+}
+
+fn relative(levels: Vec<f64>) -> Vec<f64> {
+    let loudest = levels.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    levels.iter().map(|l| (l - loudest).max(RELATIVE_FLOOR_DB)).collect()
+}
+
+fn band_levels(mono: &[f64], sample_rate: usize) -> Vec<f64> {
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(FINGERPRINT_WINDOW);
+    let hann = hann(FINGERPRINT_WINDOW);
+    let bin_hz = sample_rate as f64 / FINGERPRINT_WINDOW as f64;
+    let nyquist = sample_rate as f64 / 2.0;
+    let edges: Vec<f64> = (0..=SPECTRUM_BANDS)
+        .map(|b| LOWEST_HZ * (nyquist / LOWEST_HZ).powf(b as f64 / SPECTRUM_BANDS as f64))
+        .collect();
+
+    let mut energy = [0.0; SPECTRUM_BANDS];
+    let mut buffer = vec![Complex::new(0.0, 0.0); FINGERPRINT_WINDOW];
+    // Short audio still gets a frame, padded with silence
+    for frame in mono.chunks(FINGERPRINT_WINDOW) {
+        for (i, b) in buffer.iter_mut().enumerate() {
+            *b = Complex::new(frame.get(i).copied().unwrap_or(0.0) * hann[i], 0.0);
+        }
+        fft.process(&mut buffer);
+        for (bin, b) in buffer[..FINGERPRINT_WINDOW / 2].iter().enumerate() {
+            let hz = bin as f64 * bin_hz;
+            let band = edges.partition_point(|edge| *edge <= hz);
+            if band > 0 && band <= SPECTRUM_BANDS {
+                energy[band - 1] += b.norm_sqr();
+            }
+        }
+    }
+    relative(energy.iter().map(|e| to_db(e.sqrt())).collect())
+}
+
+fn envelope(mono: &[f64]) -> Vec<f64> {
+    let segment = (mono.len() / ENVELOPE_SEGMENTS).max(1);
+    let mut levels: Vec<f64> = mono.chunks(segment).take(ENVELOPE_SEGMENTS).map(|s| to_db(rms(s))).collect();
+    levels.resize(ENVELOPE_SEGMENTS, to_db(0.0));
+    relative(levels)
+}
+
+pub fn fingerprint(samples: &[f64], channels: usize, sample_rate: usize) -> Fingerprint {
+    let channels = channels.max(1);
+    let mono: Vec<f64> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().filter(|s| s.is_finite()).sum::<f64>() / channels as f64)
+        .collect();
+    Fingerprint {
+        spectrum: band_levels(&mono, sample_rate.max(1)),
+        envelope: envelope(&mono),
+    }
+}
+
+fn closeness(a: &[f64], b: &[f64], range_db: f64) -> f64 {
+    let difference = a.iter().zip(b.iter()).map(|(a, b)| (a - b).abs()).sum::<f64>() / a.len().max(1) as f64;
+    (1.0 - difference / range_db).clamp(0.0, 1.0)
+}
+
+impl Fingerprint {
+    // How alike two pieces of audio are, from 0 for nothing alike to 1 for the same
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let spectrum = closeness(&self.spectrum, &other.spectrum, SPECTRUM_RANGE_DB);
+        let envelope = closeness(&self.envelope, &other.envelope, ENVELOPE_RANGE_DB);
+        (spectrum + envelope) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn sine(hz: f64, gain: f64, seconds: f64) -> Vec<f64> {
+        let length = (seconds * SAMPLE_RATE as f64) as usize;
+        (0..length)
+            .map(|i| (2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64).sin() * gain)
+            .collect()
+    }
+
+    // White noise from a fixed seed, so the test is the same every run
+    fn noise(seconds: f64) -> Vec<f64> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let length = (seconds * SAMPLE_RATE as f64) as usize;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state as f64 / u64::MAX as f64) * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_audio_is_the_same() {
+        let a = fingerprint(&sine(440.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        let b = fingerprint(&sine(440.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        assert!(a.similarity(&b) >= 0.99, "{}", a.similarity(&b));
+    }
+
+    #[test]
+    fn level_does_not_change_the_fingerprint() {
+        let loud = fingerprint(&sine(440.0, 0.8, 1.0), 1, SAMPLE_RATE);
+        let quiet = fingerprint(&sine(440.0, 0.1, 1.0), 1, SAMPLE_RATE);
+        assert!(loud.similarity(&quiet) >= 0.99, "{}", loud.similarity(&quiet));
+    }
+
+    #[test]
+    fn stereo_is_mixed_to_mono() {
+        let mono = sine(440.0, 0.5, 1.0);
+        let stereo: Vec<f64> = mono.iter().flat_map(|s| [*s, *s]).collect();
+        assert_eq!(fingerprint(&mono, 1, SAMPLE_RATE), fingerprint(&stereo, 2, SAMPLE_RATE));
+    }
+
+    #[test]
+    fn noise_and_a_sine_are_not_alike() {
+        let tone = fingerprint(&sine(440.0, 0.5, 1.0), 1, SAMPLE_RATE);
+        let hiss = fingerprint(&noise(1.0), 1, SAMPLE_RATE);
+        assert!(closeness(&tone.spectrum, &hiss.spectrum, SPECTRUM_RANGE_DB) < 0.2);
+        // Both are steady, so only the spectrum tells them apart
+        assert!(tone.similarity(&hiss) < 0.6, "{}", tone.similarity(&hiss));
+    }
+
+    #[test]
+    fn different_shapes_over_time_are_told_apart() {
+        let steady = sine(440.0, 0.5, 1.0);
+        let mut decaying = steady.clone();
+        let length = decaying.len() as f64;
+        decaying.iter_mut().enumerate().for_each(|(i, s)| *s *= (1.0 - i as f64 / length).powi(4));
+        let steady = fingerprint(&steady, 1, SAMPLE_RATE);
+        let decaying = fingerprint(&decaying, 1, SAMPLE_RATE);
+        assert!(closeness(&steady.envelope, &decaying.envelope, ENVELOPE_RANGE_DB) < 0.2);
+        assert!(steady.similarity(&decaying) < 0.6, "{}", steady.similarity(&decaying));
+    }
+
+    #[test]
+    fn short_audio_still_gets_a_full_fingerprint() {
+        let print = fingerprint(&sine(440.0, 0.5, 0.01), 1, SAMPLE_RATE);
+        assert_eq!(print.spectrum.len(), SPECTRUM_BANDS);
+        assert_eq!(print.envelope.len(), ENVELOPE_SEGMENTS);
+        assert!(print.spectrum.iter().chain(print.envelope.iter()).all(|l| l.is_finite()));
+    }
+}
@@ -3,12 +3,27 @@ use std::{ffi::OsStr, fmt, path::Path};
 
 use sndfile::*;
 
+pub mod descriptors;
+pub mod fingerprint;
+pub mod quality;
+pub mod tempo;
+pub use descriptors::{describe, DescriptorTargets, Descriptors};
+pub use fingerprint::{fingerprint, Fingerprint};
+pub use quality::{QualityAction, QualityChecks, QualityFlag};
+pub use tempo::detect_bpm;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioInfo {
     pub path: String,
     pub name: String,
     pub duration_sec: f64,
     pub image: String,
+    // Detected tempo, if the audio is rhythmic enough to tell
+    #[serde(default)]
+    pub bpm: Option<f64>,
+    // Quality checks the audio failed but was kept anyway
+    #[serde(default)]
+    pub quality_flags: Vec<QualityFlag>,
 }
 
 impl AudioInfo {
@@ -18,6 +33,8 @@ impl AudioInfo {
             name: String::default(),
             image: String::default(),
             duration_sec: 0.0,
+            bpm: None,
+            quality_flags: vec![],
         }
     }
 
@@ -35,9 +52,11 @@ impl AudioInfo {
             .unwrap_or(&"")
             .to_string();
 
-        let image = AudioInfo::get_image(snd)?;
+        let samples_64: Vec<f64> = snd.read_all_to_vec()?;
+        let image = AudioInfo::get_image(&samples_64)?;
 
         self.duration_sec = duration_sec;
+        self.bpm = detect_bpm(&samples_64, channels, sample_rate);
         self.image = image;
         self.name = name;
         self.path = path;
@@ -45,9 +64,8 @@ impl AudioInfo {
         Ok(())
     }
 
-    fn get_image(mut snd: SndFile) -> Result<String, ()> {
+    fn get_image(samples_64: &[f64]) -> Result<String, ()> {
         let frames = 400;
-        let samples_64: Vec<f64> = snd.read_all_to_vec()?;
         let frame_size = samples_64.len() / frames;
 
         let mut frame_values: Vec<f64> = vec![0.0; frames];
@@ -60,7 +78,7 @@ impl AudioInfo {
             for j in start..end {
                 values.push(samples_64[j].abs());
             }
-            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            values.sort_by(|a, b| a.total_cmp(b));
             frame_values[i] = values[frame_size / 50 * 49];
         }
 
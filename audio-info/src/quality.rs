@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+
+// Samples at or above this are counted as clipped
+const CLIP_LEVEL: f64 = 0.999;
+
+// Problems a rendered output can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QualityFlag {
+    Silent,
+    NonFinite,
+    DcOffset,
+    Clipping,
+    TooShort,
+    TooLong,
+}
+
+// What to do with an output that fails a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualityAction {
+    Off,
+    // Keep the output but mark it
+    Flag,
+    // Throw the output away and render another
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QualityCheck {
+    pub action: QualityAction,
+    pub threshold: f64,
+}
+
+// Checks run on every render. Thresholds are RMS dBFS for silence, the mean sample value for
+// DC offset, the percentage of samples for clipping and seconds for duration. NaN/Inf has no threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QualityChecks {
+    pub silence: QualityCheck,
+    pub non_finite: QualityCheck,
+    pub dc_offset: QualityCheck,
+    pub clipping: QualityCheck,
+    pub min_duration_sec: QualityCheck,
+    pub max_duration_sec: QualityCheck,
+}
+
+// Nothing is rejected by default, so every output is kept unless rejecting is asked for
+impl Default for QualityChecks {
+    fn default() -> Self {
+        let check = |action, threshold| QualityCheck { action, threshold };
+        Self {
+            silence: check(QualityAction::Flag, -70.0),
+            non_finite: check(QualityAction::Flag, 0.0),
+            dc_offset: check(QualityAction::Flag, 0.05),
+            clipping: check(QualityAction::Flag, 1.0),
+            min_duration_sec: check(QualityAction::Off, 0.0),
+            max_duration_sec: check(QualityAction::Off, 600.0),
+        }
+    }
+}
+
+pub fn quality_flag_description(flag: QualityFlag) -> String {
+    match flag {
+        QualityFlag::Silent => String::from("silent"),
+        QualityFlag::NonFinite => String::from("NaN or infinite samples"),
+        QualityFlag::DcOffset => String::from("DC offset"),
+        QualityFlag::Clipping => String::from("clipping"),
+        QualityFlag::TooShort => String::from("too short"),
+        QualityFlag::TooLong => String::from("too long"),
+    }
+}
+
+impl QualityChecks {
+    // The checks the audio fails, with what should be done about each
+    pub fn assess(&self, samples: &[f64], channels: usize, sample_rate: usize) -> Vec<(QualityFlag, QualityAction)> {
+        let channels = channels.max(1);
+        let finite: Vec<f64> = samples.iter().copied().filter(|s| s.is_finite()).collect();
+        let len = finite.len().max(1) as f64;
+        let rms = (finite.iter().map(|s| s * s).sum::<f64>() / len).sqrt();
+        let rms_db = if rms > 0.0 { 20.0 * rms.log10() } else { f64::NEG_INFINITY };
+        // Worst channel, so a DC heavy side isn't hidden by a clean one
+        let dc = (0..channels)
+            .map(|c| {
+                let channel: Vec<f64> = samples.iter().skip(c).step_by(channels).copied().filter(|s| s.is_finite()).collect();
+                (channel.iter().sum::<f64>() / channel.len().max(1) as f64).abs()
+            })
+            .fold(0_f64, f64::max);
+        let clipped_percent = finite.iter().filter(|s| s.abs() >= CLIP_LEVEL).count() as f64 / len * 100.0;
+        let duration_sec = (samples.len() / channels) as f64 / sample_rate.max(1) as f64;
+
+        [
+            (QualityFlag::Silent, self.silence, rms_db < self.silence.threshold),
+            (QualityFlag::NonFinite, self.non_finite, finite.len() < samples.len()),
+            (QualityFlag::DcOffset, self.dc_offset, dc > self.dc_offset.threshold),
+            (QualityFlag::Clipping, self.clipping, clipped_percent > self.clipping.threshold),
+            (QualityFlag::TooShort, self.min_duration_sec, duration_sec < self.min_duration_sec.threshold),
+            (QualityFlag::TooLong, self.max_duration_sec, duration_sec > self.max_duration_sec.threshold),
+        ]
+        .into_iter()
+        .filter(|(_, check, failed)| *failed && check.action != QualityAction::Off)
+        .map(|(flag, check, _)| (flag, check.action))
+        .collect()
+    }
+}
+
+// Parses checks, e.g. { "clipping": { "action": "Reject", "threshold": 0.5 }, "dc_offset": { "action": "Off" } }.
+// Checks, and check fields, that aren't given keep their defaults
+pub fn parse_quality_checks(json: &str) -> Result<QualityChecks, String> {
+    let given: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("Invalid quality checks: {}", e))?;
+    let mut checks = serde_json::to_value(QualityChecks::default()).map_err(|e| e.to_string())?;
+    merge(&mut checks, given);
+    serde_json::from_value(checks).map_err(|e| format!("Invalid quality checks: {}", e))
+}
+
+fn merge(base: &mut serde_json::Value, given: serde_json::Value) {
+    match (base, given) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(given)) => {
+            for (key, value) in given {
+                merge(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, given) => *base = given,
+    }
+}
+
+pub fn load_quality_checks(path: &str) -> Result<QualityChecks, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+    parse_quality_checks(&json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: usize = 1000;
+
+    fn sine(amplitude: f64, frames: usize) -> Vec<f64> {
+        (0..frames)
+            .map(|n| (2.0 * PI * 50.0 * n as f64 / SAMPLE_RATE as f64).sin() * amplitude)
+            .collect()
+    }
+
+    fn flags(checks: &QualityChecks, samples: &[f64], channels: usize) -> Vec<QualityFlag> {
+        checks.assess(samples, channels, SAMPLE_RATE).into_iter().map(|(flag, _)| flag).collect()
+    }
+
+    #[test]
+    fn clean_audio_passes() {
+        assert!(QualityChecks::default().assess(&sine(0.5, 1000), 1, SAMPLE_RATE).is_empty());
+    }
+
+    #[test]
+    fn flags_silence() {
+        let checks = QualityChecks::default();
+        assert_eq!(flags(&checks, &[0.0; 1000], 1), vec![QualityFlag::Silent]);
+        // -80dB is under the -70dB default
+        assert_eq!(flags(&checks, &sine(0.0001 * 2_f64.sqrt(), 1000), 1), vec![QualityFlag::Silent]);
+        assert!(flags(&checks, &sine(0.001 * 2_f64.sqrt(), 1000), 1).is_empty());
+    }
+
+    #[test]
+    fn non_finite_samples_are_left_out_of_the_other_checks() {
+        let mut samples = sine(0.5, 1000);
+        samples[10] = f64::NAN;
+        samples[20] = f64::INFINITY;
+        assert_eq!(flags(&QualityChecks::default(), &samples, 1), vec![QualityFlag::NonFinite]);
+    }
+
+    #[test]
+    fn flags_dc_offset_on_either_channel() {
+        let clean = sine(0.5, 1000);
+        let offset: Vec<f64> = clean.iter().map(|s| s * 0.5 + 0.08).collect();
+        let stereo: Vec<f64> = clean.iter().zip(offset.iter()).flat_map(|(l, r)| [*l, *r]).collect();
+        assert_eq!(flags(&QualityChecks::default(), &stereo, 2), vec![QualityFlag::DcOffset]);
+        // Averaged together the offset would be under the threshold
+        assert!(flags(&QualityChecks::default(), &stereo, 1).is_empty());
+    }
+
+    #[test]
+    fn flags_clipping_over_the_percentage() {
+        let mut samples = sine(0.5, 1000);
+        samples[..10].fill(1.0);
+        assert!(flags(&QualityChecks::default(), &samples, 1).is_empty());
+        samples[10] = -1.0;
+        assert_eq!(flags(&QualityChecks::default(), &samples, 1), vec![QualityFlag::Clipping]);
+    }
+
+    #[test]
+    fn duration_checks_are_off_until_asked_for() {
+        let samples = sine(0.5, 2000);
+        assert!(flags(&QualityChecks::default(), &samples, 2).is_empty());
+        let checks = QualityChecks {
+            min_duration_sec: QualityCheck { action: QualityAction::Reject, threshold: 1.5 },
+            max_duration_sec: QualityCheck { action: QualityAction::Flag, threshold: 1.5 },
+            ..QualityChecks::default()
+        };
+        // 2000 samples of stereo is one second
+        let too_short = checks.assess(&samples, 2, SAMPLE_RATE);
+        assert_eq!(too_short, vec![(QualityFlag::TooShort, QualityAction::Reject)]);
+        let too_long = checks.assess(&samples, 1, SAMPLE_RATE);
+        assert_eq!(too_long, vec![(QualityFlag::TooLong, QualityAction::Flag)]);
+    }
+
+    #[test]
+    fn checks_that_are_off_are_skipped() {
+        let checks = QualityChecks {
+            silence: QualityCheck { action: QualityAction::Off, threshold: -70.0 },
+            ..QualityChecks::default()
+        };
+        assert!(checks.assess(&[0.0; 100], 1, SAMPLE_RATE).is_empty());
+    }
+
+    #[test]
+    fn parsed_checks_keep_the_defaults_they_dont_set() {
+        let json = r#"{ "clipping": { "action": "Reject", "threshold": 0.5 }, "dc_offset": { "action": "Off" } }"#;
+        let checks = parse_quality_checks(json).unwrap();
+        assert_eq!(checks.clipping, QualityCheck { action: QualityAction::Reject, threshold: 0.5 });
+        assert_eq!(checks.dc_offset, QualityCheck { action: QualityAction::Off, threshold: 0.05 });
+        assert_eq!(checks.silence, QualityChecks::default().silence);
+        assert_eq!(parse_quality_checks("{}").unwrap(), QualityChecks::default());
+
+        assert!(parse_quality_checks(r#"{ "loudness": { "action": "Flag" } }"#).is_err());
+        assert!(parse_quality_checks(r#"{ "clipping": { "action": "Delete" } }"#).is_err());
+        assert!(parse_quality_checks(r#"{ "clipping": { "limit": 3 } }"#).is_err());
+        assert!(parse_quality_checks("not json").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+// Hop size of the onset envelope, in frames
+const HOP: usize = 512;
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+// Tempo most music sits around. Lags are weighted towards it to settle half/double tempo confusion
+const PREFERRED_BPM: f64 = 120.0;
+// Anything shorter doesn't have enough beats to go on
+const MIN_DURATION_SEC: f64 = 4.0;
+// Autocorrelation peak relative to zero lag needed to trust the result
+const MIN_CONFIDENCE: f64 = 0.25;
+// How far the peak has to stand out from the other lags, in standard deviations.
+// Steady tones can correlate well everywhere without having a beat
+const MIN_PEAK_SCORE: f64 = 3.5;
+
+// Rising energy per hop, a rough measure of where notes start
+fn onset_envelope(samples: &[f64], channels: usize) -> Vec<f64> {
+    let log_energies: Vec<f64> = samples
+        .chunks(HOP * channels)
+        .map(|hop| {
+            let energy = hop.iter().map(|s| s * s).sum::<f64>() / hop.len() as f64;
+            (energy + 1e-10).ln()
+        })
+        .collect();
+    let flux: Vec<f64> = log_energies.windows(2).map(|w| (w[1] - w[0]).max(0.0)).collect();
+    let mean = flux.iter().sum::<f64>() / flux.len().max(1) as f64;
+    flux.iter().map(|f| f - mean).collect()
+}
+
+fn autocorrelation(envelope: &[f64], lag: usize) -> f64 {
+    envelope
+        .iter()
+        .zip(envelope[lag..].iter())
+        .map(|(a, b)| a * b)
+        .sum::<f64>()
+        / (envelope.len() - lag) as f64
+}
+
+// Estimate tempo from the periodicity of note onsets. None if the audio is too short
+// or not rhythmic enough to give a confident answer
+pub fn detect_bpm(samples: &[f64], channels: usize, sample_rate: usize) -> Option<f64> {
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+    if sample_rate == 0 || (frames as f64 / sample_rate as f64) < MIN_DURATION_SEC {
+        return None;
+    }
+    let envelope = onset_envelope(samples, channels);
+    let hops_per_sec = sample_rate as f64 / HOP as f64;
+    let min_lag = (hops_per_sec * 60.0 / MAX_BPM).floor().max(1.0) as usize;
+    let max_lag = ((hops_per_sec * 60.0 / MIN_BPM).ceil() as usize).min(envelope.len() / 2);
+    if min_lag + 2 >= max_lag {
+        return None;
+    }
+
+    let zero_lag = autocorrelation(&envelope, 0);
+    if zero_lag <= 0.0 {
+        return None;
+    }
+    let correlations: Vec<f64> = (0..=max_lag + 1).map(|lag| autocorrelation(&envelope, lag)).collect();
+    // A beat period rarely lands on a whole number of hops, and its peak is then split between
+    // two lags while double the period isn't. Counting the stronger neighbour gives it full credit
+    let strengths: Vec<f64> = (min_lag..=max_lag)
+        .map(|lag| correlations[lag] + correlations[lag - 1].max(correlations[lag + 1]))
+        .collect();
+    let strength = |lag: usize| strengths[lag - min_lag];
+
+    let weighted = |lag: usize| {
+        let bpm = 60.0 * hops_per_sec / lag as f64;
+        let octaves = (bpm / PREFERRED_BPM).log2();
+        strength(lag) * (-0.5 * octaves * octaves).exp()
+    };
+    let best = (min_lag..=max_lag).max_by(|a, b| weighted(*a).total_cmp(&weighted(*b)))?;
+    if strength(best) / zero_lag < MIN_CONFIDENCE {
+        return None;
+    }
+    let lag_count = (max_lag - min_lag + 1) as f64;
+    let mean = correlations[min_lag..=max_lag].iter().sum::<f64>() / lag_count;
+    let deviation = (correlations[min_lag..=max_lag]
+        .iter()
+        .map(|c| (c - mean).powi(2))
+        .sum::<f64>()
+        / lag_count)
+        .sqrt();
+    if deviation <= 0.0 || (strength(best) - mean) / deviation < MIN_PEAK_SCORE {
+        return None;
+    }
+
+    // Parabolic interpolation between neighbouring lags for a finer estimate
+    let (previous, current, next) = (correlations[best - 1], correlations[best], correlations[best + 1]);
+    let denominator = previous - 2.0 * current + next;
+    let offset = if denominator.abs() > 1e-12 {
+        (0.5 * (previous - next) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let bpm = (60.0 * hops_per_sec / (best as f64 + offset)).clamp(MIN_BPM, MAX_BPM);
+    Some((bpm * 100.0).round() / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    // Short decaying noise bursts on every beat
+    fn click_track(bpm: f64, seconds: f64, channels: usize) -> Vec<f64> {
+        let frames = (seconds * SAMPLE_RATE as f64) as usize;
+        let beat_frames = 60.0 / bpm * SAMPLE_RATE as f64;
+        let mut state = 0x2545F4914F6CDD1D_u64;
+        let mut samples = Vec::with_capacity(frames * channels);
+        for n in 0..frames {
+            let since_beat = n as f64 % beat_frames;
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let noise = (state as f64 / u64::MAX as f64) * 2.0 - 1.0;
+            let sample = noise * (-since_beat / 400.0).exp();
+            samples.extend(std::iter::repeat_n(sample, channels));
+        }
+        samples
+    }
+
+    #[test]
+    fn finds_the_tempo_of_a_click_track() {
+        for bpm in [90.0, 120.0, 150.0] {
+            let detected = detect_bpm(&click_track(bpm, 10.0, 1), 1, SAMPLE_RATE).unwrap();
+            assert!((detected - bpm).abs() < 1.0, "{} {}", bpm, detected);
+        }
+        let detected = detect_bpm(&click_track(120.0, 10.0, 2), 2, SAMPLE_RATE).unwrap();
+        assert!((detected - 120.0).abs() < 1.0, "{}", detected);
+    }
+
+    #[test]
+    fn short_audio_has_no_tempo() {
+        assert_eq!(detect_bpm(&click_track(120.0, 3.0, 1), 1, SAMPLE_RATE), None);
+        assert_eq!(detect_bpm(&click_track(120.0, 10.0, 1), 1, 0), None);
+    }
+
+    #[test]
+    fn audio_without_a_beat_has_no_tempo() {
+        assert_eq!(detect_bpm(&vec![0.0; SAMPLE_RATE * 10], 1, SAMPLE_RATE), None);
+        let tone: Vec<f64> = (0..SAMPLE_RATE * 10)
+            .map(|n| (2.0 * PI * 220.0 * n as f64 / SAMPLE_RATE as f64).sin() * 0.5)
+            .collect();
+        assert_eq!(detect_bpm(&tone, 1, SAMPLE_RATE), None);
+    }
+}
@@ -10,6 +10,8 @@ const DEFAULT_MAX_MEMORY_BYTES: usize = 1024 * 1024 * 1024; // 1GB
 #[derive(Clone)]
 struct CachedAudio {
     samples: Arc<Vec<f64>>,
+    channels: usize,
+    sample_rate: usize,
     size_bytes: usize,
     last_accessed: SystemTime,
 }
@@ -45,41 +47,50 @@ impl AudioCache {
     }
 
     pub fn get_samples(&self, path: &str) -> Result<Arc<Vec<f64>>, PermuteError> {
+        Ok(self.get_audio(path)?.samples)
+    }
+
+    // Channels and sample rate of the file, so other files can be matched to the audio being processed
+    pub fn get_format(&self, path: &str) -> Result<(usize, usize), PermuteError> {
+        let audio = self.get_audio(path)?;
+        Ok((audio.channels, audio.sample_rate))
+    }
+
+    fn get_audio(&self, path: &str) -> Result<CachedAudio, PermuteError> {
         let path = PathBuf::from(path);
         
         // Try to get from cache first
         if let Some(cached) = self.cache.write().unwrap().get_mut(&path) {
             cached.last_accessed = SystemTime::now();
-            return Ok(cached.samples.clone());
+            return Ok(cached.clone());
         }
 
         // Not in cache, need to load
         let mut snd = OpenOptions::ReadOnly(ReadOptions::Auto).from_path(&path)?;
         let samples: Vec<f64> = snd.read_all_to_vec()?;
         let size_bytes = samples.len() * std::mem::size_of::<f64>();
+        let cached = CachedAudio {
+            samples: Arc::new(samples),
+            channels: snd.get_channels(),
+            sample_rate: snd.get_samplerate(),
+            size_bytes,
+            last_accessed: SystemTime::now(),
+        };
 
         // If this file would exceed max memory, don't cache it
         if size_bytes > self.max_memory {
-            return Ok(Arc::new(samples));
+            return Ok(cached);
         }
 
         // Make space if needed
         self.ensure_space(size_bytes);
 
         // Add to cache
-        let cached = CachedAudio {
-            samples: Arc::new(samples),
-            size_bytes,
-            last_accessed: SystemTime::now(),
-        };
-
-        let samples_arc = cached.samples.clone();
-        
         let mut cache = self.cache.write().unwrap();
-        cache.insert(path, cached);
+        cache.insert(path, cached.clone());
         *self.current_memory.write().unwrap() += size_bytes;
 
-        Ok(samples_arc)
+        Ok(cached)
     }
 
     fn ensure_space(&self, needed_bytes: usize) {
@@ -0,0 +1,493 @@
+// External dependencies
+use rand::{rngs::ThreadRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+// Standard library
+use std::collections::HashMap;
+
+// Internal modules
+use crate::{
+    display_node::{
+        get_category_display_name, get_category_from_display_name, get_processor_display_name,
+        get_processor_from_display_name,
+    },
+    permute_files::pickable_pool,
+    process::{processor_category, PermuteNodeName, ProcessorCategory, ALL_PROCESSORS},
+    random_process::pick_processor,
+};
+
+// Rules for which processors can make up a chain, and in what order. Empty rules allow anything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainRules {
+    // Processors that have to appear somewhere in the chain
+    pub required: Vec<PermuteNodeName>,
+    // Pairs that can't run one straight after the other, e.g. Reverse then Reverse
+    pub forbidden_pairs: Vec<(PermuteNodeName, PermuteNodeName)>,
+    // Most times a processor can appear
+    pub max_occurrences: HashMap<PermuteNodeName, usize>,
+    // Most times processors from a category can appear, e.g. one time stretch
+    pub max_category_occurrences: HashMap<ProcessorCategory, usize>,
+    // Least times processors from a category have to appear
+    pub category_quotas: HashMap<ProcessorCategory, usize>,
+    pub first: Option<PermuteNodeName>,
+    pub last: Option<PermuteNodeName>,
+}
+
+// The rules as written in a config file, with processors and categories by display name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainRulesConfig {
+    pub required: Vec<String>,
+    pub forbidden_pairs: Vec<(String, String)>,
+    pub max_occurrences: HashMap<String, usize>,
+    pub max_category_occurrences: HashMap<String, usize>,
+    pub category_quotas: HashMap<String, usize>,
+    pub first: Option<String>,
+    pub last: Option<String>,
+}
+
+impl ChainRules {
+    fn forbids(&self, before: Option<PermuteNodeName>, after: Option<PermuteNodeName>) -> bool {
+        match (before, after) {
+            (Some(before), Some(after)) => self.forbidden_pairs.contains(&(before, after)),
+            _ => false,
+        }
+    }
+
+    // Whether another of the processor would stay within the processor and category maximums
+    fn allows_another(&self, chain: &[Option<PermuteNodeName>], name: PermuteNodeName) -> bool {
+        let category = processor_category(name);
+        let count = chain.iter().flatten().filter(|p| **p == name).count();
+        let category_count = chain.iter().flatten().filter(|p| processor_category(**p) == category).count();
+        self.max_occurrences.get(&name).is_none_or(|max| count < *max)
+            && self.max_category_occurrences.get(&category).is_none_or(|max| category_count < *max)
+    }
+
+    pub fn to_config(&self) -> ChainRulesConfig {
+        let processor = |name: &PermuteNodeName| get_processor_display_name(*name);
+        let category = |name: &ProcessorCategory| get_category_display_name(*name);
+        ChainRulesConfig {
+            required: self.required.iter().map(processor).collect(),
+            forbidden_pairs: self.forbidden_pairs.iter().map(|(b, a)| (processor(b), processor(a))).collect(),
+            max_occurrences: self.max_occurrences.iter().map(|(p, max)| (processor(p), *max)).collect(),
+            max_category_occurrences: self
+                .max_category_occurrences
+                .iter()
+                .map(|(c, max)| (category(c), *max))
+                .collect(),
+            category_quotas: self.category_quotas.iter().map(|(c, quota)| (category(c), *quota)).collect(),
+            first: self.first.as_ref().map(processor),
+            last: self.last.as_ref().map(processor),
+        }
+    }
+
+    // Rules that contradict each other, e.g. requiring a processor that is allowed 0 times
+    pub fn validate(&self) -> Result<(), String> {
+        for name in self.required.iter().chain(self.first.iter()).chain(self.last.iter()) {
+            let display_name = get_processor_display_name(*name);
+            if self.max_occurrences.get(name) == Some(&0) {
+                return Err(format!("{} is required but allowed 0 times", display_name));
+            }
+            if self.max_category_occurrences.get(&processor_category(*name)) == Some(&0) {
+                return Err(format!(
+                    "{} is required but {} is allowed 0 times",
+                    display_name,
+                    get_category_display_name(processor_category(*name))
+                ));
+            }
+        }
+        for (category, quota) in &self.category_quotas {
+            if self.max_category_occurrences.get(category).is_some_and(|max| max < quota) {
+                return Err(format!(
+                    "{} needs {} processors but is allowed fewer",
+                    get_category_display_name(*category),
+                    quota
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Parses rules with processors and categories by display name, e.g.
+// { "required": ["Reverb"], "forbidden_pairs": [["Reverse", "Reverse"]], "max_category_occurrences": { "Time/Pitch": 1 } }
+pub fn parse_chain_rules(json: &str) -> Result<ChainRules, String> {
+    let config: ChainRulesConfig = serde_json::from_str(json).map_err(|e| format!("Invalid chain rules: {}", e))?;
+    let processor = |name: &String| get_processor_from_display_name(name);
+    let category = |name: &String| get_category_from_display_name(name);
+
+    let rules = ChainRules {
+        required: config.required.iter().map(processor).collect::<Result<_, _>>()?,
+        forbidden_pairs: config
+            .forbidden_pairs
+            .iter()
+            .map(|(before, after)| Ok((processor(before)?, processor(after)?)))
+            .collect::<Result<_, String>>()?,
+        max_occurrences: config
+            .max_occurrences
+            .iter()
+            .map(|(name, max)| Ok((processor(name)?, *max)))
+            .collect::<Result<_, String>>()?,
+        max_category_occurrences: config
+            .max_category_occurrences
+            .iter()
+            .map(|(name, max)| Ok((category(name)?, *max)))
+            .collect::<Result<_, String>>()?,
+        category_quotas: config
+            .category_quotas
+            .iter()
+            .map(|(name, quota)| Ok((category(name)?, *quota)))
+            .collect::<Result<_, String>>()?,
+        first: config.first.as_ref().map(processor).transpose()?,
+        last: config.last.as_ref().map(processor).transpose()?,
+    };
+    rules.validate()?;
+    Ok(rules)
+}
+
+pub fn load_chain_rules(path: &str) -> Result<ChainRules, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+    parse_chain_rules(&json)
+}
+
+// A slot in a chain template, either a set processor or any processor from the pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemplateSlot {
+    Processor(PermuteNodeName),
+    Wildcard,
+}
+
+// Slots in the order they run
+pub type ChainTemplate = Vec<TemplateSlot>;
+
+const WILDCARD: &str = "*";
+
+pub fn get_template_slot_from_display_name(name: &str) -> Result<TemplateSlot, String> {
+    match name.trim() {
+        WILDCARD => Ok(TemplateSlot::Wildcard),
+        name => get_processor_from_display_name(name).map(TemplateSlot::Processor),
+    }
+}
+
+pub fn get_template_slot_display_name(slot: TemplateSlot) -> String {
+    match slot {
+        TemplateSlot::Processor(name) => get_processor_display_name(name),
+        TemplateSlot::Wildcard => String::from(WILDCARD),
+    }
+}
+
+// Parses a comma separated template of display names, with * for any processor, e.g. "Filter, *, Reverb"
+pub fn parse_template(template: &str) -> Result<ChainTemplate, String> {
+    template.split(',').map(get_template_slot_from_display_name).collect()
+}
+
+// Puts a processor in a random empty slot, avoiding forbidden neighbours where possible
+fn place(chain: &mut [Option<PermuteNodeName>], name: PermuteNodeName, rules: &ChainRules, rng: &mut ThreadRng) {
+    let free: Vec<usize> = (0..chain.len()).filter(|i| chain[*i].is_none()).collect();
+    let fits = |i: &usize| {
+        let before = i.checked_sub(1).and_then(|j| chain[j]);
+        let after = chain.get(i + 1).copied().flatten();
+        !rules.forbids(before, Some(name)) && !rules.forbids(Some(name), after)
+    };
+    let fitting: Vec<usize> = free.iter().copied().filter(fits).collect();
+    let slot = fitting.choose(rng).or(free.choose(rng)).copied();
+    if let Some(i) = slot {
+        chain[i] = Some(name);
+    }
+}
+
+/// Pick a chain of processors that follows the rules as far as the number of slots allows
+pub fn pick_chain(
+    slots: usize,
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    rules: &ChainRules,
+    extreme_stretch: Option<f64>,
+    rng: &mut ThreadRng,
+) -> Vec<PermuteNodeName> {
+    fill_chain(vec![None; slots], processor_pool, processor_weights, rules, extreme_stretch, rng)
+}
+
+/// Pick a chain with the template's structure. Wildcards are drawn from the pool under the same rules
+pub fn pick_template_chain(
+    template: &[TemplateSlot],
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    rules: &ChainRules,
+    extreme_stretch: Option<f64>,
+    rng: &mut ThreadRng,
+) -> Vec<PermuteNodeName> {
+    let chain = template
+        .iter()
+        .map(|slot| match slot {
+            TemplateSlot::Processor(name) => Some(*name),
+            TemplateSlot::Wildcard => None,
+        })
+        .collect();
+    fill_chain(chain, processor_pool, processor_weights, rules, extreme_stretch, rng)
+}
+
+// Fixed and required processors go in first, then category quotas, then the rest are
+// picked in order so each one can avoid a forbidden pair with its neighbours.
+// Slots that are already filled are left alone
+fn fill_chain(
+    mut chain: Vec<Option<PermuteNodeName>>,
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    rules: &ChainRules,
+    extreme_stretch: Option<f64>,
+    rng: &mut ThreadRng,
+) -> Vec<PermuteNodeName> {
+    let slots = chain.len();
+    if slots == 0 {
+        return vec![];
+    }
+    if let (Some(last), None) = (rules.last, chain[slots - 1]) {
+        chain[slots - 1] = Some(last);
+    }
+    if let (Some(first), None) = (rules.first, chain[0]) {
+        chain[0] = Some(first);
+    }
+    for required in &rules.required {
+        if !chain.contains(&Some(*required)) {
+            place(&mut chain, *required, rules, rng);
+        }
+    }
+
+    for (category, quota) in &rules.category_quotas {
+        // A quota is asked for explicitly, so reach outside the pool if it has nothing in the category.
+        // Only to processors the pool could have had, so nothing weighted out or without its opt-in
+        let in_category = |p: &PermuteNodeName| processor_category(*p) == *category;
+        let pool_members: Vec<PermuteNodeName> = processor_pool.iter().copied().filter(in_category).collect();
+        let members = if pool_members.is_empty() {
+            pickable_pool(&ALL_PROCESSORS, processor_weights, extreme_stretch)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(in_category)
+                .collect()
+        } else {
+            pool_members
+        };
+        loop {
+            let count = chain.iter().flatten().filter(|p| in_category(p)).count();
+            let candidates: Vec<PermuteNodeName> =
+                members.iter().copied().filter(|p| rules.allows_another(&chain, *p)).collect();
+            if count >= *quota || candidates.is_empty() || !chain.contains(&None) {
+                break;
+            }
+            // Only what's in the category and weighted out is left
+            let Some(name) = pick_processor(&candidates, processor_weights, rng) else {
+                break;
+            };
+            place(&mut chain, name, rules, rng);
+        }
+    }
+
+    for i in 0..slots {
+        if chain[i].is_some() {
+            continue;
+        }
+        let before = i.checked_sub(1).and_then(|j| chain[j]);
+        let after = chain.get(i + 1).copied().flatten();
+        let candidates: Vec<PermuteNodeName> = processor_pool
+            .iter()
+            .copied()
+            .filter(|p| rules.allows_another(&chain, *p))
+            .filter(|p| !rules.forbids(before, Some(*p)) && !rules.forbids(Some(*p), after))
+            .collect();
+        // Rules that can't all be met give way to an unconstrained pick rather than a shorter chain.
+        // Weighted out processors are never picked, the slot is left out if that's all there is
+        chain[i] = pick_processor(&candidates, processor_weights, rng)
+            .or_else(|| pick_processor(processor_pool, processor_weights, rng));
+    }
+    chain.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+    use PermuteNodeName::*;
+
+    // The rules are checked against many picks as the chains are random
+    const RUNS: usize = 500;
+
+    fn chains(slots: usize, pool: &[PermuteNodeName], rules: &ChainRules) -> Vec<Vec<PermuteNodeName>> {
+        let mut rng = thread_rng();
+        (0..RUNS).map(|_| pick_chain(slots, pool, &HashMap::new(), rules, None, &mut rng)).collect()
+    }
+
+    #[test]
+    fn forbidden_pairs_never_run_back_to_back() {
+        let rules = ChainRules {
+            forbidden_pairs: vec![(Reverse, Reverse), (Reverb, Filter)],
+            ..ChainRules::default()
+        };
+        for chain in chains(6, &[Reverse, Reverb, Filter], &rules) {
+            assert_eq!(chain.len(), 6);
+            for pair in chain.windows(2) {
+                assert!(!rules.forbidden_pairs.contains(&(pair[0], pair[1])), "{:?}", chain);
+            }
+        }
+    }
+
+    #[test]
+    fn required_processors_and_maximums_are_kept() {
+        let rules = ChainRules {
+            required: vec![Reverb],
+            max_occurrences: HashMap::from([(Fuzz, 1)]),
+            max_category_occurrences: HashMap::from([(ProcessorCategory::TimePitch, 1)]),
+            ..ChainRules::default()
+        };
+        for chain in chains(5, &[Fuzz, Reverse, HalfSpeed, Reverb, Chorus], &rules) {
+            assert!(chain.contains(&Reverb), "{:?}", chain);
+            assert!(chain.iter().filter(|p| **p == Fuzz).count() <= 1, "{:?}", chain);
+            let time_pitch = chain.iter().filter(|p| processor_category(**p) == ProcessorCategory::TimePitch);
+            assert!(time_pitch.count() <= 1, "{:?}", chain);
+        }
+    }
+
+    #[test]
+    fn quotas_reach_outside_the_pool() {
+        let rules = ChainRules {
+            category_quotas: HashMap::from([(ProcessorCategory::DelayReverb, 2)]),
+            ..ChainRules::default()
+        };
+        for chain in chains(4, &[Filter, Chorus], &rules) {
+            let delay_reverb = chain.iter().filter(|p| processor_category(**p) == ProcessorCategory::DelayReverb);
+            assert_eq!(delay_reverb.count(), 2, "{:?}", chain);
+        }
+    }
+
+    #[test]
+    fn quotas_outside_the_pool_keep_to_what_the_pool_could_have_had() {
+        let mut rng = thread_rng();
+        let pool = [Filter, Chorus];
+        // Parallel is weighted out, so a routing quota can only be met by the multiband
+        let rules = ChainRules {
+            category_quotas: HashMap::from([(ProcessorCategory::Routing, 1)]),
+            ..ChainRules::default()
+        };
+        let weights = HashMap::from([(Parallel, 0.0)]);
+        for _ in 0..RUNS {
+            let chain = pick_chain(3, &pool, &weights, &rules, None, &mut rng);
+            assert_eq!(chain.iter().filter(|p| **p == Multiband).count(), 1, "{:?}", chain);
+            assert!(!chain.contains(&Parallel), "{:?}", chain);
+        }
+
+        // Everything in the category bar the extreme stretch is weighted out, so the quota needs its opt-in
+        let rules = ChainRules {
+            category_quotas: HashMap::from([(ProcessorCategory::TimePitch, 1)]),
+            ..ChainRules::default()
+        };
+        let weights: HashMap<PermuteNodeName, f64> = ALL_PROCESSORS
+            .iter()
+            .filter(|p| processor_category(**p) == ProcessorCategory::TimePitch && **p != ExtremeStretch)
+            .map(|p| (*p, 0.0))
+            .collect();
+        for _ in 0..RUNS {
+            let chain = pick_chain(3, &pool, &weights, &rules, None, &mut rng);
+            assert_eq!(chain.len(), 3);
+            assert!(chain.iter().all(|p| pool.contains(p)), "{:?}", chain);
+            let chain = pick_chain(3, &pool, &weights, &rules, Some(20.0), &mut rng);
+            assert_eq!(chain.iter().filter(|p| **p == ExtremeStretch).count(), 1, "{:?}", chain);
+        }
+    }
+
+    #[test]
+    fn first_and_last_fill_open_template_slots() {
+        let rules = ChainRules {
+            first: Some(Reverse),
+            last: Some(Reverb),
+            ..ChainRules::default()
+        };
+        let mut rng = thread_rng();
+        let open = [TemplateSlot::Wildcard, TemplateSlot::Processor(Fuzz), TemplateSlot::Wildcard];
+        let fixed = [TemplateSlot::Processor(Filter), TemplateSlot::Wildcard, TemplateSlot::Processor(Chorus)];
+        for _ in 0..RUNS {
+            let chain = pick_template_chain(&open, &[Filter, Chorus], &HashMap::new(), &rules, None, &mut rng);
+            assert_eq!(chain, vec![Reverse, Fuzz, Reverb]);
+            // The template's own processors stay where they are
+            let chain = pick_template_chain(&fixed, &[Filter, Chorus], &HashMap::new(), &rules, None, &mut rng);
+            assert_eq!((chain.len(), chain[0], chain[2]), (3, Filter, Chorus));
+        }
+    }
+
+    #[test]
+    fn rules_that_cannot_be_met_fall_back_to_unconstrained_picks() {
+        let rules = ChainRules {
+            forbidden_pairs: vec![(Filter, Filter)],
+            max_occurrences: HashMap::from([(Filter, 1)]),
+            ..ChainRules::default()
+        };
+        for chain in chains(4, &[Filter], &rules) {
+            assert_eq!(chain, vec![Filter; 4]);
+        }
+        // Weighted out processors aren't picked even then, the slots are left out
+        let weights = HashMap::from([(Filter, 0.0)]);
+        let chain = pick_chain(4, &[Filter], &weights, &rules, None, &mut thread_rng());
+        assert!(chain.is_empty());
+    }
+
+    #[test]
+    fn parses_templates_by_display_name() {
+        let template = parse_template("Filter, *,Reverb , *").unwrap();
+        assert_eq!(
+            template,
+            vec![
+                TemplateSlot::Processor(Filter),
+                TemplateSlot::Wildcard,
+                TemplateSlot::Processor(Reverb),
+                TemplateSlot::Wildcard
+            ]
+        );
+        let names: Vec<String> = template.into_iter().map(get_template_slot_display_name).collect();
+        assert_eq!(names.join(", "), "Filter, *, Reverb, *");
+        assert!(parse_template("Filter, Not A Processor").is_err());
+        assert!(parse_template("Filter, , Reverb").is_err());
+    }
+
+    #[test]
+    fn templates_set_the_number_of_slots() {
+        let mut rng = thread_rng();
+        let rules = ChainRules::default();
+        for slots in 1..6 {
+            let template = vec![TemplateSlot::Wildcard; slots];
+            for _ in 0..20 {
+                let chain = pick_template_chain(&template, &[Filter, Chorus], &HashMap::new(), &rules, None, &mut rng);
+                assert_eq!(chain.len(), slots);
+                assert!(chain.iter().all(|p| [Filter, Chorus].contains(p)), "{:?}", chain);
+            }
+        }
+        let chain = pick_template_chain(&[], &[Filter], &HashMap::new(), &rules, None, &mut rng);
+        assert!(chain.is_empty());
+    }
+
+    #[test]
+    fn required_processors_go_in_wildcard_slots() {
+        let rules = ChainRules {
+            required: vec![Reverb],
+            ..ChainRules::default()
+        };
+        let mut rng = thread_rng();
+        let template = [TemplateSlot::Processor(Fuzz), TemplateSlot::Wildcard, TemplateSlot::Processor(Fuzz)];
+        for _ in 0..RUNS {
+            let chain = pick_template_chain(&template, &[Filter], &HashMap::new(), &rules, None, &mut rng);
+            assert_eq!(chain, vec![Fuzz, Reverb, Fuzz]);
+        }
+        // Without a free slot the template wins
+        let fixed = [TemplateSlot::Processor(Fuzz), TemplateSlot::Processor(Filter)];
+        let chain = pick_template_chain(&fixed, &[Filter], &HashMap::new(), &rules, None, &mut rng);
+        assert_eq!(chain, vec![Fuzz, Filter]);
+    }
+
+    #[test]
+    fn wildcards_with_nothing_to_pick_are_left_out() {
+        let weights = HashMap::from([(Filter, 0.0)]);
+        let template = [TemplateSlot::Wildcard, TemplateSlot::Processor(Fuzz), TemplateSlot::Wildcard];
+        let rules = ChainRules::default();
+        let chain = pick_template_chain(&template, &[Filter], &weights, &rules, None, &mut thread_rng());
+        assert_eq!(chain, vec![Fuzz]);
+    }
+}
@@ -0,0 +1,309 @@
+// External dependencies
+use rand::rngs::ThreadRng;
+use serde::{Deserialize, Serialize};
+
+// Standard library
+use std::collections::{BTreeMap, HashMap};
+
+// Internal modules
+use crate::{
+    chain_rules::ChainRules,
+    display_node::{get_category_display_name, get_category_from_display_name},
+    process::{processor_category, ProcessorCategory},
+    random_processors::utils::DistributionRng,
+};
+
+// How big and wild chains get, in place of a processor count per level of depth
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComplexityModel {
+    // Relative chance of a chain having each number of processors. Lengths not listed aren't picked
+    pub node_counts: BTreeMap<usize, f64>,
+    // Most processors from each category in a chain, e.g. one time stretch
+    pub category_budgets: HashMap<ProcessorCategory, usize>,
+    // How much further towards their full limits parameter ranges open up with each processor
+    // down the chain, from 0 to 1. 0 leaves every processor's ranges alone
+    pub intensity: f64,
+}
+
+// The model as written in a config file, with categories by display name. A preset is the starting
+// point, balanced if there isn't one, and anything else given replaces that part of it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComplexityConfig {
+    pub preset: Option<String>,
+    pub node_counts: Option<BTreeMap<usize, f64>>,
+    pub category_budgets: Option<HashMap<String, usize>>,
+    pub intensity: Option<f64>,
+}
+
+pub const COMPLEXITY_PRESETS: [&str; 4] = ["minimal", "balanced", "dense", "chaotic"];
+const DEFAULT_PRESET: &str = "balanced";
+
+pub fn get_complexity_preset(name: &str) -> Result<ComplexityModel, String> {
+    let model = |node_counts: &[(usize, f64)], category_budgets: &[(ProcessorCategory, usize)], intensity| ComplexityModel {
+        node_counts: node_counts.iter().copied().collect(),
+        category_budgets: category_budgets.iter().copied().collect(),
+        intensity,
+    };
+    match name.to_lowercase().as_str() {
+        "minimal" => Ok(model(
+            &[(1, 1.0), (2, 2.0)],
+            &[(ProcessorCategory::TimePitch, 1), (ProcessorCategory::Routing, 0)],
+            0.0,
+        )),
+        "balanced" => Ok(model(
+            &[(2, 1.0), (3, 2.0), (4, 2.0), (5, 1.0)],
+            &[(ProcessorCategory::TimePitch, 2), (ProcessorCategory::Routing, 1)],
+            0.1,
+        )),
+        "dense" => Ok(model(
+            &[(4, 1.0), (5, 2.0), (6, 2.0), (7, 1.0), (8, 1.0)],
+            &[(ProcessorCategory::TimePitch, 2), (ProcessorCategory::Routing, 2)],
+            0.2,
+        )),
+        "chaotic" => Ok(model(&(6..=12).map(|n| (n, 1.0)).collect::<Vec<_>>(), &[], 0.4)),
+        _ => Err(format!(
+            "{} is not a complexity preset. Try one of: {}",
+            name,
+            COMPLEXITY_PRESETS.join(", ")
+        )),
+    }
+}
+
+impl ComplexityModel {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.node_counts.keys().any(|n| *n == 0) {
+            return Err(String::from("Complexity node counts should be at least 1"));
+        }
+        if self.node_counts.values().any(|w| !w.is_finite() || *w < 0.0) || self.node_counts.values().all(|w| *w == 0.0) {
+            return Err(String::from("Complexity node counts need chances of 0 or more, with at least one above 0"));
+        }
+        if !(0.0..=1.0).contains(&self.intensity) {
+            return Err(String::from("Complexity intensity should be between 0 and 1"));
+        }
+        Ok(())
+    }
+
+    pub fn pick_node_count(&self, rng: &mut ThreadRng) -> usize {
+        rng.gen_distribution(self.node_counts.iter().map(|(n, w)| (*n, *w)).collect())
+    }
+
+    // The chain rules with the category budgets added. A budget never goes below what the rules
+    // themselves ask of a category, so the two can't contradict each other
+    pub fn apply_budgets(&self, rules: &ChainRules) -> ChainRules {
+        let mut rules = rules.clone();
+        for (category, budget) in &self.category_budgets {
+            let required = rules
+                .required
+                .iter()
+                .chain(rules.first.iter())
+                .chain(rules.last.iter())
+                .filter(|p| processor_category(**p) == *category)
+                .count();
+            let needed = required.max(rules.category_quotas.get(category).copied().unwrap_or(0));
+            let budget = (*budget).max(needed);
+            rules
+                .max_category_occurrences
+                .entry(*category)
+                .and_modify(|max| *max = (*max).min(budget).max(needed))
+                .or_insert(budget);
+        }
+        rules
+    }
+
+    // How far the ranges open up for a processor this many places down the chain
+    pub fn intensity_at(&self, position: usize) -> f64 {
+        (self.intensity * position as f64).min(1.0)
+    }
+
+    // The preset this model is, if it is one
+    pub fn preset_name(&self) -> Option<String> {
+        COMPLEXITY_PRESETS
+            .iter()
+            .find(|p| get_complexity_preset(p).as_ref() == Ok(self))
+            .map(|p| p.to_string())
+    }
+
+    pub fn to_config(&self) -> ComplexityConfig {
+        ComplexityConfig {
+            preset: self.preset_name(),
+            node_counts: Some(self.node_counts.clone()),
+            category_budgets: Some(
+                self.category_budgets
+                    .iter()
+                    .map(|(c, budget)| (get_category_display_name(*c), *budget))
+                    .collect(),
+            ),
+            intensity: Some(self.intensity),
+        }
+    }
+}
+
+// Parses a model, e.g. { "preset": "dense", "category_budgets": { "Time/Pitch": 1 }, "intensity": 0.5 }
+pub fn parse_complexity(json: &str) -> Result<ComplexityModel, String> {
+    let config: ComplexityConfig = serde_json::from_str(json).map_err(|e| format!("Invalid complexity: {}", e))?;
+    let mut model = get_complexity_preset(config.preset.as_deref().unwrap_or(DEFAULT_PRESET))?;
+    if let Some(node_counts) = config.node_counts {
+        model.node_counts = node_counts;
+    }
+    if let Some(category_budgets) = config.category_budgets {
+        model.category_budgets = category_budgets
+            .iter()
+            .map(|(name, budget)| Ok((get_category_from_display_name(name)?, *budget)))
+            .collect::<Result<_, String>>()?;
+    }
+    if let Some(intensity) = config.intensity {
+        model.intensity = intensity;
+    }
+    model.validate()?;
+    Ok(model)
+}
+
+// A preset name, or the path to a complexity JSON file
+pub fn load_complexity(arg: &str) -> Result<ComplexityModel, String> {
+    if COMPLEXITY_PRESETS.contains(&arg.to_lowercase().as_str()) {
+        return get_complexity_preset(arg);
+    }
+    let json = std::fs::read_to_string(arg).map_err(|e| {
+        format!(
+            "{} is not a complexity preset ({}) or a file that can be opened: {}",
+            arg,
+            COMPLEXITY_PRESETS.join(", "),
+            e
+        )
+    })?;
+    parse_complexity(&json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        process::PermuteNodeName::{self, *},
+        random_process::{generate_processor_sequence, GetProcessorNodeParams},
+    };
+    use rand::thread_rng;
+
+    const RUNS: usize = 200;
+
+    fn sequence(complexity: &ComplexityModel, processor_pool: &[PermuteNodeName]) -> Vec<PermuteNodeName> {
+        generate_processor_sequence(GetProcessorNodeParams {
+            normalise_at_end: false,
+            trim_at_end: false,
+            high_sample_rate: false,
+            depth: 1,
+            processor_pool: processor_pool.to_vec(),
+            processor_weights: HashMap::new(),
+            chain_rules: ChainRules::default(),
+            template: None,
+            complexity: Some(complexity.clone()),
+            processor_count: Some(20),
+            extreme_stretch: None,
+            constrain_length: false,
+            rng: thread_rng(),
+        })
+    }
+
+    #[test]
+    fn presets_are_valid_and_know_their_names() {
+        for preset in COMPLEXITY_PRESETS {
+            let model = get_complexity_preset(preset).unwrap();
+            assert!(model.validate().is_ok(), "{}", preset);
+            assert_eq!(model.preset_name(), Some(preset.to_string()));
+            assert_eq!(load_complexity(preset).unwrap(), model);
+        }
+        assert_eq!(get_complexity_preset("Dense"), get_complexity_preset("dense"));
+        assert!(get_complexity_preset("loud").unwrap_err().contains("minimal"));
+        assert!(load_complexity("not-a-preset-or-file").is_err());
+    }
+
+    #[test]
+    fn node_counts_set_the_chain_length_in_place_of_depth() {
+        let mut rng = thread_rng();
+        for preset in COMPLEXITY_PRESETS {
+            let model = get_complexity_preset(preset).unwrap();
+            for _ in 0..RUNS {
+                let count = model.pick_node_count(&mut rng);
+                assert!(model.node_counts.contains_key(&count), "{} {}", preset, count);
+                let length = sequence(&model, &[Filter, Chorus]).len();
+                assert!(model.node_counts.contains_key(&length), "{} {}", preset, length);
+            }
+        }
+        let model = ComplexityModel {
+            node_counts: BTreeMap::from([(2, 0.0), (7, 1.0)]),
+            ..get_complexity_preset("balanced").unwrap()
+        };
+        assert!((0..RUNS).all(|_| sequence(&model, &[Filter]).len() == 7));
+    }
+
+    #[test]
+    fn category_budgets_limit_the_chain() {
+        let minimal = get_complexity_preset("minimal").unwrap();
+        let pool = [Parallel, Multiband, HalfSpeed, DoubleSpeed, Filter];
+        for _ in 0..RUNS {
+            let chain = sequence(&minimal, &pool);
+            let count = |category| chain.iter().filter(|p| processor_category(**p) == category).count();
+            assert_eq!(count(ProcessorCategory::Routing), 0, "{:?}", chain);
+            assert!(count(ProcessorCategory::TimePitch) <= 1, "{:?}", chain);
+        }
+    }
+
+    #[test]
+    fn budgets_never_undercut_the_rules() {
+        let model = get_complexity_preset("minimal").unwrap();
+        let rules = ChainRules {
+            required: vec![HalfSpeed, DoubleSpeed],
+            max_category_occurrences: HashMap::from([(ProcessorCategory::TimePitch, 3)]),
+            category_quotas: HashMap::from([(ProcessorCategory::Routing, 1)]),
+            ..ChainRules::default()
+        };
+        let budgeted = model.apply_budgets(&rules);
+        assert_eq!(budgeted.max_category_occurrences[&ProcessorCategory::TimePitch], 2);
+        assert_eq!(budgeted.max_category_occurrences[&ProcessorCategory::Routing], 1);
+        assert_eq!(budgeted.required, rules.required);
+
+        let budgeted = model.apply_budgets(&ChainRules::default());
+        assert_eq!(budgeted.max_category_occurrences[&ProcessorCategory::TimePitch], 1);
+        assert_eq!(budgeted.max_category_occurrences[&ProcessorCategory::Routing], 0);
+    }
+
+    #[test]
+    fn intensity_builds_down_the_chain() {
+        let model = get_complexity_preset("chaotic").unwrap();
+        assert_eq!(model.intensity_at(0), 0.0);
+        assert_eq!(model.intensity_at(1), 0.4);
+        assert_eq!(model.intensity_at(2), 0.8);
+        assert_eq!(model.intensity_at(5), 1.0);
+        let minimal = get_complexity_preset("minimal").unwrap();
+        assert_eq!(minimal.intensity_at(10), 0.0);
+    }
+
+    #[test]
+    fn parsed_models_start_from_a_preset() {
+        let json = r#"{ "preset": "dense", "category_budgets": { "Time/Pitch": 1 }, "intensity": 0.5 }"#;
+        let model = parse_complexity(json).unwrap();
+        assert_eq!(model.node_counts, get_complexity_preset("dense").unwrap().node_counts);
+        assert_eq!(model.category_budgets, HashMap::from([(ProcessorCategory::TimePitch, 1)]));
+        assert_eq!(model.intensity, 0.5);
+        assert_eq!(model.preset_name(), None);
+        assert_eq!(parse_complexity("{}").unwrap(), get_complexity_preset("balanced").unwrap());
+
+        let written = serde_json::to_string(&model.to_config()).unwrap();
+        assert_eq!(parse_complexity(&written).unwrap(), model);
+    }
+
+    #[test]
+    fn rejects_models_that_cannot_be_used() {
+        for json in [
+            r#"{ "node_counts": { "0": 1.0 } }"#,
+            r#"{ "node_counts": { "3": 0.0 } }"#,
+            r#"{ "node_counts": { "3": -1.0 } }"#,
+            r#"{ "intensity": 1.5 }"#,
+            r#"{ "category_budgets": { "Loud": 1 } }"#,
+            r#"{ "preset": "loud" }"#,
+            r#"{ "depth": 3 }"#,
+        ] {
+            assert!(parse_complexity(json).is_err(), "{}", json);
+        }
+    }
+}
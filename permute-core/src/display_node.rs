@@ -1,4 +1,4 @@
-use crate::process::PermuteNodeName;
+use crate::process::{PermuteNodeName, ProcessorCategory};
 
 pub fn get_processor_display_name(name: PermuteNodeName) -> String {
     match name {
@@ -32,6 +32,30 @@ pub fn get_processor_display_name(name: PermuteNodeName) -> String {
         PermuteNodeName::CrossMix => String::from("Cross Mix"),
         PermuteNodeName::CrossGrain => String::from("Cross Grain"),
         PermuteNodeName::BlurStretch => String::from("Blur Stretch"),
+        PermuteNodeName::Compressor => String::from("Compressor"),
+        PermuteNodeName::Limiter => String::from("Limiter"),
+        PermuteNodeName::Expander => String::from("Gate/Expander"),
+        PermuteNodeName::TransientShaper => String::from("Transient Shaper"),
+        PermuteNodeName::Decorrelate => String::from("Stereo Decorrelate"),
+        PermuteNodeName::AutoPan => String::from("Auto Pan"),
+        PermuteNodeName::StereoWidth => String::from("Stereo Width"),
+        PermuteNodeName::Haas => String::from("Haas"),
+        PermuteNodeName::Glitch => String::from("Glitch"),
+        PermuteNodeName::GranularCloud => String::from("Granular Cloud"),
+        PermuteNodeName::FrequencyShifter => String::from("Frequency Shifter"),
+        PermuteNodeName::RingModulator => String::from("Ring Modulator"),
+        PermuteNodeName::Resonator => String::from("Resonator"),
+        PermuteNodeName::AutoWah => String::from("Auto Wah"),
+        PermuteNodeName::EnvelopePitch => String::from("Envelope Pitch"),
+        PermuteNodeName::EnvelopeDelay => String::from("Envelope Delay"),
+        PermuteNodeName::EnvelopeDrive => String::from("Envelope Drive"),
+        PermuteNodeName::Multiband => String::from("Multiband"),
+        PermuteNodeName::Parallel => String::from("Parallel"),
+        PermuteNodeName::TapeStop => String::from("Tape Stop"),
+        PermuteNodeName::TapeStart => String::from("Tape Start"),
+        PermuteNodeName::SpinBack => String::from("Spin Back"),
+        PermuteNodeName::Varispeed => String::from("Varispeed"),
+        PermuteNodeName::ExtremeStretch => String::from("Extreme Stretch"),
     }
 }
 
@@ -67,6 +91,93 @@ pub fn get_processor_from_display_name(name: &str) -> Result<PermuteNodeName, St
         "Cross Mix" => Ok(PermuteNodeName::CrossMix),
         "Cross Grain" => Ok(PermuteNodeName::CrossGrain),
         "Blur Stretch" => Ok(PermuteNodeName::BlurStretch),
+        "Compressor" => Ok(PermuteNodeName::Compressor),
+        "Limiter" => Ok(PermuteNodeName::Limiter),
+        "Gate/Expander" => Ok(PermuteNodeName::Expander),
+        "Transient Shaper" => Ok(PermuteNodeName::TransientShaper),
+        "Stereo Decorrelate" => Ok(PermuteNodeName::Decorrelate),
+        "Auto Pan" => Ok(PermuteNodeName::AutoPan),
+        "Stereo Width" => Ok(PermuteNodeName::StereoWidth),
+        "Haas" => Ok(PermuteNodeName::Haas),
+        "Glitch" => Ok(PermuteNodeName::Glitch),
+        "Granular Cloud" => Ok(PermuteNodeName::GranularCloud),
+        "Frequency Shifter" => Ok(PermuteNodeName::FrequencyShifter),
+        "Ring Modulator" => Ok(PermuteNodeName::RingModulator),
+        "Resonator" => Ok(PermuteNodeName::Resonator),
+        "Auto Wah" => Ok(PermuteNodeName::AutoWah),
+        "Envelope Pitch" => Ok(PermuteNodeName::EnvelopePitch),
+        "Envelope Delay" => Ok(PermuteNodeName::EnvelopeDelay),
+        "Envelope Drive" => Ok(PermuteNodeName::EnvelopeDrive),
+        "Multiband" => Ok(PermuteNodeName::Multiband),
+        "Parallel" => Ok(PermuteNodeName::Parallel),
+        "Tape Stop" => Ok(PermuteNodeName::TapeStop),
+        "Tape Start" => Ok(PermuteNodeName::TapeStart),
+        "Spin Back" => Ok(PermuteNodeName::SpinBack),
+        "Varispeed" => Ok(PermuteNodeName::Varispeed),
+        "Extreme Stretch" => Ok(PermuteNodeName::ExtremeStretch),
         _ => Err(format!("{} not found", name)),
     }
 }
+
+// Parses a processor weight like "Reverb=3" or "Metallic Delay=0.5"
+pub fn get_processor_weight_from_arg(arg: &str) -> Result<(PermuteNodeName, f64), String> {
+    let (name, weight) = arg
+        .rsplit_once('=')
+        .ok_or(format!("{} should be a processor and weight, e.g. Reverb=3", arg))?;
+    let weight = weight
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("{} is not a valid weight", weight))?;
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(format!("{} is not a valid weight", weight));
+    }
+    Ok((get_processor_from_display_name(name.trim())?, weight))
+}
+
+pub fn get_category_display_name(category: ProcessorCategory) -> String {
+    match category {
+        ProcessorCategory::TimePitch => String::from("Time/Pitch"),
+        ProcessorCategory::Modulation => String::from("Modulation"),
+        ProcessorCategory::FilterDrive => String::from("Filter/Drive"),
+        ProcessorCategory::DelayReverb => String::from("Delay/Reverb"),
+        ProcessorCategory::Stereo => String::from("Stereo"),
+        ProcessorCategory::Dynamics => String::from("Dynamics"),
+        ProcessorCategory::CrossProcessing => String::from("Cross Processing"),
+        ProcessorCategory::Routing => String::from("Routing"),
+        ProcessorCategory::Utility => String::from("Utility"),
+    }
+}
+
+pub fn get_category_from_display_name(name: &str) -> Result<ProcessorCategory, String> {
+    match name {
+        "Time/Pitch" => Ok(ProcessorCategory::TimePitch),
+        "Modulation" => Ok(ProcessorCategory::Modulation),
+        "Filter/Drive" => Ok(ProcessorCategory::FilterDrive),
+        "Delay/Reverb" => Ok(ProcessorCategory::DelayReverb),
+        "Stereo" => Ok(ProcessorCategory::Stereo),
+        "Dynamics" => Ok(ProcessorCategory::Dynamics),
+        "Cross Processing" => Ok(ProcessorCategory::CrossProcessing),
+        "Routing" => Ok(ProcessorCategory::Routing),
+        "Utility" => Ok(ProcessorCategory::Utility),
+        _ => Err(format!("{} not found", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_processor_weights() {
+        assert_eq!(get_processor_weight_from_arg("Reverb=3"), Ok((PermuteNodeName::Reverb, 3.0)));
+        assert_eq!(get_processor_weight_from_arg(" Metallic Delay = 0.5 "), Ok((PermuteNodeName::MetallicDelay, 0.5)));
+        assert_eq!(get_processor_weight_from_arg("Reverb=0"), Ok((PermuteNodeName::Reverb, 0.0)));
+    }
+
+    #[test]
+    fn rejects_weights_that_cannot_be_picked_with() {
+        for arg in ["Reverb=-1", "Reverb=NaN", "Reverb=inf", "Reverb=-inf", "Reverb=lots", "Reverb", "Nothing=1"] {
+            assert!(get_processor_weight_from_arg(arg).is_err(), "{}", arg);
+        }
+    }
+}
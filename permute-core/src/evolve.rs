@@ -0,0 +1,412 @@
+// External dependencies
+use rand::{rngs::ThreadRng, Rng};
+
+// Standard library
+use std::collections::HashMap;
+
+// Internal modules
+use crate::{
+    process::{processor_category, PermutationProcessor, PermuteNodeName, ProcessorCategory, ProcessorParams},
+    random_process::pick_processor,
+    random_processors::{
+        parameter_ranges::{range_around, ParameterRange},
+        utils::{get_filename, DistributionRng},
+    },
+};
+
+// Most a node can be changed structurally at a full amount, so a mutation still resembles its parent
+const STRUCTURE_CHANCE: f64 = 0.5;
+
+// A node in a bred chain. Ranges hold its parameters near the parent's and choices are the rest of
+// its recorded attributes, e.g. a filter type, kept as they were. Both are empty for new nodes
+#[derive(Debug, Clone)]
+pub struct EvolvedNode {
+    pub name: PermuteNodeName,
+    pub parameter_ranges: HashMap<String, ParameterRange>,
+    pub choices: HashMap<String, String>,
+    // The sub-chains of a parallel or multiband node, empty to pick new ones
+    pub branches: Vec<EvolvedChain>,
+}
+
+pub type EvolvedChain = Vec<EvolvedNode>;
+
+// A chain as it was run, with each node's attributes
+pub type RecordedChain = Vec<PermutationProcessor>;
+
+// How a new generation is bred from recorded chains. amount is 0 - 1, how far children stray
+#[derive(Debug, Clone)]
+pub enum Evolution {
+    Mutate {
+        parent: RecordedChain,
+        amount: f64,
+    },
+    Crossover {
+        parents: (RecordedChain, RecordedChain),
+        amount: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NodeMutation {
+    Replace,
+    Swap,
+    Insert,
+    Delete,
+}
+
+impl EvolvedNode {
+    pub fn new(name: PermuteNodeName) -> Self {
+        Self {
+            name,
+            parameter_ranges: HashMap::new(),
+            choices: HashMap::new(),
+            branches: vec![],
+        }
+    }
+}
+
+// Attribute values are formatted for display, e.g. "120.00 ms", "45.00%" or "2.00x". Anything else, like a synced "1/8", isn't anchored
+pub(crate) fn parse_attribute_value(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.trim_end_matches(['%', 'x']).parse().ok()
+}
+
+// Keep a recorded node, letting each of its adjustable parameters move by the amount. Its other
+// attributes are kept as they were, or with a chance of the amount left for the processor to pick again.
+// Branches keep their nodes, which vary the same way
+fn anchor(processor: &PermutationProcessor, amount: f64, rng: &mut ThreadRng) -> EvolvedNode {
+    let mut node = EvolvedNode::new(processor.name);
+    for attribute in &processor.attributes {
+        let range = parse_attribute_value(&attribute.value)
+            .and_then(|value| range_around(processor.name, &attribute.key, value, amount));
+        match range {
+            Some(range) => {
+                node.parameter_ranges.insert(attribute.key.clone(), range);
+            }
+            None if !rng.gen_bool(amount) => {
+                node.choices.insert(attribute.key.clone(), attribute.value.clone());
+            }
+            None => {}
+        }
+    }
+    node.branches = processor
+        .branches
+        .iter()
+        .map(|branch| chain_nodes(branch).into_iter().map(|p| anchor(p, amount, rng)).collect())
+        .collect();
+    node
+}
+
+/// The attribute a bred node kept from its parent, e.g. "BandPass" for a filter type
+pub fn recorded_attribute(params: &ProcessorParams, key: &str) -> Option<String> {
+    params.permutation.evolved_node.as_ref()?.choices.get(key).cloned()
+}
+
+/// The option a bred node's parent picked, if it's still one of the options. Options are matched by
+/// how the processor formats them for its attributes
+pub fn recorded_choice<T: Clone>(
+    params: &ProcessorParams,
+    key: &str,
+    options: &[T],
+    format: impl Fn(&T) -> String,
+) -> Option<T> {
+    let recorded = recorded_attribute(params, key)?;
+    options.iter().find(|o| format(o) == recorded).cloned()
+}
+
+/// The other input file a bred node's parent used, if it's still one of this run's files
+pub fn recorded_file(params: &ProcessorParams, key: &str) -> Option<String> {
+    let recorded = recorded_attribute(params, key)?;
+    params
+        .permutation
+        .files
+        .iter()
+        .find(|f| **f != params.permutation.file && get_filename(f) == recorded)
+        .cloned()
+}
+
+// Sample rate conversion, normalise and trim are added back by the run's own settings
+fn chain_nodes(chain: &[PermutationProcessor]) -> Vec<&PermutationProcessor> {
+    chain
+        .iter()
+        .filter(|p| processor_category(p.name) != ProcessorCategory::Utility)
+        .collect()
+}
+
+// Recorded chains are saved as JSON, e.g. next to an output so it can be bred from later
+pub fn load_chain(path: &str) -> Result<RecordedChain, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid chain in {}: {}", path, e))
+}
+
+pub fn save_chain(path: &str, chain: &[PermutationProcessor]) -> std::io::Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(chain)?)
+}
+
+/// Perturb a recorded chain. Each node keeps its processor with parameters near the parent's,
+/// or with a chance that grows with the amount is replaced, swapped with the node before,
+/// has a new node inserted after it or is deleted
+pub fn mutate(
+    parent: &[PermutationProcessor],
+    amount: f64,
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    rng: &mut ThreadRng,
+) -> EvolvedChain {
+    let amount = amount.clamp(0.0, 1.0);
+    let mut chain: EvolvedChain = vec![];
+    for processor in chain_nodes(parent) {
+        if !rng.gen_bool(amount * STRUCTURE_CHANCE) {
+            chain.push(anchor(processor, amount, rng));
+            continue;
+        }
+        let mutation = rng.gen_distribution(vec![
+            (NodeMutation::Replace, 0.3),
+            (NodeMutation::Swap, 0.2),
+            (NodeMutation::Insert, 0.25),
+            (NodeMutation::Delete, 0.25),
+        ]);
+        match mutation {
+            NodeMutation::Replace => {
+                chain.extend(pick_processor(processor_pool, processor_weights, rng).map(EvolvedNode::new));
+            }
+            NodeMutation::Swap => {
+                chain.push(anchor(processor, amount, rng));
+                let len = chain.len();
+                if len > 1 {
+                    chain.swap(len - 2, len - 1);
+                }
+            }
+            NodeMutation::Insert => {
+                chain.push(anchor(processor, amount, rng));
+                chain.extend(pick_processor(processor_pool, processor_weights, rng).map(EvolvedNode::new));
+            }
+            NodeMutation::Delete => {}
+        }
+    }
+    // Everything deleted, start again from one new node rather than an empty chain
+    if chain.is_empty() {
+        chain.extend(pick_processor(processor_pool, processor_weights, rng).map(EvolvedNode::new));
+    }
+    chain
+}
+
+/// Combine two recorded chains, the start of one followed by the end of the other, then mutate the result by the amount
+pub fn crossover(
+    parent_a: &[PermutationProcessor],
+    parent_b: &[PermutationProcessor],
+    amount: f64,
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    rng: &mut ThreadRng,
+) -> EvolvedChain {
+    let (nodes_a, nodes_b) = (chain_nodes(parent_a), chain_nodes(parent_b));
+    let (nodes_a, nodes_b) = if rng.gen_bool(0.5) { (nodes_a, nodes_b) } else { (nodes_b, nodes_a) };
+    // At least one node from each parent, so the child is a mix of both
+    let cut_a = if nodes_a.is_empty() { 0 } else { rng.gen_range(1..=nodes_a.len()) };
+    let cut_b = if nodes_b.is_empty() { 0 } else { rng.gen_range(0..nodes_b.len()) };
+    let child: RecordedChain = nodes_a[..cut_a]
+        .iter()
+        .chain(nodes_b[cut_b..].iter())
+        .map(|p| (*p).clone())
+        .collect();
+    mutate(&child, amount, processor_pool, processor_weights, rng)
+}
+
+/// Breed one child chain for a new generation
+pub fn evolve_chain(
+    evolution: &Evolution,
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    rng: &mut ThreadRng,
+) -> EvolvedChain {
+    match evolution {
+        Evolution::Mutate { parent, amount } => mutate(parent, *amount, processor_pool, processor_weights, rng),
+        Evolution::Crossover { parents, amount } => {
+            crossover(&parents.0, &parents.1, *amount, processor_pool, processor_weights, rng)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{Permutation, ProcessorAttribute};
+    use rand::thread_rng;
+    use std::sync::Arc;
+    use PermuteNodeName::*;
+
+    const RUNS: usize = 200;
+    const POOL: [PermuteNodeName; 2] = [Chorus, Fuzz];
+
+    fn node(name: PermuteNodeName, attributes: &[(&str, &str)]) -> PermutationProcessor {
+        PermutationProcessor {
+            name,
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| ProcessorAttribute {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            branches: vec![],
+        }
+    }
+
+    fn filter() -> PermutationProcessor {
+        node(Filter, &[("Type", "BandPass"), ("Frequency", "1200.00 hz")])
+    }
+
+    fn reverb() -> PermutationProcessor {
+        node(Reverb, &[("Algorithm", "Plate"), ("Decay", "800.00 ms"), ("Wet Mix", "40.00%")])
+    }
+
+    fn names(chain: &[EvolvedNode]) -> Vec<PermuteNodeName> {
+        chain.iter().map(|n| n.name).collect()
+    }
+
+    #[test]
+    fn parses_formatted_attribute_values() {
+        assert_eq!(parse_attribute_value("120.00 ms"), Some(120.0));
+        assert_eq!(parse_attribute_value("45.00%"), Some(45.0));
+        assert_eq!(parse_attribute_value("2.00x"), Some(2.0));
+        assert_eq!(parse_attribute_value("-6.00 db"), Some(-6.0));
+        assert_eq!(parse_attribute_value("1/8"), None);
+        assert_eq!(parse_attribute_value("BandPass"), None);
+        assert_eq!(parse_attribute_value(""), None);
+    }
+
+    #[test]
+    fn mutating_by_nothing_keeps_the_chain_and_its_choices() {
+        let parent = vec![
+            node(SampleRateConversionHigh, &[]),
+            filter(),
+            reverb(),
+            node(SampleRateConversionOriginal, &[]),
+            node(Normalise, &[]),
+        ];
+        let child = mutate(&parent, 0.0, &POOL, &HashMap::new(), &mut thread_rng());
+        // The run's own settings add the utility nodes back
+        assert_eq!(names(&child), vec![Filter, Reverb]);
+        assert_eq!(child[0].choices["Type"], "BandPass");
+        assert_eq!(child[0].parameter_ranges["Frequency"], ParameterRange { min: 1200.0, max: 1200.0 });
+        assert_eq!(child[1].choices["Algorithm"], "Plate");
+        assert_eq!(child[1].parameter_ranges["Decay"], ParameterRange { min: 800.0, max: 800.0 });
+        assert_eq!(child[1].parameter_ranges["Wet Mix"], ParameterRange { min: 40.0, max: 40.0 });
+        assert!(!child[1].choices.contains_key("Decay"));
+    }
+
+    #[test]
+    fn mutated_parameters_stay_near_the_parent() {
+        let mut rng = thread_rng();
+        for _ in 0..RUNS {
+            let child = mutate(&[filter()], 0.2, &POOL, &HashMap::new(), &mut rng);
+            for node in child.iter().filter(|n| n.name == Filter) {
+                let frequency = node.parameter_ranges["Frequency"];
+                assert!((frequency.min - 1000.0).abs() < 1e-9 && (frequency.max - 1440.0).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn mutations_draw_from_the_parent_and_pool_and_never_empty_the_chain() {
+        let mut rng = thread_rng();
+        let parent = vec![filter(), reverb()];
+        let mut changed = false;
+        for _ in 0..RUNS {
+            let child = mutate(&parent, 1.0, &POOL, &HashMap::new(), &mut rng);
+            assert!(!child.is_empty());
+            assert!(child.iter().all(|n| [Filter, Reverb, Chorus, Fuzz].contains(&n.name)), "{:?}", names(&child));
+            changed |= names(&child) != vec![Filter, Reverb];
+        }
+        assert!(changed);
+        // With the pool weighted out, replacements and inserts have nothing new to add
+        let weights = HashMap::from([(Chorus, 0.0), (Fuzz, 0.0)]);
+        for _ in 0..RUNS {
+            let child = mutate(&parent, 1.0, &POOL, &weights, &mut rng);
+            assert!(child.iter().all(|n| [Filter, Reverb].contains(&n.name)));
+        }
+    }
+
+    #[test]
+    fn branches_are_kept_with_their_choices() {
+        let mut parallel = node(Parallel, &[("Branch A Gain", "50.00%")]);
+        parallel.branches = vec![vec![filter()], vec![reverb(), node(Trim, &[])]];
+        let child = mutate(&[parallel], 0.0, &POOL, &HashMap::new(), &mut thread_rng());
+        assert_eq!(child.len(), 1);
+        let branches: Vec<Vec<PermuteNodeName>> = child[0].branches.iter().map(|b| names(b)).collect();
+        assert_eq!(branches, vec![vec![Filter], vec![Reverb]]);
+        assert_eq!(child[0].branches[1][0].choices["Algorithm"], "Plate");
+    }
+
+    #[test]
+    fn crossovers_join_the_start_of_one_parent_to_the_end_of_the_other() {
+        let mut rng = thread_rng();
+        let parent_a = vec![filter(), filter(), filter()];
+        let parent_b = vec![reverb(), reverb(), reverb()];
+        let (mut a_first, mut b_first) = (false, false);
+        for _ in 0..RUNS {
+            let child = crossover(&parent_a, &parent_b, 0.0, &POOL, &HashMap::new(), &mut rng);
+            let child_names = names(&child);
+            let first = child_names[0];
+            let last = *child_names.last().unwrap();
+            assert_ne!(first, last, "{:?}", child_names);
+            let switch = child_names.iter().position(|n| *n != first).unwrap();
+            assert!(child_names[switch..].iter().all(|n| *n == last), "{:?}", child_names);
+            assert!((2..=6).contains(&child.len()));
+            a_first |= first == Filter;
+            b_first |= first == Reverb;
+            // Recorded choices come through the crossover
+            for node in &child {
+                let expected = if node.name == Filter { ("Type", "BandPass") } else { ("Algorithm", "Plate") };
+                assert_eq!(node.choices[expected.0], expected.1);
+            }
+        }
+        assert!(a_first && b_first);
+    }
+
+    #[test]
+    fn evolving_picks_mutate_or_crossover() {
+        let mut rng = thread_rng();
+        let mutate = Evolution::Mutate {
+            parent: vec![filter()],
+            amount: 0.0,
+        };
+        assert_eq!(names(&evolve_chain(&mutate, &POOL, &HashMap::new(), &mut rng)), vec![Filter]);
+        let crossover = Evolution::Crossover {
+            parents: (vec![filter()], vec![reverb()]),
+            amount: 0.0,
+        };
+        let child = names(&evolve_chain(&crossover, &POOL, &HashMap::new(), &mut rng));
+        assert!(child == vec![Filter, Reverb] || child == vec![Reverb, Filter], "{:?}", child);
+    }
+
+    #[test]
+    fn bred_nodes_report_their_recorded_choices() {
+        let node = anchor(&filter(), 0.0, &mut thread_rng());
+        let params = ProcessorParams {
+            permutation: Permutation {
+                evolved_node: Some(Arc::new(node)),
+                ..Permutation::default()
+            },
+            ..ProcessorParams::default()
+        };
+        assert_eq!(recorded_attribute(&params, "Type"), Some("BandPass".to_string()));
+        assert_eq!(recorded_attribute(&params, "Frequency"), None);
+        let options = ["LowPass", "BandPass"];
+        assert_eq!(recorded_choice(&params, "Type", &options, |o| o.to_string()), Some("BandPass"));
+        assert_eq!(recorded_choice(&params, "Type", &["LowPass"], |o| o.to_string()), None);
+        assert_eq!(recorded_attribute(&ProcessorParams::default(), "Type"), None);
+    }
+
+    #[test]
+    fn chains_are_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("permute-evolve-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        save_chain(path, &[filter(), reverb()]).unwrap();
+        let loaded = load_chain(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.iter().map(|p| p.name).collect::<Vec<_>>(), vec![Filter, Reverb]);
+        assert_eq!(loaded[1].attributes[1].value, "800.00 ms");
+        assert!(load_chain(path).is_err());
+    }
+}
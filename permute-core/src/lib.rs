@@ -1,4 +1,7 @@
+pub mod chain_rules;
+pub mod complexity;
 pub mod display_node;
+pub mod evolve;
 pub mod files;
 pub mod permute_error;
 pub mod permute_files;
@@ -6,5 +9,7 @@ pub mod process;
 mod random_process;
 pub mod audio_cache;
 pub mod rms_cache;
+pub mod similarity;
 pub mod processors;
 pub mod random_processors;
+pub mod ratings;
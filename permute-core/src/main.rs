@@ -1,22 +1,39 @@
+mod chain_rules;
+mod complexity;
 mod display_node;
+mod evolve;
 mod files;
 mod permute_error;
 mod permute_files;
 mod audio_cache;
 mod rms_cache;
+mod similarity;
 mod process;
 mod random_process;
 mod processors;
 mod random_processors;
+mod ratings;
 
-use std::{sync::Arc, thread};
+use std::{collections::HashMap, sync::Arc, thread};
 use display_node::*;
 use permute_files::*;
 use structopt::StructOpt;
 use crossbeam_channel;
+use audio_info::{
+    descriptors::load_descriptor_targets,
+    quality::{load_quality_checks, quality_flag_description},
+    DescriptorTargets, QualityChecks,
+};
 
+use crate::chain_rules::{get_template_slot_display_name, load_chain_rules, parse_template, ChainRules, ChainTemplate};
+use crate::complexity::{load_complexity, ComplexityModel};
+use crate::evolve::{load_chain, save_chain, Evolution, RecordedChain};
 use crate::process::{PermuteNodeName, Permutation, ProcessorAttribute};
 use crate::display_node::get_processor_display_name;
+use crate::processors::oversample::{get_oversampling_from_name, Oversampling};
+use crate::processors::resample::{get_resample_quality_from_name, ResampleQuality};
+use crate::random_processors::parameter_ranges::{load_parameter_ranges, ParameterRanges};
+use crate::ratings::{get_rating_from_arg, load_ratings, save_ratings, Rating, RatingsStore};
 
 /// Permute file
 #[derive(StructOpt, Clone)]
@@ -62,11 +79,74 @@ struct PermuteArgs {
     /// Run audio through a specific process
     #[structopt(long = "processor", default_value = "")]
     processor: String,
+    /// How likely a processor is to be picked, e.g. --weight Reverb=3. Processors are 1 by default, 0 leaves them out
+    #[structopt(long = "weight", number_of_values = 1, parse(try_from_str = get_processor_weight_from_arg))]
+    weights: Vec<(PermuteNodeName, f64)>,
+    /// JSON file of ranges for processor parameters, e.g. {"Reverb": {"Decay": {"min": 200, "max": 800}}}
+    #[structopt(long = "parameterRanges", parse(try_from_str = load_parameter_ranges))]
+    parameter_ranges: Option<ParameterRanges>,
+    /// JSON file of rules for the chain, e.g. {"required": ["Reverb"], "forbidden_pairs": [["Reverse", "Reverse"]]}
+    #[structopt(long = "chainRules", parse(try_from_str = load_chain_rules))]
+    chain_rules: Option<ChainRules>,
+    /// Comma separated chain every permutation follows, with * for any processor from the pool, e.g. "Filter, *, Reverb"
+    #[structopt(long = "template", parse(try_from_str = parse_template))]
+    template: Option<ChainTemplate>,
+    /// JSON file of rated outputs. Created when the first rating is added
+    #[structopt(long = "ratings")]
+    ratings: Option<String>,
+    /// Rate an output before running, e.g. --rate out.wav=starred. Ratings are kept, deleted or starred,
+    /// and the output's chain is read from the <output>.chain.json saved by --saveChains
+    #[structopt(long = "rate", requires = "ratings", number_of_values = 1, parse(try_from_str = get_rating_from_arg))]
+    rate: Vec<(String, Rating)>,
+    /// Shift processor weights and parameter ranges towards the outputs that were kept in --ratings
+    #[structopt(long = "personalised", requires = "ratings")]
+    personalised: bool,
+    /// Chain length, category budget and intensity preset: minimal, balanced, dense or chaotic. Or a JSON file,
+    /// e.g. {"preset": "dense", "category_budgets": {"Time/Pitch": 1}, "intensity": 0.5}. Replaces depth and processor count
+    #[structopt(long = "complexity", parse(try_from_str = load_complexity))]
+    complexity: Option<ComplexityModel>,
+    /// Save each output's chain next to it as <output>.chain.json, to breed from with --mutate and --crossover
+    #[structopt(long = "saveChains")]
+    save_chains: bool,
+    /// Breed every permutation from a saved chain, perturbing its parameters and nodes
+    #[structopt(long = "mutate", parse(try_from_str = load_chain))]
+    mutate: Option<RecordedChain>,
+    /// A second saved chain to combine with the --mutate chain
+    #[structopt(long = "crossover", requires = "mutate", parse(try_from_str = load_chain))]
+    crossover: Option<RecordedChain>,
+    /// How far bred chains stray from their parents, from 0 to 1
+    #[structopt(long = "amount", default_value = "0.3")]
+    amount: f64,
+    /// JSON file of descriptor ranges outputs have to match, e.g. {"duration_sec": {"max": 4}, "centroid_hz": {"min": 2000}}
+    #[structopt(long = "targets", parse(try_from_str = load_descriptor_targets))]
+    targets: Option<DescriptorTargets>,
+    /// JSON file of quality checks to reject or flag outputs with, e.g. {"clipping": {"action": "Reject", "threshold": 0.5}}.
+    /// By default silence, NaN, DC offset and clipping are flagged and nothing is rejected
+    #[structopt(long = "qualityChecks", parse(try_from_str = load_quality_checks))]
+    quality_checks: Option<QualityChecks>,
+    /// Render outputs again when they are more similar than this, from 0 to 1, to an earlier output of the same file
+    #[structopt(long = "maxSimilarity")]
+    max_similarity: Option<f64>,
+    /// Most candidates to render for each output when they are rejected. Defaults to 10
+    #[structopt(long = "maxAttempts")]
+    max_attempts: Option<usize>,
     /// Whether to constrain the length of audio by limiting length-increasing processors
     #[structopt(long = "constrainLength", takes_value = false)]
     constrain_length: bool,
     #[structopt(long = "maxStretch", default_value = "17.0")]
     max_stretch: f64,
+    /// Lets the extreme stretch run, stretching audio up to this many times on top of max stretch
+    #[structopt(long = "extremeStretch")]
+    extreme_stretch: Option<f64>,
+    /// Resampler quality for speed and sample rate changes: low, medium or high
+    #[structopt(long = "resampleQuality", default_value = "medium", parse(try_from_str = get_resample_quality_from_name))]
+    resample_quality: ResampleQuality,
+    /// Oversampling for distortion processors: off, 2x, 4x or 8x. Picked at random for each processor if not set
+    #[structopt(long = "oversampling", parse(try_from_str = get_oversampling_from_name))]
+    oversampling: Option<Oversampling>,
+    /// Tempo to sync delays and modulation to. Detected from each file if not set
+    #[structopt(long = "bpm")]
+    bpm: Option<f64>,
 }
 
 fn main() {
@@ -74,7 +154,7 @@ fn main() {
     let (cancel_sender, cancel_receiver) = crossbeam_channel::bounded(1);
     let (tx, rx) = crossbeam_channel::bounded(100); // Buffer size of 100 for updates
 
-    let processor_pool: Vec<PermuteNodeName> = match args.processor.as_str() {
+    let mut processor_pool: Vec<PermuteNodeName> = match args.processor.as_str() {
         "" => vec![
             PermuteNodeName::GranularTimeStretch,
             PermuteNodeName::Reverse,
@@ -98,6 +178,41 @@ fn main() {
         str => vec![get_processor_from_display_name(str).expect("Processor not found")],
     };
 
+    // Weighting a processor up brings it into the pool
+    for (name, weight) in args.weights.iter() {
+        if *weight > 0.0 && !processor_pool.contains(name) {
+            processor_pool.push(*name);
+        }
+    }
+    let processor_weights: HashMap<PermuteNodeName, f64> = args.weights.iter().copied().collect();
+    let parameter_ranges = args.parameter_ranges.clone().unwrap_or_default();
+    let ratings = match &args.ratings {
+        Some(path) => match add_ratings(path, &args.rate) {
+            Ok(ratings) => Some(ratings),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return;
+            }
+        },
+        None => None,
+    };
+    let (processor_weights, parameter_ranges) = match ratings.filter(|_| args.personalised) {
+        Some(ratings) => {
+            let learned = ratings.learn();
+            let mut weights: Vec<(String, f64)> =
+                learned.weights.iter().map(|(p, w)| (get_processor_display_name(*p), *w)).collect();
+            weights.sort_by(|a, b| b.1.total_cmp(&a.1));
+            println!("Learned from {} ratings, weights {:#?}", ratings.ratings.len(), weights);
+            learned.personalise(&processor_weights, &parameter_ranges)
+        }
+        None => (processor_weights, parameter_ranges),
+    };
+
+    if let Err(err) = pickable_pool(&processor_pool, &processor_weights, args.extreme_stretch) {
+        eprintln!("Error: {}", err);
+        return;
+    }
+
     let processor_count: Option<i32> = match args.processor_count {
         0 => None,
         _ => Some(args.processor_count),
@@ -108,6 +223,26 @@ fn main() {
         args.file, args.output, args.permutations
     );
 
+    let chain_rules = args.chain_rules.unwrap_or_default();
+    if chain_rules != ChainRules::default() {
+        println!("Chain rules {:#?}", chain_rules.to_config());
+    }
+    if let Some(template) = &args.template {
+        let slots: Vec<String> = template.iter().map(|s| get_template_slot_display_name(*s)).collect();
+        println!("Template {}", slots.join(" -> "));
+    }
+
+    if let Some(complexity) = &args.complexity {
+        println!("Complexity {:#?}", complexity.to_config());
+    }
+
+    let evolution = match (args.mutate.clone(), args.crossover.clone()) {
+        (Some(parent), None) => Some(Evolution::Mutate { parent, amount: args.amount }),
+        (Some(a), Some(b)) => Some(Evolution::Crossover { parents: (a, b), amount: args.amount }),
+        _ => None,
+    };
+    let save_chains = args.save_chains;
+
     let mut all_files = vec![args.file.clone()];
     all_files.extend(args.files);
 
@@ -120,6 +255,16 @@ fn main() {
             permutations: args.permutations,
             permutation_depth: args.permutation_depth,
             processor_pool: processor_pool,
+            processor_weights,
+            parameter_ranges,
+            chain_rules,
+            template: args.template.clone(),
+            complexity: args.complexity.clone(),
+            evolution,
+            descriptor_targets: args.targets.clone().filter(|t| !t.is_empty()),
+            quality_checks: args.quality_checks.clone().unwrap_or_default(),
+            max_similarity: args.max_similarity,
+            max_attempts: args.max_attempts,
             high_sample_rate: args.high_sample_rate,
             normalise_at_end: args.normalise,
             trim_all: args.trim_all,
@@ -129,6 +274,10 @@ fn main() {
             processor_count,
             constrain_length: args.constrain_length,
             max_stretch: args.max_stretch,
+            extreme_stretch: args.extreme_stretch,
+            resample_quality: args.resample_quality,
+            oversampling: args.oversampling,
+            bpm: args.bpm,
             cancel_receiver: Arc::new(cancel_receiver),
         });
     });
@@ -160,20 +309,52 @@ fn main() {
                     eprintln!("Error: {}", err);
                     break;
             }
-            PermuteUpdate::ProcessComplete(permutations) => {
+            PermuteUpdate::ProcessComplete(permutations, diversity) => {
                     println!("Processing complete");
+                    if let Some(diversity) = diversity {
+                        println!("Diversity {:.0}%", diversity * 100.0);
+                    }
                     if let Some(permutations) = permutations {
                         print_processor_attributes(&permutations);
+                        if save_chains {
+                            for permutation in permutations.iter() {
+                                let path = format!("{}.chain.json", permutation.output);
+                                if let Err(err) = save_chain(&path, &permutation.processors) {
+                                    eprintln!("Error saving chain {}: {}", path, err);
+                                }
+                            }
+                        }
                     }
                     break;
             }
-            PermuteUpdate::AudioInfoGenerated(file, _) => {
+            PermuteUpdate::AudioInfoGenerated(file, audio_info) => {
                     println!("Generated audio info for {}", file);
+                    if !audio_info.quality_flags.is_empty() {
+                        let flags: Vec<String> = audio_info.quality_flags.iter().map(|f| quality_flag_description(*f)).collect();
+                        println!("Quality flags for {}: {}", file, flags.join(", "));
+                    }
+            }
+            PermuteUpdate::CandidateRejected(permutation, reasons) => {
+                    println!("Rejected candidate for {}: {}", permutation.output, reasons.join(", "));
             }
         }
     }
 }
 
+// Load the ratings file and record any new ratings in it
+fn add_ratings(path: &str, rate: &[(String, Rating)]) -> Result<RatingsStore, String> {
+    let mut ratings = load_ratings(path)?;
+    if rate.is_empty() {
+        return Ok(ratings);
+    }
+    for (output, rating) in rate {
+        let chain = load_chain(&format!("{}.chain.json", output))?;
+        ratings.record(output.clone(), *rating, chain);
+    }
+    save_ratings(path, &ratings).map_err(|e| format!("Could not save ratings to {}: {}", path, e))?;
+    Ok(ratings)
+}
+
 fn print_processor_attributes(perms: &Vec<Permutation>) {
     println!("\nProcessor Attributes:");
     for permutation in perms {
@@ -9,7 +9,8 @@ use crate::permute_files::PermuteUpdate;
 
 #[derive(Debug)]
 pub enum PermuteError {
-    SendError(SendError<PermuteUpdate>),
+    // Boxed as updates carry a whole permutation, which would make every result as big
+    SendError(Box<SendError<PermuteUpdate>>),
     Snd(SndFileError),
     IO(io::Error),
     Filter(FilterErrors),
@@ -24,7 +25,7 @@ impl From<SndFileError> for PermuteError {
 
 impl From<SendError<PermuteUpdate>> for PermuteError {
     fn from(error: SendError<PermuteUpdate>) -> Self {
-        PermuteError::SendError(error)
+        PermuteError::SendError(Box::new(error))
     }
 }
 
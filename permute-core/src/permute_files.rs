@@ -1,26 +1,53 @@
 use crate::{
+    chain_rules::{ChainRules, ChainTemplate, TemplateSlot},
+    complexity::ComplexityModel,
+    evolve::{evolve_chain, parse_attribute_value, recorded_attribute, Evolution},
     files::*, permute_error::PermuteError, 
     process::*, 
     random_process::*, 
     audio_cache::AUDIO_CACHE,
+    processors::{
+        oversample::Oversampling, resample::ResampleQuality, stereo::promote_to_stereo, time_pitch::change_sample_rate,
+    },
+    random_processors::{
+        parameter_ranges::{validate_parameter_ranges_for_sample_rate, widen_ranges, ParameterRanges},
+        random_parallel::{random_dry_wet, with_dry_wet},
+        random_time_pitch::high_sample_rate,
+    },
+    similarity::{diversity, most_similar, OutputFingerprint},
 };
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use sndfile::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
-use audio_info::AudioInfo;
+use audio_info::{
+    describe, detect_bpm, quality::quality_flag_description, AudioInfo, DescriptorTargets, QualityAction, QualityChecks,
+    QualityFlag,
+};
 use rayon::prelude::*;
 use crossbeam_channel::{Sender, Receiver};
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Chance of any processor being blended back with its input rather than replacing it
+const DRY_WET_CHANCE: f64 = 0.15;
+// Candidates tried per output when outputs are rejected for missing targets or failing quality checks
+const DEFAULT_ATTEMPTS_PER_OUTPUT: usize = 10;
+// Longest audio any chain can make, whatever the stretch limits, so the samples held in memory stay bounded
+pub const MAX_OUTPUT_SECONDS: f64 = 600.0;
 
 pub enum PermuteUpdate {
     Error(String),
     UpdatePermuteNodeStarted(Permutation, PermuteNodeName, PermuteNodeEvent),
     UpdatePermuteNodeCompleted(Permutation, PermuteNodeName, PermuteNodeEvent),
     UpdateSetProcessors(Permutation, Vec<(PermuteNodeName, Vec<ProcessorAttribute>)>),
-    ProcessComplete(Option<Vec<Permutation>>),
+    // The run's outputs, and how different they are from each other from 0 to 1 when there are any to compare
+    ProcessComplete(Option<Vec<Permutation>>, Option<f64>),
     AudioInfoGenerated(String, AudioInfo),
+    // A rendered candidate that missed the descriptor targets or failed a quality check, with the reasons why. It isn't written
+    CandidateRejected(Permutation, Vec<String>),
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +59,24 @@ pub struct PermuteFilesParams {
     pub permutations: usize,
     pub permutation_depth: usize,
     pub processor_pool: Vec<PermuteNodeName>,
+    // How likely each processor is to be picked. Missing processors are 1, 0 excludes them
+    pub processor_weights: HashMap<PermuteNodeName, f64>,
+    // Required, forbidden and repeated processors for every chain
+    pub chain_rules: ChainRules,
+    // Every permutation follows this chain when set, with wildcards drawn from the pool
+    pub template: Option<ChainTemplate>,
+    // Chain lengths, category budgets and parameter intensity. Replaces depth and processor count when set
+    pub complexity: Option<ComplexityModel>,
+    // Breeds every permutation from recorded chains instead of generating from scratch
+    pub evolution: Option<Evolution>,
+    // Only keep outputs whose descriptors fall within these ranges, rendering candidates until enough pass
+    pub descriptor_targets: Option<DescriptorTargets>,
+    // Silence, NaN, DC offset, clipping and duration checks run on every output, to reject or flag it
+    pub quality_checks: QualityChecks,
+    // Outputs more similar than this, from 0 to 1, to an earlier output of the same file are rendered again
+    pub max_similarity: Option<f64>,
+    // Most candidates to render for each output when they are rejected. Defaults to 10
+    pub max_attempts: Option<usize>,
     pub normalise_at_end: bool,
     pub trim_all: bool,
     pub high_sample_rate: bool,
@@ -40,8 +85,40 @@ pub struct PermuteFilesParams {
     pub update_sender: Arc<Sender<PermuteUpdate>>,
     pub create_subdirectories: bool,
     pub cancel_receiver: Arc<Receiver<()>>,
+    // Length is held to max_stretch whether or not this is set
     pub constrain_length: bool,
     pub max_stretch: f64,
+    // Overrides for the ranges the random processors pick their parameters from
+    pub parameter_ranges: ParameterRanges,
+    // Opt-in stretch limit for the extreme stretch, which goes far past max_stretch. None leaves it out
+    pub extreme_stretch: Option<f64>,
+    pub resample_quality: ResampleQuality,
+    // Oversampling for every distortion. None picks one at random for each processor
+    pub oversampling: Option<Oversampling>,
+    // User supplied tempo. Detected from each file when not set
+    pub bpm: Option<f64>,
+}
+
+// The processors a chain can be made from. The extreme stretch is only picked when it has been given
+// its own limit. Weighted out processors are dropped too, so they don't turn up inside parallel
+// branches or bands. An error when that leaves nothing, rather than ignoring the weights
+pub fn pickable_pool(
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    extreme_stretch: Option<f64>,
+) -> Result<Vec<PermuteNodeName>, String> {
+    let pool: Vec<PermuteNodeName> = processor_pool
+        .iter()
+        .copied()
+        .filter(|p| extreme_stretch.is_some() || *p != PermuteNodeName::ExtremeStretch)
+        .filter(|p| processor_weight(processor_weights, *p) > 0.0)
+        .collect();
+    if pool.is_empty() {
+        return Err(String::from(
+            "No processors to pick from. Every processor in the pool is weighted to 0, or is the extreme stretch without its own limit",
+        ));
+    }
+    Ok(pool)
 }
 
 pub fn permute_files(mut params: PermuteFilesParams) -> JoinHandle<()> {
@@ -58,19 +135,20 @@ pub fn permute_files(mut params: PermuteFilesParams) -> JoinHandle<()> {
         params.output = output;
         params.create_subdirectories = false;
         let output_permutations = Arc::new(Mutex::new(Vec::new()));
+        let output_fingerprints = Arc::new(Mutex::new(Vec::new()));
+        let cancelled = AtomicBool::new(false);
         
         // Process files in parallel using rayon
         params.files.par_iter().for_each(|file| {
-            if params.cancel_receiver.try_recv().is_ok() {
-                params.update_sender.send(PermuteUpdate::ProcessComplete(None))
-                    .expect("Error sending message");
+            if is_cancelled(&params, &cancelled) {
                 return;
             }
 
-            let result = permute_file(&params, file.clone());
+            let result = permute_file(&params, file.clone(), &cancelled);
             match result {
-                Ok(permutations) => {
+                Ok((permutations, fingerprints)) => {
                     output_permutations.lock().unwrap().extend(permutations);
+                    output_fingerprints.lock().unwrap().push(fingerprints);
                 }
                 Err(err) => {
                     params.update_sender.send(PermuteUpdate::Error(err.to_string()))
@@ -79,21 +157,35 @@ pub fn permute_files(mut params: PermuteFilesParams) -> JoinHandle<()> {
             }
         });
 
-        params
-            .update_sender
-            .send(PermuteUpdate::ProcessComplete(Some(output_permutations.lock().unwrap().clone())))
-            .expect("Error sending message");
+        let complete = if cancelled.load(Ordering::Relaxed) {
+            PermuteUpdate::ProcessComplete(None, None)
+        } else {
+            PermuteUpdate::ProcessComplete(
+                Some(output_permutations.lock().unwrap().clone()),
+                diversity(&output_fingerprints.lock().unwrap()),
+            )
+        };
+        params.update_sender.send(complete).expect("Error sending message");
     })
     .expect("Error creating thread")
 }
 
+// Cancelling sends a single message, it's kept once seen so every file stops
+fn is_cancelled(params: &PermuteFilesParams, cancelled: &AtomicBool) -> bool {
+    if params.cancel_receiver.try_recv().is_ok() {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    cancelled.load(Ordering::Relaxed)
+}
+
 
 // permute_file is used to generate a list of processor plans and parameters, then run them for each output file
 // It is called once for each file in the input list
 fn permute_file(
     params: &PermuteFilesParams,
     file: String,
-) -> Result<Vec<Permutation>, PermuteError> {
+    cancelled: &AtomicBool,
+) -> Result<(Vec<Permutation>, Vec<OutputFingerprint>), PermuteError> {
     // Open the file and get metadata
     let snd = sndfile::OpenOptions::ReadOnly(ReadOptions::Auto).from_path(file.clone())?;
     let sample_rate = snd.get_samplerate();
@@ -106,6 +198,7 @@ fn permute_file(
     let endian = snd.get_endian();
     // Get samples. Either from audio cache or from file
     let samples_64 = AUDIO_CACHE.get_samples(&file)?;
+    let bpm = params.bpm.or_else(|| detect_bpm(&samples_64, channels, sample_rate));
 
     // Create input and output buffers
     let input_trail_buffer =
@@ -115,6 +208,12 @@ fn permute_file(
     let samples_64 = [input_trail_buffer, samples_64.to_vec(), output_trail_buffer].concat();
     let sample_length = samples_64.len();
 
+    validate_parameter_ranges_for_sample_rate(&params.parameter_ranges, sample_rate)
+        .map_err(|e| PermuteError::IO(std::io::Error::other(e)))?;
+    let processor_pool = pickable_pool(&params.processor_pool, &params.processor_weights, params.extreme_stretch)
+        .map_err(|e| PermuteError::IO(std::io::Error::other(e)))?;
+    let parameter_ranges = Arc::new(params.parameter_ranges.clone());
+
     // set output directory
     let output = match params.create_subdirectories {
         true => {
@@ -124,26 +223,47 @@ fn permute_file(
         false => params.output.clone(),
     };
     
-    // Generate ordered list of processor plans and parameters for each output file
-    // Each file will have a different ordered list of processor plans
-    let mut outputs_processor_plans: Vec<(String, ProcessorParams, Vec<ProcessorPlan>)> = vec![];
-    for i in 1..=params.permutations {
+    // Generate an ordered list of processor plans and parameters for an output file
+    // Each output will have a different ordered list of processor plans
+    let plan_output = |i: usize| -> Result<(ProcessorParams, Vec<ProcessorPlan>), PermuteError> {
         let output_i = generate_file_name(file.clone(), output.clone(), i, params.output_file_as_wav);
 
+        // A bred chain sets the structure, its nodes keep their parameters near their parent's
+        let evolved = params
+            .evolution
+            .as_ref()
+            .map(|e| evolve_chain(e, &processor_pool, &params.processor_weights, &mut thread_rng()));
+        let template = match &evolved {
+            Some(chain) => Some(chain.iter().map(|n| TemplateSlot::Processor(n.name)).collect()),
+            None => params.template.clone(),
+        };
+        // Picked and bred nodes come after the sample rate conversion, if there is one
+        let chain_offset = params.high_sample_rate as usize;
+
         // Generate a random ordered list of processors
         let processors = generate_processor_sequence(GetProcessorNodeParams { 
             depth: params.permutation_depth,
             normalise_at_end: params.normalise_at_end,
             trim_at_end: params.trim_all,
-            processor_pool: params.processor_pool.clone(),
+            processor_pool: processor_pool.clone(),
+            processor_weights: params.processor_weights.clone(),
+            chain_rules: params.chain_rules.clone(),
+            template,
+            complexity: params.complexity.clone(),
             high_sample_rate: params.high_sample_rate,
             processor_count: params.processor_count,
+            extreme_stretch: params.extreme_stretch,
             constrain_length: params.constrain_length,
             rng: thread_rng(),
-            original_depth: params.permutation_depth,
         });
         let mut processor_plans: Vec<ProcessorPlan> = vec![];
-        let mut node_index = 0;
+        let mut node_index: usize = 0;
+        // Later processors are planned against the audio as it will be once a stereo processor has run
+        let mut plan_channels = channels;
+        let mut plan_samples = samples_64.clone();
+        let mut plan_bpm = bpm;
+        // and at the rate it will be once the high sample rate conversion has run
+        let mut plan_sample_rate = sample_rate;
         let mut last_params: ProcessorParams = ProcessorParams::default();
         // set the processor names so we have an ok idea of overall progress. 
         // Length of this vec is used to determine overall progress
@@ -151,16 +271,37 @@ fn permute_file(
         .map(|p| PermutationProcessor {
             name: p.clone(),
             attributes: vec![],
+            branches: vec![],
         })
         .collect::<Vec<PermutationProcessor>>();
         for processor in processors.iter() {
             let processor_plan_gen = get_processor_plan(*processor);
+            let evolved_node = evolved
+                .as_ref()
+                .and_then(|chain| chain.get(node_index.checked_sub(chain_offset)?))
+                .filter(|n| n.name == *processor);
+            let node_parameter_ranges = match evolved_node {
+                Some(node) => {
+                    let mut ranges = params.parameter_ranges.clone();
+                    ranges.entry(node.name).or_default().extend(node.parameter_ranges.clone());
+                    Arc::new(ranges)
+                }
+                None => parameter_ranges.clone(),
+            };
+            // Ranges open up further down the chain
+            let node_parameter_ranges = match &params.complexity {
+                Some(complexity) if complexity.intensity > 0.0 => {
+                    let amount = complexity.intensity_at(node_index.saturating_sub(chain_offset));
+                    Arc::new(widen_ranges(&node_parameter_ranges, *processor, amount, sample_rate, &mut thread_rng()))
+                }
+                _ => node_parameter_ranges,
+            };
 
             let mut processor_params = ProcessorParams {
-                samples: samples_64.clone(),
-                sample_length,
-                channels,
-                sample_rate,
+                sample_length: plan_samples.len(),
+                samples: plan_samples.clone(),
+                channels: plan_channels,
+                sample_rate: plan_sample_rate,
                 file_format,
                 sub_format,
                 endian,
@@ -169,65 +310,178 @@ fn permute_file(
                     file: file.clone(),
                     permutation_index: i,
                     output: output_i.clone(),
-                    processor_pool: params.processor_pool.clone(),
+                    processor_pool: processor_pool.clone(),
+                    processor_weights: params.processor_weights.clone(),
                     processors: last_params.permutation.processors.clone(),
                     original_sample_rate: sample_rate,
                     node_index: node_index,
                     files: params.files.clone(),
+                    resample_quality: params.resample_quality,
+                    oversampling: params.oversampling,
+                    bpm: plan_bpm,
+                    parameter_ranges: node_parameter_ranges,
+                    extreme_stretch: params.extreme_stretch,
+                    evolved_node: evolved_node.cloned().map(Arc::new),
                 },
             };
-            let processor_plan = processor_plan_gen(&mut processor_params);
+            let mut processor_plan = processor_plan_gen(&mut processor_params);
+            // A bred node keeps its parent's blend
+            let recorded_mix = recorded_attribute(&processor_params, "Dry/Wet")
+                .and_then(|mix| parse_attribute_value(&mix));
+            match recorded_mix {
+                Some(mix) => processor_plan = with_dry_wet(processor_plan, (mix / 100.0).clamp(0.0, 1.0)),
+                None if blends_with_dry(*processor) && thread_rng().gen_bool(DRY_WET_CHANCE) => {
+                    processor_plan = random_dry_wet(processor_plan);
+                }
+                None => {}
+            }
             // set the attributes for the processor now that we know them
             processor_params.permutation.processors[node_index].attributes = processor_plan.1.clone();
+            let promotes = processor_promotes_to_stereo(&processor_params.permutation.processors[node_index]);
       
             processor_plans.push(processor_plan);
             last_params = processor_params;
             node_index += 1;
+
+            // Following processors should sync to the sped up or slowed down tempo
+            if changes_tempo(*processor) {
+                let length_factor = processor_plans.last().map_or(1.0, |plan| processor_plan_attribute_factor(&plan.1));
+                plan_bpm = plan_bpm.map(|b| b / length_factor);
+            }
+
+            if promotes && plan_channels == 1 {
+                plan_samples = promote_to_stereo(&plan_samples);
+                plan_channels = 2;
+            }
+            (plan_samples, plan_sample_rate) = convert_plan_audio(*processor, plan_samples, plan_channels, plan_sample_rate, sample_rate)?;
         }  
+        // Processing always starts from the source audio, the stereo processors promote it as they run
+        last_params.samples = samples_64.clone();
+        last_params.sample_length = sample_length;
+        last_params.channels = channels;
+        last_params.sample_rate = sample_rate;
+        last_params.permutation.bpm = bpm;
+        last_params.permutation.evolved_node = None;
 
         // It is quite easy to get a list of processors that will increase the length of the audio way too much
-        let (processor_plans, last_params) = filter_long_processes(processor_plans, last_params, params.max_stretch);
+        let (processor_plans, last_params) = filter_long_processes(processor_plans, last_params, params.max_stretch, params.extreme_stretch);
 
         params.update_sender.send(PermuteUpdate::UpdateSetProcessors(
             last_params.permutation.clone(),
             processor_plans.iter().map(|p| (p.0, p.1.clone())).collect(),
         ))?;
 
-        outputs_processor_plans.push((output_i, last_params.clone(), processor_plans));
+        Ok((last_params, processor_plans))
+    };
+
+    // Plan every output up front, so they all show up before any are rendered
+    let mut outputs_processor_plans: Vec<(ProcessorParams, Vec<ProcessorPlan>)> = vec![];
+    for i in 1..=params.permutations {
+        outputs_processor_plans.push(plan_output(i)?);
     }
 
-    // Run each outputs processors
+    let max_attempts = params.max_attempts.unwrap_or(DEFAULT_ATTEMPTS_PER_OUTPUT);
     let mut output_permutations: Vec<Permutation> = vec![];
+    let mut output_fingerprints: Vec<OutputFingerprint> = vec![];
     let update_sender = params.update_sender.clone();
-    for (_output_i, processor_params, processor_plans) in outputs_processor_plans {
-        let output_params = run_processors(RunProcessorsParams {
-            processor_params: processor_params.clone(),
-            processor_plans,
-        })?;
-
-        output_permutations.push(output_params.permutation.clone());
-
-        let mut snd = sndfile::OpenOptions::WriteOnly(WriteOptions::new(
-            output_params.file_format,
-            output_params.sub_format,
-            output_params.endian,
-            output_params.sample_rate,
-            output_params.channels,
-        ))
-        .from_path(output_params.permutation.output.clone())?;
-
-        snd.write_from_iter(output_params.samples.clone().into_iter())?;
-
-        // Generate audio info for the output file
-        let mut audio_info = AudioInfo::default();
-        if let Ok(()) = audio_info.update_file(output_params.permutation.output.clone()) {
-            update_sender.send(PermuteUpdate::AudioInfoGenerated(
-                output_params.permutation.output.clone(),
-                audio_info,
-            ))?;
+    for (i, planned) in outputs_processor_plans.into_iter().enumerate() {
+        let mut candidate = Some(planned);
+        // Each output has its own attempts, so one hard to meet output doesn't use up the others'
+        let mut attempts = 0;
+        // A rejected candidate is replaced by a new one with the same name, until the attempts run out
+        while let Some((processor_params, processor_plans)) = candidate.take() {
+            if is_cancelled(params, cancelled) {
+                return Ok((output_permutations, output_fingerprints));
+            }
+            attempts += 1;
+            let output_params = run_processors(RunProcessorsParams {
+                processor_params,
+                processor_plans,
+            })?;
+            let (rejections, quality_flags, fingerprint) = check_output(params, &output_params, &output_fingerprints);
+            if rejections.is_empty() {
+                write_output(&output_params, quality_flags, &update_sender)?;
+                output_permutations.push(output_params.permutation);
+                output_fingerprints.push(fingerprint);
+            } else {
+                let mut rejections = rejections;
+                if attempts >= max_attempts {
+                    rejections.push(format!("No output written after {} attempts", attempts));
+                }
+                update_sender.send(PermuteUpdate::CandidateRejected(output_params.permutation, rejections))?;
+                if attempts < max_attempts {
+                    candidate = Some(plan_output(i + 1)?);
+                }
+            }
         }
     }
-    Ok(output_permutations)
+    Ok((output_permutations, output_fingerprints))
+}
+
+// Why a rendered output should be thrown away, and the quality problems to keep it with otherwise
+fn check_output(
+    params: &PermuteFilesParams,
+    output_params: &ProcessorParams,
+    earlier: &[OutputFingerprint],
+) -> (Vec<String>, Vec<QualityFlag>, OutputFingerprint) {
+    let mut rejections = match &params.descriptor_targets {
+        Some(targets) => {
+            targets.failures(&describe(&output_params.samples, output_params.channels, output_params.sample_rate))
+        }
+        None => vec![],
+    };
+    let mut quality_flags = vec![];
+    let failed = params
+        .quality_checks
+        .assess(&output_params.samples, output_params.channels, output_params.sample_rate);
+    for (flag, action) in failed {
+        match action {
+            QualityAction::Reject => rejections.push(quality_flag_description(flag)),
+            _ => quality_flags.push(flag),
+        }
+    }
+    let fingerprint = OutputFingerprint::new(
+        output_params.permutation.output.clone(),
+        &output_params.permutation.processors,
+        &output_params.samples,
+        output_params.channels,
+        output_params.sample_rate,
+    );
+    if let Some(max_similarity) = params.max_similarity {
+        if let Some((closest, similarity)) = most_similar(&fingerprint, earlier).filter(|(_, s)| *s > max_similarity) {
+            rejections.push(format!("{:.0}% similar to {}", similarity * 100.0, closest.output));
+        }
+    }
+    (rejections, quality_flags, fingerprint)
+}
+
+// Write a rendered output and send its audio info
+fn write_output(
+    output_params: &ProcessorParams,
+    quality_flags: Vec<QualityFlag>,
+    update_sender: &Sender<PermuteUpdate>,
+) -> Result<(), PermuteError> {
+    let mut snd = sndfile::OpenOptions::WriteOnly(WriteOptions::new(
+        output_params.file_format,
+        output_params.sub_format,
+        output_params.endian,
+        output_params.sample_rate,
+        output_params.channels,
+    ))
+    .from_path(output_params.permutation.output.clone())?;
+
+    snd.write_from_iter(output_params.samples.clone().into_iter())?;
+
+    // Generate audio info for the output file
+    let mut audio_info = AudioInfo::default();
+    if let Ok(()) = audio_info.update_file(output_params.permutation.output.clone()) {
+        audio_info.quality_flags = quality_flags;
+        update_sender.send(PermuteUpdate::AudioInfoGenerated(
+            output_params.permutation.output.clone(),
+            audio_info,
+        ))?;
+    }
+    Ok(())
 }
 
 
@@ -266,11 +520,19 @@ pub fn process_file(
             output: file.clone(),
             permutation_index: 0,
             processor_pool: vec![process],
+            processor_weights: HashMap::new(),
             processors: vec![PermutationProcessor {
                 name: process,
                 attributes: vec![],
+                branches: vec![],
             }],
             files: vec![file.clone()],
+            resample_quality: ResampleQuality::default(),
+            oversampling: None,
+            bpm: None,
+            parameter_ranges: Arc::new(ParameterRanges::new()),
+            extreme_stretch: None,
+            evolved_node: None,
         },
     };
     let process_plan_fn = get_processor_plan(process);
@@ -288,14 +550,14 @@ pub fn process_file(
         sub_format,
         endian,
         sample_rate,
-        channels,
+        output_params.channels,
     ))
     .from_path(file)?;
 
    snd.write_from_iter(output_params.samples.clone().into_iter())?;
 
     update_sender
-        .send(PermuteUpdate::ProcessComplete(Some(vec![output_params.permutation])))
+        .send(PermuteUpdate::ProcessComplete(Some(vec![output_params.permutation]), None))
         .expect("Error sending message");
     Ok(())
 }
@@ -305,6 +567,34 @@ pub struct RunProcessorsParams {
     pub processor_params: ProcessorParams,
 }
 
+// The audio later nodes are planned against after a sample rate conversion, and its rate
+fn convert_plan_audio(
+    processor: PermuteNodeName,
+    samples: Vec<f64>,
+    channels: usize,
+    sample_rate: usize,
+    original_sample_rate: usize,
+) -> Result<(Vec<f64>, usize), PermuteError> {
+    let new_sample_rate = match processor {
+        PermuteNodeName::SampleRateConversionHigh => high_sample_rate(sample_rate),
+        PermuteNodeName::SampleRateConversionOriginal => original_sample_rate,
+        _ => sample_rate,
+    };
+    if new_sample_rate == sample_rate {
+        return Ok((samples, sample_rate));
+    }
+    let params = ProcessorParams {
+        sample_length: samples.len(),
+        samples,
+        channels,
+        sample_rate,
+        ..ProcessorParams::default()
+    };
+    // Planning only needs the length and timing of the audio, so the quickest quality will do
+    let converted = change_sample_rate(params, new_sample_rate, ResampleQuality::Low)?;
+    Ok((converted.samples, new_sample_rate))
+}
+
 pub fn run_processors(params: RunProcessorsParams) -> Result<ProcessorParams, PermuteError> {
     let mut processor_params = params.processor_params.clone();
     processor_params.permutation.node_index = 0;
@@ -318,38 +608,111 @@ pub fn run_processors(params: RunProcessorsParams) -> Result<ProcessorParams, Pe
 }
 
 
-fn filter_long_processes(processors: Vec<ProcessorPlan>, mut last_params: ProcessorParams, max_stretch: f64) -> (Vec<ProcessorPlan>, ProcessorParams) {
-    // First pass: calculate length factors and mark processors to keep
+// Pull the number out of attribute values like "2.00", "2.0x" or "1500.00 ms"
+fn parse_attribute_number(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| !c.is_ascii_digit())
+        .trim()
+        .parse::<f64>()
+        .ok()
+}
+
+fn processor_plan_attribute_factor(attributes: &[ProcessorAttribute]) -> f64 {
+    attributes
+        .iter()
+        .filter(|attr| attr.key == "Length Factor" || attr.key == "Stretch Factor")
+        .find_map(|attr| parse_attribute_number(&attr.value))
+        .filter(|factor| *factor > 0.0)
+        .unwrap_or(1.0)
+}
+
+fn parse_attribute_seconds(value: &str) -> Option<f64> {
+    let number = parse_attribute_number(value)?;
+    if value.trim().ends_with("ms") {
+        Some(number / 1000.0)
+    } else {
+        Some(number)
+    }
+}
+
+// Length in seconds after a processor has run. Stretches multiply it, reverb style tails and tape curves add to it
+fn estimate_length(length: f64, attributes: &[ProcessorAttribute]) -> f64 {
+    let mut processor_stretch = 1.0;
+    let mut processor_tail = 0.0;
+
+    for attr in attributes {
+        match attr.key.as_str() {
+            "Length Factor" | "Stretch Factor" => {
+                if let Some(factor) = parse_attribute_number(&attr.value) {
+                    processor_stretch = factor;
+                }
+            },
+            "Tail" | "Added Length" => {
+                if let Some(tail) = parse_attribute_seconds(&attr.value) {
+                    processor_tail = tail;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    length * processor_stretch + processor_tail
+}
+
+// Branches all start from the same audio, so the output is as long as the longest branch
+fn estimate_processor_length(length: f64, processor: &PermutationProcessor) -> f64 {
+    let branch_length = processor
+        .branches
+        .iter()
+        .map(|branch| branch.iter().fold(length, estimate_processor_length))
+        .fold(length, f64::max);
+    estimate_length(branch_length, &processor.attributes)
+}
+
+fn filter_long_processes(
+    processors: Vec<ProcessorPlan>,
+    mut last_params: ProcessorParams,
+    max_stretch: f64,
+    extreme_stretch: Option<f64>,
+) -> (Vec<ProcessorPlan>, ProcessorParams) {
     let mut filtered_processors = Vec::new();
     let mut filtered_processor_info = Vec::new();
-    let mut cumulative_stretch = 1.0; // Start at 1 since it's multiplicative
+    // Track the estimated output length in seconds. Stretches multiply it, reverb style tails add to it
+    let frames = last_params.samples.len() / last_params.channels.max(1);
+    let original_length = frames as f64 / last_params.sample_rate.max(1) as f64;
+    let max_length = original_length * max_stretch;
+    let mut length = original_length;
+    // How far extreme stretches have taken the audio. The other processors are held to max_stretch on top of it
+    let mut extreme_factor = 1.0;
 
     for (i, (name, attributes, closure)) in processors.into_iter().enumerate() {
-        let mut processor_stretch = 1.0;
-        
-        // Find stretch/length factor in attributes
-        for attr in &attributes {
-            match attr.key.as_str() {
-                "Length Factor" | "Stretch Factor" => {
-                    if let Ok(factor) = attr.value.parse::<f64>() {
-                        processor_stretch = factor;
-                        break;
-                    }
-                },
-                _ => {}
-            }
-        }
+        let new_length = match last_params.permutation.processors.get(i) {
+            Some(processor) => estimate_processor_length(length, processor),
+            None => estimate_length(length, &attributes),
+        };
 
-        // Calculate new cumulative stretch
-        let new_cumulative = cumulative_stretch * processor_stretch;
-        
-        // Only keep processor if it doesn't exceed max_stretch
-        if new_cumulative <= max_stretch {
-            cumulative_stretch = new_cumulative;
+        // Only keep processor if it doesn't take the audio past max_stretch times the original length.
+        // An extreme stretch is measured against its own limit instead. Nothing can lengthen the audio past
+        // MAX_OUTPUT_SECONDS
+        let keep = if new_length > MAX_OUTPUT_SECONDS && new_length > length {
+            false
+        } else if name == PermuteNodeName::ExtremeStretch {
+            let factor = new_length / length.max(f64::EPSILON);
+            let keep = extreme_stretch.is_some_and(|limit| factor <= limit);
+            if keep {
+                extreme_factor *= factor;
+            }
+            keep
+        } else {
+            new_length <= max_length * extreme_factor
+        };
+        if keep {
+            length = new_length;
             filtered_processors.push((name, attributes.clone(), closure));
             filtered_processor_info.push(last_params.permutation.processors[i].clone());
         } else {
-            println!("Filtering out processor {:?} as it would increase stretch to {}", name, new_cumulative);
+            println!("Filtering out processor {:?} as it would increase stretch to {}", name, new_length / original_length);
         }
     }
 
@@ -358,4 +721,76 @@ fn filter_long_processes(processors: Vec<ProcessorPlan>, mut last_params: Proces
     last_params.permutation.node_index = 0;
 
     (filtered_processors, last_params)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random_processors::{parameter_ranges::ParameterRange, utils::format_float_ms};
+
+    const SAMPLE_RATE: usize = 44100;
+
+    #[test]
+    fn delays_after_a_high_sample_rate_conversion_keep_their_length_in_seconds() {
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let ranges = ParameterRanges::from([(
+            PermuteNodeName::RhythmicDelay,
+            HashMap::from([
+                (String::from("Delay"), ParameterRange { min: 500.0, max: 500.0 }),
+                (String::from("Feedback"), ParameterRange { min: 0.0, max: 0.0 }),
+            ]),
+        )]);
+        // An impulse a tenth of a second in, with room for its echo
+        let mut samples = vec![0.0; SAMPLE_RATE];
+        samples[SAMPLE_RATE / 10] = 1.0;
+        let source = ProcessorParams {
+            sample_length: samples.len(),
+            samples: samples.clone(),
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            update_sender: Arc::new(sender),
+            permutation: Permutation {
+                original_sample_rate: SAMPLE_RATE,
+                parameter_ranges: Arc::new(ranges),
+                ..Permutation::default()
+            },
+            ..ProcessorParams::default()
+        };
+
+        let chain = [
+            PermuteNodeName::SampleRateConversionHigh,
+            PermuteNodeName::RhythmicDelay,
+            PermuteNodeName::SampleRateConversionOriginal,
+        ];
+        let mut plans = vec![];
+        let mut plan_sample_rate = SAMPLE_RATE;
+        for processor in chain {
+            let mut params = ProcessorParams {
+                sample_length: samples.len(),
+                samples: samples.clone(),
+                sample_rate: plan_sample_rate,
+                ..source.clone()
+            };
+            plans.push(get_processor_plan(processor)(&mut params));
+            (samples, plan_sample_rate) = convert_plan_audio(processor, samples, 1, plan_sample_rate, SAMPLE_RATE).unwrap();
+        }
+        assert_eq!(plan_sample_rate, SAMPLE_RATE);
+        let delay = plans[1].1.iter().find(|a| a.key == "Delay").unwrap();
+        assert_eq!(delay.value, format_float_ms(500.0));
+
+        let output = run_processors(RunProcessorsParams {
+            processor_params: source,
+            processor_plans: plans,
+        })
+        .unwrap();
+        assert_eq!(output.sample_rate, SAMPLE_RATE);
+        let after_impulse = SAMPLE_RATE * 3 / 10;
+        let echo = output.samples[after_impulse..]
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .map(|(i, _)| after_impulse + i)
+            .unwrap();
+        let echo_seconds = echo as f64 / SAMPLE_RATE as f64;
+        assert!((echo_seconds - 0.6).abs() < 0.001, "{}", echo_seconds);
+    }
+}
@@ -5,12 +5,16 @@ use strum::EnumIter;
 use crossbeam_channel::Sender;
 
 // Standard library
+use std::collections::HashMap;
 use std::sync::Arc;
 
 // Internal modules
 use crate::{
+    evolve::EvolvedNode,
     permute_error::PermuteError,
     permute_files::PermuteUpdate,
+    processors::{oversample::Oversampling, resample::ResampleQuality},
+    random_processors::parameter_ranges::ParameterRanges,
 };
 
 pub type ProcessorPlanGenerator = fn(&mut ProcessorParams) -> ProcessorPlan;
@@ -56,10 +60,22 @@ pub struct Permutation {
     pub permutation_index: usize,
     pub output: String,
     pub processor_pool: Vec<PermuteNodeName>,
+    pub processor_weights: HashMap<PermuteNodeName, f64>,
     pub processors: Vec<PermutationProcessor>,
     pub original_sample_rate: usize,
     pub node_index: usize,
     pub files: Vec<String>,
+    pub resample_quality: ResampleQuality,
+    // Oversampling for every distortion. None picks one at random for each processor
+    pub oversampling: Option<Oversampling>,
+    // Tempo of the audio at this point in the chain, if known
+    pub bpm: Option<f64>,
+    // User ranges for processor parameters, shared as every node gets a copy of the permutation
+    pub parameter_ranges: Arc<ParameterRanges>,
+    // Separate opt-in stretch limit for the extreme stretch. It isn't picked when not set
+    pub extreme_stretch: Option<f64>,
+    // The bred node being planned, whose recorded choices the processor keeps
+    pub evolved_node: Option<Arc<EvolvedNode>>,
 }
 
 impl Default for Permutation {
@@ -69,22 +85,31 @@ impl Default for Permutation {
             permutation_index: 0, 
             output: String::new(), 
             processor_pool: vec![], 
+            processor_weights: HashMap::new(),
             processors: vec![], 
             original_sample_rate: 0, 
             node_index: 0, 
-            files: vec![] 
+            files: vec![],
+            resample_quality: ResampleQuality::default(),
+            oversampling: None,
+            bpm: None,
+            parameter_ranges: Arc::new(ParameterRanges::new()),
+            extreme_stretch: None,
+            evolved_node: None,
         }
     }
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermutationProcessor {
     pub name: PermuteNodeName,
     pub attributes: Vec<ProcessorAttribute>,
+    // Sub-chains run inside this processor, e.g. the two sides of a parallel split
+    pub branches: Vec<Vec<PermutationProcessor>>,
 }       
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessorAttribute {
     pub key: String,
     pub value: String,
@@ -128,10 +153,34 @@ pub enum PermuteNodeName {
     CrossMix,
     CrossGrain,
     BlurStretch,
+    Compressor,
+    Limiter,
+    Expander,
+    TransientShaper,
+    Decorrelate,
+    AutoPan,
+    StereoWidth,
+    Haas,
+    Glitch,
+    GranularCloud,
+    FrequencyShifter,
+    RingModulator,
+    Resonator,
+    AutoWah,
+    EnvelopePitch,
+    EnvelopeDelay,
+    EnvelopeDrive,
+    Multiband,
+    Parallel,
+    TapeStop,
+    TapeStart,
+    SpinBack,
+    Varispeed,
+    ExtremeStretch,
 }
 
 // Only processors we want to be visible to users
-pub const ALL_PROCESSORS: [PermuteNodeName; 25] = [
+pub const ALL_PROCESSORS: [PermuteNodeName; 49] = [
     PermuteNodeName::GranularTimeStretch,
     PermuteNodeName::Fuzz,
     PermuteNodeName::Saturate,
@@ -164,4 +213,145 @@ pub const ALL_PROCESSORS: [PermuteNodeName; 25] = [
     PermuteNodeName::CrossMix,
     PermuteNodeName::CrossGrain,
     PermuteNodeName::BlurStretch,
-];
\ No newline at end of file
+    PermuteNodeName::Compressor,
+    PermuteNodeName::Limiter,
+    PermuteNodeName::Expander,
+    PermuteNodeName::TransientShaper,
+    PermuteNodeName::Decorrelate,
+    PermuteNodeName::AutoPan,
+    PermuteNodeName::StereoWidth,
+    PermuteNodeName::Haas,
+    PermuteNodeName::Glitch,
+    PermuteNodeName::GranularCloud,
+    PermuteNodeName::FrequencyShifter,
+    PermuteNodeName::RingModulator,
+    PermuteNodeName::Resonator,
+    PermuteNodeName::AutoWah,
+    PermuteNodeName::EnvelopePitch,
+    PermuteNodeName::EnvelopeDelay,
+    PermuteNodeName::EnvelopeDrive,
+    PermuteNodeName::Multiband,
+    PermuteNodeName::Parallel,
+    PermuteNodeName::TapeStop,
+    PermuteNodeName::TapeStart,
+    PermuteNodeName::SpinBack,
+    PermuteNodeName::Varispeed,
+    PermuteNodeName::ExtremeStretch,
+];
+
+// Processors that speed up or slow down the audio, changing its tempo by their length factor
+pub fn changes_tempo(name: PermuteNodeName) -> bool {
+    matches!(
+        name,
+        PermuteNodeName::DoubleSpeed
+            | PermuteNodeName::HalfSpeed
+            | PermuteNodeName::RandomPitch
+            | PermuteNodeName::GranularTimeStretch
+            | PermuteNodeName::BlurStretch
+            | PermuteNodeName::Varispeed
+            | PermuteNodeName::ExtremeStretch
+    )
+}
+
+// Processors that can be blended back with their unprocessed input. Utilities and containers with
+// their own mix are left alone, as are tempo changes so the tempo of the audio stays known
+pub fn blends_with_dry(name: PermuteNodeName) -> bool {
+    !changes_tempo(name)
+        && !matches!(
+            name,
+            PermuteNodeName::Normalise
+                | PermuteNodeName::Trim
+                | PermuteNodeName::SampleRateConversionHigh
+                | PermuteNodeName::SampleRateConversionOriginal
+                | PermuteNodeName::Parallel
+        )
+}
+
+// Broad groups of processors, the same as the groups shown to users
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
+pub enum ProcessorCategory {
+    TimePitch,
+    Modulation,
+    FilterDrive,
+    DelayReverb,
+    Stereo,
+    Dynamics,
+    CrossProcessing,
+    Routing,
+    // Processors that aren't picked at random, e.g. normalise
+    Utility,
+}
+
+pub fn processor_category(name: PermuteNodeName) -> ProcessorCategory {
+    match name {
+        PermuteNodeName::HalfSpeed
+        | PermuteNodeName::DoubleSpeed
+        | PermuteNodeName::RandomPitch
+        | PermuteNodeName::GranularTimeStretch
+        | PermuteNodeName::BlurStretch
+        | PermuteNodeName::Reverse
+        | PermuteNodeName::Glitch
+        | PermuteNodeName::GranularCloud
+        | PermuteNodeName::EnvelopePitch
+        | PermuteNodeName::TapeStop
+        | PermuteNodeName::TapeStart
+        | PermuteNodeName::SpinBack
+        | PermuteNodeName::Varispeed
+        | PermuteNodeName::ExtremeStretch => ProcessorCategory::TimePitch,
+        PermuteNodeName::Wow
+        | PermuteNodeName::Flutter
+        | PermuteNodeName::Chorus
+        | PermuteNodeName::Flange
+        | PermuteNodeName::Phaser
+        | PermuteNodeName::Tremolo
+        | PermuteNodeName::Lazer
+        | PermuteNodeName::FrequencyShifter
+        | PermuteNodeName::RingModulator => ProcessorCategory::Modulation,
+        PermuteNodeName::Fuzz
+        | PermuteNodeName::Saturate
+        | PermuteNodeName::OscillatingFilter
+        | PermuteNodeName::LineFilter
+        | PermuteNodeName::Filter
+        | PermuteNodeName::Resonator
+        | PermuteNodeName::AutoWah
+        | PermuteNodeName::EnvelopeDrive => ProcessorCategory::FilterDrive,
+        PermuteNodeName::MetallicDelay
+        | PermuteNodeName::RhythmicDelay
+        | PermuteNodeName::Reverb
+        | PermuteNodeName::EnvelopeDelay => ProcessorCategory::DelayReverb,
+        PermuteNodeName::Decorrelate
+        | PermuteNodeName::AutoPan
+        | PermuteNodeName::StereoWidth
+        | PermuteNodeName::Haas => ProcessorCategory::Stereo,
+        PermuteNodeName::Compressor
+        | PermuteNodeName::Limiter
+        | PermuteNodeName::Expander
+        | PermuteNodeName::TransientShaper => ProcessorCategory::Dynamics,
+        PermuteNodeName::CrossGain
+        | PermuteNodeName::CrossFilter
+        | PermuteNodeName::CrossDistort
+        | PermuteNodeName::CrossMix
+        | PermuteNodeName::CrossGrain => ProcessorCategory::CrossProcessing,
+        PermuteNodeName::Multiband | PermuteNodeName::Parallel => ProcessorCategory::Routing,
+        PermuteNodeName::Normalise
+        | PermuteNodeName::Trim
+        | PermuteNodeName::SampleRateConversionHigh
+        | PermuteNodeName::SampleRateConversionOriginal => ProcessorCategory::Utility,
+    }
+}
+
+// Processors that always output stereo, turning a mono input into two channels
+pub fn promotes_to_stereo(name: PermuteNodeName) -> bool {
+    matches!(
+        name,
+        PermuteNodeName::Decorrelate
+            | PermuteNodeName::AutoPan
+            | PermuteNodeName::StereoWidth
+            | PermuteNodeName::Haas
+    )
+}
+
+// Whether the processor, or anything running in its branches, turns mono into stereo
+pub fn processor_promotes_to_stereo(processor: &PermutationProcessor) -> bool {
+    promotes_to_stereo(processor.name) || processor.branches.iter().flatten().any(processor_promotes_to_stereo)
+}
\ No newline at end of file
@@ -3,6 +3,7 @@ use crate::{
 process::ProcessorParams,
 permute_error::PermuteError,
 processors::gain_distortion::{DistortionAlgorithm, apply_distortion, calculate_rms},
+processors::oversample::{oversample_process, Oversampling},
 rms_cache::{get_cached_rms, cache_rms},
 audio_cache::AUDIO_CACHE,
 };
@@ -185,7 +186,7 @@ pub struct CrossGrainParams {
 /// Find the next zero crossing at or after `nominal`, stepping by `channels`,
 /// searching up to `max_search` samples ahead. Falls back to the channel-aligned
 /// nominal position if none is found.
-fn find_zero_crossing(samples: &[f64], nominal: usize, max_search: usize, channels: usize) -> usize {
+pub(crate) fn find_zero_crossing(samples: &[f64], nominal: usize, max_search: usize, channels: usize) -> usize {
     let aligned = (nominal / channels) * channels;
     let limit = (aligned + max_search).min(samples.len().saturating_sub(channels));
     let mut pos = aligned;
@@ -198,7 +199,7 @@ fn find_zero_crossing(samples: &[f64], nominal: usize, max_search: usize, channe
     aligned
 }
 
-fn apply_grain_fade(samples: &[f64], blend: usize, fade_in: bool, fade_out: bool) -> Vec<f64> {
+pub(crate) fn apply_grain_fade(samples: &[f64], blend: usize, fade_in: bool, fade_out: bool) -> Vec<f64> {
     let len = samples.len();
     let blend = blend.min(len / 2);
     let mut result = samples.to_vec();
@@ -282,6 +283,7 @@ pub struct CrossDistortParams {
     pub window_size_ms: f64,
     pub algorithm: DistortionAlgorithm,
     pub invert: bool,
+    pub oversampling: Oversampling,
 }
 
 pub fn cross_distort(params: &ProcessorParams, distort_params: &CrossDistortParams) -> Result<ProcessorParams, PermuteError> {
@@ -293,10 +295,8 @@ pub fn cross_distort(params: &ProcessorParams, distort_params: &CrossDistortPara
         params.sample_rate,
     )?;
 
-    let mut new_samples = params.samples.clone();
-    
     // Process each sample
-    for (i, sample) in new_samples.iter_mut().enumerate() {
+    let new_samples = oversample_process(&params.samples, params.channels, distort_params.oversampling, |i, sample| {
         let rms = if distort_params.invert {
             1.0 - rms_signal[i]
         } else {
@@ -307,8 +307,8 @@ pub fn cross_distort(params: &ProcessorParams, distort_params: &CrossDistortPara
         let factor = distort_params.min_factor + (rms * (distort_params.max_factor - distort_params.min_factor));
         
         // Apply the selected distortion algorithm
-        *sample = apply_distortion(*sample, factor, distort_params.algorithm);
-    }
+        apply_distortion(sample, factor, distort_params.algorithm)
+    });
 
     Ok(ProcessorParams {
         samples: new_samples,
@@ -230,6 +230,30 @@ fn modulated_comb_filter(
     comb_samples
 }
 
+// Feedback comb tuned by a fractional delay, so it can ring at a pitch.
+// Damping (0 - 1) is a one pole low pass in the feedback path, making higher harmonics die away first
+pub fn feedback_comb_filter(samples: &[f64], delay_samples: f64, feedback: f64, damping: f64) -> Vec<f64> {
+    let delay_samples = delay_samples.max(1.0);
+    let whole = delay_samples.floor() as usize;
+    let frac = delay_samples - whole as f64;
+    let mut combed = vec![0_f64; samples.len()];
+    let mut filtered = 0_f64;
+    for i in 0..samples.len() {
+        let delayed = if i > whole {
+            let a = combed[i - whole];
+            let b = combed[i - whole - 1];
+            a + (b - a) * frac
+        } else if i == whole {
+            combed[0] * (1.0 - frac)
+        } else {
+            0.0
+        };
+        filtered = delayed * (1.0 - damping) + filtered * damping;
+        combed[i] = samples[i] + filtered * feedback;
+    }
+    combed
+}
+
 // All pass filter for reverb
 fn all_pass(samples: &Vec<f64>, delay_ms: f64, decay_factor: f64, sample_rate: usize) -> Vec<f64> {
     let delay_samples = ((sample_rate as f64) / (1000.0 / delay_ms)) as usize;
@@ -0,0 +1,439 @@
+use std::collections::VecDeque;
+
+use crate::{
+    permute_error::PermuteError,
+    process::ProcessorParams,
+    processors::cross::get_sidechain_rms_signal,
+};
+
+// Floor for level detection so silence doesn't produce -inf
+pub(crate) const MIN_DB: f64 = -120.0;
+
+pub fn db_to_gain(db: f64) -> f64 {
+    10_f64.powf(db / 20.0)
+}
+
+pub fn gain_to_db(gain: f64) -> f64 {
+    (20.0 * gain.abs().log10()).max(MIN_DB)
+}
+
+// One pole smoothing coefficient for a given time constant
+pub fn time_coefficient(ms: f64, sample_rate: usize) -> f64 {
+    if ms <= 0.0 {
+        return 0.0;
+    }
+    (-1.0 / ((ms / 1000.0) * sample_rate as f64)).exp()
+}
+
+// Peak of each frame across channels, so stereo images don't shift under gain changes
+pub(crate) fn frame_peaks(samples: &[f64], channels: usize) -> Vec<f64> {
+    samples
+        .chunks(channels.max(1))
+        .map(|frame| frame.iter().fold(0_f64, |a, b| a.max(b.abs())))
+        .collect()
+}
+
+// Attack/release follower in the dB domain
+pub(crate) fn follow_db(levels_db: &[f64], attack: f64, release: f64) -> Vec<f64> {
+    let mut envelope = MIN_DB;
+    levels_db
+        .iter()
+        .map(|level| {
+            let coefficient = if *level > envelope { attack } else { release };
+            envelope = level + coefficient * (envelope - level);
+            envelope
+        })
+        .collect()
+}
+
+fn apply_frame_gains_db(params: &ProcessorParams, gains_db: &[f64]) -> ProcessorParams {
+    let channels = params.channels.max(1);
+    let new_samples = params
+        .samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| s * db_to_gain(gains_db[(i / channels).min(gains_db.len() - 1)]))
+        .collect();
+    ProcessorParams {
+        samples: new_samples,
+        ..params.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompressorParams {
+    pub threshold_db: f64,
+    pub ratio: f64,
+    pub knee_db: f64,
+    pub attack_ms: f64,
+    pub release_ms: f64,
+    pub makeup_db: f64,
+    // Raise quiet material up towards the threshold instead of pulling loud material down
+    pub upward: bool,
+    pub max_boost_db: f64,
+    pub sidechain_file: Option<String>,
+}
+
+fn compressor_gain_db(level: f64, params: &CompressorParams) -> f64 {
+    let slope = 1.0 - params.ratio.max(1.0).recip();
+    let over = level - params.threshold_db;
+    if params.upward {
+        if over >= 0.0 {
+            0.0
+        } else {
+            (-over * slope).min(params.max_boost_db)
+        }
+    } else if 2.0 * over < -params.knee_db {
+        0.0
+    } else if params.knee_db > 0.0 && 2.0 * over.abs() <= params.knee_db {
+        -slope * (over + params.knee_db / 2.0).powi(2) / (2.0 * params.knee_db)
+    } else {
+        -slope * over
+    }
+}
+
+pub fn compress(params: &ProcessorParams, compressor_params: &CompressorParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let levels_db: Vec<f64> = match &compressor_params.sidechain_file {
+        Some(sidechain_file) => {
+            let rms_signal = get_sidechain_rms_signal(
+                sidechain_file,
+                10.0,
+                params.samples.len(),
+                params.sample_rate,
+            )?;
+            rms_signal.iter().step_by(channels).map(|r| gain_to_db(*r)).collect()
+        }
+        None => frame_peaks(&params.samples, channels)
+            .iter()
+            .map(|p| gain_to_db(*p))
+            .collect(),
+    };
+    if levels_db.is_empty() {
+        return Ok(params.clone());
+    }
+
+    let envelope = follow_db(
+        &levels_db,
+        time_coefficient(compressor_params.attack_ms, params.sample_rate),
+        time_coefficient(compressor_params.release_ms, params.sample_rate),
+    );
+    let gains_db: Vec<f64> = envelope
+        .iter()
+        .map(|level| compressor_gain_db(*level, compressor_params) + compressor_params.makeup_db)
+        .collect();
+
+    Ok(apply_frame_gains_db(params, &gains_db))
+}
+
+#[derive(Debug, Clone)]
+pub struct LimiterParams {
+    pub input_gain_db: f64,
+    pub ceiling_db: f64,
+    pub lookahead_ms: f64,
+    pub release_ms: f64,
+}
+
+// Brickwall limiter. The gain is pulled down over the lookahead window ahead of each peak
+// so no sample leaves above the ceiling
+pub fn limit(params: &ProcessorParams, limiter_params: &LimiterParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let input_gain = db_to_gain(limiter_params.input_gain_db);
+    let ceiling = db_to_gain(limiter_params.ceiling_db);
+    let lookahead = (((limiter_params.lookahead_ms / 1000.0) * params.sample_rate as f64) as usize).max(1);
+    let release = time_coefficient(limiter_params.release_ms, params.sample_rate);
+
+    let peaks = frame_peaks(&params.samples, channels);
+    let frames = peaks.len();
+    if frames == 0 {
+        return Ok(params.clone());
+    }
+    let required: Vec<f64> = peaks
+        .iter()
+        .map(|p| {
+            let peak = p * input_gain;
+            if peak > ceiling { ceiling / peak } else { 1.0 }
+        })
+        .collect();
+
+    // Minimum gain required anywhere in the lookahead window
+    let mut held = vec![1_f64; frames];
+    let mut window: VecDeque<usize> = VecDeque::new();
+    for n in (0..frames).rev() {
+        while let Some(&back) = window.back() {
+            if required[back] >= required[n] {
+                window.pop_back();
+            } else {
+                break;
+            }
+        }
+        window.push_back(n);
+        while let Some(&front) = window.front() {
+            if front > n + lookahead {
+                window.pop_front();
+            } else {
+                break;
+            }
+        }
+        held[n] = required[*window.front().unwrap()];
+    }
+
+    // Release back up slowly, but never above the held gain
+    let mut released = vec![1_f64; frames];
+    let mut gain = 1_f64;
+    for n in 0..frames {
+        gain = if held[n] < gain {
+            held[n]
+        } else {
+            held[n] + release * (gain - held[n])
+        };
+        released[n] = gain;
+    }
+
+    // Average over the lookahead so the gain ramps down rather than stepping
+    let mut gains = vec![1_f64; frames];
+    let mut running = 0_f64;
+    for n in 0..frames {
+        running += released[n];
+        if n >= lookahead {
+            running -= released[n - lookahead];
+        }
+        let count = (n + 1).min(lookahead) as f64;
+        gains[n] = running / count;
+    }
+
+    let new_samples = params
+        .samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s * input_gain * gains[i / channels]).clamp(-ceiling, ceiling))
+        .collect();
+
+    Ok(ProcessorParams {
+        samples: new_samples,
+        ..params.clone()
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct ExpanderParams {
+    pub threshold_db: f64,
+    // High ratios behave as a gate
+    pub ratio: f64,
+    pub range_db: f64,
+    pub attack_ms: f64,
+    pub hold_ms: f64,
+    pub release_ms: f64,
+}
+
+pub fn expand(params: &ProcessorParams, expander_params: &ExpanderParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let attack = time_coefficient(expander_params.attack_ms, params.sample_rate);
+    let release = time_coefficient(expander_params.release_ms, params.sample_rate);
+    let hold_frames = ((expander_params.hold_ms / 1000.0) * params.sample_rate as f64) as usize;
+
+    let mut gain_db = -expander_params.range_db;
+    let mut hold = 0;
+    let gains_db: Vec<f64> = frame_peaks(&params.samples, channels)
+        .iter()
+        .map(|peak| {
+            let under = (gain_to_db(*peak) - expander_params.threshold_db).min(0.0);
+            let target = (under * (expander_params.ratio - 1.0)).max(-expander_params.range_db);
+            if target >= gain_db {
+                gain_db = target + attack * (gain_db - target);
+                hold = hold_frames;
+            } else if hold > 0 {
+                hold -= 1;
+            } else {
+                gain_db = target + release * (gain_db - target);
+            }
+            gain_db
+        })
+        .collect();
+    if gains_db.is_empty() {
+        return Ok(params.clone());
+    }
+
+    Ok(apply_frame_gains_db(params, &gains_db))
+}
+
+#[derive(Debug, Clone)]
+pub struct TransientShaperParams {
+    // dB of gain per dB of difference between the fast and slow envelopes
+    pub attack: f64,
+    pub sustain: f64,
+    pub max_gain_db: f64,
+}
+
+// Compares a fast and slow envelope. The fast one leads on attacks and falls away first in the tail
+pub fn shape_transients(params: &ProcessorParams, shaper_params: &TransientShaperParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let levels_db: Vec<f64> = frame_peaks(&params.samples, channels)
+        .iter()
+        .map(|p| gain_to_db(*p))
+        .collect();
+    if levels_db.is_empty() {
+        return Ok(params.clone());
+    }
+    let fast = follow_db(
+        &levels_db,
+        time_coefficient(1.0, params.sample_rate),
+        time_coefficient(60.0, params.sample_rate),
+    );
+    let slow = follow_db(
+        &levels_db,
+        time_coefficient(30.0, params.sample_rate),
+        time_coefficient(400.0, params.sample_rate),
+    );
+
+    let gains_db: Vec<f64> = fast
+        .iter()
+        .zip(slow.iter())
+        .map(|(f, s)| {
+            let difference = f - s;
+            let gain = if difference > 0.0 {
+                shaper_params.attack * difference
+            } else {
+                -shaper_params.sustain * difference
+            };
+            gain.clamp(-shaper_params.max_gain_db, shaper_params.max_gain_db)
+        })
+        .collect();
+
+    Ok(apply_frame_gains_db(params, &gains_db))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn sine(gain: f64, seconds: f64) -> ProcessorParams {
+        let samples: Vec<f64> = (0..(seconds * SAMPLE_RATE as f64) as usize)
+            .map(|i| (2.0 * PI * 1000.0 * i as f64 / SAMPLE_RATE as f64).sin() * gain)
+            .collect();
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    // Peak of the second half, once the envelopes have settled
+    fn settled_peak_db(params: &ProcessorParams) -> f64 {
+        gain_to_db(params.samples[params.samples.len() / 2..].iter().fold(0_f64, |a, s| a.max(s.abs())))
+    }
+
+    fn compressor(threshold_db: f64, ratio: f64, knee_db: f64, upward: bool) -> CompressorParams {
+        CompressorParams {
+            threshold_db,
+            ratio,
+            knee_db,
+            attack_ms: 0.0,
+            release_ms: 0.0,
+            makeup_db: 0.0,
+            upward,
+            max_boost_db: 12.0,
+            sidechain_file: None,
+        }
+    }
+
+    fn close(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    #[test]
+    fn compressor_curve_reduces_by_the_ratio_above_the_threshold() {
+        let hard = compressor(-20.0, 4.0, 0.0, false);
+        assert_eq!(compressor_gain_db(-30.0, &hard), 0.0);
+        assert_eq!(compressor_gain_db(-20.0, &hard), 0.0);
+        assert!(close(compressor_gain_db(-8.0, &hard), -9.0, 1e-9));
+
+        // The knee meets the straight lines at both of its edges
+        let soft = compressor(-20.0, 4.0, 10.0, false);
+        assert!(close(compressor_gain_db(-25.0, &soft), 0.0, 1e-9));
+        assert!(close(compressor_gain_db(-15.0, &soft), compressor_gain_db(-15.0, &hard), 1e-9));
+        assert!(compressor_gain_db(-20.0, &soft) < 0.0);
+    }
+
+    #[test]
+    fn upward_compression_raises_quiet_material_up_to_its_max_boost() {
+        let upward = compressor(-20.0, 2.0, 0.0, true);
+        assert_eq!(compressor_gain_db(-10.0, &upward), 0.0);
+        assert!(close(compressor_gain_db(-30.0, &upward), 5.0, 1e-9));
+        assert_eq!(compressor_gain_db(-80.0, &upward), 12.0);
+    }
+
+    #[test]
+    fn compress_pulls_a_steady_tone_down_by_the_curve() {
+        // A -6 dB peak is 14 dB over, so 4:1 takes off 10.5 dB
+        let compressed = compress(&sine(0.5, 1.0), &compressor(-20.0, 4.0, 0.0, false)).unwrap();
+        assert!(close(settled_peak_db(&compressed), gain_to_db(0.5) - 10.5, 0.1), "{}", settled_peak_db(&compressed));
+    }
+
+    #[test]
+    fn limiter_never_goes_over_the_ceiling() {
+        let limiter = LimiterParams {
+            input_gain_db: 12.0,
+            ceiling_db: -1.0,
+            lookahead_ms: 5.0,
+            release_ms: 50.0,
+        };
+        let limited = limit(&sine(0.5, 1.0), &limiter).unwrap();
+        let ceiling = db_to_gain(-1.0);
+        assert!(limited.samples.iter().all(|s| s.abs() <= ceiling + 1e-12));
+        assert!(close(settled_peak_db(&limited), -1.0, 0.5), "{}", settled_peak_db(&limited));
+
+        // Audio under the ceiling is left alone
+        let quiet = sine(0.1, 0.5);
+        let untouched = limit(&quiet, &LimiterParams { input_gain_db: 0.0, ..limiter }).unwrap();
+        assert_eq!(untouched.samples, quiet.samples);
+    }
+
+    #[test]
+    fn expander_pushes_quiet_material_down_within_its_range() {
+        let expander = ExpanderParams {
+            threshold_db: -20.0,
+            ratio: 2.0,
+            range_db: 40.0,
+            attack_ms: 0.0,
+            hold_ms: 0.0,
+            release_ms: 0.0,
+        };
+        // 10 dB under the threshold at 2:1 goes down another 10 dB
+        let expanded = expand(&sine(db_to_gain(-30.0), 1.0), &expander).unwrap();
+        assert!(close(settled_peak_db(&expanded), -40.0, 0.1), "{}", settled_peak_db(&expanded));
+        // Over the threshold is left at unity
+        let loud = sine(0.5, 1.0);
+        assert!(close(settled_peak_db(&expand(&loud, &expander).unwrap()), settled_peak_db(&loud), 0.01));
+        // A gate can't take off more than its range
+        let gate = ExpanderParams { ratio: 100.0, ..expander };
+        let gated = expand(&sine(db_to_gain(-30.0), 1.0), &gate).unwrap();
+        assert!(close(settled_peak_db(&gated), -70.0, 0.1), "{}", settled_peak_db(&gated));
+    }
+
+    #[test]
+    fn transient_shaper_boosts_onsets_and_leaves_steady_tones() {
+        let shaper = TransientShaperParams {
+            attack: 1.0,
+            sustain: 0.0,
+            max_gain_db: 12.0,
+        };
+        // Silence, then a tone that starts suddenly
+        let mut params = sine(0.25, 2.0);
+        params.samples[..SAMPLE_RATE].iter_mut().for_each(|s| *s = 0.0);
+        let shaped = shape_transients(&params, &shaper).unwrap();
+        let onset = SAMPLE_RATE..SAMPLE_RATE + SAMPLE_RATE / 100;
+        let onset_peak = shaped.samples[onset].iter().fold(0_f64, |a, s| a.max(s.abs()));
+        assert!(onset_peak > 0.5, "{}", onset_peak);
+        assert!(onset_peak <= 0.25 * db_to_gain(12.0) + 1e-9);
+        let tail = &shaped.samples[SAMPLE_RATE * 7 / 4..];
+        // The follower ripples a little on a sine, but nowhere near the onset's boost
+        let tail_peak = tail.iter().fold(0_f64, |a, s| a.max(s.abs()));
+        assert!(close(gain_to_db(tail_peak), gain_to_db(0.25), 1.5), "{}", tail_peak);
+    }
+}
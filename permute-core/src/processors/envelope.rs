@@ -0,0 +1,443 @@
+use std::f64::consts::PI;
+
+use biquad::{Biquad, Coefficients, DirectForm2Transposed, ToHertz, Q_BUTTERWORTH_F64};
+
+use crate::{
+    permute_error::PermuteError,
+    process::ProcessorParams,
+    processors::{
+        dynamics::{follow_db, frame_peaks, gain_to_db, time_coefficient, MIN_DB},
+        filter::FilterType,
+        gain_distortion::{apply_distortion, interleave_channels, split_channels, DistortionAlgorithm},
+        oversample::{oversample_process, Oversampling},
+    },
+};
+
+// Frames between filter coefficient updates. Recalculating every sample is slow and inaudibly different
+const WAH_UPDATE_FRAMES: usize = 16;
+// Smooths changes in delay time so the read head glides rather than jumps
+const DELAY_GLIDE_MS: f64 = 20.0;
+
+#[derive(Debug, Clone)]
+pub struct EnvelopeFollowerParams {
+    pub attack_ms: f64,
+    pub release_ms: f64,
+    // Levels more than this far below the loudest point read as zero
+    pub range_db: f64,
+    // Modulate the most when the audio is quietest
+    pub invert: bool,
+}
+
+// Level of the audio over time as a 0 - 1 modulation source, one value per frame.
+// Follows in dB so the modulation tracks loudness rather than raw amplitude
+pub fn follow_envelope(
+    samples: &[f64],
+    channels: usize,
+    sample_rate: usize,
+    follower: &EnvelopeFollowerParams,
+) -> Vec<f64> {
+    let levels_db: Vec<f64> = frame_peaks(samples, channels).iter().map(|p| gain_to_db(*p)).collect();
+    let envelope = follow_db(
+        &levels_db,
+        time_coefficient(follower.attack_ms, sample_rate),
+        time_coefficient(follower.release_ms, sample_rate),
+    );
+    let peak_db = envelope.iter().fold(MIN_DB, |acc, e| acc.max(*e));
+    let range_db = follower.range_db.max(1.0);
+    envelope
+        .iter()
+        .map(|e| {
+            let amount = if peak_db <= MIN_DB {
+                0.0
+            } else {
+                ((e - (peak_db - range_db)) / range_db).clamp(0.0, 1.0)
+            };
+            if follower.invert { 1.0 - amount } else { amount }
+        })
+        .collect()
+}
+
+fn envelope_at(envelope: &[f64], frame: usize) -> f64 {
+    envelope.get(frame).copied().or(envelope.last().copied()).unwrap_or(0.0)
+}
+
+// Exponential sweep so equal envelope steps sound like equal pitch steps
+fn sweep(from: f64, to: f64, amount: f64) -> f64 {
+    from * (to / from).powf(amount)
+}
+
+#[derive(Clone)]
+pub struct AutoWahParams {
+    pub follower: EnvelopeFollowerParams,
+    pub filter_type: FilterType<f64>,
+    pub min_hz: f64,
+    pub max_hz: f64,
+    pub q: Option<f64>,
+    pub mix: f64,
+}
+
+// Filter whose cutoff follows the level of the audio
+pub fn auto_wah(params: &ProcessorParams, wah_params: &AutoWahParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let envelope = follow_envelope(&params.samples, channels, params.sample_rate, &wah_params.follower);
+    if envelope.is_empty() {
+        return Ok(params.clone());
+    }
+    let fs = (params.sample_rate as u32).hz();
+    let max_hz = (params.sample_rate as f64 * 0.45).min(wah_params.max_hz);
+    let min_hz = wah_params.min_hz.clamp(20.0, max_hz);
+    let q = wah_params.q.unwrap_or(Q_BUTTERWORTH_F64);
+    let coefficients = |frame: usize| {
+        let frequency = sweep(min_hz, max_hz, envelope_at(&envelope, frame));
+        Coefficients::<f64>::from_params(wah_params.filter_type, fs, frequency.hz(), q)
+    };
+
+    let mut channel_samples = vec![];
+    for cs in split_channels(params.samples.to_owned(), channels) {
+        let mut biquad = DirectForm2Transposed::<f64>::new(coefficients(0)?);
+        let mut filtered = Vec::with_capacity(cs.len());
+        for (frame, sample) in cs.iter().enumerate() {
+            if frame % WAH_UPDATE_FRAMES == 0 {
+                biquad.update_coefficients(coefficients(frame)?);
+            }
+            filtered.push(sample * (1.0 - wah_params.mix) + biquad.run(*sample) * wah_params.mix);
+        }
+        channel_samples.push(filtered);
+    }
+
+    Ok(ProcessorParams {
+        samples: interleave_channels(Ok(channel_samples))?,
+        ..params.clone()
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvelopePitchParams {
+    pub follower: EnvelopeFollowerParams,
+    // Shift at full envelope, may be negative
+    pub semitones: f64,
+    pub window_ms: f64,
+    pub mix: f64,
+}
+
+// Delay line pitch shifter with the shift set by the envelope. Two read heads sweep through
+// a short window half a cycle apart, each faded out as it wraps around
+fn envelope_pitch_channel(samples: &[f64], envelope: &[f64], window: f64, semitones: f64) -> Vec<f64> {
+    let read = |position: f64| {
+        if position < 0.0 {
+            return 0.0;
+        }
+        let index = position.floor() as usize;
+        let frac = position - index as f64;
+        let a = samples.get(index).copied().unwrap_or(0.0);
+        let b = samples.get(index + 1).copied().unwrap_or(0.0);
+        a + (b - a) * frac
+    };
+    let mut phase = 0_f64;
+    (0..samples.len())
+        .map(|n| {
+            let other_phase = (phase + 0.5) % 1.0;
+            let out = read(n as f64 - (1.0 - phase) * window) * (PI * phase).sin()
+                + read(n as f64 - (1.0 - other_phase) * window) * (PI * other_phase).sin();
+            let ratio = 2_f64.powf(semitones * envelope_at(envelope, n) / 12.0);
+            phase = (phase + (ratio - 1.0) / window).rem_euclid(1.0);
+            out
+        })
+        .collect()
+}
+
+pub fn envelope_pitch(params: &ProcessorParams, pitch_params: &EnvelopePitchParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let envelope = follow_envelope(&params.samples, channels, params.sample_rate, &pitch_params.follower);
+    let window = (pitch_params.window_ms / 1000.0 * params.sample_rate as f64).max(16.0);
+    // The read heads sit half a window behind on average, delay the dry signal to match
+    let latency = (window / 2.0) as usize;
+
+    let channel_samples = split_channels(params.samples.to_owned(), channels)
+        .iter()
+        .map(|cs| {
+            let shifted = envelope_pitch_channel(cs, &envelope, window, pitch_params.semitones);
+            shifted
+                .iter()
+                .enumerate()
+                .map(|(n, wet)| {
+                    let dry = if n >= latency { cs[n - latency] } else { 0.0 };
+                    dry * (1.0 - pitch_params.mix) + wet * pitch_params.mix
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(ProcessorParams {
+        samples: interleave_channels(Ok(channel_samples))?,
+        ..params.clone()
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvelopeDelayParams {
+    pub follower: EnvelopeFollowerParams,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub feedback: f64,
+    pub mix: f64,
+}
+
+// Delay whose time follows the level of the audio. Moving the read head bends the pitch
+// of the repeats, like pushing on a tape machine's reel
+pub fn envelope_delay(params: &ProcessorParams, delay_params: &EnvelopeDelayParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let envelope = follow_envelope(&params.samples, channels, params.sample_rate, &delay_params.follower);
+    let sr = params.sample_rate as f64;
+    let min_delay = (delay_params.min_ms / 1000.0 * sr).max(1.0);
+    let max_delay = (delay_params.max_ms / 1000.0 * sr).max(min_delay);
+    let buffer_length = max_delay as usize + 2;
+    let glide = time_coefficient(DELAY_GLIDE_MS, params.sample_rate);
+    let feedback = delay_params.feedback.clamp(0.0, 0.95);
+
+    let channel_samples = split_channels(params.samples.to_owned(), channels)
+        .iter()
+        .map(|cs| {
+            let mut buffer = vec![0_f64; buffer_length];
+            let mut write = 0;
+            let mut delay = min_delay + (max_delay - min_delay) * envelope_at(&envelope, 0);
+            cs.iter()
+                .enumerate()
+                .map(|(n, dry)| {
+                    let target = min_delay + (max_delay - min_delay) * envelope_at(&envelope, n);
+                    delay = target + glide * (delay - target);
+                    let read = (write as f64 - delay).rem_euclid(buffer_length as f64);
+                    let index = read.floor() as usize;
+                    let frac = read - index as f64;
+                    let a = buffer[index % buffer_length];
+                    let b = buffer[(index + 1) % buffer_length];
+                    let wet = a + (b - a) * frac;
+                    buffer[write] = dry + wet * feedback;
+                    write = (write + 1) % buffer_length;
+                    dry * (1.0 - delay_params.mix) + wet * delay_params.mix
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(ProcessorParams {
+        samples: interleave_channels(Ok(channel_samples))?,
+        ..params.clone()
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvelopeDriveParams {
+    pub follower: EnvelopeFollowerParams,
+    pub min_drive: f64,
+    pub max_drive: f64,
+    pub algorithm: DistortionAlgorithm,
+    pub oversampling: Oversampling,
+    pub mix: f64,
+}
+
+// Distortion whose drive follows the level of the audio, e.g. attacks break up while tails stay clean
+pub fn envelope_drive(params: &ProcessorParams, drive_params: &EnvelopeDriveParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let envelope = follow_envelope(&params.samples, channels, params.sample_rate, &drive_params.follower);
+    let min_drive = drive_params.min_drive.max(0.1);
+    let max_drive = drive_params.max_drive.max(min_drive);
+
+    let driven = oversample_process(&params.samples, channels, drive_params.oversampling, |i, sample| {
+        let drive = sweep(min_drive, max_drive, envelope_at(&envelope, i / channels));
+        // Full scale in stays full scale out, so only the shape changes with drive
+        let full_scale = apply_distortion(1.0, drive, drive_params.algorithm);
+        let distorted = apply_distortion(sample, drive, drive_params.algorithm) / full_scale;
+        sample * (1.0 - drive_params.mix) + distorted * drive_params.mix
+    });
+
+    // Heavy drive raises quiet passages a lot, bring the peak back to the original
+    let peak = |samples: &[f64]| samples.iter().fold(0_f64, |acc, s| acc.max(s.abs()));
+    let driven_peak = peak(&driven);
+    let gain = if driven_peak > 0.0 { peak(&params.samples) / driven_peak } else { 0.0 };
+
+    Ok(ProcessorParams {
+        samples: driven.iter().map(|s| s * gain).collect(),
+        ..params.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use biquad::Type;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn params(samples: Vec<f64>) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn sine(hz: f64, amplitude: f64, frames: usize) -> Vec<f64> {
+        (0..frames)
+            .map(|n| (2.0 * PI * hz * n as f64 / SAMPLE_RATE as f64).sin() * amplitude)
+            .collect()
+    }
+
+    // A loud fifth of a second followed by a quiet one
+    fn loud_then_quiet(hz: f64, quiet: f64) -> Vec<f64> {
+        let mut samples = sine(hz, 1.0, SAMPLE_RATE / 5);
+        samples.extend(sine(hz, quiet, SAMPLE_RATE / 5));
+        samples
+    }
+
+    fn follower(range_db: f64, invert: bool) -> EnvelopeFollowerParams {
+        EnvelopeFollowerParams {
+            attack_ms: 1.0,
+            release_ms: 10.0,
+            range_db,
+            invert,
+        }
+    }
+
+    fn rms(samples: &[f64]) -> f64 {
+        (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    fn peak(samples: &[f64]) -> f64 {
+        samples.iter().fold(0_f64, |acc, s| acc.max(s.abs()))
+    }
+
+    #[test]
+    fn follows_loudness_across_the_range() {
+        let samples = loud_then_quiet(440.0, 0.01);
+        let quarter = SAMPLE_RATE / 10;
+        let envelope = follow_envelope(&samples, 1, SAMPLE_RATE, &follower(60.0, false));
+        assert_eq!(envelope.len(), samples.len());
+        assert!(envelope[quarter - 1000..quarter].iter().all(|e| *e > 0.95));
+        // 40dB down in a 60dB range
+        let quiet = &envelope[envelope.len() - 1000..];
+        assert!(quiet.iter().all(|e| (e - 1.0 / 3.0).abs() < 0.05), "{:?}", &quiet[..4]);
+
+        let inverted = follow_envelope(&samples, 1, SAMPLE_RATE, &follower(60.0, true));
+        assert!(envelope.iter().zip(inverted.iter()).all(|(e, i)| (e + i - 1.0).abs() < 1e-12));
+
+        let narrow = follow_envelope(&samples, 1, SAMPLE_RATE, &follower(20.0, false));
+        assert!(narrow[narrow.len() - 1000..].iter().all(|e| *e == 0.0));
+
+        let silence = follow_envelope(&[0.0; 100], 1, SAMPLE_RATE, &follower(60.0, false));
+        assert!(silence.iter().all(|e| *e == 0.0));
+    }
+
+    #[test]
+    fn auto_wah_opens_with_the_envelope() {
+        let tone = params(sine(4000.0, 0.5, SAMPLE_RATE / 5));
+        let wah = |invert: bool, mix: f64| AutoWahParams {
+            follower: follower(40.0, invert),
+            filter_type: Type::LowPass,
+            min_hz: 200.0,
+            max_hz: 10000.0,
+            q: None,
+            mix,
+        };
+        let settled = SAMPLE_RATE / 10..SAMPLE_RATE / 5;
+        let open = auto_wah(&tone, &wah(false, 1.0)).unwrap();
+        let closed = auto_wah(&tone, &wah(true, 1.0)).unwrap();
+        assert!(rms(&open.samples[settled.clone()]) > rms(&tone.samples[settled.clone()]) * 0.9);
+        assert!(rms(&closed.samples[settled.clone()]) < rms(&tone.samples[settled]) * 0.01);
+
+        let dry = auto_wah(&tone, &wah(true, 0.0)).unwrap();
+        assert_eq!(dry.samples, tone.samples);
+    }
+
+    #[test]
+    fn envelope_pitch_without_a_shift_only_delays() {
+        let input = params(sine(440.0, 0.5, SAMPLE_RATE / 10));
+        let shifted = envelope_pitch(
+            &input,
+            &EnvelopePitchParams {
+                follower: follower(60.0, false),
+                semitones: 0.0,
+                window_ms: 20.0,
+                mix: 1.0,
+            },
+        )
+        .unwrap();
+        // 20ms window at 44.1kHz puts the read head 441 frames behind
+        assert_eq!(shifted.samples.len(), input.samples.len());
+        assert!(shifted.samples[..441].iter().all(|s| s.abs() < 1e-12));
+        for (out, dry) in shifted.samples[441..].iter().zip(input.samples.iter()) {
+            assert!((out - dry).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn envelope_pitch_shifts_loud_audio() {
+        let input = params(sine(440.0, 0.5, SAMPLE_RATE / 2));
+        let shifted = envelope_pitch(
+            &input,
+            &EnvelopePitchParams {
+                follower: follower(40.0, false),
+                semitones: 12.0,
+                window_ms: 40.0,
+                mix: 1.0,
+            },
+        )
+        .unwrap();
+        let settled = &shifted.samples[SAMPLE_RATE / 10..];
+        let crossings = settled.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count() as f64;
+        let hz = crossings / (settled.len() as f64 / SAMPLE_RATE as f64);
+        assert!((hz - 880.0).abs() < 30.0, "{}", hz);
+    }
+
+    #[test]
+    fn envelope_delay_repeats_at_a_fixed_time() {
+        let mut samples = vec![0.0; SAMPLE_RATE / 2];
+        samples[0] = 1.0;
+        let echoed = envelope_delay(
+            &params(samples),
+            &EnvelopeDelayParams {
+                follower: follower(60.0, false),
+                min_ms: 100.0,
+                max_ms: 100.0,
+                feedback: 0.5,
+                mix: 1.0,
+            },
+        )
+        .unwrap();
+        let repeat = SAMPLE_RATE / 10;
+        assert_eq!(echoed.samples[0], 0.0);
+        assert!((echoed.samples[repeat] - 1.0).abs() < 1e-9);
+        assert!((echoed.samples[repeat * 2] - 0.5).abs() < 1e-9);
+        assert!((echoed.samples[repeat * 3] - 0.25).abs() < 1e-9);
+        assert!((echoed.samples[repeat * 4] - 0.125).abs() < 1e-9);
+        let others: f64 = echoed.samples.iter().map(|s| s.abs()).sum::<f64>() - 1.875;
+        assert!(others.abs() < 1e-9);
+    }
+
+    #[test]
+    fn envelope_drive_breaks_up_loud_parts_and_keeps_the_peak() {
+        let input = params(loud_then_quiet(220.0, 0.05));
+        let driven = envelope_drive(
+            &input,
+            &EnvelopeDriveParams {
+                follower: follower(20.0, false),
+                min_drive: 0.5,
+                max_drive: 20.0,
+                algorithm: DistortionAlgorithm::Tanh,
+                oversampling: Oversampling::None,
+                mix: 1.0,
+            },
+        )
+        .unwrap();
+        assert!((peak(&driven.samples) - peak(&input.samples)).abs() < 1e-9);
+        let crest = |samples: &[f64]| peak(samples) / rms(samples);
+        let fifth = SAMPLE_RATE / 5;
+        let loud = &driven.samples[fifth / 2..fifth];
+        let quiet = &driven.samples[fifth + fifth / 2..];
+        // Squared off versus still a sine
+        assert!(crest(loud) < 1.1, "{}", crest(loud));
+        assert!((crest(quiet) - 2_f64.sqrt()).abs() < 0.02, "{}", crest(quiet));
+    }
+}
@@ -0,0 +1,206 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::{
+    permute_error::PermuteError,
+    permute_files::MAX_OUTPUT_SECONDS,
+    process::ProcessorParams,
+};
+
+pub struct ExtremeStretchParams {
+    pub window_size: usize,
+    pub stretch_factor: f64,
+}
+
+// Smoother at the edges than a hann, so overlapping frames at half a window apart sum without a flutter
+fn paul_window(window_size: usize) -> Vec<f64> {
+    (0..window_size)
+        .map(|i| {
+            let x = 2.0 * i as f64 / (window_size - 1).max(1) as f64 - 1.0;
+            (1.0 - x * x).max(0.0).powf(1.25)
+        })
+        .collect()
+}
+
+// The window and transforms for one stretch, shared by every channel
+struct Stretcher {
+    window: Vec<f64>,
+    fft: Arc<dyn Fft<f64>>,
+    ifft: Arc<dyn Fft<f64>>,
+    stretch_factor: f64,
+}
+
+impl Stretcher {
+    // Each output frame takes the spectrum of a window of the input and throws its phases away.
+    // The input window crawls forward by hop / stretch_factor so every moment is smeared across
+    // many output frames. Frames are overlap-added into a window long accumulator and each hop is
+    // written into the interleaved output as soon as nothing later can overlap it
+    fn stretch_channel(&self, samples: &[f64], channel: usize, channels: usize, rng: &mut StdRng, output: &mut [f64]) {
+        let window_size = self.window.len();
+        let hop = window_size / 2;
+        let input_frames = samples.len() / channels;
+        let output_frames = output.len() / channels;
+        let mut accumulator = vec![0.0; window_size];
+        let mut buffer = vec![Complex::new(0.0, 0.0); window_size];
+        let scale = 1.0 / window_size as f64;
+
+        let mut output_pos = 0;
+        while output_pos < output_frames {
+            let input_pos = (output_pos as f64 / self.stretch_factor) as usize;
+            for (i, b) in buffer.iter_mut().enumerate() {
+                let frame = input_pos + i;
+                let s = if frame < input_frames { samples[frame * channels + channel] } else { 0.0 };
+                *b = Complex::new(s * self.window[i], 0.0);
+            }
+            self.fft.process(&mut buffer);
+            for b in buffer.iter_mut() {
+                *b = Complex::from_polar(b.norm(), rng.gen_range(0.0..2.0 * PI));
+            }
+            self.ifft.process(&mut buffer);
+            for (i, b) in buffer.iter().enumerate() {
+                accumulator[i] += b.re * scale * self.window[i];
+            }
+            for (i, a) in accumulator[..hop].iter().enumerate().take(output_frames - output_pos) {
+                output[(output_pos + i) * channels + channel] = *a;
+            }
+            accumulator.copy_within(hop.., 0);
+            accumulator[window_size - hop..].fill(0.0);
+            output_pos += hop;
+        }
+    }
+}
+
+// Paulstretch style stretch for stretching audio tens to hundreds of times into a drone.
+// The factor is held to MAX_OUTPUT_SECONDS of output for the audio as it arrives, as earlier
+// processors may have made it longer than it was when the stretch was planned
+pub fn extreme_stretch(params: ProcessorParams, stretch_params: &ExtremeStretchParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let frames = params.samples.len() / channels;
+    let max_factor = MAX_OUTPUT_SECONDS * params.sample_rate as f64 / frames.max(1) as f64;
+    let window_size = stretch_params.window_size.max(64);
+    let mut planner = FftPlanner::new();
+    let stretcher = Stretcher {
+        window: paul_window(window_size),
+        fft: planner.plan_fft_forward(window_size),
+        ifft: planner.plan_fft_inverse(window_size),
+        stretch_factor: stretch_params.stretch_factor.min(max_factor).max(1.0),
+    };
+    let mut rng = StdRng::from_entropy();
+
+    let mut new_samples = vec![0.0; (frames as f64 * stretcher.stretch_factor) as usize * channels];
+    for channel in 0..channels {
+        stretcher.stretch_channel(&params.samples, channel, channels, &mut rng, &mut new_samples);
+    }
+
+    // Random phases spread the energy unpredictably, bring the peak back to the original
+    let peak = |samples: &[f64]| samples.iter().fold(0_f64, |acc, s| acc.max(s.abs()));
+    let stretched_peak = peak(&new_samples);
+    let gain = if stretched_peak > 0.0 { peak(&params.samples) / stretched_peak } else { 0.0 };
+    new_samples.iter_mut().for_each(|s| *s *= gain);
+
+    Ok(ProcessorParams {
+        sample_length: new_samples.len(),
+        samples: new_samples,
+        ..params
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processors::gain_distortion::split_channels;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn params(samples: Vec<f64>, channels: usize, sample_rate: usize) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn sine(hz: f64, frames: usize) -> Vec<f64> {
+        (0..frames)
+            .map(|n| (2.0 * PI * hz * n as f64 / SAMPLE_RATE as f64).sin() * 0.5)
+            .collect()
+    }
+
+    fn level_db(samples: &[f64], hz: f64) -> f64 {
+        let n = samples.len() as f64;
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, s) in samples.iter().enumerate() {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / n).cos();
+            let angle = 2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64;
+            re += s * window * angle.cos();
+            im -= s * window * angle.sin();
+        }
+        20.0 * ((re * re + im * im).sqrt() / n).max(1e-12).log10()
+    }
+
+    fn stretch(window_size: usize, stretch_factor: f64) -> ExtremeStretchParams {
+        ExtremeStretchParams {
+            window_size,
+            stretch_factor,
+        }
+    }
+
+    fn peak(samples: &[f64]) -> f64 {
+        samples.iter().fold(0_f64, |acc, s| acc.max(s.abs()))
+    }
+
+    #[test]
+    fn window_fades_to_nothing_at_the_edges() {
+        let window = paul_window(101);
+        assert_eq!(window[0], 0.0);
+        assert_eq!(window[100], 0.0);
+        assert_eq!(window[50], 1.0);
+        assert!((0..50).all(|i| (window[i] - window[100 - i]).abs() < 1e-12));
+        assert!(window[..51].windows(2).all(|w| w[1] >= w[0]));
+    }
+
+    #[test]
+    fn stretches_by_the_factor_and_keeps_the_peak() {
+        let input = params(sine(440.0, 4410), 1, SAMPLE_RATE);
+        let stretched = extreme_stretch(input.clone(), &stretch(2048, 10.0)).unwrap();
+        assert_eq!(stretched.samples.len(), 44100);
+        assert_eq!(stretched.sample_length, 44100);
+        assert!((peak(&stretched.samples) - peak(&input.samples)).abs() < 1e-9);
+        assert!(stretched.samples.iter().all(|s| s.is_finite()));
+
+        // Never shrinks
+        let shrunk = extreme_stretch(input, &stretch(2048, 0.5)).unwrap();
+        assert_eq!(shrunk.samples.len(), 4410);
+    }
+
+    #[test]
+    fn stretches_are_held_to_the_output_limit() {
+        // 10 seconds at 10Hz can stretch to 600 seconds at most
+        let input = params(vec![0.5; 100], 1, 10);
+        let stretched = extreme_stretch(input, &stretch(64, 100.0)).unwrap();
+        assert_eq!(stretched.samples.len(), 6000);
+    }
+
+    #[test]
+    fn channels_keep_their_own_spectrum() {
+        let left = sine(440.0, 8820);
+        let right = sine(1000.0, 8820);
+        let samples = left.iter().zip(right.iter()).flat_map(|(l, r)| [*l, *r]).collect();
+        let stretched = extreme_stretch(params(samples, 2, SAMPLE_RATE), &stretch(4096, 8.0)).unwrap();
+        let channels = split_channels(stretched.samples, 2);
+        assert_eq!(channels[0].len(), 8820 * 8);
+        assert!(level_db(&channels[0], 440.0) > level_db(&channels[0], 1000.0) + 30.0);
+        assert!(level_db(&channels[1], 1000.0) > level_db(&channels[1], 440.0) + 30.0);
+    }
+
+    #[test]
+    fn silence_stays_silent() {
+        let stretched = extreme_stretch(params(vec![0.0; 2000], 2, SAMPLE_RATE), &stretch(256, 4.0)).unwrap();
+        assert_eq!(stretched.samples.len(), 8000);
+        assert!(stretched.samples.iter().all(|s| *s == 0.0));
+    }
+}
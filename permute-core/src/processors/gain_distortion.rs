@@ -4,6 +4,7 @@ use crate::permute_files::PermuteUpdate;
 use crate::process::{PermuteNodeEvent, PermuteNodeName, ProcessorParams, ProcessorClosure, ProcessorAttribute};
 use crate::permute_error::PermuteError;
 use crate::random_processors::utils::format_float;
+use crate::processors::oversample::{oversample_process, Oversampling};
 
 pub fn ceiling(
     ProcessorParams {
@@ -108,24 +109,20 @@ pub fn interleave_channels(
     Ok(samples)
 }
 
-pub fn distort(params: &ProcessorParams, factor: f64) -> Result<ProcessorParams, PermuteError> {
-    let new_samples = params
-        .samples
-        .iter()
-        .map(|f| apply_distortion(*f, factor, DistortionAlgorithm::Power))
-        .collect();
+pub fn distort(params: &ProcessorParams, factor: f64, oversampling: Oversampling) -> Result<ProcessorParams, PermuteError> {
+    let new_samples = oversample_process(&params.samples, params.channels, oversampling, |_, f| {
+        apply_distortion(f, factor, DistortionAlgorithm::Power)
+    });
     Ok(ProcessorParams {
         samples: new_samples,
         ..params.clone()
     })
 }
 
-pub fn saturate(params: &ProcessorParams) -> Result<ProcessorParams, PermuteError> {
-    let new_samples = params
-        .samples
-        .iter()
-        .map(|f| apply_distortion(*f, 1.0, DistortionAlgorithm::Saturate))
-        .collect();
+pub fn saturate(params: &ProcessorParams, oversampling: Oversampling) -> Result<ProcessorParams, PermuteError> {
+    let new_samples = oversample_process(&params.samples, params.channels, oversampling, |_, f| {
+        apply_distortion(f, 1.0, DistortionAlgorithm::Saturate)
+    });
     Ok(ProcessorParams {
         samples: new_samples,
         ..params.clone()
@@ -241,24 +238,21 @@ pub fn apply_distortion(sample: f64, factor: f64, algorithm: DistortionAlgorithm
 pub struct FuzzParams {
     pub gain: f64,
     pub output_gain: f64,
+    pub oversampling: Oversampling,
 }
 
-pub fn fuzz(params: ProcessorParams, FuzzParams { gain, output_gain }: FuzzParams) -> Result<ProcessorParams, PermuteError> {
-    let new_samples = params
-        .samples
-        .iter()
-        .map(|f| {
-            let distorted = f * gain;
-            let clipped = if distorted > 1.0 {
-                1.0
-            } else if distorted < -1.0 {
-                -1.0
-            } else {
-                distorted
-            };
-            clipped * output_gain
-        })
-        .collect();
+pub fn fuzz(params: ProcessorParams, FuzzParams { gain, output_gain, oversampling }: FuzzParams) -> Result<ProcessorParams, PermuteError> {
+    let new_samples = oversample_process(&params.samples, params.channels, oversampling, |_, f| {
+        let distorted = f * gain;
+        let clipped = if distorted > 1.0 {
+            1.0
+        } else if distorted < -1.0 {
+            -1.0
+        } else {
+            distorted
+        };
+        clipped * output_gain
+    });
     Ok(ProcessorParams {
         samples: new_samples,
         ..params
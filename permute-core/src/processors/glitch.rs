@@ -0,0 +1,341 @@
+use crate::{
+    permute_error::PermuteError,
+    process::ProcessorParams,
+    processors::{
+        cross::{apply_grain_fade, find_zero_crossing},
+        gain_distortion::{interleave_channels, split_channels},
+        resample::{ResampleQuality, SincResampler},
+    },
+};
+
+// Hop size for the onset detection function, in frames
+const ONSET_HOP: usize = 512;
+// Onsets closer together than this many hops are treated as one
+const ONSET_MIN_GAP: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SliceMode {
+    Onset,
+    Grid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlitchOp {
+    Play,
+    // Play the whole slice n times
+    Repeat(usize),
+    // Repeat the start of the slice n times within the slice's length
+    Stutter(usize),
+    Reverse,
+    Drop,
+    // Semitones
+    Pitch(f64),
+}
+
+// One entry of the output, in the order it's played
+#[derive(Debug, Clone, Copy)]
+pub struct GlitchSlice {
+    pub source: usize,
+    pub op: GlitchOp,
+}
+
+#[derive(Debug, Clone)]
+pub struct GlitchParams {
+    pub mode: SliceMode,
+    pub slices: usize,
+    pub slice_map: Vec<GlitchSlice>,
+    pub crossfade_ms: f64,
+    pub quality: ResampleQuality,
+    // Fixed grid slice length, e.g. a note division at the audio's tempo. Otherwise the audio is split evenly
+    pub grid_ms: Option<f64>,
+}
+
+// Compact description of the slice map, e.g. "1 3r 2x2 4s4 - 6p+7"
+pub fn format_slice_map(slice_map: &[GlitchSlice]) -> String {
+    slice_map
+        .iter()
+        .map(|s| {
+            let n = s.source + 1;
+            match s.op {
+                GlitchOp::Play => format!("{}", n),
+                GlitchOp::Repeat(times) => format!("{}x{}", n, times),
+                GlitchOp::Stutter(times) => format!("{}s{}", n, times),
+                GlitchOp::Reverse => format!("{}r", n),
+                GlitchOp::Drop => "-".to_string(),
+                GlitchOp::Pitch(semitones) => format!("{}p{:+}", n, semitones),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Rough change in length from applying the slice map, assuming equal slices
+pub fn slice_map_length_factor(slice_map: &[GlitchSlice], slices: usize) -> f64 {
+    let total: f64 = slice_map
+        .iter()
+        .map(|s| match s.op {
+            GlitchOp::Play | GlitchOp::Stutter(_) | GlitchOp::Reverse => 1.0,
+            GlitchOp::Repeat(times) => times as f64,
+            GlitchOp::Drop => 0.0,
+            GlitchOp::Pitch(semitones) => 2_f64.powf(-semitones / 12.0),
+        })
+        .sum();
+    total / slices.max(1) as f64
+}
+
+// Frame positions of the strongest rises in energy, strongest first
+pub fn detect_onsets(samples: &[f64], channels: usize) -> Vec<usize> {
+    let channels = channels.max(1);
+    let energies: Vec<f64> = samples
+        .chunks(ONSET_HOP * channels)
+        .map(|hop| {
+            let energy = hop.iter().map(|s| s * s).sum::<f64>() / hop.len() as f64;
+            (energy + 1e-10).ln()
+        })
+        .collect();
+    let flux: Vec<f64> = energies
+        .windows(2)
+        .map(|w| (w[1] - w[0]).max(0.0))
+        .collect();
+    if flux.is_empty() {
+        return vec![];
+    }
+    let mean = flux.iter().sum::<f64>() / flux.len() as f64;
+
+    // Local maxima of the flux that stand clear of the average
+    let mut peaks: Vec<(usize, f64)> = (0..flux.len())
+        .filter(|&i| {
+            let previous = if i > 0 { flux[i - 1] } else { 0.0 };
+            let next = if i + 1 < flux.len() { flux[i + 1] } else { 0.0 };
+            flux[i] > mean * 1.5 && flux[i] >= previous && flux[i] > next
+        })
+        .map(|i| (i, flux[i]))
+        .collect();
+    peaks.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut onsets: Vec<usize> = vec![];
+    for (hop, _) in peaks {
+        if onsets.iter().all(|o| (*o as i64 - hop as i64).unsigned_abs() as usize >= ONSET_MIN_GAP) {
+            onsets.push(hop);
+        }
+    }
+    // flux[i] is the rise into hop i + 1
+    onsets.iter().map(|hop| (hop + 1) * ONSET_HOP).collect()
+}
+
+// Interleaved sample positions where each slice starts
+fn slice_boundaries(params: &ProcessorParams, mode: SliceMode, slices: usize, grid_ms: Option<f64>) -> Vec<usize> {
+    let channels = params.channels.max(1);
+    let frames = params.samples.len() / channels;
+    let grid_frames = match grid_ms {
+        Some(ms) => (ms / 1000.0 * params.sample_rate as f64) as usize,
+        None => frames / slices.max(1),
+    }
+    .max(1);
+    let max_snap = (grid_frames / 4) * channels;
+
+    let mut starts: Vec<usize> = match mode {
+        SliceMode::Onset => {
+            let mut onsets: Vec<usize> = detect_onsets(&params.samples, channels)
+                .into_iter()
+                .filter(|f| *f > 0 && *f < frames)
+                .take(slices.saturating_sub(1))
+                .collect();
+            // Not enough onsets, fill in the largest gaps from the grid
+            while onsets.len() + 1 < slices {
+                let mut edges = [vec![0], onsets.clone(), vec![frames]].concat();
+                edges.sort_unstable();
+                let (gap_start, gap_length) = edges
+                    .windows(2)
+                    .map(|w| (w[0], w[1] - w[0]))
+                    .max_by_key(|(_, length)| *length)
+                    .unwrap_or((0, frames));
+                if gap_length < 2 {
+                    break;
+                }
+                onsets.push(gap_start + gap_length / 2);
+            }
+            onsets.iter().map(|f| f * channels).collect()
+        }
+        SliceMode::Grid => (1..slices)
+            .take_while(|i| i * grid_frames < frames)
+            .map(|i| find_zero_crossing(&params.samples, i * grid_frames * channels, max_snap, channels))
+            .collect(),
+    };
+    starts.push(0);
+    starts.sort_unstable();
+    starts.dedup();
+    starts
+}
+
+fn reverse_frames(samples: &[f64], channels: usize) -> Vec<f64> {
+    samples.chunks(channels).rev().flatten().copied().collect()
+}
+
+fn pitch_slice(samples: &[f64], channels: usize, semitones: f64, quality: ResampleQuality) -> Result<Vec<f64>, PermuteError> {
+    let speed = 2_f64.powf(semitones / 12.0);
+    let resampler = SincResampler::new(quality);
+    let channel_samples: Vec<Vec<f64>> = split_channels(samples.to_vec(), channels)
+        .iter()
+        .map(|cs| resampler.resample(cs, speed))
+        .collect();
+    interleave_channels(Ok(channel_samples))
+}
+
+pub fn glitch(params: &ProcessorParams, glitch_params: &GlitchParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let length = params.samples.len();
+    if length < channels * glitch_params.slices.max(1) * 2 {
+        return Ok(params.clone());
+    }
+    let starts = slice_boundaries(params, glitch_params.mode, glitch_params.slices, glitch_params.grid_ms);
+    let slice_ranges: Vec<(usize, usize)> = starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| (*start, *starts.get(i + 1).unwrap_or(&length)))
+        .collect();
+    let crossfade = ((glitch_params.crossfade_ms / 1000.0) * params.sample_rate as f64) as usize * channels;
+
+    let mut output: Vec<f64> = Vec::with_capacity(length);
+    for glitch_slice in &glitch_params.slice_map {
+        // Fewer slices than planned if the audio was too short to cut
+        let (start, end) = match slice_ranges.get(glitch_slice.source) {
+            Some(range) => *range,
+            None => continue,
+        };
+        let slice = &params.samples[start..end];
+        let pieces: Vec<Vec<f64>> = match glitch_slice.op {
+            GlitchOp::Play => vec![slice.to_vec()],
+            GlitchOp::Repeat(times) => vec![slice.to_vec(); times],
+            GlitchOp::Stutter(times) => {
+                let stutter_length = ((slice.len() / channels) / times.max(1)).max(1) * channels;
+                vec![slice[..stutter_length.min(slice.len())].to_vec(); times]
+            }
+            GlitchOp::Reverse => vec![reverse_frames(slice, channels)],
+            GlitchOp::Drop => vec![],
+            GlitchOp::Pitch(semitones) => vec![pitch_slice(slice, channels, semitones, glitch_params.quality)?],
+        };
+
+        for piece in pieces {
+            let fade = crossfade.min((piece.len() / 2) / channels * channels);
+            let faded = apply_grain_fade(&piece, fade, true, true);
+            // Overlap with the tail of the previous piece
+            let overlap = fade.min(output.len());
+            let offset = output.len() - overlap;
+            for (i, s) in faded.iter().enumerate() {
+                if offset + i < output.len() {
+                    output[offset + i] += s;
+                } else {
+                    output.push(*s);
+                }
+            }
+        }
+    }
+
+    if output.is_empty() {
+        return Ok(params.clone());
+    }
+
+    Ok(ProcessorParams {
+        sample_length: output.len(),
+        samples: output,
+        ..params.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    // A rising ramp, so every frame is different and slices can be told apart
+    fn ramp(frames: usize, channels: usize) -> ProcessorParams {
+        let samples: Vec<f64> = (0..frames * channels).map(|i| (i / channels) as f64 / frames as f64).collect();
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn slice_map(ops: &[(usize, GlitchOp)]) -> Vec<GlitchSlice> {
+        ops.iter().map(|(source, op)| GlitchSlice { source: *source, op: *op }).collect()
+    }
+
+    fn grid_glitch(params: &ProcessorParams, ops: &[(usize, GlitchOp)]) -> ProcessorParams {
+        let glitch_params = GlitchParams {
+            mode: SliceMode::Grid,
+            slices: 4,
+            slice_map: slice_map(ops),
+            crossfade_ms: 0.0,
+            quality: ResampleQuality::Low,
+            grid_ms: None,
+        };
+        glitch(params, &glitch_params).unwrap()
+    }
+
+    #[test]
+    fn slice_maps_are_described_compactly() {
+        let map = slice_map(&[
+            (0, GlitchOp::Play),
+            (2, GlitchOp::Reverse),
+            (1, GlitchOp::Repeat(2)),
+            (3, GlitchOp::Stutter(4)),
+            (4, GlitchOp::Drop),
+            (5, GlitchOp::Pitch(7.0)),
+        ]);
+        assert_eq!(format_slice_map(&map), "1 3r 2x2 4s4 - 6p+7");
+    }
+
+    #[test]
+    fn length_factor_follows_the_slice_map() {
+        let map = slice_map(&[(0, GlitchOp::Repeat(3)), (1, GlitchOp::Drop), (2, GlitchOp::Stutter(2)), (3, GlitchOp::Pitch(12.0))]);
+        assert_eq!(slice_map_length_factor(&map, 4), (3.0 + 0.0 + 1.0 + 0.5) / 4.0);
+    }
+
+    #[test]
+    fn onsets_are_found_where_the_audio_jumps() {
+        // Quiet, then bursts of loud audio half a second apart
+        let mut samples = vec![0.001; SAMPLE_RATE * 2];
+        for start in [SAMPLE_RATE / 2, SAMPLE_RATE, SAMPLE_RATE * 3 / 2] {
+            for (i, s) in samples[start..start + SAMPLE_RATE / 10].iter_mut().enumerate() {
+                *s = if i % 2 == 0 { 0.8 } else { -0.8 };
+            }
+        }
+        let mut onsets = detect_onsets(&samples, 1);
+        onsets.sort_unstable();
+        assert_eq!(onsets.len(), 3, "{:?}", onsets);
+        for (onset, expected) in onsets.iter().zip([SAMPLE_RATE / 2, SAMPLE_RATE, SAMPLE_RATE * 3 / 2]) {
+            assert!(onset.abs_diff(expected) <= ONSET_HOP, "{} {}", onset, expected);
+        }
+    }
+
+    #[test]
+    fn playing_every_slice_in_order_gives_back_the_input() {
+        let params = ramp(4000, 2);
+        let played = grid_glitch(&params, &[(0, GlitchOp::Play), (1, GlitchOp::Play), (2, GlitchOp::Play), (3, GlitchOp::Play)]);
+        assert_eq!(played.samples, params.samples);
+    }
+
+    #[test]
+    fn ops_change_the_length_and_order_of_slices() {
+        let params = ramp(4000, 2);
+        let slice = 1000 * 2;
+        let repeated = grid_glitch(&params, &[(0, GlitchOp::Repeat(2))]);
+        assert_eq!(repeated.samples, [&params.samples[..slice], &params.samples[..slice]].concat());
+        let dropped = grid_glitch(&params, &[(0, GlitchOp::Play), (1, GlitchOp::Drop), (3, GlitchOp::Play)]);
+        assert_eq!(dropped.samples, [&params.samples[..slice], &params.samples[slice * 3..]].concat());
+        // Reversing keeps the channels of each frame together
+        let reversed = grid_glitch(&params, &[(1, GlitchOp::Reverse)]);
+        assert_eq!(reversed.samples, reverse_frames(&params.samples[slice..slice * 2], 2));
+        assert_eq!(reversed.samples[..2], [params.samples[slice * 2 - 2], params.samples[slice * 2 - 1]]);
+        // A stutter repeats the start of the slice within the slice's length
+        let stuttered = grid_glitch(&params, &[(2, GlitchOp::Stutter(4))]);
+        assert_eq!(stuttered.samples, params.samples[slice * 2..slice * 2 + slice / 4].repeat(4));
+        let pitched = grid_glitch(&params, &[(0, GlitchOp::Pitch(12.0))]);
+        assert_eq!(pitched.samples.len(), slice / 2);
+    }
+}
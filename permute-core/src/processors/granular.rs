@@ -0,0 +1,272 @@
+use std::f64::consts::{FRAC_PI_4, PI, SQRT_2};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    audio_cache::AUDIO_CACHE,
+    permute_error::PermuteError,
+    process::ProcessorParams,
+    processors::time_pitch::change_sample_rate,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrainEnvelope {
+    Hann,
+    Triangle,
+    Trapezoid,
+    Percussive,
+}
+
+impl GrainEnvelope {
+    // Gain at a point 0 - 1 through the grain
+    fn gain(&self, x: f64) -> f64 {
+        match self {
+            GrainEnvelope::Hann => 0.5 - 0.5 * (2.0 * PI * x).cos(),
+            GrainEnvelope::Triangle => 1.0 - (2.0 * x - 1.0).abs(),
+            GrainEnvelope::Trapezoid => (x / 0.2).min((1.0 - x) / 0.2).min(1.0),
+            GrainEnvelope::Percussive => {
+                if x < 0.05 {
+                    x / 0.05
+                } else {
+                    (-5.0 * (x - 0.05) / 0.95).exp() * (1.0 - x).min(0.05) / 0.05
+                }
+            }
+        }
+    }
+}
+
+// Where in the source each grain is read from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrainPointer {
+    // Moves through the source in time with the output
+    Follow,
+    // Stays at one position, 0 - 1 through the source
+    Freeze(f64),
+    // Sweeps from one position to another over the length of the output
+    Scan(f64, f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct GranularCloudParams {
+    pub density: f64, // grains per second
+    pub grain_ms: f64,
+    pub grain_size_jitter: f64, // 0 - 1, fraction of grain size
+    pub position_jitter_ms: f64,
+    pub pitch_scatter: f64, // semitones either side
+    // Round pitch scatter to whole octaves for shimmer type clouds
+    pub octave_pitch: bool,
+    pub pan_scatter: f64, // 0 - 1
+    pub envelope: GrainEnvelope,
+    pub pointer: GrainPointer,
+    // Take grains from another file rather than the audio being processed
+    pub source_file: Option<String>,
+    pub mix: f64,
+    pub seed: u64,
+}
+
+struct GrainSource {
+    samples: Vec<f64>,
+    channels: usize,
+}
+
+impl GrainSource {
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    // Linear interpolation is plenty for short enveloped grains and keeps dense clouds fast
+    fn read(&self, position: f64, channel: usize) -> f64 {
+        let frames = self.frames();
+        if position < 0.0 || frames < 2 {
+            return 0.0;
+        }
+        let index = position.floor() as usize;
+        if index + 1 >= frames {
+            return 0.0;
+        }
+        let frac = position - index as f64;
+        let channel = channel % self.channels;
+        let a = self.samples[index * self.channels + channel];
+        let b = self.samples[(index + 1) * self.channels + channel];
+        a + (b - a) * frac
+    }
+}
+
+pub fn granular_cloud(params: &ProcessorParams, cloud_params: &GranularCloudParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let source = match &cloud_params.source_file {
+        Some(file) => {
+            // The other file is brought to this audio's sample rate so grains keep their pitch and length
+            let (source_channels, source_sample_rate) = AUDIO_CACHE.get_format(file)?;
+            let source = ProcessorParams {
+                samples: AUDIO_CACHE.get_samples(file)?.to_vec(),
+                channels: source_channels.max(1),
+                sample_rate: source_sample_rate,
+                ..ProcessorParams::default()
+            };
+            let source = change_sample_rate(source, params.sample_rate, params.permutation.resample_quality)?;
+            GrainSource {
+                samples: source.samples,
+                channels: source.channels,
+            }
+        }
+        None => GrainSource {
+            samples: params.samples.clone(),
+            channels,
+        },
+    };
+    let output_frames = params.samples.len() / channels;
+    let source_frames = source.frames();
+    if output_frames == 0 || source_frames < 2 {
+        return Ok(params.clone());
+    }
+
+    let sample_rate = params.sample_rate as f64;
+    let mut rng = StdRng::seed_from_u64(cloud_params.seed);
+    let mut cloud = vec![0_f64; params.samples.len()];
+
+    let mean_interval = sample_rate / cloud_params.density.max(0.1);
+    let mut onset = rng.gen_range(0.0..mean_interval);
+    while (onset as usize) < output_frames {
+        let start = onset as usize;
+        let progress = start as f64 / output_frames as f64;
+
+        let size_jitter = 1.0 + rng.gen_range(-1.0..=1.0) * cloud_params.grain_size_jitter;
+        let grain_frames = ((cloud_params.grain_ms * size_jitter / 1000.0) * sample_rate).max(16.0) as usize;
+
+        let pointer = match cloud_params.pointer {
+            GrainPointer::Follow => progress,
+            GrainPointer::Freeze(position) => position,
+            GrainPointer::Scan(from, to) => from + (to - from) * progress,
+        };
+        let jitter = rng.gen_range(-1.0..=1.0) * (cloud_params.position_jitter_ms / 1000.0) * sample_rate;
+        let read_start = (pointer.clamp(0.0, 1.0) * source_frames as f64 + jitter).max(0.0);
+
+        let semitones = rng.gen_range(-1.0..=1.0) * cloud_params.pitch_scatter;
+        let semitones = if cloud_params.octave_pitch {
+            (semitones / 12.0).round() * 12.0
+        } else {
+            semitones
+        };
+        let rate = 2_f64.powf(semitones / 12.0);
+
+        // Equal power pan, unity in the centre
+        let pan = rng.gen_range(-1.0..=1.0) * cloud_params.pan_scatter;
+        let angle = (pan + 1.0) * FRAC_PI_4;
+        let pan_gains = [angle.cos() * SQRT_2, angle.sin() * SQRT_2];
+
+        for i in 0..grain_frames {
+            let frame = start + i;
+            if frame >= output_frames {
+                break;
+            }
+            let envelope = cloud_params.envelope.gain(i as f64 / grain_frames as f64);
+            let position = read_start + i as f64 * rate;
+            for c in 0..channels {
+                let value = if source.channels == 1 || channels == 1 {
+                    // Fold the source down to mono when the channel counts don't line up
+                    (0..source.channels).map(|sc| source.read(position, sc)).sum::<f64>() / source.channels as f64
+                } else {
+                    source.read(position, c)
+                };
+                let pan_gain = if channels == 2 { pan_gains[c] } else { 1.0 };
+                cloud[frame * channels + c] += value * envelope * pan_gain;
+            }
+        }
+
+        onset += mean_interval * rng.gen_range(0.5..1.5);
+    }
+
+    // Overlapping grains with unrelated phases add up roughly by the square root of the overlap
+    let overlap = cloud_params.density * cloud_params.grain_ms / 1000.0;
+    let cloud_gain = overlap.max(1.0).sqrt().recip();
+    let new_samples = params
+        .samples
+        .iter()
+        .zip(cloud.iter())
+        .map(|(dry, wet)| dry * (1.0 - cloud_params.mix) + wet * cloud_gain * cloud_params.mix)
+        .collect();
+
+    Ok(ProcessorParams {
+        samples: new_samples,
+        ..params.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn sine(channels: usize) -> ProcessorParams {
+        let samples: Vec<f64> = (0..SAMPLE_RATE)
+            .flat_map(|i| vec![(2.0 * PI * 220.0 * i as f64 / SAMPLE_RATE as f64).sin() * 0.5; channels])
+            .collect();
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn cloud(pan_scatter: f64, mix: f64, seed: u64) -> GranularCloudParams {
+        GranularCloudParams {
+            density: 40.0,
+            grain_ms: 60.0,
+            grain_size_jitter: 0.3,
+            position_jitter_ms: 20.0,
+            pitch_scatter: 3.0,
+            octave_pitch: false,
+            pan_scatter,
+            envelope: GrainEnvelope::Hann,
+            pointer: GrainPointer::Follow,
+            source_file: None,
+            mix,
+            seed,
+        }
+    }
+
+    #[test]
+    fn envelopes_rise_from_silence_and_stay_within_unity() {
+        for envelope in [GrainEnvelope::Hann, GrainEnvelope::Triangle, GrainEnvelope::Trapezoid, GrainEnvelope::Percussive] {
+            assert!(envelope.gain(0.0).abs() < 1e-9, "{:?}", envelope);
+            assert!(envelope.gain(1.0).abs() < 1e-9, "{:?}", envelope);
+            assert!((0..=100).all(|i| (0.0..=1.0 + 1e-9).contains(&envelope.gain(i as f64 / 100.0))), "{:?}", envelope);
+        }
+        assert!((GrainEnvelope::Hann.gain(0.5) - 1.0).abs() < 1e-9);
+        assert!((GrainEnvelope::Triangle.gain(0.5) - 1.0).abs() < 1e-9);
+        assert_eq!(GrainEnvelope::Trapezoid.gain(0.5), 1.0);
+    }
+
+    #[test]
+    fn clouds_keep_the_length_and_repeat_with_the_seed() {
+        let params = sine(1);
+        let a = granular_cloud(&params, &cloud(0.0, 0.8, 7)).unwrap();
+        let b = granular_cloud(&params, &cloud(0.0, 0.8, 7)).unwrap();
+        let c = granular_cloud(&params, &cloud(0.0, 0.8, 8)).unwrap();
+        assert_eq!(a.samples.len(), params.samples.len());
+        assert_eq!(a.samples, b.samples);
+        assert_ne!(a.samples, c.samples);
+        assert!(a.samples.iter().all(|s| s.is_finite()));
+    }
+
+    #[test]
+    fn no_mix_leaves_the_input() {
+        let params = sine(2);
+        assert_eq!(granular_cloud(&params, &cloud(1.0, 0.0, 7)).unwrap().samples, params.samples);
+    }
+
+    #[test]
+    fn pan_scatter_moves_grains_between_stereo_channels() {
+        let params = sine(2);
+        let sides_differ = |pan_scatter: f64| {
+            let output = granular_cloud(&params, &cloud(pan_scatter, 1.0, 7)).unwrap();
+            output.samples.chunks(2).any(|frame| (frame[0] - frame[1]).abs() > 1e-6)
+        };
+        assert!(!sides_differ(0.0));
+        assert!(sides_differ(1.0));
+    }
+}
@@ -1,7 +1,19 @@
 pub mod cross;
 pub mod delay_reverb;
+pub mod dynamics;
+pub mod envelope;
+pub mod extreme_stretch;
 pub mod filter;
 pub mod gain_distortion;
+pub mod glitch;
+pub mod granular;
 pub mod modulation;
+pub mod multiband;
 pub mod time_pitch; 
-pub mod osc;
\ No newline at end of file
+pub mod osc;
+pub mod stereo;
+pub mod oversample;
+pub mod parallel;
+pub mod resample;
+pub mod resonator;
+pub mod reverb;
\ No newline at end of file
@@ -2,12 +2,14 @@ use std::f64::consts::PI;
 use strum::EnumIter;
 
 use crate::{
+audio_cache::AUDIO_CACHE,
 permute_files::PermuteUpdate,
 processors::gain_distortion::{split_channels, interleave_channels, sum, SampleLine},
 process::{PermuteNodeEvent, PermuteNodeName, ProcessorParams},
 permute_error::PermuteError,
 processors::filter::FilterType,
 processors::osc::{lfo_sin, lfo_tri, new_oscillator},
+processors::oversample::kaiser_window,
 };
 use biquad::{Biquad, Coefficients, DirectForm1, ToHertz};
 
@@ -394,3 +396,281 @@ fn phase_stage(
     Ok(new_samples)
 }
 
+// Taps either side of the centre of the hilbert filter. Sets how low the shifter stays accurate,
+// roughly sample rate / taps, so ~85hz at 44.1khz
+const HILBERT_HALF_LENGTH: usize = 256;
+const HILBERT_KAISER_BETA: f64 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sideband {
+    Up,
+    Down,
+    // Both sidebands, which is the same as ring modulating by a sine
+    Both,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrequencyShifterParams {
+    pub shift_hz: f64,
+    pub sideband: Sideband,
+    pub feedback: f64, // 0 - 0.9. Feeds the output back in so each pass is shifted again
+    pub mix: f64,
+}
+
+// Odd length FIR approximating a 90 degree phase shift. Only odd offsets from the centre are non-zero
+fn hilbert_coefficients() -> Vec<f64> {
+    let taps = HILBERT_HALF_LENGTH * 2 + 1;
+    let window = kaiser_window(taps, HILBERT_KAISER_BETA);
+    (0..taps)
+        .map(|i| {
+            let k = i as i64 - HILBERT_HALF_LENGTH as i64;
+            if k % 2 == 0 {
+                0.0
+            } else {
+                2.0 / (PI * k as f64) * window[i]
+            }
+        })
+        .collect()
+}
+
+fn frequency_shift_channel(
+    samples: &[f64],
+    sample_rate: usize,
+    shift_params: &FrequencyShifterParams,
+    coefficients: &[f64],
+) -> Vec<f64> {
+    let taps = coefficients.len();
+    let latency = HILBERT_HALF_LENGTH;
+    let phase_inc = 2.0 * PI * shift_params.shift_hz / sample_rate as f64;
+    let feedback = shift_params.feedback.clamp(0.0, 0.9);
+
+    let mut history = vec![0_f64; taps];
+    let mut write = 0;
+    let mut phase = 0_f64;
+    let mut last_out = 0_f64;
+    let mut shifted = Vec::with_capacity(samples.len());
+
+    // Run on past the end so the output can be lined back up with the input
+    for i in 0..samples.len() + latency {
+        let input = samples.get(i).copied().unwrap_or(0.0);
+        history[write] = input + last_out * feedback;
+
+        // In phase path is the input delayed to match the filter
+        let real = history[(write + taps - latency) % taps];
+        let mut imaginary = 0_f64;
+        for k in (1..taps).step_by(2) {
+            imaginary += coefficients[k] * history[(write + taps - k) % taps];
+        }
+
+        let (sin, cos) = phase.sin_cos();
+        let out = match shift_params.sideband {
+            Sideband::Up => real * cos - imaginary * sin,
+            Sideband::Down => real * cos + imaginary * sin,
+            Sideband::Both => real * cos,
+        };
+        last_out = out;
+        if i >= latency {
+            shifted.push(out);
+        }
+
+        write = (write + 1) % taps;
+        phase = (phase + phase_inc) % (2.0 * PI);
+    }
+    shifted
+}
+
+// Moves every frequency by the same number of hz, using a hilbert transform to get the analytic signal.
+// Unlike pitch shifting this breaks harmonic relationships, giving metallic and inharmonic results
+pub fn frequency_shift(
+    params: &ProcessorParams,
+    shift_params: &FrequencyShifterParams,
+) -> Result<ProcessorParams, PermuteError> {
+    let coefficients = hilbert_coefficients();
+    let channel_samples = split_channels(params.samples.to_owned(), params.channels);
+    let shifted_channels: Vec<Vec<f64>> = channel_samples
+        .iter()
+        .map(|cs| frequency_shift_channel(cs, params.sample_rate, shift_params, &coefficients))
+        .collect();
+    let shifted = interleave_channels(Ok(shifted_channels))?;
+
+    let summed = sum(vec![
+        SampleLine {
+            samples: params.samples.to_owned(),
+            gain_factor: 1.0 - shift_params.mix,
+        },
+        SampleLine {
+            samples: shifted,
+            gain_factor: shift_params.mix,
+        },
+    ]);
+
+    Ok(ProcessorParams {
+        sample_length: summed.len(),
+        samples: summed,
+        ..params.clone()
+    })
+}
+
+#[derive(Debug, Clone)]
+pub enum RingCarrier {
+    // Triangle oscillator, gliding from the first frequency to the second over the audio
+    Oscillator { start_hz: f64, end_hz: f64 },
+    // Another file used as the carrier, looped if shorter than the audio
+    Sidechain { file: String, channels: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct RingModulatorParams {
+    pub carrier: RingCarrier,
+    pub mix: f64,
+}
+
+// One carrier value per frame
+fn ring_carrier(params: &ProcessorParams, carrier: &RingCarrier, frames: usize) -> Result<Vec<f64>, PermuteError> {
+    match carrier {
+        RingCarrier::Oscillator { start_hz, end_hz } => {
+            let mut osc = new_oscillator(params.sample_rate as f64);
+            let frames_f = frames.max(1) as f64;
+            Ok((0..frames)
+                .map(|i| {
+                    // Exponential glide so the sweep sounds even
+                    let progress = i as f64 / frames_f;
+                    osc.set_frequency(start_hz * (end_hz / start_hz).powf(progress));
+                    osc.process()
+                })
+                .collect())
+        }
+        RingCarrier::Sidechain { file, channels } => {
+            let sidechain = AUDIO_CACHE.get_samples(file)?;
+            let channels = (*channels).max(1);
+            let mono: Vec<f64> = sidechain
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f64>() / frame.len() as f64)
+                .collect();
+            if mono.is_empty() {
+                return Ok(vec![1.0; frames]);
+            }
+            // Normalise so a quiet carrier doesn't just turn the audio down
+            let peak = mono.iter().fold(0_f64, |acc, s| acc.max(s.abs()));
+            let gain = if peak > 0.0 { peak.recip() } else { 0.0 };
+            Ok((0..frames).map(|i| mono[i % mono.len()] * gain).collect())
+        }
+    }
+}
+
+pub fn ring_modulate(
+    params: &ProcessorParams,
+    ring_params: &RingModulatorParams,
+) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let frames = params.samples.len() / channels;
+    let carrier = ring_carrier(params, &ring_params.carrier, frames)?;
+
+    let new_samples = params
+        .samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let modulated = s * carrier.get(i / channels).copied().unwrap_or(0.0);
+            s * (1.0 - ring_params.mix) + modulated * ring_params.mix
+        })
+        .collect();
+
+    Ok(ProcessorParams {
+        samples: new_samples,
+        ..params.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn sine(hz: f64) -> ProcessorParams {
+        let samples: Vec<f64> = (0..SAMPLE_RATE / 2)
+            .map(|i| (2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64).sin() * 0.5)
+            .collect();
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    // Level at one frequency, Hann windowed so nearby peaks don't leak into it
+    fn level_db(samples: &[f64], hz: f64) -> f64 {
+        let n = samples.len() as f64;
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, s) in samples.iter().enumerate() {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / n).cos();
+            let angle = 2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64;
+            re += s * window * angle.cos();
+            im -= s * window * angle.sin();
+        }
+        20.0 * ((re * re + im * im).sqrt() / n).max(1e-12).log10()
+    }
+
+    fn shift(sideband: Sideband) -> ProcessorParams {
+        let shift_params = FrequencyShifterParams {
+            shift_hz: 200.0,
+            sideband,
+            feedback: 0.0,
+            mix: 1.0,
+        };
+        frequency_shift(&sine(1000.0), &shift_params).unwrap()
+    }
+
+    #[test]
+    fn frequency_shifter_moves_a_tone_by_the_shift() {
+        let up = shift(Sideband::Up);
+        assert_eq!(up.samples.len(), SAMPLE_RATE / 2);
+        assert!(level_db(&up.samples, 1200.0) > level_db(&up.samples, 800.0) + 40.0);
+        assert!(level_db(&up.samples, 1200.0) > level_db(&up.samples, 1000.0) + 40.0);
+
+        let down = shift(Sideband::Down);
+        assert!(level_db(&down.samples, 800.0) > level_db(&down.samples, 1200.0) + 40.0);
+
+        // Both sidebands at half the level each
+        let both = shift(Sideband::Both);
+        assert!((level_db(&both.samples, 800.0) - level_db(&both.samples, 1200.0)).abs() < 1.0);
+        assert!((level_db(&up.samples, 1200.0) - level_db(&both.samples, 1200.0) - 6.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn frequency_shifter_output_lines_up_with_the_input() {
+        // Shifting by nothing is a delay matched filter, so the input comes back
+        let params = sine(1000.0);
+        let shift_params = FrequencyShifterParams {
+            shift_hz: 0.0,
+            sideband: Sideband::Up,
+            feedback: 0.0,
+            mix: 1.0,
+        };
+        let shifted = frequency_shift(&params, &shift_params).unwrap();
+        assert!(shifted.samples.iter().zip(params.samples.iter()).all(|(a, b)| (a - b).abs() < 1e-9));
+    }
+
+    #[test]
+    fn ring_modulation_moves_a_tone_to_either_side_of_the_carrier() {
+        let params = sine(1000.0);
+        let ring_params = RingModulatorParams {
+            carrier: RingCarrier::Oscillator { start_hz: 300.0, end_hz: 300.0 },
+            mix: 1.0,
+        };
+        let modulated = ring_modulate(&params, &ring_params).unwrap();
+        let (below, above, original) = (
+            level_db(&modulated.samples, 700.0),
+            level_db(&modulated.samples, 1300.0),
+            level_db(&modulated.samples, 1000.0),
+        );
+        assert!((below - above).abs() < 1.0, "{} {}", below, above);
+        assert!(below > original + 30.0, "{} {}", below, original);
+
+        let dry = ring_modulate(&params, &RingModulatorParams { mix: 0.0, ..ring_params }).unwrap();
+        assert_eq!(dry.samples, params.samples);
+    }
+}
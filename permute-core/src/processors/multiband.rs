@@ -0,0 +1,241 @@
+use biquad::{Biquad, Coefficients, DirectForm2Transposed, ToHertz, Q_BUTTERWORTH_F64};
+
+use crate::{
+    permute_error::PermuteError,
+    process::{ProcessorClosure, ProcessorParams},
+    processors::{
+        filter::FilterType,
+        gain_distortion::{interleave_channels, split_channels, sum, SampleLine},
+        parallel::run_chain,
+    },
+};
+
+// 4th order Linkwitz-Riley, two butterworth biquads in series.
+// The low and high outputs are in phase with each other at every frequency and sum back to an all-pass
+fn linkwitz_riley(samples: &[f64], filter_type: FilterType<f64>, frequency: f64, sample_rate: usize) -> Result<Vec<f64>, PermuteError> {
+    let coefficients = Coefficients::<f64>::from_params(
+        filter_type,
+        (sample_rate as u32).hz(),
+        frequency.hz(),
+        Q_BUTTERWORTH_F64,
+    )?;
+    let mut first = DirectForm2Transposed::<f64>::new(coefficients);
+    let mut second = DirectForm2Transposed::<f64>::new(coefficients);
+    Ok(samples.iter().map(|s| second.run(first.run(*s))).collect())
+}
+
+// Same phase shift as splitting at the crossover and summing, without splitting
+fn crossover_all_pass(samples: &[f64], frequency: f64, sample_rate: usize) -> Result<Vec<f64>, PermuteError> {
+    let low = linkwitz_riley(samples, FilterType::LowPass, frequency, sample_rate)?;
+    let high = linkwitz_riley(samples, FilterType::HighPass, frequency, sample_rate)?;
+    Ok(low.iter().zip(high.iter()).map(|(l, h)| l + h).collect())
+}
+
+// Splits one channel into crossovers.len() + 1 bands, lowest first.
+// Each band is passed through the all-pass of every crossover it didn't go through,
+// so all bands have the same phase response and sum back to the original magnitude
+fn split_channel(samples: &[f64], crossovers: &[f64], sample_rate: usize) -> Result<Vec<Vec<f64>>, PermuteError> {
+    let mut bands = vec![];
+    let mut remaining = samples.to_vec();
+    for (i, frequency) in crossovers.iter().enumerate() {
+        let mut low = linkwitz_riley(&remaining, FilterType::LowPass, *frequency, sample_rate)?;
+        for higher in &crossovers[i + 1..] {
+            low = crossover_all_pass(&low, *higher, sample_rate)?;
+        }
+        bands.push(low);
+        remaining = linkwitz_riley(&remaining, FilterType::HighPass, *frequency, sample_rate)?;
+    }
+    bands.push(remaining);
+    Ok(bands)
+}
+
+// Interleaved audio for each band, lowest first
+pub fn split_bands(params: &ProcessorParams, crossovers: &[f64]) -> Result<Vec<Vec<f64>>, PermuteError> {
+    let channels = params.channels.max(1);
+    let mut crossovers = crossovers.to_vec();
+    crossovers.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut channel_bands = vec![];
+    for cs in split_channels(params.samples.to_owned(), channels) {
+        channel_bands.push(split_channel(&cs, &crossovers, params.sample_rate)?);
+    }
+    let mut bands = vec![];
+    for band in 0..=crossovers.len() {
+        let channel_samples = channel_bands.iter().map(|cb| cb[band].clone()).collect();
+        bands.push(interleave_channels(Ok(channel_samples))?);
+    }
+    Ok(bands)
+}
+
+// Runs a separate chain of processors on each band, then sums the bands back together
+pub fn multiband(
+    params: &ProcessorParams,
+    crossovers: &[f64],
+    band_chains: Vec<Vec<ProcessorClosure>>,
+) -> Result<ProcessorParams, PermuteError> {
+    let bands = split_bands(params, crossovers)?;
+
+    let mut band_lines: Vec<SampleLine> = vec![];
+    for (band, chain) in bands.into_iter().zip(band_chains) {
+        let band_params = ProcessorParams {
+            sample_length: band.len(),
+            samples: band,
+            ..params.clone()
+        };
+        let band_samples = band_params.samples.clone();
+        let band_params = run_chain(&band_params, chain)?;
+        // Bands can only be summed back if they still line up with the rest of the audio
+        let samples = if band_params.channels == params.channels {
+            band_params.samples
+        } else {
+            band_samples
+        };
+        band_lines.push(SampleLine {
+            samples,
+            gain_factor: 1.0,
+        });
+    }
+    let summed = sum(band_lines);
+
+    Ok(ProcessorParams {
+        sample_length: summed.len(),
+        samples: summed,
+        ..params.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn params(samples: Vec<f64>, channels: usize) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn tones(frequencies: &[f64]) -> Vec<f64> {
+        (0..SAMPLE_RATE / 2)
+            .map(|n| {
+                frequencies
+                    .iter()
+                    .map(|hz| (2.0 * PI * hz * n as f64 / SAMPLE_RATE as f64).sin() * 0.2)
+                    .sum()
+            })
+            .collect()
+    }
+
+    // Level at one frequency, skipping the filters settling in
+    fn level_db(samples: &[f64], hz: f64) -> f64 {
+        let samples = &samples[SAMPLE_RATE / 10..];
+        let n = samples.len() as f64;
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, s) in samples.iter().enumerate() {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / n).cos();
+            let angle = 2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64;
+            re += s * window * angle.cos();
+            im -= s * window * angle.sin();
+        }
+        20.0 * ((re * re + im * im).sqrt() / n).max(1e-12).log10()
+    }
+
+    fn sum_bands(bands: &[Vec<f64>]) -> Vec<f64> {
+        (0..bands[0].len()).map(|i| bands.iter().map(|b| b[i]).sum()).collect()
+    }
+
+    const PROBES: [f64; 6] = [60.0, 300.0, 500.0, 1500.0, 4000.0, 12000.0];
+
+    #[test]
+    fn tones_land_in_their_bands() {
+        let crossovers = [500.0, 4000.0];
+        for (hz, expected) in [(100.0, 0), (1500.0, 1), (10000.0, 2)] {
+            let input = tones(&[hz]);
+            let bands = split_bands(&params(input.clone(), 1), &crossovers).unwrap();
+            assert_eq!(bands.len(), 3);
+            for (band, samples) in bands.iter().enumerate() {
+                let relative = level_db(samples, hz) - level_db(&input, hz);
+                if band == expected {
+                    assert!(relative > -0.5, "{} {} {}", hz, band, relative);
+                } else {
+                    assert!(relative < -30.0, "{} {} {}", hz, band, relative);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bands_sum_back_to_the_input() {
+        let input = params(tones(&PROBES), 1);
+        for crossovers in [vec![], vec![1000.0], vec![4000.0, 250.0], vec![200.0, 1000.0, 6000.0]] {
+            let bands = split_bands(&input, &crossovers).unwrap();
+            assert_eq!(bands.len(), crossovers.len() + 1);
+            let summed = sum_bands(&bands);
+            assert_eq!(summed.len(), input.samples.len());
+            for hz in PROBES {
+                let difference = level_db(&summed, hz) - level_db(&input.samples, hz);
+                assert!(difference.abs() < 0.1, "{:?} {} {}", crossovers, hz, difference);
+            }
+        }
+    }
+
+    #[test]
+    fn stereo_bands_stay_interleaved() {
+        let left = tones(&[100.0]);
+        let right = tones(&[10000.0]);
+        let samples = left.iter().zip(right.iter()).flat_map(|(l, r)| [*l, *r]).collect();
+        let bands = split_bands(&params(samples, 2), &[1000.0]).unwrap();
+        let low = split_channels(bands[0].clone(), 2);
+        let high = split_channels(bands[1].clone(), 2);
+        assert!(level_db(&low[0], 100.0) > level_db(&left, 100.0) - 0.5);
+        assert!(level_db(&low[1], 10000.0) < level_db(&right, 10000.0) - 30.0);
+        assert!(level_db(&high[1], 10000.0) > level_db(&right, 10000.0) - 0.5);
+        assert!(level_db(&high[0], 100.0) < level_db(&left, 100.0) - 30.0);
+    }
+
+    #[test]
+    fn empty_band_chains_leave_the_input() {
+        let input = params(tones(&PROBES), 1);
+        let out = multiband(&input, &[300.0, 3000.0], vec![vec![], vec![], vec![]]).unwrap();
+        assert_eq!(out.samples.len(), input.samples.len());
+        assert_eq!(out.sample_length, input.sample_length);
+        for hz in PROBES {
+            assert!((level_db(&out.samples, hz) - level_db(&input.samples, hz)).abs() < 0.1, "{}", hz);
+        }
+    }
+
+    #[test]
+    fn each_chain_only_touches_its_band() {
+        let input = params(tones(&[100.0, 10000.0]), 1);
+        let mute: ProcessorClosure = Box::new(|params: ProcessorParams| {
+            Ok(ProcessorParams {
+                samples: vec![0.0; params.samples.len()],
+                ..params
+            })
+        });
+        let out = multiband(&input, &[1000.0], vec![vec![], vec![mute]]).unwrap();
+        assert!((level_db(&out.samples, 100.0) - level_db(&input.samples, 100.0)).abs() < 0.1);
+        assert!(level_db(&out.samples, 10000.0) < -60.0);
+    }
+
+    #[test]
+    fn bands_that_change_channels_are_left_dry() {
+        let input = params(tones(&[100.0, 10000.0]), 1);
+        let to_stereo: ProcessorClosure = Box::new(|params: ProcessorParams| {
+            Ok(ProcessorParams {
+                samples: params.samples.iter().flat_map(|s| [*s, *s]).collect(),
+                channels: 2,
+                ..params
+            })
+        });
+        let out = multiband(&input, &[1000.0], vec![vec![to_stereo], vec![]]).unwrap();
+        assert_eq!(out.samples.len(), input.samples.len());
+        assert!((level_db(&out.samples, 100.0) - level_db(&input.samples, 100.0)).abs() < 0.1);
+    }
+}
@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+use crate::processors::gain_distortion::{interleave_channels, split_channels};
+
+// Number of low rate samples of delay on each side of the anti-aliasing filters.
+// The filter length is derived from this so the round trip delay lands on a whole sample
+const HALF_FILTER_LENGTH: usize = 16;
+// Kaiser beta of ~8 gives roughly 80dB of stopband rejection
+const KAISER_BETA: f64 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Oversampling {
+    None = 1,
+    X2 = 2,
+    X4 = 4,
+    X8 = 8,
+}
+
+impl Oversampling {
+    pub fn factor(&self) -> usize {
+        *self as usize
+    }
+}
+
+pub fn get_oversampling_from_name(name: &str) -> Result<Oversampling, String> {
+    match name.to_lowercase().as_str() {
+        "off" | "1x" => Ok(Oversampling::None),
+        "2x" => Ok(Oversampling::X2),
+        "4x" => Ok(Oversampling::X4),
+        "8x" => Ok(Oversampling::X8),
+        _ => Err(format!("{} not found. Try off, 2x, 4x or 8x", name)),
+    }
+}
+
+// Zeroth order modified bessel function, used for the kaiser window
+pub fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..50 {
+        term *= half_x / k as f64;
+        let squared = term * term;
+        sum += squared;
+        if squared < sum * 1e-16 {
+            break;
+        }
+    }
+    sum
+}
+
+pub fn kaiser_window(length: usize, beta: f64) -> Vec<f64> {
+    if length == 1 {
+        return vec![1.0];
+    }
+    let denominator = bessel_i0(beta);
+    let m = (length - 1) as f64;
+    (0..length)
+        .map(|i| {
+            let r = (2.0 * i as f64 / m) - 1.0;
+            bessel_i0(beta * (1.0 - r * r).max(0.0).sqrt()) / denominator
+        })
+        .collect()
+}
+
+pub fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-12 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Linear phase low pass FIR. Cutoff is in cycles per sample (0 - 0.5)
+pub fn windowed_sinc_lowpass(taps: usize, cutoff: f64, beta: f64) -> Vec<f64> {
+    let window = kaiser_window(taps, beta);
+    let centre = (taps - 1) as f64 / 2.0;
+    let coefficients: Vec<f64> = (0..taps)
+        .map(|i| 2.0 * cutoff * sinc(2.0 * cutoff * (i as f64 - centre)) * window[i])
+        .collect();
+    // normalise for unity gain at DC
+    let dc_gain: f64 = coefficients.iter().sum();
+    coefficients.iter().map(|c| c / dc_gain).collect()
+}
+
+// Polyphase interpolator/decimator pair sharing one anti-aliasing filter
+pub struct Oversampler {
+    factor: usize,
+    taps: Vec<f64>,
+    phases: Vec<Vec<f64>>,
+}
+
+impl Oversampler {
+    pub fn new(oversampling: Oversampling) -> Self {
+        let factor = oversampling.factor();
+        let taps = windowed_sinc_lowpass(2 * HALF_FILTER_LENGTH * factor + 1, 0.46 / factor as f64, KAISER_BETA);
+        let phases = (0..factor)
+            .map(|p| taps.iter().skip(p).step_by(factor).copied().collect())
+            .collect();
+        Oversampler { factor, taps, phases }
+    }
+
+    // Round trip delay through upsample and downsample, in low rate samples
+    pub fn latency(&self) -> usize {
+        if self.factor == 1 {
+            0
+        } else {
+            2 * HALF_FILTER_LENGTH
+        }
+    }
+
+    pub fn upsample(&self, samples: &[f64]) -> Vec<f64> {
+        if self.factor == 1 {
+            return samples.to_vec();
+        }
+        let gain = self.factor as f64;
+        let mut upsampled = vec![0_f64; samples.len() * self.factor];
+        for n in 0..samples.len() {
+            for (p, phase) in self.phases.iter().enumerate() {
+                let mut acc = 0.0;
+                for (k, coefficient) in phase.iter().enumerate() {
+                    if k > n {
+                        break;
+                    }
+                    acc += coefficient * samples[n - k];
+                }
+                upsampled[n * self.factor + p] = acc * gain;
+            }
+        }
+        upsampled
+    }
+
+    pub fn downsample(&self, samples: &[f64]) -> Vec<f64> {
+        if self.factor == 1 {
+            return samples.to_vec();
+        }
+        let length = samples.len() / self.factor;
+        let mut downsampled = vec![0_f64; length];
+        for (n, out) in downsampled.iter_mut().enumerate() {
+            let centre = n * self.factor;
+            let mut acc = 0.0;
+            for (j, coefficient) in self.taps.iter().enumerate() {
+                if j > centre {
+                    break;
+                }
+                acc += coefficient * samples[centre - j];
+            }
+            *out = acc;
+        }
+        downsampled
+    }
+}
+
+// Run a non-linear function at a higher sample rate to keep its harmonics from folding back.
+// The closure receives the interleaved index of the original sample being processed.
+// Output is time aligned with, and the same length as, the input
+pub fn oversample_process<F: FnMut(usize, f64) -> f64>(
+    samples: &[f64],
+    channels: usize,
+    oversampling: Oversampling,
+    mut process: F,
+) -> Vec<f64> {
+    let channels = channels.max(1);
+    if oversampling == Oversampling::None {
+        return samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| process(i, *s))
+            .collect();
+    }
+    let oversampler = Oversampler::new(oversampling);
+    let latency = oversampler.latency();
+    let factor = oversampling.factor();
+
+    let channel_samples = split_channels(samples.to_vec(), channels);
+    let new_channel_samples = channel_samples
+        .into_iter()
+        .enumerate()
+        .map(|(c, cs)| {
+            let length = cs.len();
+            let padded = [cs, vec![0_f64; latency]].concat();
+            let upsampled = oversampler.upsample(&padded);
+            let processed: Vec<f64> = upsampled
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    // the upsampled signal lags by half the round trip latency
+                    let frame = (i / factor).saturating_sub(latency / 2).min(length.saturating_sub(1));
+                    process(frame * channels + c, *s)
+                })
+                .collect();
+            let downsampled = oversampler.downsample(&processed);
+            downsampled[latency..latency + length].to_vec()
+        })
+        .collect();
+
+    interleave_channels(Ok(new_channel_samples)).unwrap_or_else(|_| samples.to_vec())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::{num_complex::Complex, FftPlanner};
+
+    const SAMPLE_RATE: usize = 44100;
+    // A whole number of cycles in a second, so each harmonic sits on a single FFT bin
+    const TONE_HZ: usize = 3001;
+
+    fn clipped_tone(oversampling: Oversampling) -> Vec<f64> {
+        let tone: Vec<f64> = (0..SAMPLE_RATE)
+            .map(|i| (2.0 * PI * TONE_HZ as f64 * i as f64 / SAMPLE_RATE as f64).sin())
+            .collect();
+        oversample_process(&tone, 1, oversampling, |_, s| (s * 8.0).clamp(-1.0, 1.0))
+    }
+
+    // Share of the energy that isn't at a harmonic below nyquist, which is where the harmonics
+    // above nyquist fold back to
+    fn aliased_db(samples: &[f64]) -> f64 {
+        let window = kaiser_window(samples.len(), KAISER_BETA);
+        let mut buffer: Vec<Complex<f64>> = samples.iter().zip(window.iter()).map(|(s, w)| Complex::new(s * w, 0.0)).collect();
+        FftPlanner::new().plan_fft_forward(buffer.len()).process(&mut buffer);
+        let spectrum: Vec<f64> = buffer[..buffer.len() / 2].iter().map(|c| c.norm_sqr()).collect();
+        // The window spreads each harmonic over a few bins
+        let is_harmonic = |bin: usize| {
+            let nearest = ((bin as f64 / TONE_HZ as f64).round() as usize) * TONE_HZ;
+            nearest > 0 && bin.abs_diff(nearest) <= 8
+        };
+        let total: f64 = spectrum.iter().sum();
+        let aliased: f64 = spectrum.iter().enumerate().filter(|(bin, _)| !is_harmonic(*bin)).map(|(_, e)| e).sum();
+        10.0 * (aliased / total).log10()
+    }
+
+    #[test]
+    fn oversampling_reduces_aliasing() {
+        let plain = aliased_db(&clipped_tone(Oversampling::None));
+        let oversampled = aliased_db(&clipped_tone(Oversampling::X8));
+        assert!(oversampled < plain - 20.0, "plain {:.1}dB, oversampled {:.1}dB", plain, oversampled);
+    }
+
+    #[test]
+    fn more_oversampling_aliases_less() {
+        let x2 = aliased_db(&clipped_tone(Oversampling::X2));
+        let x8 = aliased_db(&clipped_tone(Oversampling::X8));
+        assert!(x8 < x2, "2x {:.1}dB, 8x {:.1}dB", x2, x8);
+    }
+
+    #[test]
+    fn oversampled_output_is_aligned_with_input() {
+        let tone: Vec<f64> = (0..4096).map(|i| (2.0 * PI * 440.0 * i as f64 / SAMPLE_RATE as f64).sin() * 0.5).collect();
+        let output = oversample_process(&tone, 1, Oversampling::X4, |_, s| s);
+        assert_eq!(output.len(), tone.len());
+        let error = tone.iter().zip(output.iter()).skip(64).take(3900).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+        assert!(error < 0.01, "error {}", error);
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use crate::{
+    permute_error::PermuteError,
+    process::{ProcessorClosure, ProcessorParams},
+    processors::{
+        gain_distortion::{sum, SampleLine},
+        stereo::promote_to_stereo,
+        time_pitch::change_sample_rate,
+    },
+};
+
+// Sums layers of audio that started from the same input but may have come out a different
+// length, channel count or sample rate. Shorter layers are padded with silence, layers are
+// resampled to the template's rate and mono layers are promoted to stereo when another layer
+// is stereo. Layers that still can't be lined up are an error rather than left out of the mix
+pub fn mix_layers(template: &ProcessorParams, layers: Vec<(ProcessorParams, f64)>) -> Result<ProcessorParams, PermuteError> {
+    let channels = layers.iter().fold(template.channels, |acc, (layer, _)| acc.max(layer.channels));
+    let mut lines: Vec<SampleLine> = vec![];
+    for (layer, gain) in layers {
+        let layer = change_sample_rate(layer, template.sample_rate, template.permutation.resample_quality)?;
+        let samples = if layer.channels == channels {
+            layer.samples
+        } else if layer.channels == 1 && channels == 2 {
+            promote_to_stereo(&layer.samples)
+        } else {
+            return Err(PermuteError::IO(std::io::Error::other(format!(
+                "Can't mix a {} channel layer into {} channels",
+                layer.channels, channels
+            ))));
+        };
+        lines.push(SampleLine {
+            samples,
+            gain_factor: gain,
+        });
+    }
+    let mixed = sum(lines);
+
+    Ok(ProcessorParams {
+        sample_length: mixed.len(),
+        samples: mixed,
+        channels,
+        ..template.clone()
+    })
+}
+
+// Runs a node's sub-chain. The sub-chain's processors share the node's index, so their events are
+// kept back rather than marking the node complete while the rest of it is still running
+pub fn run_chain(params: &ProcessorParams, chain: Vec<ProcessorClosure>) -> Result<ProcessorParams, PermuteError> {
+    let (sender, _receiver) = crossbeam_channel::unbounded();
+    let mut chain_params = ProcessorParams {
+        update_sender: Arc::new(sender),
+        ..params.clone()
+    };
+    for closure in chain {
+        chain_params = closure(chain_params)?;
+    }
+    Ok(ProcessorParams {
+        update_sender: params.update_sender.clone(),
+        ..chain_params
+    })
+}
+
+// Splits the signal, runs each chain on its own copy, then mixes the results back with their gains.
+// An empty chain passes the input through untouched
+pub fn parallel(params: &ProcessorParams, branches: Vec<(Vec<ProcessorClosure>, f64)>) -> Result<ProcessorParams, PermuteError> {
+    let mut layers = vec![];
+    for (chain, gain) in branches {
+        layers.push((run_chain(params, chain)?, gain));
+    }
+    mix_layers(params, layers)
+}
+
+// Runs a processor and blends its output back with the unprocessed input
+pub fn dry_wet(params: &ProcessorParams, processor: ProcessorClosure, mix: f64) -> Result<ProcessorParams, PermuteError> {
+    let wet = processor(params.clone())?;
+    let template = wet.clone();
+    mix_layers(&template, vec![(params.clone(), 1.0 - mix), (wet, mix)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(samples: Vec<f64>, channels: usize) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate: 44100,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn ramp(length: usize) -> Vec<f64> {
+        (0..length).map(|i| i as f64 / length as f64 - 0.5).collect()
+    }
+
+    fn scale(factor: f64) -> ProcessorClosure {
+        Box::new(move |params: ProcessorParams| {
+            Ok(ProcessorParams {
+                samples: params.samples.iter().map(|s| s * factor).collect(),
+                ..params
+            })
+        })
+    }
+
+    fn extend(frames: usize) -> ProcessorClosure {
+        Box::new(move |params: ProcessorParams| {
+            let mut samples = params.samples.clone();
+            samples.extend(vec![1.0; frames * params.channels]);
+            Ok(ProcessorParams {
+                sample_length: samples.len(),
+                samples,
+                ..params
+            })
+        })
+    }
+
+    fn to_stereo() -> ProcessorClosure {
+        Box::new(|params: ProcessorParams| {
+            Ok(ProcessorParams {
+                samples: promote_to_stereo(&params.samples),
+                channels: 2,
+                ..params
+            })
+        })
+    }
+
+    fn same(a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-12)
+    }
+
+    #[test]
+    fn branches_are_mixed_with_their_gains() {
+        let input = params(ramp(100), 1);
+        let out = parallel(&input, vec![(vec![], 0.5), (vec![], 0.5)]).unwrap();
+        assert!(same(&out.samples, &input.samples));
+
+        let out = parallel(&input, vec![(vec![], 1.0), (vec![scale(2.0), scale(-1.0)], 0.5)]).unwrap();
+        // The inverted branch cancels the dry one
+        assert!(same(&out.samples, &[0.0; 100]));
+
+        let out = parallel(&input, vec![(vec![scale(3.0)], 1.0)]).unwrap();
+        let expected: Vec<f64> = input.samples.iter().map(|s| s * 3.0).collect();
+        assert!(same(&out.samples, &expected));
+    }
+
+    #[test]
+    fn shorter_branches_are_padded() {
+        let input = params(ramp(100), 1);
+        let out = parallel(&input, vec![(vec![], 1.0), (vec![extend(20)], 1.0)]).unwrap();
+        assert_eq!(out.samples.len(), 120);
+        assert_eq!(out.sample_length, 120);
+        let doubled: Vec<f64> = input.samples.iter().map(|s| s * 2.0).collect();
+        assert!(same(&out.samples[..100], &doubled));
+        assert!(out.samples[100..].iter().all(|s| *s == 1.0));
+    }
+
+    #[test]
+    fn mono_branches_are_promoted_when_another_is_stereo() {
+        let input = params(ramp(100), 1);
+        let out = parallel(&input, vec![(vec![], 1.0), (vec![to_stereo()], 1.0)]).unwrap();
+        assert_eq!(out.channels, 2);
+        let expected: Vec<f64> = promote_to_stereo(&input.samples).iter().map(|s| s * 2.0).collect();
+        assert!(same(&out.samples, &expected));
+    }
+
+    #[test]
+    fn layers_that_cant_line_up_are_an_error() {
+        let template = params(ramp(100), 2);
+        let surround = params(ramp(300), 3);
+        assert!(mix_layers(&template, vec![(template.clone(), 1.0), (surround, 1.0)]).is_err());
+    }
+
+    #[test]
+    fn layers_are_resampled_to_the_template_rate() {
+        let template = params(vec![0.0; 1000], 1);
+        let half_rate = ProcessorParams {
+            sample_rate: 22050,
+            ..params(vec![0.5; 500], 1)
+        };
+        let out = mix_layers(&template, vec![(template.clone(), 1.0), (half_rate, 1.0)]).unwrap();
+        assert_eq!(out.sample_rate, 44100);
+        assert!((out.samples.len() as i64 - 1000).abs() <= 2, "{}", out.samples.len());
+        assert!(out.samples[200..800].iter().all(|s| (s - 0.5).abs() < 0.01));
+    }
+
+    #[test]
+    fn dry_wet_blends_the_input_and_output() {
+        let input = params(ramp(100), 1);
+        let dry = dry_wet(&input, scale(-1.0), 0.0).unwrap();
+        assert!(same(&dry.samples, &input.samples));
+        let wet = dry_wet(&input, scale(-1.0), 1.0).unwrap();
+        let inverted: Vec<f64> = input.samples.iter().map(|s| -s).collect();
+        assert!(same(&wet.samples, &inverted));
+        let blend = dry_wet(&input, scale(3.0), 0.25).unwrap();
+        let expected: Vec<f64> = input.samples.iter().map(|s| s * 0.75 + s * 3.0 * 0.25).collect();
+        assert!(same(&blend.samples, &expected));
+    }
+
+    #[test]
+    fn dry_wet_follows_the_shape_of_the_wet_output() {
+        let input = params(ramp(100), 1);
+        let out = dry_wet(&input, to_stereo(), 0.5).unwrap();
+        assert_eq!(out.channels, 2);
+        assert!(same(&out.samples, &promote_to_stereo(&input.samples)));
+
+        let out = dry_wet(&input, extend(50), 0.5).unwrap();
+        assert_eq!(out.samples.len(), 150);
+        assert!(same(&out.samples[..100], &input.samples));
+        assert!(out.samples[100..].iter().all(|s| *s == 0.5));
+    }
+
+    #[test]
+    fn chains_report_to_the_callers_sender() {
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let input = ProcessorParams {
+            update_sender: Arc::new(sender),
+            ..params(ramp(10), 1)
+        };
+        let out = run_chain(&input, vec![scale(2.0), scale(0.5)]).unwrap();
+        assert!(Arc::ptr_eq(&out.update_sender, &input.update_sender));
+        assert!(same(&out.samples, &input.samples));
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use strum::EnumIter;
+
+use crate::processors::oversample::{bessel_i0, sinc};
+
+// Kernel lookup resolution, points per zero crossing of the sinc
+const TABLE_RESOLUTION: usize = 512;
+// Keep the cutoff slightly below nyquist so the transition band doesn't fold back
+const ROLLOFF: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
+pub enum ResampleQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ResampleQuality {
+    // Zero crossings of the sinc either side of the interpolation point
+    fn half_taps(&self) -> usize {
+        match self {
+            ResampleQuality::Low => 8,
+            ResampleQuality::Medium => 24,
+            ResampleQuality::High => 64,
+        }
+    }
+
+    fn kaiser_beta(&self) -> f64 {
+        match self {
+            ResampleQuality::Low => 6.0,
+            ResampleQuality::Medium => 8.5,
+            ResampleQuality::High => 11.0,
+        }
+    }
+}
+
+pub fn get_resample_quality_from_name(name: &str) -> Result<ResampleQuality, String> {
+    match name.to_lowercase().as_str() {
+        "low" => Ok(ResampleQuality::Low),
+        "medium" => Ok(ResampleQuality::Medium),
+        "high" => Ok(ResampleQuality::High),
+        _ => Err(format!("{} not found", name)),
+    }
+}
+
+// Kaiser windowed sinc interpolator.
+// The kernel is tabulated once so each tap is a table lookup rather than a sin() call
+pub struct SincResampler {
+    half_taps: usize,
+    table: Vec<f64>,
+}
+
+impl SincResampler {
+    pub fn new(quality: ResampleQuality) -> Self {
+        let half_taps = quality.half_taps();
+        let beta = quality.kaiser_beta();
+        let denominator = bessel_i0(beta);
+        let table_length = half_taps * TABLE_RESOLUTION + 2;
+        let table = (0..table_length)
+            .map(|i| {
+                let u = i as f64 / TABLE_RESOLUTION as f64;
+                let r = (u / half_taps as f64).min(1.0);
+                let window = bessel_i0(beta * (1.0 - r * r).sqrt()) / denominator;
+                sinc(u) * window
+            })
+            .collect();
+        SincResampler { half_taps, table }
+    }
+
+    fn kernel(&self, u: f64) -> f64 {
+        let position = u.abs() * TABLE_RESOLUTION as f64;
+        let index = position as usize;
+        if index + 1 >= self.table.len() {
+            return 0.0;
+        }
+        let frac = position - index as f64;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * frac
+    }
+
+    // Value of the band limited signal at a fractional sample position.
+    // Cutoff is relative to the input nyquist. Values below 1 widen the kernel to band limit before decimating
+    pub fn interpolate(&self, samples: &[f64], position: f64, cutoff: f64) -> f64 {
+        let cutoff = cutoff.clamp(0.001, 1.0);
+        let width = self.half_taps as f64 / cutoff;
+        let first = (position - width).ceil().max(0.0) as usize;
+        let last = ((position + width).floor() as usize).min(samples.len().saturating_sub(1));
+        if samples.is_empty() || first > last {
+            return 0.0;
+        }
+        let mut acc = 0.0;
+        for (j, sample) in samples.iter().enumerate().take(last + 1).skip(first) {
+            acc += sample * self.kernel(cutoff * (j as f64 - position));
+        }
+        acc * cutoff
+    }
+
+    // Constant speed resample of a single channel. Speeds above 1 shorten the audio
+    pub fn resample(&self, samples: &[f64], speed: f64) -> Vec<f64> {
+        let new_sample_length = ((samples.len() as f64) / speed).ceil() as usize;
+        let cutoff = speed.recip().min(1.0) * ROLLOFF;
+        (0..new_sample_length)
+            .map(|i| self.interpolate(samples, i as f64 * speed, cutoff))
+            .collect()
+    }
+
+    // Variable speed resample of a single channel, one output sample per (read position, speed) step.
+    // The band limit follows the speed so fast passages don't alias. Negative speeds play backwards
+    pub fn resample_positions(&self, samples: &[f64], steps: &[(f64, f64)]) -> Vec<f64> {
+        steps
+            .iter()
+            .map(|(position, speed)| {
+                let cutoff = speed.abs().recip().min(1.0) * ROLLOFF;
+                self.interpolate(samples, *position, cutoff)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::{num_complex::Complex, FftPlanner};
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    // Linear sine sweep, faded in and out so the edges don't splatter across the spectrum
+    fn sweep(start_hz: f64, end_hz: f64, seconds: f64) -> Vec<f64> {
+        let length = (seconds * SAMPLE_RATE) as usize;
+        let fade = length / 10;
+        let rate = (end_hz - start_hz) / seconds;
+        (0..length)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE;
+                let edge = i.min(length - 1 - i);
+                let gain = if edge < fade {
+                    0.5 - 0.5 * (PI * edge as f64 / fade as f64).cos()
+                } else {
+                    1.0
+                };
+                (2.0 * PI * (start_hz * t + 0.5 * rate * t * t)).sin() * 0.5 * gain
+            })
+            .collect()
+    }
+
+    // Mean power, from the spectrum via parseval
+    fn power(samples: &[f64], low_hz: f64, high_hz: f64) -> f64 {
+        band_energy(samples, low_hz, high_hz) / (samples.len() as f64).powi(2)
+    }
+
+    fn band_energy(samples: &[f64], low_hz: f64, high_hz: f64) -> f64 {
+        let mut buffer: Vec<Complex<f64>> = samples.iter().map(|s| Complex::new(*s, 0.0)).collect();
+        FftPlanner::new().plan_fft_forward(buffer.len()).process(&mut buffer);
+        let bin_hz = SAMPLE_RATE / buffer.len() as f64;
+        buffer
+            .iter()
+            .take(buffer.len() / 2)
+            .enumerate()
+            .filter(|(i, _)| {
+                let hz = *i as f64 * bin_hz;
+                hz >= low_hz && hz <= high_hz
+            })
+            .map(|(_, c)| c.norm_sqr())
+            .sum()
+    }
+
+    fn to_db(ratio: f64) -> f64 {
+        10.0 * ratio.log10()
+    }
+
+    // Everything in the sweep ends up above nyquist when played at double speed,
+    // so anything left in the output is aliasing
+    fn aliasing_rejection_db(quality: ResampleQuality) -> f64 {
+        let input = sweep(12000.0, 20000.0, 1.0);
+        let output = SincResampler::new(quality).resample(&input, 2.0);
+        let input_energy = power(&input, 0.0, SAMPLE_RATE / 2.0);
+        let output_energy = power(&output, 0.0, SAMPLE_RATE / 2.0);
+        -to_db(output_energy / input_energy)
+    }
+
+    // Slowing a full band sweep down leaves images above the new band edge
+    fn imaging_rejection_db(quality: ResampleQuality) -> f64 {
+        let input = sweep(100.0, 20000.0, 1.0);
+        let output = SincResampler::new(quality).resample(&input, 0.5);
+        let wanted = band_energy(&output, 0.0, 10050.0);
+        let images = band_energy(&output, 11500.0, SAMPLE_RATE / 2.0);
+        -to_db(images / wanted)
+    }
+
+    #[test]
+    fn double_speed_rejects_aliasing() {
+        assert!(aliasing_rejection_db(ResampleQuality::Low) > 45.0);
+        assert!(aliasing_rejection_db(ResampleQuality::Medium) > 90.0);
+        assert!(aliasing_rejection_db(ResampleQuality::High) > 110.0);
+    }
+
+    #[test]
+    fn half_speed_rejects_images() {
+        assert!(imaging_rejection_db(ResampleQuality::Low) > 45.0);
+        assert!(imaging_rejection_db(ResampleQuality::Medium) > 90.0);
+        assert!(imaging_rejection_db(ResampleQuality::High) > 110.0);
+    }
+
+    #[test]
+    fn passband_is_preserved() {
+        let input = sweep(100.0, 8000.0, 1.0);
+        let output = SincResampler::new(ResampleQuality::Medium).resample(&input, 2.0);
+        let input_energy = power(&input, 0.0, SAMPLE_RATE / 2.0);
+        let output_energy = power(&output, 0.0, SAMPLE_RATE / 2.0);
+        assert!(to_db(output_energy / input_energy).abs() < 0.5);
+    }
+
+    #[test]
+    fn resample_length_follows_speed() {
+        let input = vec![0.0; 1000];
+        let resampler = SincResampler::new(ResampleQuality::Low);
+        assert_eq!(resampler.resample(&input, 2.0).len(), 500);
+        assert_eq!(resampler.resample(&input, 0.5).len(), 2000);
+    }
+}
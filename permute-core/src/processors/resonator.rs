@@ -0,0 +1,227 @@
+use crate::{
+    permute_error::PermuteError,
+    process::ProcessorParams,
+    processors::{
+        delay_reverb::feedback_comb_filter,
+        filter::{multi_channel_filter, FilterForm, FilterParams, FilterType},
+        gain_distortion::{interleave_channels, split_channels, sum, SampleLine},
+    },
+};
+
+// Longest ring out added to the end of the audio
+const MAX_TAIL_MS: f64 = 4000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResonatorMode {
+    // Feedback combs, harmonically rich like a plucked string
+    Comb,
+    // High Q band pass filters, a pure ringing tone at each note
+    Bandpass,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResonatorParams {
+    pub mode: ResonatorMode,
+    pub frequencies: Vec<f64>,
+    pub decay_ms: f64, // time for each resonator to ring down by 60dB
+    pub damping: f64,  // 0 - 1, comb only
+    pub mix: f64,
+}
+
+// How long the resonators ring on past the end of the audio
+pub fn resonator_tail_ms(decay_ms: f64) -> f64 {
+    decay_ms.min(MAX_TAIL_MS)
+}
+
+pub fn midi_to_hz(note: f64) -> f64 {
+    440.0 * 2_f64.powf((note - 69.0) / 12.0)
+}
+
+fn comb_resonator(samples: &[f64], sample_rate: usize, frequency: f64, decay_ms: f64, damping: f64) -> Vec<f64> {
+    let delay_samples = sample_rate as f64 / frequency;
+    // Feedback for a 60dB drop over the decay time
+    let feedback = 0.001_f64.powf(delay_samples / (decay_ms / 1000.0 * sample_rate as f64)).min(0.999);
+    // Peak gain of a comb is 1 / (1 - feedback), bring it back to unity
+    feedback_comb_filter(samples, delay_samples, feedback, damping)
+        .iter()
+        .map(|s| s * (1.0 - feedback))
+        .collect()
+}
+
+// A two pole resonator decays by 60dB in ~2.2 / bandwidth seconds
+fn bandpass_q(frequency: f64, decay_ms: f64) -> f64 {
+    (frequency * (decay_ms / 1000.0) / 2.2).clamp(1.0, 3000.0)
+}
+
+// Bank of resonators tuned to a set of frequencies, e.g. a chord, run in parallel over the audio
+pub fn resonator_bank(
+    params: &ProcessorParams,
+    resonator_params: &ResonatorParams,
+) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let nyquist = params.sample_rate as f64 / 2.0;
+    let frequencies: Vec<f64> = resonator_params
+        .frequencies
+        .iter()
+        .copied()
+        .filter(|f| *f > 20.0 && *f < nyquist * 0.9)
+        .collect();
+    if frequencies.is_empty() {
+        return Ok(params.clone());
+    }
+
+    // Let the resonators ring out past the end of the audio
+    let tail_frames = (resonator_tail_ms(resonator_params.decay_ms) / 1000.0 * params.sample_rate as f64) as usize;
+    let mut padded = params.samples.clone();
+    padded.extend(vec![0_f64; tail_frames * channels]);
+    let padded_params = ProcessorParams {
+        sample_length: padded.len(),
+        samples: padded,
+        ..params.clone()
+    };
+
+    let resonator_gain = (frequencies.len() as f64).sqrt().recip();
+    let mut resonator_lines: Vec<SampleLine> = vec![];
+    for frequency in frequencies {
+        let resonated = match resonator_params.mode {
+            ResonatorMode::Comb => {
+                let channel_samples = split_channels(padded_params.samples.to_owned(), channels)
+                    .iter()
+                    .map(|cs| {
+                        comb_resonator(
+                            cs,
+                            params.sample_rate,
+                            frequency,
+                            resonator_params.decay_ms,
+                            resonator_params.damping,
+                        )
+                    })
+                    .collect();
+                interleave_channels(Ok(channel_samples))?
+            }
+            ResonatorMode::Bandpass => {
+                let filter_params = FilterParams {
+                    frequency,
+                    q: Some(bandpass_q(frequency, resonator_params.decay_ms)),
+                    filter_type: FilterType::BandPass,
+                    form: FilterForm::Form1,
+                };
+                multi_channel_filter(&padded_params, &filter_params)?.samples
+            }
+        };
+        resonator_lines.push(SampleLine {
+            samples: resonated,
+            gain_factor: resonator_gain,
+        });
+    }
+    let resonated = sum(resonator_lines);
+
+    // Narrow resonators can be much quieter or louder than the input, so match peaks before mixing
+    let peak = |samples: &[f64]| samples.iter().fold(0_f64, |acc, s| acc.max(s.abs()));
+    let wet_peak = peak(&resonated);
+    let wet_gain = if wet_peak > 0.0 { peak(&params.samples) / wet_peak } else { 0.0 };
+
+    let summed = sum(vec![
+        SampleLine {
+            samples: params.samples.to_owned(),
+            gain_factor: 1.0 - resonator_params.mix,
+        },
+        SampleLine {
+            samples: resonated,
+            gain_factor: wet_gain * resonator_params.mix,
+        },
+    ]);
+
+    Ok(ProcessorParams {
+        sample_length: summed.len(),
+        samples: summed,
+        ..params.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn params(samples: Vec<f64>) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn impulse() -> ProcessorParams {
+        let mut samples = vec![0.0; SAMPLE_RATE / 10];
+        samples[0] = 1.0;
+        params(samples)
+    }
+
+    fn resonator(mode: ResonatorMode, frequencies: Vec<f64>, mix: f64) -> ResonatorParams {
+        ResonatorParams {
+            mode,
+            frequencies,
+            decay_ms: 1000.0,
+            damping: 0.0,
+            mix,
+        }
+    }
+
+    fn level_db(samples: &[f64], hz: f64) -> f64 {
+        let n = samples.len() as f64;
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, s) in samples.iter().enumerate() {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / n).cos();
+            let angle = 2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64;
+            re += s * window * angle.cos();
+            im -= s * window * angle.sin();
+        }
+        20.0 * ((re * re + im * im).sqrt() / n).max(1e-12).log10()
+    }
+
+    #[test]
+    fn notes_are_tuned_to_a440() {
+        assert_eq!(midi_to_hz(69.0), 440.0);
+        assert_eq!(midi_to_hz(81.0), 880.0);
+        assert!((midi_to_hz(60.0) - 261.626).abs() < 0.001);
+    }
+
+    #[test]
+    fn resonators_ring_at_their_frequencies() {
+        for mode in [ResonatorMode::Comb, ResonatorMode::Bandpass] {
+            let rung = resonator_bank(&impulse(), &resonator(mode, vec![440.0, 660.0], 1.0)).unwrap();
+            assert!(level_db(&rung.samples, 440.0) > level_db(&rung.samples, 550.0) + 20.0, "{:?}", mode);
+            assert!(level_db(&rung.samples, 660.0) > level_db(&rung.samples, 550.0) + 20.0, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn resonators_ring_out_over_the_decay_time() {
+        for mode in [ResonatorMode::Comb, ResonatorMode::Bandpass] {
+            let rung = resonator_bank(&impulse(), &resonator(mode, vec![440.0], 1.0)).unwrap();
+            // The tail is added on the end of the audio
+            assert_eq!(rung.samples.len(), SAMPLE_RATE / 10 + SAMPLE_RATE);
+            // Measured at the note, as a comb's upper harmonics die away sooner
+            let window = SAMPLE_RATE / 20;
+            let start = level_db(&rung.samples[..window], 440.0);
+            let after_decay = level_db(&rung.samples[SAMPLE_RATE..SAMPLE_RATE + window], 440.0);
+            assert!((start - after_decay - 60.0).abs() < 5.0, "{:?} {} {}", mode, start, after_decay);
+        }
+    }
+
+    #[test]
+    fn no_mix_or_no_usable_frequencies_leave_the_input() {
+        let input = impulse();
+        let dry = resonator_bank(&input, &resonator(ResonatorMode::Comb, vec![440.0], 0.0)).unwrap();
+        assert_eq!(dry.samples[..input.samples.len()], input.samples[..]);
+        assert!(dry.samples[input.samples.len()..].iter().all(|s| *s == 0.0));
+        // Past nyquist there is nothing to tune to
+        let untuned = resonator_bank(&input, &resonator(ResonatorMode::Comb, vec![10.0, 30000.0], 1.0)).unwrap();
+        assert_eq!(untuned.samples, input.samples);
+    }
+}
@@ -0,0 +1,406 @@
+use std::f64::consts::PI;
+
+use crate::{
+    permute_error::PermuteError,
+    process::ProcessorParams,
+    processors::{
+        delay_reverb::{reverb, ReverbParams},
+        gain_distortion::{interleave_channels, split_channels, sum, SampleLine},
+        stereo::schroeder_all_pass,
+    },
+};
+
+const FDN_LINES: usize = 8;
+// Roughly mutually prime so the echoes don't pile up on the same samples
+const HALL_DELAYS_MS: [f64; FDN_LINES] = [29.7, 37.1, 41.1, 43.7, 53.3, 59.9, 67.7, 73.1];
+const PLATE_DELAYS_MS: [f64; FDN_LINES] = [7.1, 9.4, 11.3, 13.7, 15.9, 19.1, 22.3, 25.7];
+const HALL_DIFFUSION_MS: [f64; 2] = [4.1, 6.7];
+// Plates build up density almost instantly
+const PLATE_DIFFUSION_MS: [f64; 4] = [1.3, 2.9, 4.7, 7.1];
+const DIFFUSION_GAIN: f64 = 0.65;
+// Each pass is shifted up an octave and fed through the hall again
+const SHIMMER_PASSES: usize = 2;
+// Fraction of the tail faded out at the end, so a long reverb doesn't stop with a click
+const TAIL_FADE: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReverbAlgorithm {
+    // The original 80s style modulated comb reverb
+    Classic,
+    // Feedback delay network
+    Hall,
+    Plate,
+    // Hall with octave up feedback
+    Shimmer,
+    // Reverb that swells into the sound rather than trailing after it
+    Reverse,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReverbEngineParams {
+    pub algorithm: ReverbAlgorithm,
+    pub predelay_ms: f64,
+    pub wet_mix: f64,
+    pub size: f64,         // scales delay lengths, around 0.1 - 1.5. Classic uses this as its length factor
+    pub decay_ms: f64,     // time to fall by 60dB
+    pub damping_hz: f64,   // higher frequencies die away faster than this
+    pub decay_factor: f64, // Classic only
+    pub shimmer_feedback: f64,
+}
+
+// How much longer the output is than the input
+pub fn reverb_tail_ms(engine_params: &ReverbEngineParams) -> f64 {
+    match engine_params.algorithm {
+        // Rough guess from listening, the classic reverb has no direct decay time
+        ReverbAlgorithm::Classic => engine_params.predelay_ms + 800.0 * engine_params.size,
+        ReverbAlgorithm::Hall | ReverbAlgorithm::Plate | ReverbAlgorithm::Reverse => {
+            engine_params.predelay_ms + engine_params.decay_ms
+        }
+        ReverbAlgorithm::Shimmer => engine_params.predelay_ms + engine_params.decay_ms * 1.5,
+    }
+}
+
+// In place fast walsh-hadamard transform, normalised so the mixing matrix is orthogonal
+fn hadamard(values: &mut [f64; FDN_LINES]) {
+    let mut h = 1;
+    while h < FDN_LINES {
+        for i in (0..FDN_LINES).step_by(h * 2) {
+            for j in i..i + h {
+                let (a, b) = (values[j], values[j + h]);
+                values[j] = a + b;
+                values[j + h] = a - b;
+            }
+        }
+        h *= 2;
+    }
+    let norm = (FDN_LINES as f64).sqrt().recip();
+    values.iter_mut().for_each(|v| *v *= norm);
+}
+
+// Eight delay lines mixed through a hadamard matrix. Each line's gain is set from its length
+// so every path decays at the same rate, which keeps the network stable at any decay time
+fn feedback_delay_network(
+    input: &[f64],
+    sample_rate: usize,
+    delays_ms: &[f64; FDN_LINES],
+    engine_params: &ReverbEngineParams,
+    modulation_ms: f64,
+    channel: usize,
+) -> Vec<f64> {
+    let sr = sample_rate as f64;
+    let delays: Vec<f64> = delays_ms.iter().map(|ms| ms * engine_params.size / 1000.0 * sr).collect();
+    let modulation = modulation_ms / 1000.0 * sr;
+    let decay_samples = (engine_params.decay_ms / 1000.0 * sr).max(1.0);
+    let gains: Vec<f64> = delays.iter().map(|d| 0.001_f64.powf(d / decay_samples)).collect();
+    let damping = 1.0 - (-2.0 * PI * engine_params.damping_hz / sr).exp();
+    let buffer_length = (delays.iter().fold(0_f64, |acc, d| acc.max(*d)) + modulation) as usize + 2;
+
+    // Different output taps per channel keep stereo channels apart
+    let output_signs: Vec<f64> = (0..FDN_LINES)
+        .map(|i| if (i >> (channel % 3)) & 1 == 0 { 1.0 } else { -1.0 })
+        .collect();
+
+    let mut buffers = vec![vec![0_f64; buffer_length]; FDN_LINES];
+    let mut lowpass = [0_f64; FDN_LINES];
+    let mut write = 0;
+    let mut output = Vec::with_capacity(input.len());
+    for (n, x) in input.iter().enumerate() {
+        let mut values = [0_f64; FDN_LINES];
+        for i in 0..FDN_LINES {
+            // Slow, unrelated modulation per line smears the modes of the room
+            let lfo = (2.0 * PI * (0.13 + 0.07 * i as f64) * n as f64 / sr + i as f64).sin();
+            let delay = delays[i] + modulation * (1.0 + lfo) * 0.5;
+            let read = (write as f64 - delay).rem_euclid(buffer_length as f64);
+            let index = read.floor() as usize;
+            let frac = read - index as f64;
+            let a = buffers[i][index % buffer_length];
+            let b = buffers[i][(index + 1) % buffer_length];
+            lowpass[i] += damping * (a + (b - a) * frac - lowpass[i]);
+            values[i] = lowpass[i] * gains[i];
+        }
+        output.push(values.iter().zip(output_signs.iter()).map(|(v, s)| v * s).sum::<f64>());
+
+        hadamard(&mut values);
+        for i in 0..FDN_LINES {
+            let input_sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            buffers[i][write] = values[i] + x * input_sign;
+        }
+        write = (write + 1) % buffer_length;
+    }
+    output
+}
+
+fn diffuse(samples: &[f64], sample_rate: usize, delays_ms: &[f64], size: f64) -> Vec<f64> {
+    delays_ms.iter().fold(samples.to_vec(), |acc, ms| {
+        let delay_samples = (ms * size.max(0.5) / 1000.0 * sample_rate as f64) as usize;
+        schroeder_all_pass(&acc, delay_samples, DIFFUSION_GAIN)
+    })
+}
+
+fn hall(samples: &[f64], sample_rate: usize, engine_params: &ReverbEngineParams, channel: usize) -> Vec<f64> {
+    let diffused = diffuse(samples, sample_rate, &HALL_DIFFUSION_MS, engine_params.size);
+    feedback_delay_network(&diffused, sample_rate, &HALL_DELAYS_MS, engine_params, 0.4, channel)
+}
+
+fn plate(samples: &[f64], sample_rate: usize, engine_params: &ReverbEngineParams, channel: usize) -> Vec<f64> {
+    let diffused = diffuse(samples, sample_rate, &PLATE_DIFFUSION_MS, engine_params.size);
+    feedback_delay_network(&diffused, sample_rate, &PLATE_DELAYS_MS, engine_params, 0.1, channel)
+}
+
+// Delay line pitch shifter. Two read heads sweep through a short window half a cycle apart,
+// each faded out as it wraps around
+fn octave_up(samples: &[f64], sample_rate: usize) -> Vec<f64> {
+    let window = 0.08 * sample_rate as f64;
+    let step = 1.0 / window;
+    let read = |position: f64| {
+        if position < 0.0 {
+            return 0.0;
+        }
+        let index = position.floor() as usize;
+        let frac = position - index as f64;
+        let a = samples.get(index).copied().unwrap_or(0.0);
+        let b = samples.get(index + 1).copied().unwrap_or(0.0);
+        a + (b - a) * frac
+    };
+    let mut phase = 0_f64;
+    (0..samples.len())
+        .map(|n| {
+            let other_phase = (phase + 0.5) % 1.0;
+            let out = read(n as f64 - (1.0 - phase) * window) * (PI * phase).sin()
+                + read(n as f64 - (1.0 - other_phase) * window) * (PI * other_phase).sin();
+            phase = (phase + step) % 1.0;
+            out
+        })
+        .collect()
+}
+
+fn shimmer(samples: &[f64], sample_rate: usize, engine_params: &ReverbEngineParams, channel: usize) -> Vec<f64> {
+    let mut layer = hall(samples, sample_rate, engine_params, channel);
+    let mut wet = layer.clone();
+    for _ in 0..SHIMMER_PASSES {
+        layer = hall(&octave_up(&layer, sample_rate), sample_rate, engine_params, channel)
+            .iter()
+            .map(|s| s * engine_params.shimmer_feedback)
+            .collect();
+        wet.iter_mut().zip(layer.iter()).for_each(|(w, l)| *w += l);
+    }
+    wet
+}
+
+// Wet signal for one channel, the input already padded with the tail
+fn reverb_channel(samples: &[f64], sample_rate: usize, engine_params: &ReverbEngineParams, channel: usize) -> Vec<f64> {
+    let predelay = (engine_params.predelay_ms / 1000.0 * sample_rate as f64) as usize;
+    let mut delayed = vec![0_f64; predelay.min(samples.len())];
+    delayed.extend_from_slice(&samples[..samples.len() - delayed.len()]);
+
+    match engine_params.algorithm {
+        ReverbAlgorithm::Plate => plate(&delayed, sample_rate, engine_params, channel),
+        ReverbAlgorithm::Shimmer => shimmer(&delayed, sample_rate, engine_params, channel),
+        _ => hall(&delayed, sample_rate, engine_params, channel),
+    }
+}
+
+fn peak(samples: &[f64]) -> f64 {
+    samples.iter().fold(0_f64, |acc, s| acc.max(s.abs()))
+}
+
+pub fn reverb_engine(params: &ProcessorParams, engine_params: &ReverbEngineParams) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let tail_frames = (reverb_tail_ms(engine_params) / 1000.0 * params.sample_rate as f64) as usize;
+    let mut padded = params.samples.clone();
+    padded.extend(vec![0_f64; tail_frames * channels]);
+
+    if engine_params.algorithm == ReverbAlgorithm::Classic {
+        let padded_params = ProcessorParams {
+            sample_length: padded.len(),
+            samples: padded,
+            ..params.clone()
+        };
+        return reverb(
+            &padded_params,
+            ReverbParams {
+                predelay_ms: engine_params.predelay_ms,
+                wet_mix: engine_params.wet_mix,
+                len_factor: engine_params.size,
+                decay_factor: engine_params.decay_factor,
+            },
+        );
+    }
+
+    let reverse = engine_params.algorithm == ReverbAlgorithm::Reverse;
+    let wet_channels: Vec<Vec<f64>> = split_channels(params.samples.to_owned(), channels)
+        .iter()
+        .enumerate()
+        .map(|(c, cs)| {
+            let mut input = cs.clone();
+            if reverse {
+                input.reverse();
+            }
+            input.extend(vec![0_f64; tail_frames]);
+            let mut wet = reverb_channel(&input, params.sample_rate, engine_params, c);
+            if reverse {
+                // Swell now leads into the sound
+                wet.reverse();
+            } else {
+                let fade_frames = ((tail_frames as f64 * TAIL_FADE) as usize).min(wet.len());
+                let fade_start = wet.len() - fade_frames;
+                for (i, s) in wet[fade_start..].iter_mut().enumerate() {
+                    *s *= 1.0 - i as f64 / fade_frames as f64;
+                }
+            }
+            wet
+        })
+        .collect();
+    let wet = interleave_channels(Ok(wet_channels))?;
+
+    // The reverse reverb plays before the sound, so the dry audio moves back by the tail
+    let dry = if reverse {
+        let mut dry = vec![0_f64; tail_frames * channels];
+        dry.extend_from_slice(&params.samples);
+        dry
+    } else {
+        params.samples.clone()
+    };
+
+    let wet_peak = peak(&wet);
+    let wet_gain = if wet_peak > 0.0 { peak(&params.samples) / wet_peak } else { 0.0 };
+    let mixed = sum(vec![
+        SampleLine {
+            samples: dry,
+            gain_factor: 1.0,
+        },
+        SampleLine {
+            samples: wet,
+            gain_factor: wet_gain * engine_params.wet_mix,
+        },
+    ]);
+
+    Ok(ProcessorParams {
+        sample_length: mixed.len(),
+        samples: mixed,
+        ..params.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 22050;
+
+    fn params(samples: Vec<f64>, channels: usize) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn impulse(channels: usize) -> ProcessorParams {
+        let mut samples = vec![0.0; SAMPLE_RATE / 10 * channels];
+        samples[..channels].iter_mut().for_each(|s| *s = 1.0);
+        params(samples, channels)
+    }
+
+    fn engine(algorithm: ReverbAlgorithm, wet_mix: f64) -> ReverbEngineParams {
+        ReverbEngineParams {
+            algorithm,
+            predelay_ms: 20.0,
+            wet_mix,
+            size: 1.0,
+            decay_ms: 400.0,
+            damping_hz: 6000.0,
+            decay_factor: 0.5,
+            shimmer_feedback: 0.5,
+        }
+    }
+
+    fn rms(samples: &[f64]) -> f64 {
+        (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    fn frames(ms: f64) -> usize {
+        (ms / 1000.0 * SAMPLE_RATE as f64) as usize
+    }
+
+    #[test]
+    fn tails_cover_the_predelay_and_decay() {
+        assert_eq!(reverb_tail_ms(&engine(ReverbAlgorithm::Hall, 1.0)), 420.0);
+        assert_eq!(reverb_tail_ms(&engine(ReverbAlgorithm::Plate, 1.0)), 420.0);
+        assert_eq!(reverb_tail_ms(&engine(ReverbAlgorithm::Reverse, 1.0)), 420.0);
+        assert_eq!(reverb_tail_ms(&engine(ReverbAlgorithm::Shimmer, 1.0)), 620.0);
+        assert_eq!(reverb_tail_ms(&engine(ReverbAlgorithm::Classic, 1.0)), 820.0);
+    }
+
+    #[test]
+    fn output_is_extended_by_the_tail() {
+        let input = impulse(2);
+        for algorithm in [
+            ReverbAlgorithm::Hall,
+            ReverbAlgorithm::Plate,
+            ReverbAlgorithm::Shimmer,
+            ReverbAlgorithm::Reverse,
+        ] {
+            let engine_params = engine(algorithm, 0.5);
+            let tail = frames(reverb_tail_ms(&engine_params));
+            let out = reverb_engine(&input, &engine_params).unwrap();
+            assert_eq!(out.samples.len(), input.samples.len() + tail * 2, "{:?}", algorithm);
+            assert_eq!(out.sample_length, out.samples.len());
+            assert!(out.samples.iter().all(|s| s.is_finite()), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn wet_signal_starts_after_the_predelay_at_the_dry_peak() {
+        for algorithm in [ReverbAlgorithm::Hall, ReverbAlgorithm::Plate, ReverbAlgorithm::Shimmer] {
+            let out = reverb_engine(&impulse(1), &engine(algorithm, 0.5)).unwrap();
+            assert_eq!(out.samples[0], 1.0);
+            assert!(out.samples[1..frames(20.0)].iter().all(|s| *s == 0.0), "{:?}", algorithm);
+            assert!((peak(&out.samples[1..]) - 0.5).abs() < 1e-9, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn tails_die_away_and_fade_out() {
+        for algorithm in [ReverbAlgorithm::Hall, ReverbAlgorithm::Plate] {
+            let out = reverb_engine(&impulse(1), &engine(algorithm, 1.0)).unwrap();
+            let early = rms(&out.samples[frames(20.0)..frames(120.0)]);
+            let late = rms(&out.samples[frames(320.0)..frames(420.0)]);
+            assert!(late < early * 0.1, "{:?} {} {}", algorithm, early, late);
+            assert!(out.samples.last().unwrap().abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn reverse_swells_into_the_delayed_dry_sound() {
+        let engine_params = engine(ReverbAlgorithm::Reverse, 1.0);
+        let tail = frames(reverb_tail_ms(&engine_params));
+        let out = reverb_engine(&impulse(1), &engine_params).unwrap();
+        assert!((out.samples[tail] - 1.0).abs() < 1e-9);
+        let start = rms(&out.samples[..frames(100.0)]);
+        let before_dry = rms(&out.samples[tail - frames(120.0)..tail - frames(20.0)]);
+        assert!(before_dry > start * 10.0, "{} {}", start, before_dry);
+    }
+
+    #[test]
+    fn stereo_channels_get_different_tails() {
+        let out = reverb_engine(&impulse(2), &engine(ReverbAlgorithm::Hall, 1.0)).unwrap();
+        let channels = split_channels(out.samples, 2);
+        let difference: Vec<f64> = channels[0].iter().zip(channels[1].iter()).map(|(l, r)| l - r).collect();
+        assert!(rms(&difference) > rms(&channels[0]) * 0.5);
+    }
+
+    #[test]
+    fn no_wet_mix_leaves_the_dry_sound_and_silence() {
+        let input = impulse(1);
+        let out = reverb_engine(&input, &engine(ReverbAlgorithm::Hall, 0.0)).unwrap();
+        assert_eq!(out.samples[..input.samples.len()], input.samples[..]);
+        assert!(out.samples[input.samples.len()..].iter().all(|s| *s == 0.0));
+
+        let silence = params(vec![0.0; 1000], 1);
+        let out = reverb_engine(&silence, &engine(ReverbAlgorithm::Plate, 1.0)).unwrap();
+        assert!(out.samples.iter().all(|s| *s == 0.0));
+    }
+}
@@ -0,0 +1,314 @@
+use std::f64::consts::{FRAC_PI_4, SQRT_2};
+
+use crate::{
+    permute_error::PermuteError,
+    process::ProcessorParams,
+    processors::{
+        gain_distortion::{interleave_channels, split_channels},
+        osc::{lfo_sin, lfo_tri},
+    },
+};
+
+// Duplicate a mono signal into interleaved stereo
+pub fn promote_to_stereo(samples: &[f64]) -> Vec<f64> {
+    samples.iter().flat_map(|s| [*s, *s]).collect()
+}
+
+// Left and right channels to work on. Mono is duplicated, anything wider than stereo is left alone
+fn stereo_pair(params: &ProcessorParams) -> Option<(Vec<f64>, Vec<f64>)> {
+    match params.channels {
+        1 => Some((params.samples.clone(), params.samples.clone())),
+        2 => {
+            let mut channel_samples = split_channels(params.samples.clone(), 2);
+            let right = channel_samples.pop()?;
+            let left = channel_samples.pop()?;
+            Some((left, right))
+        }
+        _ => None,
+    }
+}
+
+fn from_stereo_pair(params: &ProcessorParams, left: Vec<f64>, right: Vec<f64>) -> Result<ProcessorParams, PermuteError> {
+    let samples = interleave_channels(Ok(vec![left, right]))?;
+    Ok(ProcessorParams {
+        sample_length: samples.len(),
+        samples,
+        channels: 2,
+        ..params.clone()
+    })
+}
+
+// Schroeder all pass. Flat magnitude response, only the phase is smeared
+pub(crate) fn schroeder_all_pass(samples: &[f64], delay_samples: usize, gain: f64) -> Vec<f64> {
+    let delay_samples = delay_samples.max(1);
+    let mut output = vec![0_f64; samples.len()];
+    for i in 0..samples.len() {
+        let delayed_input = if i >= delay_samples { samples[i - delay_samples] } else { 0.0 };
+        let delayed_output = if i >= delay_samples { output[i - delay_samples] } else { 0.0 };
+        output[i] = -gain * samples[i] + delayed_input + gain * delayed_output;
+    }
+    output
+}
+
+#[derive(Debug, Clone)]
+pub struct DecorrelateParams {
+    pub left_delays_ms: Vec<f64>,
+    pub right_delays_ms: Vec<f64>,
+    pub gain: f64,
+    pub amount: f64,
+}
+
+fn decorrelate_channel(samples: &[f64], delays_ms: &[f64], gain: f64, amount: f64, sample_rate: usize) -> Vec<f64> {
+    let diffused = delays_ms.iter().fold(samples.to_vec(), |acc, ms| {
+        schroeder_all_pass(&acc, ((ms / 1000.0) * sample_rate as f64) as usize, gain)
+    });
+    samples
+        .iter()
+        .zip(diffused.iter())
+        .map(|(dry, wet)| dry * (1.0 - amount) + wet * amount)
+        .collect()
+}
+
+// Runs each side through a different all pass chain so the two channels stop being identical
+pub fn decorrelate(params: &ProcessorParams, decorrelate_params: &DecorrelateParams) -> Result<ProcessorParams, PermuteError> {
+    let (left, right) = match stereo_pair(params) {
+        Some(pair) => pair,
+        None => return Ok(params.clone()),
+    };
+    let DecorrelateParams { left_delays_ms, right_delays_ms, gain, amount } = decorrelate_params;
+    let left = decorrelate_channel(&left, left_delays_ms, *gain, *amount, params.sample_rate);
+    let right = decorrelate_channel(&right, right_delays_ms, *gain, *amount, params.sample_rate);
+    from_stereo_pair(params, left, right)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PanWaveform {
+    Sine,
+    Triangle,
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoPanParams {
+    pub rate_hz: f64,
+    pub depth: f64, // 0 - 1
+    pub waveform: PanWaveform,
+    pub phase: f64,
+}
+
+// Equal power pan, scaled so the centre position is unity gain
+fn pan_gains(pan: f64) -> (f64, f64) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    (angle.cos() * SQRT_2, angle.sin() * SQRT_2)
+}
+
+pub fn auto_pan(params: &ProcessorParams, pan_params: &AutoPanParams) -> Result<ProcessorParams, PermuteError> {
+    let (left, right) = match stereo_pair(params) {
+        Some(pair) => pair,
+        None => return Ok(params.clone()),
+    };
+    let (left, right): (Vec<f64>, Vec<f64>) = left
+        .iter()
+        .zip(right.iter())
+        .enumerate()
+        .map(|(i, (l, r))| {
+            let lfo = match pan_params.waveform {
+                PanWaveform::Sine => lfo_sin(i, params.sample_rate, pan_params.rate_hz, pan_params.phase),
+                PanWaveform::Triangle => lfo_tri(i, params.sample_rate, pan_params.rate_hz),
+            };
+            let (left_gain, right_gain) = pan_gains(lfo * pan_params.depth);
+            (l * left_gain, r * right_gain)
+        })
+        .unzip();
+    from_stereo_pair(params, left, right)
+}
+
+#[derive(Debug, Clone)]
+pub struct StereoWidthParams {
+    // 0 is mono, 1 leaves the image untouched, above 1 widens
+    pub width: f64,
+    // Mono sources have no side signal, so they're decorrelated first to give width something to work on
+    pub decorrelate: Option<DecorrelateParams>,
+}
+
+pub fn stereo_width(params: &ProcessorParams, width_params: &StereoWidthParams) -> Result<ProcessorParams, PermuteError> {
+    let params = match (&width_params.decorrelate, params.channels) {
+        (Some(decorrelate_params), 1) => decorrelate(params, decorrelate_params)?,
+        _ => params.clone(),
+    };
+    let (left, right) = match stereo_pair(&params) {
+        Some(pair) => pair,
+        None => return Ok(params),
+    };
+    // Pull the mid back a little as the sides grow so the overall level stays similar
+    let mid_gain = 2.0 / (1.0 + width_params.width.max(1.0));
+    let (left, right): (Vec<f64>, Vec<f64>) = left
+        .iter()
+        .zip(right.iter())
+        .map(|(l, r)| {
+            let mid = (l + r) * 0.5 * mid_gain;
+            let side = (l - r) * 0.5 * width_params.width;
+            (mid + side, mid - side)
+        })
+        .unzip();
+    from_stereo_pair(&params, left, right)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HaasSide {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+pub struct HaasParams {
+    pub delay_ms: f64,
+    pub delayed_side: HaasSide,
+    pub delayed_gain: f64,
+}
+
+// Delay one side by a few ms. The ear fuses the two and places the sound towards the earlier side
+pub fn haas(params: &ProcessorParams, haas_params: &HaasParams) -> Result<ProcessorParams, PermuteError> {
+    let (left, right) = match stereo_pair(params) {
+        Some(pair) => pair,
+        None => return Ok(params.clone()),
+    };
+    let delay_frames = ((haas_params.delay_ms / 1000.0) * params.sample_rate as f64) as usize;
+    let delay = |samples: Vec<f64>| -> Vec<f64> {
+        [vec![0_f64; delay_frames], samples.iter().map(|s| s * haas_params.delayed_gain).collect()].concat()
+    };
+    let pad = |samples: Vec<f64>| -> Vec<f64> { [samples, vec![0_f64; delay_frames]].concat() };
+    let (left, right) = match haas_params.delayed_side {
+        HaasSide::Left => (delay(left), pad(right)),
+        HaasSide::Right => (pad(left), delay(right)),
+    };
+    from_stereo_pair(params, left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn params(samples: Vec<f64>, channels: usize) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate: SAMPLE_RATE,
+            ..ProcessorParams::default()
+        }
+    }
+
+    // Repeatable noise so the channels have something to differ on
+    fn noise(length: usize) -> Vec<f64> {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state as f64 / u64::MAX as f64) - 0.5
+            })
+            .collect()
+    }
+
+    fn same(a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-9)
+    }
+
+    fn channels(params: &ProcessorParams) -> (Vec<f64>, Vec<f64>) {
+        stereo_pair(params).unwrap()
+    }
+
+    fn decorrelate_params(amount: f64) -> DecorrelateParams {
+        DecorrelateParams {
+            left_delays_ms: vec![3.1, 7.3],
+            right_delays_ms: vec![4.7, 9.1],
+            gain: 0.6,
+            amount,
+        }
+    }
+
+    #[test]
+    fn all_pass_keeps_the_energy() {
+        let mut impulse = vec![0.0; SAMPLE_RATE];
+        impulse[0] = 1.0;
+        let energy: f64 = schroeder_all_pass(&impulse, 100, 0.7).iter().map(|s| s * s).sum();
+        assert!((energy - 1.0).abs() < 1e-6, "{}", energy);
+    }
+
+    #[test]
+    fn decorrelate_turns_mono_into_two_different_channels() {
+        let mono = params(noise(SAMPLE_RATE), 1);
+        let decorrelated = decorrelate(&mono, &decorrelate_params(1.0)).unwrap();
+        assert_eq!((decorrelated.channels, decorrelated.samples.len()), (2, SAMPLE_RATE * 2));
+        let (left, right) = channels(&decorrelated);
+        assert!(!same(&left, &right));
+        // With none of it mixed in the input is only copied to both sides
+        let untouched = decorrelate(&mono, &decorrelate_params(0.0)).unwrap();
+        assert!(same(&untouched.samples, &promote_to_stereo(&mono.samples)));
+    }
+
+    #[test]
+    fn pan_is_equal_power_and_unity_in_the_centre() {
+        let (left, right) = pan_gains(0.0);
+        assert!((left - 1.0).abs() < 1e-9 && (right - 1.0).abs() < 1e-9);
+        for pan in [-1.0, -0.5, 0.25, 1.0] {
+            let (left, right) = pan_gains(pan);
+            assert!((left * left + right * right - 2.0).abs() < 1e-9);
+        }
+        assert!(pan_gains(-1.0).1.abs() < 1e-9);
+        assert!(pan_gains(1.0).0.abs() < 1e-9);
+    }
+
+    #[test]
+    fn auto_pan_without_depth_leaves_the_audio_centred() {
+        let mono = params(noise(SAMPLE_RATE), 1);
+        let pan = AutoPanParams {
+            rate_hz: 2.0,
+            depth: 0.0,
+            waveform: PanWaveform::Sine,
+            phase: 0.0,
+        };
+        let panned = auto_pan(&mono, &pan).unwrap();
+        assert!(same(&panned.samples, &promote_to_stereo(&mono.samples)));
+    }
+
+    #[test]
+    fn width_scales_the_side_signal() {
+        let left = noise(SAMPLE_RATE);
+        let right: Vec<f64> = noise(SAMPLE_RATE).iter().rev().copied().collect();
+        let stereo = params(interleave_channels(Ok(vec![left, right])).unwrap(), 2);
+        let width = |width: f64| {
+            stereo_width(&stereo, &StereoWidthParams { width, decorrelate: None }).unwrap()
+        };
+        let unchanged = width(1.0);
+        assert!(same(&unchanged.samples, &stereo.samples));
+        let (left, right) = channels(&width(0.0));
+        assert!(same(&left, &right));
+        // Wider is more side than mid
+        let side = |params: &ProcessorParams| {
+            let (left, right) = channels(params);
+            left.iter().zip(right.iter()).map(|(l, r)| (l - r).powi(2)).sum::<f64>()
+        };
+        assert!(side(&width(2.0)) > side(&unchanged) * 3.0);
+    }
+
+    #[test]
+    fn haas_delays_one_side_and_pads_the_other() {
+        let mono = params(noise(1000), 1);
+        let haas_params = HaasParams {
+            delay_ms: 10.0,
+            delayed_side: HaasSide::Right,
+            delayed_gain: 0.5,
+        };
+        let (left, right) = channels(&haas(&mono, &haas_params).unwrap());
+        let delay = SAMPLE_RATE / 100;
+        assert_eq!((left.len(), right.len()), (1000 + delay, 1000 + delay));
+        assert!(same(&left[..1000], &mono.samples));
+        assert!(left[1000..].iter().all(|s| *s == 0.0));
+        assert!(right[..delay].iter().all(|s| *s == 0.0));
+        assert!(right[delay..].iter().zip(mono.samples.iter()).all(|(r, m)| *r == m * 0.5));
+    }
+}
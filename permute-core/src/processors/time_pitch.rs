@@ -3,8 +3,8 @@ use crate::{
     permute_error::PermuteError,
     permute_files::PermuteUpdate,
     process::{PermuteNodeEvent, PermuteNodeName},
-    processors::{filter::{FilterType, FilterForm, FilterParams, multi_channel_filter}, 
-    gain_distortion::{split_channels, interleave_channels}},
+    processors::{gain_distortion::{split_channels, interleave_channels},
+    resample::{ResampleQuality, SincResampler}},
 };
 use std::f64::consts::PI;
 use rand::{rngs::ThreadRng, Rng};
@@ -33,32 +33,22 @@ pub fn reverse(
 pub fn change_sample_rate(
     params: ProcessorParams,
     new_sample_rate: usize,
+    quality: ResampleQuality,
 ) -> Result<ProcessorParams, PermuteError> {
     if params.sample_rate == new_sample_rate {
         return Ok(params);
     }
-    let mut new_params = params.clone();
     let speed = params.sample_rate as f64 / new_sample_rate as f64;
 
-    if speed >= 2.0 {
-        new_params = multi_channel_filter(
-            &new_params,
-            &FilterParams {
-                filter_type: FilterType::LowPass,
-                frequency: (new_sample_rate / 2) as f64,
-                form: FilterForm::Form2,
-                q: None,
-            },
-        )?;
-    }
-
-    let resampled = change_speed(new_params, speed);
+    // The resampler band limits to the lower of the two nyquist frequencies
+    let resampled = change_speed(params, speed, quality);
 
-    Ok(resampled)
+    Ok(ProcessorParams {
+        sample_rate: new_sample_rate,
+        ..resampled
+    })
 }
 
-
-
 pub fn change_speed(
     ProcessorParams {
         samples,
@@ -72,38 +62,16 @@ pub fn change_speed(
         ..
     }: ProcessorParams,
     speed: f64,
+    quality: ResampleQuality,
 ) -> ProcessorParams {
     let channel_samples = split_channels(samples, channels);
-    let mut new_channel_samples: Vec<Result<Vec<f64>, PermuteError>> = vec![];
-
-    for c in 0..channel_samples.len() {
-        let cs = &channel_samples[c];
-        let new_sample_length: usize = ((cs.len() as f64) / speed).ceil() as usize;
-        let mut ns: Vec<f64> = vec![0_f64; new_sample_length];
-
-        let mut v1: f64;
-        let mut v2: f64;
-        let len = new_sample_length - 1;
-        for i in 0..len {
-            let offset_f = (i as f64 - 1_f64) * speed;
-            let offset = offset_f.floor() as usize;
-            let frac = offset_f - offset as f64;
-
-            v1 = cs[offset];
-            v2 = if offset + 1 < cs.len() {
-                cs[offset + 1]
-            } else {
-                cs[offset]
-            };
-
-            ns[i] = v1 + (v2 - v1) * frac;
-        }
-        new_channel_samples.push(Ok(ns));
-    }
-
-    let new_channel_samples = new_channel_samples.into_iter().collect();
+    let resampler = SincResampler::new(quality);
+    let new_channel_samples: Vec<Vec<f64>> = channel_samples
+        .iter()
+        .map(|cs| resampler.resample(cs, speed))
+        .collect();
 
-    let interleave_samples = interleave_channels(new_channel_samples).unwrap();
+    let interleave_samples = interleave_channels(Ok(new_channel_samples)).unwrap();
     let interleave_sample_length = interleave_samples.len();
 
     return ProcessorParams {
@@ -119,6 +87,139 @@ pub fn change_speed(
     };
 }
 
+// Below this the tape has stopped
+const STOPPED_SPEED: f64 = 1e-4;
+// Longest a speed curve can stretch the audio, in case a curve lingers near standstill
+const MAX_CURVE_STRETCH: f64 = 16.0;
+// Fade at the end of a speed curve, which may stop part way through the audio
+const CURVE_FADE_MS: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RampShape {
+    Linear,
+    EaseIn,
+    EaseOut,
+    SCurve,
+}
+
+impl RampShape {
+    // Progress along a ramp, u from 0 - 1
+    fn ease(&self, u: f64) -> f64 {
+        match self {
+            RampShape::Linear => u,
+            RampShape::EaseIn => u * u,
+            RampShape::EaseOut => 1.0 - (1.0 - u) * (1.0 - u),
+            RampShape::SCurve => u * u * (3.0 - 2.0 * u),
+        }
+    }
+}
+
+// Speed of the tape over time. Stops, starts and spins are timed in output frames. A curve of 1 is
+// constant acceleration, above 1 the speed changes quickly then crawls, below 1 it changes slowly then all at once
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpeedCurve {
+    // Slows to a halt, ending as the audio ends
+    TapeStop { stop_frames: f64, curve: f64 },
+    // Runs up to speed from standstill
+    TapeStart { start_frames: f64, curve: f64 },
+    // Plays forwards until a point through the audio then whips backwards, slowing to a halt
+    SpinBack { at: f64, spin_frames: f64, speed: f64, curve: f64 },
+    // Pitch in semitones at evenly spaced points through the audio, ramped between with the given shapes
+    Varispeed { semitones: Vec<f64>, shapes: Vec<RampShape> },
+}
+
+impl SpeedCurve {
+    fn speed(&self, output_frame: usize, position: f64, frames: usize) -> f64 {
+        let t = output_frame as f64;
+        match self {
+            // Slowing from speed 1 covers stop_frames / (curve + 1) of the input
+            SpeedCurve::TapeStop { stop_frames, curve } => {
+                let stop_start = (frames as f64 - stop_frames / (curve + 1.0)).max(0.0);
+                if t < stop_start {
+                    1.0
+                } else {
+                    (1.0 - (t - stop_start) / stop_frames).max(0.0).powf(*curve)
+                }
+            }
+            // Floored so the first frames don't count as stopped
+            SpeedCurve::TapeStart { start_frames, curve } => {
+                ((t + 1.0) / start_frames).min(1.0).powf(*curve).max(STOPPED_SPEED)
+            }
+            SpeedCurve::SpinBack { at, spin_frames, speed, curve } => {
+                let spin_start = at * frames as f64;
+                if t < spin_start {
+                    1.0
+                } else {
+                    -speed * (1.0 - (t - spin_start) / spin_frames).max(0.0).powf(*curve)
+                }
+            }
+            SpeedCurve::Varispeed { semitones, shapes } => {
+                if semitones.len() < 2 {
+                    return 2_f64.powf(semitones.first().copied().unwrap_or(0.0) / 12.0);
+                }
+                let segments = semitones.len() - 1;
+                let progress = (position / frames.max(1) as f64).clamp(0.0, 1.0) * segments as f64;
+                let segment = (progress as usize).min(segments - 1);
+                let shape = shapes.get(segment).copied().unwrap_or(RampShape::Linear);
+                let eased = shape.ease(progress - segment as f64);
+                let pitch = semitones[segment] + (semitones[segment + 1] - semitones[segment]) * eased;
+                2_f64.powf(pitch / 12.0)
+            }
+        }
+    }
+}
+
+// Read position and speed for each output frame, until the tape stops or runs off either end of the audio
+pub fn speed_curve_steps(curve: &SpeedCurve, frames: usize) -> Vec<(f64, f64)> {
+    let max_frames = (frames as f64 * MAX_CURVE_STRETCH) as usize;
+    let mut steps = vec![];
+    let mut position = 0_f64;
+    while position >= 0.0 && position < frames as f64 && steps.len() < max_frames {
+        let speed = curve.speed(steps.len(), position, frames);
+        if speed.abs() < STOPPED_SPEED {
+            break;
+        }
+        steps.push((position, speed));
+        position += speed;
+    }
+    steps
+}
+
+// Length of the output relative to the input
+pub fn speed_curve_length_factor(curve: &SpeedCurve, frames: usize) -> f64 {
+    speed_curve_steps(curve, frames).len() as f64 / frames.max(1) as f64
+}
+
+// Generalises change_speed to a speed that changes over time
+pub fn change_speed_curve(params: ProcessorParams, curve: &SpeedCurve, quality: ResampleQuality) -> Result<ProcessorParams, PermuteError> {
+    let channels = params.channels.max(1);
+    let frames = params.samples.len() / channels;
+    let steps = speed_curve_steps(curve, frames);
+    if steps.is_empty() {
+        return Ok(params);
+    }
+    let resampler = SincResampler::new(quality);
+    let fade_frames = ((CURVE_FADE_MS / 1000.0) * params.sample_rate as f64) as usize;
+    let fade_start = steps.len().saturating_sub(fade_frames);
+    let new_channel_samples: Vec<Vec<f64>> = split_channels(params.samples.clone(), channels)
+        .iter()
+        .map(|cs| {
+            let mut resampled = resampler.resample_positions(cs, &steps);
+            for (i, s) in resampled[fade_start..].iter_mut().enumerate() {
+                *s *= 1.0 - i as f64 / fade_frames.max(1) as f64;
+            }
+            resampled
+        })
+        .collect();
+    let new_samples = interleave_channels(Ok(new_channel_samples))?;
+
+    Ok(ProcessorParams {
+        sample_length: new_samples.len(),
+        samples: new_samples,
+        ..params
+    })
+}
+
 pub struct TimeStretchParams {
     pub grain_samples: usize,
     pub blend_samples: usize, // exclusive in grain
@@ -333,3 +434,131 @@ pub fn stft_time_stretch(
     })
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAMES: usize = 10000;
+
+    fn params(samples: Vec<f64>, channels: usize) -> ProcessorParams {
+        ProcessorParams {
+            sample_length: samples.len(),
+            samples,
+            channels,
+            sample_rate: 44100,
+            ..ProcessorParams::default()
+        }
+    }
+
+    fn varispeed(semitones: Vec<f64>, shapes: Vec<RampShape>) -> SpeedCurve {
+        SpeedCurve::Varispeed { semitones, shapes }
+    }
+
+    #[test]
+    fn ramps_run_from_start_to_end() {
+        for shape in [RampShape::Linear, RampShape::EaseIn, RampShape::EaseOut, RampShape::SCurve] {
+            assert_eq!(shape.ease(0.0), 0.0, "{:?}", shape);
+            assert_eq!(shape.ease(1.0), 1.0, "{:?}", shape);
+        }
+        assert_eq!(RampShape::EaseIn.ease(0.5), 0.25);
+        assert_eq!(RampShape::EaseOut.ease(0.5), 0.75);
+        assert_eq!(RampShape::SCurve.ease(0.5), 0.5);
+    }
+
+    #[test]
+    fn tape_stops_slow_down_as_the_audio_ends() {
+        for curve in [0.5, 1.0, 2.0] {
+            let stop = SpeedCurve::TapeStop { stop_frames: 2000.0, curve };
+            let steps = speed_curve_steps(&stop, FRAMES);
+            // The stop stretches its part of the input by curve + 1. Stepping frame by frame runs off the
+            // end of the audio a fraction of a frame early, trimming the last of the crawl
+            let expected = FRAMES as f64 + 2000.0 * curve / (curve + 1.0);
+            let length = steps.len() as f64;
+            assert!(length <= expected + 1.0 && length > expected - 200.0, "{} {}", curve, length);
+            assert!(steps.windows(2).all(|w| w[1].1 <= w[0].1));
+            assert!(steps.last().unwrap().0 > FRAMES as f64 - 5.0);
+            assert_eq!(steps[0], (0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn tape_starts_run_up_to_speed() {
+        let start = SpeedCurve::TapeStart { start_frames: 2000.0, curve: 1.0 };
+        let steps = speed_curve_steps(&start, FRAMES);
+        assert!((steps.len() as f64 - (FRAMES as f64 + 1000.0)).abs() < 5.0, "{}", steps.len());
+        assert!(steps[0].1 > 0.0 && steps[0].1 < 0.001);
+        assert!(steps.windows(2).all(|w| w[1].1 >= w[0].1));
+        assert!(steps[2000..].iter().all(|(_, speed)| *speed == 1.0));
+    }
+
+    #[test]
+    fn spin_backs_turn_around_and_stop() {
+        let spin = SpeedCurve::SpinBack { at: 0.5, spin_frames: 1000.0, speed: 4.0, curve: 1.0 };
+        let steps = speed_curve_steps(&spin, FRAMES);
+        assert!(steps[..5000].iter().all(|(_, speed)| *speed == 1.0));
+        assert!(steps[5000..].iter().all(|(_, speed)| *speed < 0.0));
+        // Spinning back from 4x over 1000 frames covers about 2000 frames of the input
+        let end = steps.last().unwrap().0;
+        assert!((end - 3000.0).abs() < 10.0, "{}", end);
+        assert!(steps.len() <= 6000);
+
+        // A faster spin runs off the start of the audio
+        let spin = SpeedCurve::SpinBack { at: 0.1, spin_frames: 1000.0, speed: 8.0, curve: 1.0 };
+        let steps = speed_curve_steps(&spin, FRAMES);
+        assert!(steps.iter().all(|(position, _)| *position >= 0.0));
+        assert!(steps.len() < 1000 + 1000);
+    }
+
+    #[test]
+    fn varispeed_changes_the_length_with_the_pitch() {
+        assert!((speed_curve_length_factor(&varispeed(vec![12.0], vec![]), FRAMES) - 0.5).abs() < 0.001);
+        assert!((speed_curve_length_factor(&varispeed(vec![-12.0], vec![]), FRAMES) - 2.0).abs() < 0.001);
+        assert!((speed_curve_length_factor(&varispeed(vec![], vec![]), FRAMES) - 1.0).abs() < 0.001);
+        assert!((speed_curve_length_factor(&varispeed(vec![5.0, 5.0, 5.0], vec![]), FRAMES) - 2_f64.powf(-5.0 / 12.0)).abs() < 0.001);
+
+        let rising = speed_curve_steps(&varispeed(vec![0.0, 12.0], vec![RampShape::Linear]), FRAMES);
+        assert!((rising[0].1 - 1.0).abs() < 1e-9);
+        assert!((rising.last().unwrap().1 - 2.0).abs() < 0.01);
+        assert!(rising.windows(2).all(|w| w[1].1 >= w[0].1));
+        // Eased ramps spend longer at the slower speed
+        let eased = speed_curve_steps(&varispeed(vec![0.0, 12.0], vec![RampShape::EaseIn]), FRAMES);
+        assert!(eased.len() > rising.len());
+    }
+
+    #[test]
+    fn curves_that_crawl_are_capped() {
+        let steps = speed_curve_steps(&varispeed(vec![-120.0], vec![]), 100);
+        assert_eq!(steps.len(), (100.0 * MAX_CURVE_STRETCH) as usize);
+    }
+
+    #[test]
+    fn speed_curves_resample_every_channel_and_fade_out() {
+        let frames = 4410;
+        let samples: Vec<f64> = (0..frames)
+            .flat_map(|n| {
+                let s = (2.0 * PI * 220.0 * n as f64 / 44100.0).sin() * 0.5;
+                [s, -s]
+            })
+            .collect();
+        let input = params(samples, 2);
+        let out = change_speed_curve(input.clone(), &varispeed(vec![0.0], vec![]), ResampleQuality::Medium).unwrap();
+        assert_eq!(out.samples.len(), input.samples.len());
+        assert_eq!(out.sample_length, out.samples.len());
+        // 5ms fade at the end, otherwise unchanged at normal speed
+        let fade = 220 * 2;
+        let unfaded = out.samples.len() - fade;
+        for (o, i) in out.samples[..unfaded].iter().zip(input.samples[..unfaded].iter()) {
+            assert!((o - i).abs() < 1e-3);
+        }
+        assert!(out.samples[out.samples.len() - 2..].iter().all(|s| s.abs() < 0.01));
+        let channels = split_channels(out.samples, 2);
+        assert!(channels[0].iter().zip(channels[1].iter()).all(|(l, r)| (l + r).abs() < 1e-9));
+
+        let doubled = change_speed_curve(input.clone(), &varispeed(vec![12.0], vec![]), ResampleQuality::Low).unwrap();
+        assert_eq!(doubled.samples.len(), input.samples.len() / 2);
+
+        let silent = params(vec![], 2);
+        assert!(change_speed_curve(silent, &varispeed(vec![0.0], vec![]), ResampleQuality::Low).unwrap().samples.is_empty());
+    }
+}
@@ -1,15 +1,37 @@
 // External dependencies
 use rand::{rngs::ThreadRng, Rng};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 // Internal modules
 use crate::{
-    process::{PermuteNodeName, ProcessorPlanGenerator}, random_processors::{
+    chain_rules::{pick_chain, pick_template_chain, ChainRules, ChainTemplate},
+    complexity::ComplexityModel,
+    evolve::EvolvedNode,
+    process::{PermutationProcessor, PermuteNodeName, ProcessorClosure, ProcessorParams, ProcessorPlanGenerator}, random_processors::{
         random_cross::{random_cross_distort, random_cross_filter, random_cross_gain, random_cross_grain, random_cross_mix},
         random_delay_verb::{random_metallic_delay, random_reverb, random_rhythmic_delay},
+        random_dynamics::{random_compressor, random_expander, random_limiter, random_transient_shaper},
+        random_envelope::{random_auto_wah, random_envelope_delay, random_envelope_drive, random_envelope_pitch},
+        random_extreme_stretch::random_extreme_stretch,
         random_filter::{random_filter, random_line_filter, random_oscillating_filter},
+        random_glitch::random_glitch,
+        random_granular::random_granular_cloud,
+        random_resonator::random_resonator,
         random_gain_distortion::{auto_trim, normalise, random_fuzz, random_saturate},
-        random_modulation::{random_chorus, random_flutter, random_lazer, random_phaser, random_tremolo, random_wow, random_zero_flange},
-        random_time_pitch::{change_sample_rate_high, change_sample_rate_original, double_speed, half_speed, random_blur_stretch, random_granular_time_stretch, random_pitch, reverse_with_plan},
+        random_stereo::{random_auto_pan, random_decorrelate, random_haas, random_stereo_width},
+        random_multiband::random_multiband,
+        random_parallel::random_parallel,
+        random_modulation::{
+            random_chorus, random_flutter, random_frequency_shifter, random_lazer, random_phaser, random_ring_modulator,
+            random_tremolo, random_wow, random_zero_flange,
+        },
+        random_time_pitch::{
+            change_sample_rate_high, change_sample_rate_original, double_speed, half_speed, random_blur_stretch,
+            random_granular_time_stretch, random_pitch, random_spin_back, random_tape_start, random_tape_stop,
+            random_varispeed, reverse_with_plan,
+        },
+        utils::DistributionRng,
     }
 };
 
@@ -45,15 +67,41 @@ pub struct GetProcessorNodeParams  {
     pub high_sample_rate: bool,
     pub depth: usize,
     pub processor_pool: Vec<PermuteNodeName>,
+    pub processor_weights: HashMap<PermuteNodeName, f64>,
+    pub chain_rules: ChainRules,
+    // A fixed structure for the chain, which overrides depth and processor count
+    pub template: Option<ChainTemplate>,
+    // Chain length and category budgets, which override depth and processor count
+    pub complexity: Option<ComplexityModel>,
     pub processor_count: Option<i32>,
+    // Separate opt-in stretch limit, without which the extreme stretch isn't picked
+    pub extreme_stretch: Option<f64>,
+    // Passed through from the settings, the stretch limits are what keep the length in check
+    #[allow(dead_code)]
     pub constrain_length: bool,
     pub rng: ThreadRng,
-    pub original_depth: usize,
 }
 
-/// Select a random processor from the processor pool
-pub fn select_random_processor(processor_pool: &[PermuteNodeName]) -> PermuteNodeName {
-    processor_pool[rand::thread_rng().gen_range(0..processor_pool.len())]
+/// How likely a processor is to be picked relative to the others. Processors without a weight are 1, 0 excludes them
+pub fn processor_weight(processor_weights: &HashMap<PermuteNodeName, f64>, name: PermuteNodeName) -> f64 {
+    processor_weights.get(&name).copied().unwrap_or(1.0).max(0.0)
+}
+
+/// Pick a processor from the pool according to its weight. None when everything is weighted out
+pub fn pick_processor(
+    processor_pool: &[PermuteNodeName],
+    processor_weights: &HashMap<PermuteNodeName, f64>,
+    rng: &mut ThreadRng,
+) -> Option<PermuteNodeName> {
+    let distribution: Vec<(PermuteNodeName, f64)> = processor_pool
+        .iter()
+        .map(|p| (*p, processor_weight(processor_weights, *p)))
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect();
+    if distribution.is_empty() {
+        return None;
+    }
+    Some(rng.gen_distribution(distribution))
 }
 
 pub fn generate_processor_sequence(
@@ -65,51 +113,42 @@ pub fn generate_processor_sequence(
         high_sample_rate,
         depth,
         processor_pool,
+        processor_weights,
+        chain_rules,
+        template,
+        complexity,
         processor_count,
-        constrain_length,
+        extreme_stretch,
         mut rng,
-        original_depth,
+        ..
     } = params;
-    let mut processors: Vec<PermuteNodeName> = vec![];
-    if depth == 0 {
-        if original_depth == depth {
-            processors.push(select_random_processor(&processor_pool));
-        }
-        if high_sample_rate {
-            processors.insert(0, PermuteNodeName::SampleRateConversionHigh);
-            processors.push(PermuteNodeName::SampleRateConversionOriginal);
-        }
-        if normalise_at_end {
-            processors.push(PermuteNodeName::Normalise);
-        }
-        if trim_at_end {
-            processors.push(PermuteNodeName::Trim);
+    let chain_rules = match &complexity {
+        Some(complexity) => complexity.apply_budgets(&chain_rules),
+        None => chain_rules,
+    };
+    let mut processors = match template {
+        Some(template) => pick_template_chain(&template, &processor_pool, &processor_weights, &chain_rules, extreme_stretch, &mut rng),
+        None => {
+            // Each level of depth adds the same number of processors, a depth of 0 is a single processor
+            let slots = match (&complexity, depth) {
+                (Some(complexity), _) => complexity.pick_node_count(&mut rng),
+                (None, 0) => 1,
+                (None, _) => depth * processor_count.unwrap_or(rng.gen_range(2..5)).max(0) as usize,
+            };
+            pick_chain(slots, &processor_pool, &processor_weights, &chain_rules, extreme_stretch, &mut rng)
         }
-        return processors;
     };
 
-    let processor_count = processor_count.unwrap_or(rng.gen_range(2..5));
-
-    for _ in 0..processor_count {
-        processors.push(processor_pool[rng.gen_range(0..processor_pool.len())]);
+    if high_sample_rate {
+        processors.insert(0, PermuteNodeName::SampleRateConversionHigh);
+        processors.push(PermuteNodeName::SampleRateConversionOriginal);
+    }
+    if normalise_at_end {
+        processors.push(PermuteNodeName::Normalise);
+    }
+    if trim_at_end {
+        processors.push(PermuteNodeName::Trim);
     }
-
-    processors = [
-        processors,
-        generate_processor_sequence(GetProcessorNodeParams {
-            depth: depth - 1,
-            normalise_at_end: normalise_at_end,
-            trim_at_end: trim_at_end,
-            processor_pool,
-            high_sample_rate: high_sample_rate,
-            processor_count: Some(processor_count),
-            constrain_length,
-            rng,
-            original_depth: original_depth,
-        }),
-    ]
-    .concat();
-
     processors
 }
 
@@ -122,6 +161,14 @@ pub fn get_processor_plan(name: PermuteNodeName) -> ProcessorPlanGenerator {
         PermuteNodeName::BlurStretch => random_blur_stretch,
         PermuteNodeName::HalfSpeed => half_speed,
         PermuteNodeName::Reverse => reverse_with_plan,
+        PermuteNodeName::Glitch => random_glitch,
+        PermuteNodeName::GranularCloud => random_granular_cloud,
+        PermuteNodeName::EnvelopePitch => random_envelope_pitch,
+        PermuteNodeName::TapeStop => random_tape_stop,
+        PermuteNodeName::TapeStart => random_tape_start,
+        PermuteNodeName::SpinBack => random_spin_back,
+        PermuteNodeName::Varispeed => random_varispeed,
+        PermuteNodeName::ExtremeStretch => random_extreme_stretch,
         // // Modulation
         PermuteNodeName::Chorus => random_chorus,
         PermuteNodeName::Phaser => random_phaser,
@@ -130,19 +177,39 @@ pub fn get_processor_plan(name: PermuteNodeName) -> ProcessorPlanGenerator {
         PermuteNodeName::Wow => random_wow,
         PermuteNodeName::Tremolo => random_tremolo,
         PermuteNodeName::Lazer => random_lazer,
+        PermuteNodeName::FrequencyShifter => random_frequency_shifter,
+        PermuteNodeName::RingModulator => random_ring_modulator,
         
         // // Delay and reverb
         PermuteNodeName::RhythmicDelay => random_rhythmic_delay,
         PermuteNodeName::Reverb => random_reverb,
         PermuteNodeName::MetallicDelay => random_metallic_delay,
+        PermuteNodeName::EnvelopeDelay => random_envelope_delay,
         
         // // Gain and distortion
         PermuteNodeName::Fuzz => random_fuzz,
         PermuteNodeName::Saturate => random_saturate,
+        PermuteNodeName::EnvelopeDrive => random_envelope_drive,
+
+        // // Dynamics
+        PermuteNodeName::Compressor => random_compressor,
+        PermuteNodeName::Limiter => random_limiter,
+        PermuteNodeName::Expander => random_expander,
+        PermuteNodeName::TransientShaper => random_transient_shaper,
+
+        // // Stereo
+        PermuteNodeName::Decorrelate => random_decorrelate,
+        PermuteNodeName::AutoPan => random_auto_pan,
+        PermuteNodeName::StereoWidth => random_stereo_width,
+        PermuteNodeName::Haas => random_haas,
         // // Filters
         PermuteNodeName::Filter => random_filter,
         PermuteNodeName::LineFilter => random_line_filter,
         PermuteNodeName::OscillatingFilter => random_oscillating_filter,
+        PermuteNodeName::Resonator => random_resonator,
+        PermuteNodeName::AutoWah => random_auto_wah,
+        PermuteNodeName::Multiband => random_multiband,
+        PermuteNodeName::Parallel => random_parallel,
         
         // // Cross/sidechain
         PermuteNodeName::CrossGain => random_cross_gain,
@@ -158,3 +225,92 @@ pub fn get_processor_plan(name: PermuteNodeName) -> ProcessorPlanGenerator {
         _ => panic!("Processor not found {:?}", name),
     }
 }
+
+/// Plan a chain of processors to run inside another processor, e.g. one band of a multiband split.
+/// Nodes bred from a parent keep its ranges and choices while they're planned.
+/// Returns the closures to run and the chain's description for the permutation
+pub fn plan_sub_chain(
+    params: &mut ProcessorParams,
+    nodes: &[EvolvedNode],
+) -> (Vec<ProcessorClosure>, Vec<PermutationProcessor>) {
+    let mut closures: Vec<ProcessorClosure> = vec![];
+    let mut chain: Vec<PermutationProcessor> = vec![];
+    let parameter_ranges = params.permutation.parameter_ranges.clone();
+    let evolved_node = params.permutation.evolved_node.clone();
+    for node in nodes {
+        if !node.parameter_ranges.is_empty() {
+            let mut ranges = (*parameter_ranges).clone();
+            ranges.entry(node.name).or_default().extend(node.parameter_ranges.clone());
+            params.permutation.parameter_ranges = Arc::new(ranges);
+        }
+        params.permutation.evolved_node = Some(Arc::new(node.clone()));
+        let (name, attributes, closure) = get_processor_plan(node.name)(params);
+        params.permutation.parameter_ranges = parameter_ranges.clone();
+        params.permutation.evolved_node = evolved_node.clone();
+        closures.push(closure);
+        chain.push(PermutationProcessor {
+            name,
+            attributes,
+            branches: vec![],
+        });
+    }
+    (closures, chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_rules::TemplateSlot;
+    use rand::thread_rng;
+
+    fn node_params(template: Option<ChainTemplate>) -> GetProcessorNodeParams {
+        GetProcessorNodeParams {
+            normalise_at_end: false,
+            trim_at_end: false,
+            high_sample_rate: false,
+            depth: 3,
+            processor_pool: vec![PermuteNodeName::Filter, PermuteNodeName::Chorus],
+            processor_weights: HashMap::new(),
+            chain_rules: ChainRules::default(),
+            template,
+            complexity: None,
+            processor_count: Some(4),
+            extreme_stretch: None,
+            constrain_length: false,
+            rng: thread_rng(),
+        }
+    }
+
+    #[test]
+    fn depth_and_processor_count_set_the_chain_length() {
+        assert_eq!(generate_processor_sequence(node_params(None)).len(), 12);
+        let single = GetProcessorNodeParams {
+            depth: 0,
+            ..node_params(None)
+        };
+        assert_eq!(generate_processor_sequence(single).len(), 1);
+    }
+
+    #[test]
+    fn templates_override_depth_and_processor_count() {
+        let template = vec![TemplateSlot::Processor(PermuteNodeName::Reverb), TemplateSlot::Wildcard];
+        let processors = generate_processor_sequence(node_params(Some(template.clone())));
+        assert_eq!(processors.len(), 2);
+        assert_eq!(processors[0], PermuteNodeName::Reverb);
+
+        let bookended = GetProcessorNodeParams {
+            high_sample_rate: true,
+            normalise_at_end: true,
+            trim_at_end: true,
+            ..node_params(Some(template))
+        };
+        let processors = generate_processor_sequence(bookended);
+        assert_eq!(processors.len(), 6);
+        assert_eq!(processors[0], PermuteNodeName::SampleRateConversionHigh);
+        assert_eq!(processors[1], PermuteNodeName::Reverb);
+        assert_eq!(
+            processors[3..],
+            [PermuteNodeName::SampleRateConversionOriginal, PermuteNodeName::Normalise, PermuteNodeName::Trim]
+        );
+    }
+}
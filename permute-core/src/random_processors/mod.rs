@@ -1,7 +1,18 @@
+pub mod parameter_ranges;
 pub mod random_cross;
 pub mod random_delay_verb;
+pub mod random_dynamics;
+pub mod random_envelope;
+pub mod random_extreme_stretch;
 pub mod random_filter;
 pub mod random_gain_distortion;
+pub mod random_glitch;
+pub mod random_granular;
 pub mod random_modulation;
+pub mod random_multiband;
+pub mod random_parallel;
+pub mod random_resonator;
+pub mod random_stereo;
 pub mod random_time_pitch;
+pub mod tempo;
 pub mod utils; 
\ No newline at end of file
@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+
+use rand::{rngs::ThreadRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    display_node::{get_processor_display_name, get_processor_from_display_name},
+    process::{PermuteNodeName, PermuteNodeName::*, ProcessorParams},
+};
+use ParameterUnit::{Db, Factor, Hz, Ms, Percent, Semitones};
+
+// A user's range for one parameter, in the units shown for that parameter, e.g. ms, hz or %
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ParameterRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+// Ranges by processor, then by parameter name
+pub type ParameterRanges = HashMap<PermuteNodeName, HashMap<String, ParameterRange>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParameterUnit {
+    Ms,
+    Hz,
+    Percent,
+    Factor,
+    Db,
+    Semitones,
+}
+
+// Limits a user's range has to sit inside, so a config can't ask for something that breaks a processor
+struct ParameterBounds {
+    processor: PermuteNodeName,
+    parameter: &'static str,
+    min: f64,
+    max: f64,
+    unit: ParameterUnit,
+}
+
+const fn bounds(processor: PermuteNodeName, parameter: &'static str, min: f64, max: f64, unit: ParameterUnit) -> ParameterBounds {
+    ParameterBounds { processor, parameter, min, max, unit }
+}
+
+// Parameters named as the processor reports them. Ones picked from a fixed set, like pitch intervals,
+// slice counts or window sizes, and ones worked out from others, like a compressor's makeup gain, stay with the processor
+const PARAMETER_BOUNDS: &[ParameterBounds] = &[
+    // Delay and reverb
+    bounds(MetallicDelay, "Delay", 0.2, 100.0, Ms),
+    bounds(MetallicDelay, "Feedback", 0.0, 95.0, Percent),
+    bounds(MetallicDelay, "Wet", 0.0, 100.0, Percent),
+    bounds(RhythmicDelay, "Delay", 20.0, 2000.0, Ms),
+    bounds(RhythmicDelay, "Feedback", 0.0, 95.0, Percent),
+    bounds(Reverb, "Predelay", 0.0, 500.0, Ms),
+    bounds(Reverb, "Decay", 100.0, 20000.0, Ms),
+    bounds(Reverb, "Size", 0.05, 2.0, Factor),
+    bounds(Reverb, "Wet Mix", 0.0, 100.0, Percent),
+    bounds(Reverb, "Damping", 500.0, 16000.0, Hz),
+    bounds(Reverb, "Shimmer", 0.0, 90.0, Percent),
+    bounds(EnvelopeDelay, "Min Delay", 1.0, 1000.0, Ms),
+    bounds(EnvelopeDelay, "Max Delay", 1.0, 2000.0, Ms),
+    bounds(EnvelopeDelay, "Feedback", 0.0, 95.0, Percent),
+    bounds(EnvelopeDelay, "Mix", 0.0, 100.0, Percent),
+    // Filters
+    bounds(Filter, "Frequency", 20.0, 20000.0, Hz),
+    bounds(Filter, "Q", 0.1, 10.0, Factor),
+    bounds(OscillatingFilter, "Frequency", 20.0, 20000.0, Hz),
+    bounds(OscillatingFilter, "LFO Rate", 0.05, 100.0, Hz),
+    bounds(OscillatingFilter, "Q", 0.1, 10.0, Factor),
+    bounds(LineFilter, "From", 20.0, 20000.0, Hz),
+    bounds(LineFilter, "To", 20.0, 20000.0, Hz),
+    bounds(LineFilter, "Q", 0.1, 10.0, Factor),
+    bounds(AutoWah, "Min Frequency", 20.0, 5000.0, Hz),
+    bounds(AutoWah, "Max Frequency", 20.0, 20000.0, Hz),
+    bounds(AutoWah, "Q", 0.1, 10.0, Factor),
+    bounds(AutoWah, "Mix", 0.0, 100.0, Percent),
+    bounds(Resonator, "Decay", 50.0, 10000.0, Ms),
+    bounds(Resonator, "Damping", 0.0, 95.0, Percent),
+    bounds(Resonator, "Mix", 0.0, 100.0, Percent),
+    // Gain and distortion
+    bounds(Fuzz, "Gain", 0.1, 10.0, Factor),
+    bounds(Fuzz, "Output Gain", 0.01, 2.0, Factor),
+    bounds(EnvelopeDrive, "Min Drive", 0.1, 10.0, Factor),
+    bounds(EnvelopeDrive, "Max Drive", 0.1, 100.0, Factor),
+    bounds(EnvelopeDrive, "Mix", 0.0, 100.0, Percent),
+    // Modulation
+    bounds(Wow, "Speed", 0.05, 5.0, Hz),
+    bounds(Wow, "Depth", 0.0, 100.0, Percent),
+    bounds(Flutter, "Speed", 1.0, 40.0, Hz),
+    bounds(Flutter, "Depth", 0.0, 20.0, Percent),
+    bounds(Tremolo, "Speed", 0.1, 300.0, Hz),
+    bounds(Tremolo, "Depth", 0.0, 100.0, Percent),
+    bounds(Lazer, "Min Speed", 0.1, 20000.0, Hz),
+    bounds(Lazer, "Max Speed", 0.1, 20000.0, Hz),
+    bounds(Lazer, "Depth", 0.0, 100.0, Percent),
+    bounds(Chorus, "Feedback", 0.0, 95.0, Percent),
+    bounds(Chorus, "Delay", 1.0, 50.0, Ms),
+    bounds(Chorus, "Speed", 0.05, 5.0, Hz),
+    bounds(Chorus, "Depth", 0.0, 100.0, Percent),
+    bounds(Chorus, "Wet", 0.0, 100.0, Percent),
+    bounds(Phaser, "Base Frequency", 50.0, 5000.0, Hz),
+    bounds(Phaser, "LFO Rate", 0.05, 20.0, Hz),
+    bounds(Phaser, "Q", 0.05, 2.0, Factor),
+    bounds(Phaser, "LFO Depth", 0.0, 100.0, Percent),
+    bounds(Phaser, "Wet", 0.0, 100.0, Percent),
+    bounds(Flange, "Speed", 0.01, 5.0, Hz),
+    bounds(Flange, "Depth", 0.0, 50.0, Percent),
+    bounds(Flange, "Delay", 0.5, 20.0, Ms),
+    // Mixed in inverted, which is what makes it a zero flange
+    bounds(Flange, "Wet", -100.0, 0.0, Percent),
+    bounds(FrequencyShifter, "Shift", 0.1, 5000.0, Hz),
+    bounds(FrequencyShifter, "Feedback", 0.0, 95.0, Percent),
+    bounds(FrequencyShifter, "Mix", 0.0, 100.0, Percent),
+    bounds(RingModulator, "Carrier", 5.0, 10000.0, Hz),
+    bounds(RingModulator, "Mix", 0.0, 100.0, Percent),
+    // Dynamics
+    bounds(Compressor, "Threshold", -60.0, 0.0, Db),
+    bounds(Compressor, "Ratio", 1.0, 30.0, Factor),
+    bounds(Compressor, "Knee", 0.0, 24.0, Db),
+    bounds(Compressor, "Attack", 0.05, 200.0, Ms),
+    bounds(Compressor, "Release", 5.0, 2000.0, Ms),
+    bounds(Compressor, "Max Boost", 0.0, 30.0, Db),
+    bounds(Limiter, "Input Gain", 0.0, 24.0, Db),
+    bounds(Limiter, "Ceiling", -12.0, 0.0, Db),
+    bounds(Limiter, "Lookahead", 0.1, 20.0, Ms),
+    bounds(Limiter, "Release", 1.0, 1000.0, Ms),
+    bounds(Expander, "Threshold", -70.0, 0.0, Db),
+    bounds(Expander, "Ratio", 1.0, 50.0, Factor),
+    bounds(Expander, "Range", 0.0, 90.0, Db),
+    bounds(Expander, "Attack", 0.05, 50.0, Ms),
+    bounds(Expander, "Hold", 0.0, 500.0, Ms),
+    bounds(Expander, "Release", 1.0, 2000.0, Ms),
+    bounds(TransientShaper, "Attack", -1.0, 1.0, Factor),
+    bounds(TransientShaper, "Sustain", -1.0, 1.0, Factor),
+    bounds(TransientShaper, "Max Gain", 0.0, 24.0, Db),
+    // Time and pitch
+    bounds(GranularTimeStretch, "Grain", 1.0, 1000.0, Ms),
+    bounds(GranularTimeStretch, "Blend", 0.0, 200.0, Ms),
+    bounds(TapeStop, "Stop Time", 50.0, 8000.0, Ms),
+    bounds(TapeStop, "Curve", 0.2, 4.0, Factor),
+    bounds(TapeStart, "Start Time", 50.0, 8000.0, Ms),
+    bounds(TapeStart, "Curve", 0.2, 4.0, Factor),
+    bounds(SpinBack, "Spin Point", 5.0, 99.0, Percent),
+    bounds(SpinBack, "Spin Time", 50.0, 4000.0, Ms),
+    bounds(SpinBack, "Spin Speed", 1.0, 10.0, Factor),
+    bounds(SpinBack, "Curve", 0.2, 4.0, Factor),
+    bounds(EnvelopePitch, "Shift", -24.0, 24.0, Semitones),
+    bounds(EnvelopePitch, "Window", 10.0, 200.0, Ms),
+    bounds(EnvelopePitch, "Mix", 0.0, 100.0, Percent),
+    bounds(ExtremeStretch, "Window", 50.0, 4000.0, Ms),
+    bounds(Glitch, "Crossfade", 0.5, 50.0, Ms),
+    bounds(GranularCloud, "Density", 1.0, 400.0, Factor),
+    bounds(GranularCloud, "Grain Size", 5.0, 1000.0, Ms),
+    bounds(GranularCloud, "Size Jitter", 0.0, 100.0, Percent),
+    bounds(GranularCloud, "Position Jitter", 0.0, 2000.0, Ms),
+    bounds(GranularCloud, "Pitch Scatter", 0.0, 24.0, Semitones),
+    bounds(GranularCloud, "Pan Scatter", 0.0, 100.0, Percent),
+    bounds(GranularCloud, "Mix", 0.0, 100.0, Percent),
+    // Stereo
+    bounds(Decorrelate, "Amount", 0.0, 100.0, Percent),
+    bounds(Decorrelate, "Diffusion", 0.05, 0.95, Factor),
+    bounds(AutoPan, "Rate", 0.01, 40.0, Hz),
+    bounds(AutoPan, "Depth", 0.0, 100.0, Percent),
+    bounds(StereoWidth, "Width", 0.0, 300.0, Percent),
+    bounds(Haas, "Delay", 0.5, 50.0, Ms),
+    bounds(Haas, "Delayed Gain", 0.0, 1.0, Factor),
+    // The envelope followers
+    bounds(AutoWah, "Attack", 0.1, 1000.0, Ms),
+    bounds(AutoWah, "Release", 5.0, 5000.0, Ms),
+    bounds(AutoWah, "Range", 3.0, 72.0, Db),
+    bounds(EnvelopePitch, "Attack", 0.1, 1000.0, Ms),
+    bounds(EnvelopePitch, "Release", 5.0, 5000.0, Ms),
+    bounds(EnvelopePitch, "Range", 3.0, 72.0, Db),
+    bounds(EnvelopeDelay, "Attack", 0.1, 1000.0, Ms),
+    bounds(EnvelopeDelay, "Release", 5.0, 5000.0, Ms),
+    bounds(EnvelopeDelay, "Range", 3.0, 72.0, Db),
+    bounds(EnvelopeDrive, "Attack", 0.1, 1000.0, Ms),
+    bounds(EnvelopeDrive, "Release", 5.0, 5000.0, Ms),
+    bounds(EnvelopeDrive, "Range", 3.0, 72.0, Db),
+    // Cross/sidechain
+    bounds(CrossGain, "Depth", 0.0, 100.0, Percent),
+    bounds(CrossGain, "Window Size", 10.0, 1000.0, Ms),
+    bounds(CrossFilter, "Base Frequency", 20.0, 5000.0, Hz),
+    bounds(CrossFilter, "Max Frequency", 20.0, 20000.0, Hz),
+    bounds(CrossFilter, "Q", 0.1, 10.0, Factor),
+    bounds(CrossGrain, "Grain Size", 20.0, 4000.0, Ms),
+    bounds(CrossMix, "Mix", 0.0, 100.0, Percent),
+    // Containers
+    bounds(Parallel, "Branch A Gain", 5.0, 95.0, Percent),
+];
+
+fn parameter_bounds(processor: PermuteNodeName, parameter: &str) -> Option<&'static ParameterBounds> {
+    PARAMETER_BOUNDS
+        .iter()
+        .find(|b| b.processor == processor && b.parameter == parameter)
+}
+
+// Parameters that can be given a range, e.g. for listing in an error or a UI
+pub fn adjustable_parameters(processor: PermuteNodeName) -> Vec<&'static str> {
+    PARAMETER_BOUNDS
+        .iter()
+        .filter(|b| b.processor == processor)
+        .map(|b| b.parameter)
+        .collect()
+}
+
+// Checks every range is for a parameter we know about and sits inside its safe bounds
+pub fn validate_parameter_ranges(ranges: &ParameterRanges) -> Result<(), String> {
+    for (processor, parameters) in ranges {
+        let display_name = get_processor_display_name(*processor);
+        for (parameter, range) in parameters {
+            let bounds = parameter_bounds(*processor, parameter).ok_or(format!(
+                "{} has no adjustable parameter {}. Try one of: {}",
+                display_name,
+                parameter,
+                adjustable_parameters(*processor).join(", ")
+            ))?;
+            if !range.min.is_finite() || !range.max.is_finite() || range.min > range.max {
+                return Err(format!("{} {} range should have a min below its max", display_name, parameter));
+            }
+            if range.min < bounds.min || range.max > bounds.max {
+                return Err(format!(
+                    "{} {} range should be within {} - {}",
+                    display_name, parameter, bounds.min, bounds.max
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Highest frequency a range can reach at a sample rate. Kept under nyquist, where filters and
+// oscillators stop working, with some room like the resonator's notes
+pub fn highest_frequency(sample_rate: usize) -> f64 {
+    sample_rate as f64 / 2.0 * 0.9
+}
+
+// Checks frequency ranges can be met for audio at a sample rate. Ranges reaching past nyquist are
+// held under it, ones starting past it can't be
+pub fn validate_parameter_ranges_for_sample_rate(ranges: &ParameterRanges, sample_rate: usize) -> Result<(), String> {
+    let highest = highest_frequency(sample_rate);
+    for (processor, parameters) in ranges {
+        for (parameter, range) in parameters {
+            let is_frequency = parameter_bounds(*processor, parameter).is_some_and(|b| b.unit == Hz);
+            if is_frequency && range.min > highest {
+                return Err(format!(
+                    "{} {} range starts above {:.0} hz, the highest frequency for audio at {} hz",
+                    get_processor_display_name(*processor),
+                    parameter,
+                    highest,
+                    sample_rate
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Parses ranges keyed by processor display name, e.g. { "Reverb": { "Decay": { "min": 200, "max": 800 } } }
+pub fn parse_parameter_ranges(json: &str) -> Result<ParameterRanges, String> {
+    let by_display_name: HashMap<String, HashMap<String, ParameterRange>> =
+        serde_json::from_str(json).map_err(|e| format!("Invalid parameter ranges: {}", e))?;
+    let mut ranges = ParameterRanges::new();
+    for (name, parameters) in by_display_name {
+        ranges.insert(get_processor_from_display_name(&name)?, parameters);
+    }
+    validate_parameter_ranges(&ranges)?;
+    Ok(ranges)
+}
+
+pub fn load_parameter_ranges(path: &str) -> Result<ParameterRanges, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+    parse_parameter_ranges(&json)
+}
+
+// A range around a value a processor picked before, so a node can vary but still sound like itself.
+// amount is 0 - 1, how far the value can move. Frequencies move by ratio and percentages by points
+pub fn range_around(processor: PermuteNodeName, parameter: &str, value: f64, amount: f64) -> Option<ParameterRange> {
+    let bounds = parameter_bounds(processor, parameter)?;
+    let value = value.clamp(bounds.min, bounds.max);
+    let (min, max) = match bounds.unit {
+        Hz => (value / (1.0 + amount), value * (1.0 + amount)),
+        Percent => (value - amount * 50.0, value + amount * 50.0),
+        Db | Semitones => (value - amount * 12.0, value + amount * 12.0),
+        Ms | Factor => (value - value.abs() * amount, value + value.abs() * amount),
+    };
+    Some(ParameterRange {
+        min: min.max(bounds.min),
+        max: max.min(bounds.max),
+    })
+}
+
+// Opens a processor's ranges towards their full safe bounds, amount is 0 - 1 where 1 is the full bounds.
+// Parameters without a user range are left to the processor, so they pick from the full bounds that share of the time.
+// Frequencies only open up to nyquist for the sample rate. Parameters without bounds stay with the processor
+pub fn widen_ranges(
+    ranges: &ParameterRanges,
+    processor: PermuteNodeName,
+    amount: f64,
+    sample_rate: usize,
+    rng: &mut ThreadRng,
+) -> ParameterRanges {
+    let mut ranges = ranges.clone();
+    let amount = amount.clamp(0.0, 1.0);
+    if amount == 0.0 {
+        return ranges;
+    }
+    let parameters = ranges.entry(processor).or_default();
+    for bounds in PARAMETER_BOUNDS.iter().filter(|b| b.processor == processor) {
+        let (bounds_min, bounds_max) = match bounds.unit {
+            Hz => {
+                let highest = highest_frequency(sample_rate);
+                (bounds.min.min(highest), bounds.max.min(highest))
+            }
+            Ms | Percent | Factor | Db | Semitones => (bounds.min, bounds.max),
+        };
+        // Frequencies open up on a log scale, to match how they're picked
+        let towards = |from: f64, to: f64| match bounds.unit {
+            Hz => (from.ln() + (to.ln() - from.ln()) * amount).exp(),
+            Ms | Percent | Factor | Db | Semitones => from + (to - from) * amount,
+        };
+        match parameters.get_mut(bounds.parameter) {
+            Some(range) => {
+                range.min = towards(range.min, bounds_min);
+                range.max = towards(range.max, bounds_max);
+            }
+            None if rng.gen_bool(amount) => {
+                parameters.insert(
+                    bounds.parameter.to_string(),
+                    ParameterRange { min: bounds_min, max: bounds_max },
+                );
+            }
+            None => {}
+        }
+    }
+    ranges
+}
+
+pub fn parameter_range(params: &ProcessorParams, processor: PermuteNodeName, parameter: &str) -> Option<ParameterRange> {
+    params
+        .permutation
+        .parameter_ranges
+        .get(&processor)
+        .and_then(|parameters| parameters.get(parameter))
+        .copied()
+}
+
+// A value from the user's range for a parameter, if they set one. Frequencies are picked on a log scale.
+// Percentages come back as a 0 - 1 factor, to match how the processors take them
+pub fn random_override(
+    params: &ProcessorParams,
+    processor: PermuteNodeName,
+    parameter: &str,
+    rng: &mut ThreadRng,
+) -> Option<f64> {
+    let range = parameter_range(params, processor, parameter)?;
+    let bounds = parameter_bounds(processor, parameter)?;
+    let (mut min, mut max) = (range.min.clamp(bounds.min, bounds.max), range.max.clamp(bounds.min, bounds.max));
+    if bounds.unit == Hz {
+        let highest = highest_frequency(params.sample_rate);
+        (min, max) = (min.min(highest), max.min(highest));
+    }
+    let value = if min >= max {
+        min
+    } else if bounds.unit == Hz {
+        rng.gen_range(min.ln()..max.ln()).exp()
+    } else {
+        rng.gen_range(min..max)
+    };
+    match bounds.unit {
+        Percent => Some(value / 100.0),
+        Ms | Hz | Factor | Db | Semitones => Some(value),
+    }
+}
+
+// The user's range for a parameter if there is one, otherwise the processor's own choice
+pub fn random_parameter(
+    params: &ProcessorParams,
+    processor: PermuteNodeName,
+    parameter: &str,
+    rng: &mut ThreadRng,
+    default: impl FnOnce(&mut ThreadRng) -> f64,
+) -> f64 {
+    random_override(params, processor, parameter, rng).unwrap_or_else(|| default(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::Permutation;
+    use rand::thread_rng;
+    use std::sync::Arc;
+
+    fn params_with_range(processor: PermuteNodeName, parameter: &str, range: ParameterRange, sample_rate: usize) -> ProcessorParams {
+        let mut ranges = ParameterRanges::new();
+        ranges.entry(processor).or_default().insert(parameter.to_string(), range);
+        ProcessorParams {
+            sample_rate,
+            permutation: Permutation {
+                parameter_ranges: Arc::new(ranges),
+                ..Permutation::default()
+            },
+            ..ProcessorParams::default()
+        }
+    }
+
+    #[test]
+    fn bounds_are_ordered_and_unique() {
+        for (i, b) in PARAMETER_BOUNDS.iter().enumerate() {
+            assert!(b.min < b.max, "{:?} {}", b.processor, b.parameter);
+            assert!(
+                !PARAMETER_BOUNDS[..i].iter().any(|o| o.processor == b.processor && o.parameter == b.parameter),
+                "{:?} {} is listed twice",
+                b.processor,
+                b.parameter
+            );
+        }
+    }
+
+    #[test]
+    fn frequencies_are_held_under_nyquist() {
+        let mut rng = thread_rng();
+        let range = ParameterRange { min: 5000.0, max: 20000.0 };
+        let params = params_with_range(PermuteNodeName::Filter, "Frequency", range, 22050);
+        for _ in 0..200 {
+            let frequency = random_override(&params, PermuteNodeName::Filter, "Frequency", &mut rng).unwrap();
+            assert!((5000.0..=highest_frequency(22050)).contains(&frequency), "{}", frequency);
+        }
+    }
+
+    #[test]
+    fn widened_frequencies_stop_at_nyquist() {
+        let mut rng = thread_rng();
+        let widened = widen_ranges(&ParameterRanges::new(), PermuteNodeName::LineFilter, 1.0, 22050, &mut rng);
+        for parameter in ["From", "To"] {
+            let range = widened[&PermuteNodeName::LineFilter][parameter];
+            assert_eq!(range.max, highest_frequency(22050));
+        }
+    }
+
+    #[test]
+    fn ranges_past_nyquist_are_rejected_for_the_sample_rate() {
+        let mut ranges = ParameterRanges::new();
+        ranges
+            .entry(PermuteNodeName::LineFilter)
+            .or_default()
+            .insert("From".to_string(), ParameterRange { min: 15000.0, max: 20000.0 });
+        assert!(validate_parameter_ranges(&ranges).is_ok());
+        assert!(validate_parameter_ranges_for_sample_rate(&ranges, 44100).is_ok());
+        assert!(validate_parameter_ranges_for_sample_rate(&ranges, 22050).is_err());
+    }
+
+    #[test]
+    fn overrides_are_clamped_to_the_bounds() {
+        let mut rng = thread_rng();
+        // Wider than validation allows, e.g. ranges built in code rather than loaded
+        let range = ParameterRange { min: -50.0, max: 200.0 };
+        let params = params_with_range(PermuteNodeName::RhythmicDelay, "Feedback", range, 44100);
+        for _ in 0..200 {
+            let feedback = random_override(&params, PermuteNodeName::RhythmicDelay, "Feedback", &mut rng).unwrap();
+            assert!((0.0..=0.95).contains(&feedback), "{}", feedback);
+        }
+
+        let range = ParameterRange { min: 50000.0, max: 60000.0 };
+        let params = params_with_range(PermuteNodeName::Reverb, "Decay", range, 44100);
+        assert_eq!(random_override(&params, PermuteNodeName::Reverb, "Decay", &mut rng), Some(20000.0));
+    }
+
+    #[test]
+    fn overrides_come_back_in_processor_units() {
+        let mut rng = thread_rng();
+        let range = ParameterRange { min: 40.0, max: 40.0 };
+        let params = params_with_range(PermuteNodeName::Reverb, "Wet Mix", range, 44100);
+        assert_eq!(random_override(&params, PermuteNodeName::Reverb, "Wet Mix", &mut rng), Some(0.4));
+
+        let range = ParameterRange { min: 200.0, max: 800.0 };
+        let params = params_with_range(PermuteNodeName::Reverb, "Decay", range, 44100);
+        for _ in 0..100 {
+            let decay = random_override(&params, PermuteNodeName::Reverb, "Decay", &mut rng).unwrap();
+            assert!((200.0..=800.0).contains(&decay), "{}", decay);
+        }
+    }
+
+    #[test]
+    fn parameters_without_a_range_are_left_to_the_processor() {
+        let mut rng = thread_rng();
+        let range = ParameterRange { min: 200.0, max: 800.0 };
+        let params = params_with_range(PermuteNodeName::Reverb, "Decay", range, 44100);
+        assert_eq!(random_override(&params, PermuteNodeName::Reverb, "Predelay", &mut rng), None);
+        assert_eq!(random_override(&params, PermuteNodeName::Filter, "Frequency", &mut rng), None);
+        assert_eq!(random_parameter(&params, PermuteNodeName::Reverb, "Predelay", &mut rng, |_| 12.0), 12.0);
+        let decay = random_parameter(&params, PermuteNodeName::Reverb, "Decay", &mut rng, |_| 12.0);
+        assert!((200.0..=800.0).contains(&decay));
+    }
+
+    #[test]
+    fn ranges_outside_their_bounds_are_rejected() {
+        let validate = |processor: PermuteNodeName, parameter: &str, min: f64, max: f64| {
+            let mut ranges = ParameterRanges::new();
+            ranges.entry(processor).or_default().insert(parameter.to_string(), ParameterRange { min, max });
+            validate_parameter_ranges(&ranges)
+        };
+        assert!(validate(PermuteNodeName::Reverb, "Decay", 100.0, 20000.0).is_ok());
+        assert!(validate(PermuteNodeName::Reverb, "Decay", 50.0, 800.0).is_err());
+        assert!(validate(PermuteNodeName::Reverb, "Decay", 200.0, 30000.0).is_err());
+        assert!(validate(PermuteNodeName::Reverb, "Decay", 800.0, 200.0).is_err());
+        assert!(validate(PermuteNodeName::Reverb, "Decay", f64::NAN, 800.0).is_err());
+        let unknown = validate(PermuteNodeName::Reverb, "Colour", 0.0, 1.0).unwrap_err();
+        assert!(unknown.contains("Predelay"), "{}", unknown);
+    }
+
+    #[test]
+    fn parses_ranges_by_display_name() {
+        let ranges = parse_parameter_ranges(r#"{ "Rhythmic Delay": { "Delay": { "min": 100, "max": 400 } } }"#).unwrap();
+        assert_eq!(
+            ranges[&PermuteNodeName::RhythmicDelay]["Delay"],
+            ParameterRange { min: 100.0, max: 400.0 }
+        );
+        assert!(parse_parameter_ranges(r#"{ "Not A Processor": {} }"#).is_err());
+        assert!(parse_parameter_ranges(r#"{ "Rhythmic Delay": { "Delay": { "min": 1, "max": 400 } } }"#).is_err());
+        assert!(parse_parameter_ranges("not json").is_err());
+    }
+
+    #[test]
+    fn ranges_around_a_value_stay_in_bounds() {
+        let around = |parameter: &str, value: f64, amount: f64| {
+            range_around(PermuteNodeName::Reverb, parameter, value, amount).unwrap()
+        };
+        assert_eq!(around("Damping", 4000.0, 1.0), ParameterRange { min: 2000.0, max: 8000.0 });
+        assert_eq!(around("Wet Mix", 50.0, 0.2), ParameterRange { min: 40.0, max: 60.0 });
+        assert_eq!(around("Wet Mix", 95.0, 0.2), ParameterRange { min: 85.0, max: 100.0 });
+        assert_eq!(around("Decay", 1000.0, 0.5), ParameterRange { min: 500.0, max: 1500.0 });
+        assert_eq!(around("Decay", 50.0, 0.5), ParameterRange { min: 100.0, max: 150.0 });
+        assert_eq!(range_around(PermuteNodeName::Reverb, "Colour", 1.0, 0.5), None);
+    }
+
+    #[test]
+    fn widening_moves_ranges_towards_their_bounds() {
+        let mut rng = thread_rng();
+        let mut ranges = ParameterRanges::new();
+        let reverb = ranges.entry(PermuteNodeName::Reverb).or_default();
+        reverb.insert("Predelay".to_string(), ParameterRange { min: 100.0, max: 200.0 });
+        reverb.insert("Damping".to_string(), ParameterRange { min: 1000.0, max: 4000.0 });
+
+        assert_eq!(widen_ranges(&ranges, PermuteNodeName::Reverb, 0.0, 44100, &mut rng), ranges);
+
+        let widened = widen_ranges(&ranges, PermuteNodeName::Reverb, 0.5, 44100, &mut rng);
+        assert_eq!(widened[&PermuteNodeName::Reverb]["Predelay"], ParameterRange { min: 50.0, max: 350.0 });
+        // Halfway on a log scale
+        let damping = widened[&PermuteNodeName::Reverb]["Damping"];
+        assert!((damping.min - (500.0_f64 * 1000.0).sqrt()).abs() < 1e-6);
+        assert!((damping.max - 8000.0).abs() < 1e-6);
+
+        let full = widen_ranges(&ranges, PermuteNodeName::Reverb, 1.0, 44100, &mut rng);
+        assert_eq!(full[&PermuteNodeName::Reverb].len(), adjustable_parameters(PermuteNodeName::Reverb).len());
+        assert_eq!(full[&PermuteNodeName::Reverb]["Decay"], ParameterRange { min: 100.0, max: 20000.0 });
+    }
+}
@@ -3,6 +3,7 @@ use rand::{thread_rng, Rng};
 
 // Internal modules
 use crate::{
+    evolve::{parse_attribute_value, recorded_attribute, recorded_choice, recorded_file},
     permute_files::PermuteUpdate,
     process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorClosure, ProcessorParams, ProcessorPlan},
     processors::{
@@ -10,14 +11,16 @@ use crate::{
         gain_distortion::DistortionAlgorithm},
         random_process::{complete_event, start_event},
         random_processors::utils::{format_float, format_float_ms, format_float_percent, format_hz, get_filename
- }
+ },
+        random_processors::random_gain_distortion::random_oversampling,
+        random_processors::parameter_ranges::random_parameter,
 };
 
 pub fn random_cross_gain(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
     // Get a random file from the files list
-    let sidechain_file = match select_sidechain_file(&params.permutation.file, &params.permutation.files) {
+    let sidechain_file = match sidechain_file(params) {
         Some(file) => file,
         None => {
             // If there's only one file, just return the original
@@ -30,9 +33,9 @@ pub fn random_cross_gain(params: &mut ProcessorParams) -> ProcessorPlan {
         }
     };
 
-    let depth = rng.gen_range(0.2..0.9);
-    let invert = rng.gen_bool(0.5);
-    let window_size_ms = 100.0; // 100ms window size
+    let depth = random_parameter(params, PermuteNodeName::CrossGain, "Depth", &mut rng, |rng| rng.gen_range(0.2..0.9));
+    let invert = random_invert(params, &mut rng);
+    let window_size_ms = random_parameter(params, PermuteNodeName::CrossGain, "Window Size", &mut rng, |_| 100.0);
 
     let attributes = vec![
         ProcessorAttribute {
@@ -74,7 +77,7 @@ pub fn random_cross_filter(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
     
     // Get a random file from the files list
-    let sidechain_file = match select_sidechain_file(&params.permutation.file, &params.permutation.files) {
+    let sidechain_file = match sidechain_file(params) {
         Some(file) => file,
         None => {
             // If there's only one file, just return the original
@@ -94,16 +97,21 @@ pub fn random_cross_filter(params: &mut ProcessorParams) -> ProcessorPlan {
         biquad::Type::LowPass, // make low pass most likely
         biquad::Type::BandPass,
     ];
-    let filter_type = types[rng.gen_range(0..types.len())];
+    let filter_type = recorded_choice(params, "Filter Type", &types, |t| format!("{:?}", t))
+        .unwrap_or_else(|| types[rng.gen_range(0..types.len())]);
 
     // Base frequency between 200hz and 2000hz
-    let base_freq = rng.gen_range(50.0..800.0);
+    let base_freq = random_parameter(params, PermuteNodeName::CrossFilter, "Base Frequency", &mut rng, |rng| rng.gen_range(50.0..800.0));
     // Maximum frequency between base_freq and 10000hz
-    let max_freq = rng.gen_range(base_freq..10000.0);
+    let max_freq = random_parameter(params, PermuteNodeName::CrossFilter, "Max Frequency", &mut rng, |rng| {
+        rng.gen_range(base_freq..10000.0_f64.max(base_freq * 2.0))
+    });
+    // Ranges for both ends can overlap
+    let (base_freq, max_freq) = (base_freq.min(max_freq), base_freq.max(max_freq));
     // Q factor between 0.5 and 1.35 (similar to random_line_filter)
-    let q = rng.gen_range(0.5..1.35);
+    let q = random_parameter(params, PermuteNodeName::CrossFilter, "Q", &mut rng, |rng| rng.gen_range(0.5..1.35));
     let window_size_ms = 100.0; // Fixed 10ms window for RMS calculation
-    let invert = rng.gen_bool(0.5);
+    let invert = random_invert(params, &mut rng);
 
     let attributes = vec![
         ProcessorAttribute {
@@ -160,7 +168,7 @@ pub fn random_cross_distort(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = rand::thread_rng();
     
     // Get a random file from the files list
-    let sidechain_file = match select_sidechain_file(&params.permutation.file, &params.permutation.files) {
+    let sidechain_file = match sidechain_file(params) {
         Some(file) => file,
         None => {
             // If there's only one file, just return the original
@@ -187,45 +195,25 @@ pub fn random_cross_distort(params: &mut ProcessorParams) -> ProcessorPlan {
     ];
     
     // Factor ranges depend on the algorithm
-    let algorithm = algorithms[rng.gen_range(0..algorithms.len())];
-    let (min_factor, increase) = match algorithm {
-        DistortionAlgorithm::Power => {
-            // Original power function - needs smaller values but wider range
-            let min_factors = [0.4, 0.5, 0.6];
-            let increases = [0.4, 0.5, 0.6];
-            (
-                min_factors[rng.gen_range(0..min_factors.len())],
-                increases[rng.gen_range(0..increases.len())]
-            )
-        },
-        DistortionAlgorithm::Tanh | DistortionAlgorithm::Atan => {
-            // These work well with larger ranges
-            let min_factors = [0.5, 1.0, 1.5];
-            let increases = [2.0, 3.0, 4.0];
-            (
-                min_factors[rng.gen_range(0..min_factors.len())],
-                increases[rng.gen_range(0..increases.len())]
-            )
-        },
-        DistortionAlgorithm::Cubic => {
-            // Cubic needs values around 1.0 for soft clipping but can handle wider range
-            let min_factors = [0.3, 0.4, 0.5];
-            let increases = [0.8, 1.0, 1.2];
-            (
-                min_factors[rng.gen_range(0..min_factors.len())],
-                increases[rng.gen_range(0..increases.len())]
-            )
-        },
-        DistortionAlgorithm::Saturate => {
-            // Saturate works well with moderate to high ranges
-            let min_factors = [0.5, 0.8, 1.0];
-            let increases = [1.5, 2.0, 2.5];
-            (
-                min_factors[rng.gen_range(0..min_factors.len())],
-                increases[rng.gen_range(0..increases.len())]
-            )
-        }
+    let algorithm = recorded_choice(params, "Algorithm", &algorithms, |a| format!("{:?}", a))
+        .unwrap_or_else(|| algorithms[rng.gen_range(0..algorithms.len())]);
+    let (min_factors, increases) = match algorithm {
+        // Original power function - needs smaller values but wider range
+        DistortionAlgorithm::Power => ([0.4, 0.5, 0.6], [0.4, 0.5, 0.6]),
+        // These work well with larger ranges
+        DistortionAlgorithm::Tanh | DistortionAlgorithm::Atan => ([0.5, 1.0, 1.5], [2.0, 3.0, 4.0]),
+        // Cubic needs values around 1.0 for soft clipping but can handle wider range
+        DistortionAlgorithm::Cubic => ([0.3, 0.4, 0.5], [0.8, 1.0, 1.2]),
+        // Saturate works well with moderate to high ranges
+        DistortionAlgorithm::Saturate => ([0.5, 0.8, 1.0], [1.5, 2.0, 2.5]),
     };
+    let min_factor = recorded_choice(params, "Min Factor", &min_factors, |f| format_float(*f))
+        .unwrap_or_else(|| min_factors[rng.gen_range(0..min_factors.len())]);
+    let increase = recorded_choice(params, "Max Factor", &increases, |i| format_float(min_factor + i))
+        .unwrap_or_else(|| increases[rng.gen_range(0..increases.len())]);
+    let invert = random_invert(params, &mut rng);
+
+    let oversampling = random_oversampling(params, &mut rng);
 
     let attributes = vec![
         ProcessorAttribute {
@@ -250,7 +238,11 @@ pub fn random_cross_distort(params: &mut ProcessorParams) -> ProcessorPlan {
         },
         ProcessorAttribute {
             key: "Invert".to_string(),
-            value: rng.gen_bool(0.5).to_string(),
+            value: invert.to_string(),
+        },
+        ProcessorAttribute {
+            key: "Oversampling".to_string(),
+            value: format!("{}x", oversampling.factor()),
         },
     ];
     
@@ -260,7 +252,8 @@ pub fn random_cross_distort(params: &mut ProcessorParams) -> ProcessorPlan {
         max_factor: min_factor + increase,
         window_size_ms: 100.0, // Fixed 100ms window for RMS calculation
         algorithm,
-        invert: rng.gen_bool(0.5),
+        invert,
+        oversampling,
     };
 
     let processor = move |params: ProcessorParams| {
@@ -276,7 +269,7 @@ pub fn random_cross_distort(params: &mut ProcessorParams) -> ProcessorPlan {
 pub fn random_cross_grain(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
-    let sidechain_file = match select_sidechain_file(&params.permutation.file, &params.permutation.files) {
+    let sidechain_file = match sidechain_file(params) {
         Some(file) => file,
         None => {
             let processor = move |params: ProcessorParams| {
@@ -293,11 +286,17 @@ pub fn random_cross_grain(params: &mut ProcessorParams) -> ProcessorPlan {
 
     // Grain size distributed across musically useful ranges (80ms–2000ms)
     let grain_ms_options: [f64; 9] = [80.0, 150.0, 250.0, 400.0, 600.0, 800.0, 1200.0, 1600.0, 2000.0];
-    let grain_ms = grain_ms_options[rng.gen_range(0..grain_ms_options.len())];
+    let grain_ms = random_parameter(params, PermuteNodeName::CrossGrain, "Grain Size", &mut rng, |rng| {
+        grain_ms_options[rng.gen_range(0..grain_ms_options.len())]
+    });
     let grain_samples = ((grain_ms / 1000.0) * sample_rate as f64) as usize / channels * channels;
 
     // Blend: 10–25% of grain size
-    let blend_ratio = rng.gen_range(0.10_f64..0.25);
+    let recorded_blend_ms = recorded_attribute(params, "Blend").and_then(|blend| parse_attribute_value(&blend));
+    let blend_ratio = match recorded_blend_ms {
+        Some(blend_ms) => (blend_ms / grain_ms).clamp(0.10, 0.25),
+        None => rng.gen_range(0.10_f64..0.25),
+    };
     let blend_samples = (grain_samples as f64 * blend_ratio) as usize / channels * channels;
 
     let attributes = vec![
@@ -334,7 +333,7 @@ pub fn random_cross_grain(params: &mut ProcessorParams) -> ProcessorPlan {
 pub fn random_cross_mix(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
-    let sidechain_file = match select_sidechain_file(&params.permutation.file, &params.permutation.files) {
+    let sidechain_file = match sidechain_file(params) {
         Some(file) => file,
         None => {
             let processor = move |params: ProcessorParams| {
@@ -350,11 +349,15 @@ pub fn random_cross_mix(params: &mut ProcessorParams) -> ProcessorPlan {
     // so both files play in full with a varying overlap each run
     let channels = params.channels.max(1);
     let current_frames = params.samples.len() / channels;
-    let offset_frames = rng.gen_range(0..=current_frames);
+    let recorded_offset_ms = recorded_attribute(params, "Offset").and_then(|offset| parse_attribute_value(&offset));
+    let offset_frames = match recorded_offset_ms {
+        Some(offset_ms) => ((offset_ms / 1000.0 * params.sample_rate as f64) as usize).min(current_frames),
+        None => rng.gen_range(0..=current_frames),
+    };
     let offset_samples = offset_frames * channels;
     let offset_ms = (offset_frames as f64 / params.sample_rate as f64) * 1000.0;
 
-    let mix = rng.gen_range(0.3..0.7_f64);
+    let mix = random_parameter(params, PermuteNodeName::CrossMix, "Mix", &mut rng, |rng| rng.gen_range(0.3..0.7_f64));
 
     let attributes = vec![
         ProcessorAttribute {
@@ -387,6 +390,16 @@ pub fn random_cross_mix(params: &mut ProcessorParams) -> ProcessorPlan {
     (PermuteNodeName::CrossMix, attributes, Box::new(processor))
 }
 
+// A bred node keeps its parent's other file while it's still one of the inputs
+fn sidechain_file(params: &ProcessorParams) -> Option<String> {
+    recorded_file(params, "Sidechain File")
+        .or_else(|| select_sidechain_file(&params.permutation.file, &params.permutation.files))
+}
+
+fn random_invert(params: &ProcessorParams, rng: &mut impl Rng) -> bool {
+    recorded_choice(params, "Invert", &[false, true], |invert| invert.to_string()).unwrap_or_else(|| rng.gen_bool(0.5))
+}
+
 /// Select a random file from the available files list that is different from the current file
 pub fn select_sidechain_file(current_file: &str, available_files: &[String]) -> Option<String> {
     if available_files.len() < 2 {
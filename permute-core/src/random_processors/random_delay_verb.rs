@@ -3,8 +3,16 @@ use rand::{thread_rng, Rng};
 
 // Internal modules
 use crate::{
-    processors::delay_reverb::{DelayLineParams, ReverbParams, delay_line, reverb},
-    random_processors::utils::{format_float_percent, format_samples_as_ms, format_float_ms},
+    evolve::recorded_choice,
+    processors::{
+        delay_reverb::{DelayLineParams, delay_line},
+        reverb::{reverb_engine, reverb_tail_ms, ReverbAlgorithm, ReverbEngineParams},
+    },
+    random_processors::{
+        parameter_ranges::{parameter_range, random_override, random_parameter},
+        tempo::{format_sync, random_note_division, sync_to_tempo},
+        utils::{format_float, format_float_percent, format_hz, format_samples_as_ms, format_float_ms, DistributionRng},
+    },
     process::{ProcessorParams, ProcessorPlan, PermuteNodeName, ProcessorAttribute, PermuteNodeEvent, ProcessorClosure},
     random_process::{start_event, complete_event},
     permute_files::PermuteUpdate,
@@ -14,9 +22,16 @@ pub fn random_metallic_delay(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
     let sec_10 = (params.sample_rate as f64 * 0.1) as usize;
-    let feedback_factor = rng.gen_range(0_f64..0.9);
-    let delay_sample_length = rng.gen_range(10..sec_10);
-    let wet_gain_factor = rng.gen_range(0.3..1_f64);
+    let feedback_factor = random_parameter(params, PermuteNodeName::MetallicDelay, "Feedback", &mut rng, |rng| {
+        rng.gen_range(0_f64..0.9)
+    });
+    let delay_sample_length = match random_override(params, PermuteNodeName::MetallicDelay, "Delay", &mut rng) {
+        Some(delay_ms) => ((delay_ms / 1000.0 * params.sample_rate as f64) as usize).max(1),
+        None => rng.gen_range(10..sec_10),
+    };
+    let wet_gain_factor = random_parameter(params, PermuteNodeName::MetallicDelay, "Wet", &mut rng, |rng| {
+        rng.gen_range(0.3..1_f64)
+    });
 
     let attributes = vec![
         ProcessorAttribute {
@@ -55,19 +70,43 @@ pub fn random_rhythmic_delay(params: &mut ProcessorParams) -> ProcessorPlan {
 
     let sec_10 = (params.sample_rate as f64 * 0.1) as usize;
     let sec = params.sample_rate as usize;
-    let feedback_factor = rng.gen_range(0_f64..0.9);
-    let delay_sample_length = rng.gen_range(sec_10..sec);
+    let feedback_factor = random_parameter(params, PermuteNodeName::RhythmicDelay, "Feedback", &mut rng, |rng| {
+        rng.gen_range(0_f64..0.9)
+    });
+    // A user's delay range takes over from syncing to the tempo
+    let delay_ms = random_override(params, PermuteNodeName::RhythmicDelay, "Delay", &mut rng);
+    let sync = sync_to_tempo(&mut rng, params.permutation.bpm)
+        .filter(|_| delay_ms.is_none())
+        .and_then(|bpm| random_note_division(&mut rng, bpm, 100.0, 1000.0).map(|division| (division, bpm)));
+    let delay_sample_length = match (sync, delay_ms) {
+        // Delay line works on interleaved samples
+        (Some((division, bpm)), _) => {
+            (division.to_ms(bpm) / 1000.0 * params.sample_rate as f64) as usize * params.channels.max(1)
+        }
+        (None, Some(delay_ms)) => (delay_ms / 1000.0 * params.sample_rate as f64) as usize * params.channels.max(1),
+        (None, None) => rng.gen_range(sec_10..sec),
+    };
 
-    let attributes = vec![
+    let mut attributes = vec![
         ProcessorAttribute {
             key: "Feedback".to_string(),
             value: format_float_percent(feedback_factor),
         },
         ProcessorAttribute {
             key: "Delay".to_string(),
-            value: format_samples_as_ms(delay_sample_length, params.sample_rate),
+            value: match (sync, delay_ms) {
+                (Some((division, bpm)), _) => format_float_ms(division.to_ms(bpm)),
+                (None, Some(delay_ms)) => format_float_ms(delay_ms),
+                (None, None) => format_samples_as_ms(delay_sample_length, params.sample_rate),
+            },
         },
     ];
+    if let Some((division, bpm)) = sync {
+        attributes.push(ProcessorAttribute {
+            key: "Sync".to_string(),
+            value: format_sync(division, bpm),
+        });
+    }
 
     let delay_params = DelayLineParams {
         feedback_factor,
@@ -89,15 +128,61 @@ pub fn random_rhythmic_delay(params: &mut ProcessorParams) -> ProcessorPlan {
 pub fn random_reverb(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
+    let algorithm_distributions = vec![
+        (ReverbAlgorithm::Classic, 0.2),
+        (ReverbAlgorithm::Hall, 0.3),
+        (ReverbAlgorithm::Plate, 0.2),
+        (ReverbAlgorithm::Shimmer, 0.15),
+        (ReverbAlgorithm::Reverse, 0.15),
+    ];
+    // Only the newer engines have a decay time, keep to them when the user has set one
+    let algorithm_distributions = match parameter_range(params, PermuteNodeName::Reverb, "Decay") {
+        Some(_) => algorithm_distributions.into_iter().filter(|(a, _)| *a != ReverbAlgorithm::Classic).collect(),
+        None => algorithm_distributions,
+    };
+    let algorithms: Vec<ReverbAlgorithm> = algorithm_distributions.iter().map(|(a, _)| *a).collect();
+    let algorithm = recorded_choice(params, "Algorithm", &algorithms, |a| format!("{:?}", a))
+        .unwrap_or_else(|| rng.gen_distribution(algorithm_distributions));
+
     let len_factors = [0.1, 0.3, 0.6, 1.0, 1.2, 1.4];
     let decay_factors = [0.2, 0.3, 0.325, 0.35, 0.4];
 
-    let predelay_ms = rng.gen_range(0.0..90.0);
-    let wet_mix = rng.gen_range(0.1_f64..0.4);
-    let len_factor = len_factors[rng.gen_range(0..len_factors.len())];
-    let decay_factor = decay_factors[rng.gen_range(0..decay_factors.len())];
+    let predelay_ms = random_parameter(params, PermuteNodeName::Reverb, "Predelay", &mut rng, |rng| rng.gen_range(0.0..90.0));
+    let (size, decay_ms, wet_mix) = match algorithm {
+        ReverbAlgorithm::Classic => (len_factors[rng.gen_range(0..len_factors.len())], 0.0, rng.gen_range(0.1_f64..0.4)),
+        ReverbAlgorithm::Hall => (rng.gen_range(0.7..1.5), rng.gen_range(1200.0..6000.0), rng.gen_range(0.15_f64..0.5)),
+        ReverbAlgorithm::Plate => (rng.gen_range(0.6..1.3), rng.gen_range(800.0..3500.0), rng.gen_range(0.15_f64..0.5)),
+        ReverbAlgorithm::Shimmer => (rng.gen_range(0.8..1.5), rng.gen_range(2000.0..6000.0), rng.gen_range(0.25_f64..0.6)),
+        ReverbAlgorithm::Reverse => (rng.gen_range(0.7..1.5), rng.gen_range(600.0..3000.0), rng.gen_range(0.3_f64..0.8)),
+    };
+    let size = random_override(params, PermuteNodeName::Reverb, "Size", &mut rng).unwrap_or(size);
+    let decay_ms = random_override(params, PermuteNodeName::Reverb, "Decay", &mut rng).unwrap_or(decay_ms);
+    let wet_mix = random_override(params, PermuteNodeName::Reverb, "Wet Mix", &mut rng).unwrap_or(wet_mix);
+    let decay_factor = recorded_choice(params, "Decay Factor", &decay_factors, |f| f.to_string())
+        .unwrap_or_else(|| decay_factors[rng.gen_range(0..decay_factors.len())]);
+    let damping_hz = random_parameter(params, PermuteNodeName::Reverb, "Damping", &mut rng, |rng| match algorithm {
+        ReverbAlgorithm::Plate => rng.gen_range(6000.0..12000.0),
+        _ => rng.gen_range(2500.0..8000.0),
+    });
+    let shimmer_feedback = random_parameter(params, PermuteNodeName::Reverb, "Shimmer", &mut rng, |rng| rng.gen_range(0.3..0.7));
+
+    let engine_params = ReverbEngineParams {
+        algorithm,
+        predelay_ms,
+        wet_mix,
+        size,
+        decay_ms,
+        damping_hz,
+        decay_factor,
+        shimmer_feedback,
+    };
+    let tail_ms = reverb_tail_ms(&engine_params);
 
-    let attributes = vec![
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Algorithm".to_string(),
+            value: format!("{:?}", algorithm),
+        },
         ProcessorAttribute {
             key: "Predelay".to_string(),
             value: format_float_ms(predelay_ms),
@@ -107,25 +192,39 @@ pub fn random_reverb(params: &mut ProcessorParams) -> ProcessorPlan {
             value: format_float_percent(wet_mix),
         },
         ProcessorAttribute {
-            key: "Length Factor".to_string(),
-            value: len_factor.to_string(),
+            key: "Size".to_string(),
+            value: format_float(size),
         },
-        ProcessorAttribute {
+    ];
+    if algorithm == ReverbAlgorithm::Classic {
+        attributes.push(ProcessorAttribute {
             key: "Decay Factor".to_string(),
             value: decay_factor.to_string(),
-        },
-    ];
-
-    let reverb_params = ReverbParams {
-        predelay_ms,
-        wet_mix,
-        len_factor,
-        decay_factor,
-    };
+        });
+    } else {
+        attributes.push(ProcessorAttribute {
+            key: "Decay".to_string(),
+            value: format_float_ms(decay_ms),
+        });
+        attributes.push(ProcessorAttribute {
+            key: "Damping".to_string(),
+            value: format_hz(damping_hz),
+        });
+    }
+    if algorithm == ReverbAlgorithm::Shimmer {
+        attributes.push(ProcessorAttribute {
+            key: "Shimmer".to_string(),
+            value: format_float_percent(shimmer_feedback),
+        });
+    }
+    attributes.push(ProcessorAttribute {
+        key: "Tail".to_string(),
+        value: format_float_ms(tail_ms),
+    });
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::Reverb, &params);
-        let new_params = reverb(&params, reverb_params)?;
+        let new_params = reverb_engine(&params, &engine_params)?;
         complete_event!(PermuteNodeName::Reverb, new_params);
         Ok(new_params)
     };
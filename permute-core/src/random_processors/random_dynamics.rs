@@ -0,0 +1,305 @@
+// External dependencies
+use rand::{thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    evolve::{recorded_choice, recorded_file},
+    permute_files::PermuteUpdate,
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan},
+    processors::dynamics::{
+        compress, expand, limit, shape_transients, CompressorParams, ExpanderParams, LimiterParams,
+        TransientShaperParams,
+    },
+    random_process::{complete_event, start_event},
+    random_processors::{
+        parameter_ranges::random_parameter,
+        random_cross::select_sidechain_file,
+        utils::{format_db, format_float, format_float_ms, get_filename, DistributionRng},
+    },
+};
+
+pub fn random_compressor(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let upward = recorded_choice(params, "Mode", &[true, false], |upward| format_compressor_mode(*upward))
+        .unwrap_or_else(|| rng.gen_bool(0.25));
+    // Only key off another file some of the time, it's a very different effect
+    let sidechain_file = match recorded_file(params, "Sidechain File") {
+        Some(file) if !upward => Some(file),
+        _ if !upward && rng.gen_bool(0.3) => select_sidechain_file(&params.permutation.file, &params.permutation.files),
+        _ => None,
+    };
+
+    let ratio = random_parameter(params, PermuteNodeName::Compressor, "Ratio", &mut rng, |rng| {
+        let ratio_distributions = vec![
+            (rng.gen_range(1.5_f64..3.0), 0.3),
+            (rng.gen_range(3.0..8.0), 0.5),
+            (rng.gen_range(8.0..20.0), 0.2),
+        ];
+        rng.gen_distribution(ratio_distributions)
+    });
+    let threshold_db = random_parameter(params, PermuteNodeName::Compressor, "Threshold", &mut rng, |rng| {
+        if upward {
+            rng.gen_range(-40.0..-20.0)
+        } else {
+            rng.gen_range(-36.0..-8.0)
+        }
+    });
+    let knee_db = random_parameter(params, PermuteNodeName::Compressor, "Knee", &mut rng, |rng| rng.gen_range(0.0..12.0));
+    let attack_ms = random_parameter(params, PermuteNodeName::Compressor, "Attack", &mut rng, |rng| {
+        let attack_distributions = vec![
+            (rng.gen_range(0.1..5.0), 0.4),
+            (rng.gen_range(5.0..50.0), 0.6),
+        ];
+        rng.gen_distribution(attack_distributions)
+    });
+    let release_ms = random_parameter(params, PermuteNodeName::Compressor, "Release", &mut rng, |rng| rng.gen_range(40.0..600.0));
+    let max_boost_db = random_parameter(params, PermuteNodeName::Compressor, "Max Boost", &mut rng, |rng| rng.gen_range(6.0..24.0));
+    // Make up roughly half of the gain lost at the threshold's level of reduction
+    let makeup_db = if upward {
+        0.0
+    } else {
+        -threshold_db * (1.0 - ratio.recip()) * 0.5
+    };
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Mode".to_string(),
+            value: format_compressor_mode(upward),
+        },
+        ProcessorAttribute {
+            key: "Threshold".to_string(),
+            value: format_db(threshold_db),
+        },
+        ProcessorAttribute {
+            key: "Ratio".to_string(),
+            value: format_float(ratio),
+        },
+        ProcessorAttribute {
+            key: "Knee".to_string(),
+            value: format_db(knee_db),
+        },
+        ProcessorAttribute {
+            key: "Attack".to_string(),
+            value: format_float_ms(attack_ms),
+        },
+        ProcessorAttribute {
+            key: "Release".to_string(),
+            value: format_float_ms(release_ms),
+        },
+    ];
+    if upward {
+        attributes.push(ProcessorAttribute {
+            key: "Max Boost".to_string(),
+            value: format_db(max_boost_db),
+        });
+    } else {
+        attributes.push(ProcessorAttribute {
+            key: "Makeup Gain".to_string(),
+            value: format_db(makeup_db),
+        });
+    }
+    if let Some(file) = &sidechain_file {
+        attributes.push(ProcessorAttribute {
+            key: "Sidechain File".to_string(),
+            value: get_filename(file),
+        });
+    }
+
+    let compressor_params = CompressorParams {
+        threshold_db,
+        ratio,
+        knee_db,
+        attack_ms,
+        release_ms,
+        makeup_db,
+        upward,
+        max_boost_db,
+        sidechain_file,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Compressor, &params);
+        let new_params = compress(&params, &compressor_params)?;
+        complete_event!(PermuteNodeName::Compressor, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Compressor, attributes, Box::new(processor))
+}
+
+fn format_compressor_mode(upward: bool) -> String {
+    if upward { "Upward" } else { "Downward" }.to_string()
+}
+
+pub fn random_limiter(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    // Drive into the limiter, otherwise normalised audio barely touches the ceiling
+    let input_gain_db = random_parameter(params, PermuteNodeName::Limiter, "Input Gain", &mut rng, |rng| rng.gen_range(3.0..18.0));
+    let ceiling_db = random_parameter(params, PermuteNodeName::Limiter, "Ceiling", &mut rng, |rng| rng.gen_range(-3.0..-0.1));
+    let lookahead_ms = random_parameter(params, PermuteNodeName::Limiter, "Lookahead", &mut rng, |rng| rng.gen_range(1.0..10.0));
+    let release_ms = random_parameter(params, PermuteNodeName::Limiter, "Release", &mut rng, |rng| {
+        let release_distributions = vec![
+            (rng.gen_range(10.0..60.0), 0.4),
+            (rng.gen_range(60.0..400.0), 0.6),
+        ];
+        rng.gen_distribution(release_distributions)
+    });
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Input Gain".to_string(),
+            value: format_db(input_gain_db),
+        },
+        ProcessorAttribute {
+            key: "Ceiling".to_string(),
+            value: format_db(ceiling_db),
+        },
+        ProcessorAttribute {
+            key: "Lookahead".to_string(),
+            value: format_float_ms(lookahead_ms),
+        },
+        ProcessorAttribute {
+            key: "Release".to_string(),
+            value: format_float_ms(release_ms),
+        },
+    ];
+
+    let limiter_params = LimiterParams {
+        input_gain_db,
+        ceiling_db,
+        lookahead_ms,
+        release_ms,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Limiter, &params);
+        let new_params = limit(&params, &limiter_params)?;
+        complete_event!(PermuteNodeName::Limiter, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Limiter, attributes, Box::new(processor))
+}
+
+pub fn random_expander(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let gate = recorded_choice(params, "Mode", &[true, false], |gate| format_expander_mode(*gate))
+        .unwrap_or_else(|| rng.gen_bool(0.5));
+    let ratio = random_parameter(params, PermuteNodeName::Expander, "Ratio", &mut rng, |rng| {
+        if gate { rng.gen_range(10.0..40.0) } else { rng.gen_range(1.5..4.0) }
+    });
+    let range_db = random_parameter(params, PermuteNodeName::Expander, "Range", &mut rng, |rng| {
+        if gate { rng.gen_range(40.0..80.0) } else { rng.gen_range(10.0..30.0) }
+    });
+    let threshold_db = random_parameter(params, PermuteNodeName::Expander, "Threshold", &mut rng, |rng| rng.gen_range(-45.0..-15.0));
+    let attack_ms = random_parameter(params, PermuteNodeName::Expander, "Attack", &mut rng, |rng| rng.gen_range(0.1..10.0));
+    let hold_ms = random_parameter(params, PermuteNodeName::Expander, "Hold", &mut rng, |rng| rng.gen_range(0.0..150.0));
+    let release_ms = random_parameter(params, PermuteNodeName::Expander, "Release", &mut rng, |rng| {
+        let release_distributions = vec![
+            (rng.gen_range(5.0..50.0), 0.4),
+            (rng.gen_range(50.0..500.0), 0.6),
+        ];
+        rng.gen_distribution(release_distributions)
+    });
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Mode".to_string(),
+            value: format_expander_mode(gate),
+        },
+        ProcessorAttribute {
+            key: "Threshold".to_string(),
+            value: format_db(threshold_db),
+        },
+        ProcessorAttribute {
+            key: "Ratio".to_string(),
+            value: format_float(ratio),
+        },
+        ProcessorAttribute {
+            key: "Range".to_string(),
+            value: format_db(range_db),
+        },
+        ProcessorAttribute {
+            key: "Attack".to_string(),
+            value: format_float_ms(attack_ms),
+        },
+        ProcessorAttribute {
+            key: "Hold".to_string(),
+            value: format_float_ms(hold_ms),
+        },
+        ProcessorAttribute {
+            key: "Release".to_string(),
+            value: format_float_ms(release_ms),
+        },
+    ];
+
+    let expander_params = ExpanderParams {
+        threshold_db,
+        ratio,
+        range_db,
+        attack_ms,
+        hold_ms,
+        release_ms,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Expander, &params);
+        let new_params = expand(&params, &expander_params)?;
+        complete_event!(PermuteNodeName::Expander, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Expander, attributes, Box::new(processor))
+}
+
+fn format_expander_mode(gate: bool) -> String {
+    if gate { "Gate" } else { "Expander" }.to_string()
+}
+
+pub fn random_transient_shaper(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    // Either punch up the attacks, soften them, or bring out the tails
+    let shape_distributions = vec![
+        ((rng.gen_range(0.3..1.0), rng.gen_range(-0.5..0.0)), 0.4),
+        ((rng.gen_range(-1.0..-0.3), rng.gen_range(0.0..0.5)), 0.3),
+        ((rng.gen_range(-0.2..0.2), rng.gen_range(0.4..1.0)), 0.3),
+    ];
+    let (attack, sustain) = rng.gen_distribution(shape_distributions);
+    let attack = random_parameter(params, PermuteNodeName::TransientShaper, "Attack", &mut rng, |_| attack);
+    let sustain = random_parameter(params, PermuteNodeName::TransientShaper, "Sustain", &mut rng, |_| sustain);
+    let max_gain_db = random_parameter(params, PermuteNodeName::TransientShaper, "Max Gain", &mut rng, |rng| rng.gen_range(6.0..18.0));
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Attack".to_string(),
+            value: format_float(attack),
+        },
+        ProcessorAttribute {
+            key: "Sustain".to_string(),
+            value: format_float(sustain),
+        },
+        ProcessorAttribute {
+            key: "Max Gain".to_string(),
+            value: format_db(max_gain_db),
+        },
+    ];
+
+    let shaper_params = TransientShaperParams {
+        attack,
+        sustain,
+        max_gain_db,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::TransientShaper, &params);
+        let new_params = shape_transients(&params, &shaper_params)?;
+        complete_event!(PermuteNodeName::TransientShaper, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::TransientShaper, attributes, Box::new(processor))
+}
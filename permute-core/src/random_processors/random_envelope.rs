@@ -0,0 +1,313 @@
+// External dependencies
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    evolve::recorded_choice,
+    permute_files::PermuteUpdate,
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan},
+    processors::{
+        envelope::{
+            auto_wah, envelope_delay, envelope_drive, envelope_pitch, AutoWahParams, EnvelopeDelayParams,
+            EnvelopeDriveParams, EnvelopeFollowerParams, EnvelopePitchParams,
+        },
+        gain_distortion::DistortionAlgorithm,
+    },
+    random_process::{complete_event, start_event},
+    random_processors::{
+        parameter_ranges::random_parameter,
+        random_gain_distortion::random_oversampling,
+        utils::{format_db, format_float, format_float_ms, format_float_percent, format_hz, DistributionRng},
+    },
+};
+
+// Attack and release shape how closely the modulation tracks the audio.
+// Fast settings chatter along with every note, slow ones swell with phrases
+fn random_follower(params: &ProcessorParams, processor: PermuteNodeName, rng: &mut ThreadRng) -> EnvelopeFollowerParams {
+    let attack_ms = random_parameter(params, processor, "Attack", rng, |rng| {
+        let attack_distributions = vec![
+            (rng.gen_range(1.0..10.0), 0.5),
+            (rng.gen_range(10.0..80.0), 0.4),
+            (rng.gen_range(80.0..300.0), 0.1),
+        ];
+        rng.gen_distribution(attack_distributions)
+    });
+    let release_ms = random_parameter(params, processor, "Release", rng, |rng| {
+        let release_distributions = vec![
+            (rng.gen_range(30.0..150.0), 0.4),
+            (rng.gen_range(150.0..600.0), 0.5),
+            (rng.gen_range(600.0..2000.0), 0.1),
+        ];
+        rng.gen_distribution(release_distributions)
+    });
+    EnvelopeFollowerParams {
+        attack_ms,
+        release_ms,
+        range_db: random_parameter(params, processor, "Range", rng, |rng| rng.gen_range(18.0..48.0)),
+        invert: recorded_choice(params, "Follow", &[false, true], |invert| format_follow(*invert))
+            .unwrap_or_else(|| rng.gen_bool(0.2)),
+    }
+}
+
+fn format_follow(invert: bool) -> String {
+    if invert { "Inverted" } else { "Up" }.to_string()
+}
+
+fn follower_attributes(follower: &EnvelopeFollowerParams) -> Vec<ProcessorAttribute> {
+    vec![
+        ProcessorAttribute {
+            key: "Attack".to_string(),
+            value: format_float_ms(follower.attack_ms),
+        },
+        ProcessorAttribute {
+            key: "Release".to_string(),
+            value: format_float_ms(follower.release_ms),
+        },
+        ProcessorAttribute {
+            key: "Range".to_string(),
+            value: format_db(follower.range_db),
+        },
+        ProcessorAttribute {
+            key: "Follow".to_string(),
+            value: format_follow(follower.invert),
+        },
+    ]
+}
+
+pub fn random_auto_wah(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let follower = random_follower(params, PermuteNodeName::AutoWah, &mut rng);
+    let type_distributions = vec![
+        (biquad::Type::BandPass, 0.5),
+        (biquad::Type::LowPass, 0.4),
+        (biquad::Type::HighPass, 0.1),
+    ];
+    let filter_type = recorded_choice(
+        params,
+        "Filter Type",
+        &[biquad::Type::BandPass, biquad::Type::LowPass, biquad::Type::HighPass],
+        |t| format!("{:?}", t),
+    )
+    .unwrap_or_else(|| rng.gen_distribution(type_distributions));
+    let min_hz = random_parameter(params, PermuteNodeName::AutoWah, "Min Frequency", &mut rng, |rng| rng.gen_range(150.0..600.0));
+    let max_hz = random_parameter(params, PermuteNodeName::AutoWah, "Max Frequency", &mut rng, |rng| {
+        min_hz * rng.gen_range(3.0..16.0)
+    });
+    // Ranges for both ends can overlap
+    let (min_hz, max_hz) = (min_hz.min(max_hz), min_hz.max(max_hz));
+    let q = random_parameter(params, PermuteNodeName::AutoWah, "Q", &mut rng, |rng| rng.gen_range(1.0..6.0));
+    let mix = random_parameter(params, PermuteNodeName::AutoWah, "Mix", &mut rng, |rng| rng.gen_range(0.6..1.0));
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Filter Type".to_string(),
+            value: format!("{:?}", filter_type),
+        },
+        ProcessorAttribute {
+            key: "Min Frequency".to_string(),
+            value: format_hz(min_hz),
+        },
+        ProcessorAttribute {
+            key: "Max Frequency".to_string(),
+            value: format_hz(max_hz),
+        },
+        ProcessorAttribute {
+            key: "Q".to_string(),
+            value: format_float(q),
+        },
+        ProcessorAttribute {
+            key: "Mix".to_string(),
+            value: format_float_percent(mix),
+        },
+    ];
+    attributes.extend(follower_attributes(&follower));
+
+    let wah_params = AutoWahParams {
+        follower,
+        filter_type,
+        min_hz,
+        max_hz,
+        q: Some(q),
+        mix,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::AutoWah, &params);
+        let new_params = auto_wah(&params, &wah_params)?;
+        complete_event!(PermuteNodeName::AutoWah, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::AutoWah, attributes, Box::new(processor))
+}
+
+pub fn random_envelope_pitch(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let follower = random_follower(params, PermuteNodeName::EnvelopePitch, &mut rng);
+    let semitones = random_parameter(params, PermuteNodeName::EnvelopePitch, "Shift", &mut rng, |rng| {
+        let semitone_distributions = vec![
+            // Slight bends on loud notes
+            (rng.gen_range(0.2..1.0), 0.35),
+            (rng.gen_range(-1.0..-0.2), 0.25),
+            (rng.gen_range(2.0..12.0), 0.25),
+            (rng.gen_range(-12.0..-2.0), 0.15),
+        ];
+        rng.gen_distribution(semitone_distributions)
+    });
+    let window_ms = random_parameter(params, PermuteNodeName::EnvelopePitch, "Window", &mut rng, |rng| rng.gen_range(30.0..90.0));
+    let mix = random_parameter(params, PermuteNodeName::EnvelopePitch, "Mix", &mut rng, |rng| rng.gen_range(0.5..1.0));
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Shift".to_string(),
+            value: format!("{} semitones", format_float(semitones)),
+        },
+        ProcessorAttribute {
+            key: "Window".to_string(),
+            value: format_float_ms(window_ms),
+        },
+        ProcessorAttribute {
+            key: "Mix".to_string(),
+            value: format_float_percent(mix),
+        },
+    ];
+    attributes.extend(follower_attributes(&follower));
+
+    let pitch_params = EnvelopePitchParams {
+        follower,
+        semitones,
+        window_ms,
+        mix,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::EnvelopePitch, &params);
+        let new_params = envelope_pitch(&params, &pitch_params)?;
+        complete_event!(PermuteNodeName::EnvelopePitch, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::EnvelopePitch, attributes, Box::new(processor))
+}
+
+pub fn random_envelope_delay(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let follower = random_follower(params, PermuteNodeName::EnvelopeDelay, &mut rng);
+    let min_ms = random_parameter(params, PermuteNodeName::EnvelopeDelay, "Min Delay", &mut rng, |rng| rng.gen_range(20.0..250.0));
+    let max_ms = random_parameter(params, PermuteNodeName::EnvelopeDelay, "Max Delay", &mut rng, |rng| {
+        min_ms + rng.gen_range(30.0..500.0)
+    });
+    // Ranges for both ends can overlap
+    let (min_ms, max_ms) = (min_ms.min(max_ms), min_ms.max(max_ms));
+    let feedback = random_parameter(params, PermuteNodeName::EnvelopeDelay, "Feedback", &mut rng, |rng| rng.gen_range(0.1..0.7));
+    let mix = random_parameter(params, PermuteNodeName::EnvelopeDelay, "Mix", &mut rng, |rng| rng.gen_range(0.2..0.6));
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Min Delay".to_string(),
+            value: format_float_ms(min_ms),
+        },
+        ProcessorAttribute {
+            key: "Max Delay".to_string(),
+            value: format_float_ms(max_ms),
+        },
+        ProcessorAttribute {
+            key: "Feedback".to_string(),
+            value: format_float_percent(feedback),
+        },
+        ProcessorAttribute {
+            key: "Mix".to_string(),
+            value: format_float_percent(mix),
+        },
+    ];
+    attributes.extend(follower_attributes(&follower));
+
+    let delay_params = EnvelopeDelayParams {
+        follower,
+        min_ms,
+        max_ms,
+        feedback,
+        mix,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::EnvelopeDelay, &params);
+        let new_params = envelope_delay(&params, &delay_params)?;
+        complete_event!(PermuteNodeName::EnvelopeDelay, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::EnvelopeDelay, attributes, Box::new(processor))
+}
+
+pub fn random_envelope_drive(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let follower = random_follower(params, PermuteNodeName::EnvelopeDrive, &mut rng);
+    let algorithm_distributions = vec![
+        (DistortionAlgorithm::Tanh, 0.35),
+        (DistortionAlgorithm::Atan, 0.25),
+        (DistortionAlgorithm::Cubic, 0.2),
+        (DistortionAlgorithm::Saturate, 0.2),
+    ];
+    let algorithm = recorded_choice(
+        params,
+        "Algorithm",
+        &[DistortionAlgorithm::Tanh, DistortionAlgorithm::Atan, DistortionAlgorithm::Cubic, DistortionAlgorithm::Saturate],
+        |a| format!("{:?}", a),
+    )
+    .unwrap_or_else(|| rng.gen_distribution(algorithm_distributions));
+    let min_drive = random_parameter(params, PermuteNodeName::EnvelopeDrive, "Min Drive", &mut rng, |rng| rng.gen_range(0.5..2.0));
+    let max_drive = random_parameter(params, PermuteNodeName::EnvelopeDrive, "Max Drive", &mut rng, |rng| {
+        min_drive * rng.gen_range(3.0..20.0)
+    });
+    // Ranges for both ends can overlap
+    let (min_drive, max_drive) = (min_drive.min(max_drive), min_drive.max(max_drive));
+    let oversampling = random_oversampling(params, &mut rng);
+    let mix = random_parameter(params, PermuteNodeName::EnvelopeDrive, "Mix", &mut rng, |rng| rng.gen_range(0.5..1.0));
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Algorithm".to_string(),
+            value: format!("{:?}", algorithm),
+        },
+        ProcessorAttribute {
+            key: "Min Drive".to_string(),
+            value: format_float(min_drive),
+        },
+        ProcessorAttribute {
+            key: "Max Drive".to_string(),
+            value: format_float(max_drive),
+        },
+        ProcessorAttribute {
+            key: "Oversampling".to_string(),
+            value: format!("{}x", oversampling.factor()),
+        },
+        ProcessorAttribute {
+            key: "Mix".to_string(),
+            value: format_float_percent(mix),
+        },
+    ];
+    attributes.extend(follower_attributes(&follower));
+
+    let drive_params = EnvelopeDriveParams {
+        follower,
+        min_drive,
+        max_drive,
+        algorithm,
+        oversampling,
+        mix,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::EnvelopeDrive, &params);
+        let new_params = envelope_drive(&params, &drive_params)?;
+        complete_event!(PermuteNodeName::EnvelopeDrive, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::EnvelopeDrive, attributes, Box::new(processor))
+}
@@ -0,0 +1,68 @@
+// External dependencies
+use rand::{thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    evolve::{parse_attribute_value, recorded_attribute},
+    permute_files::{PermuteUpdate, MAX_OUTPUT_SECONDS},
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan},
+    processors::extreme_stretch::{extreme_stretch, ExtremeStretchParams},
+    random_process::{complete_event, start_event},
+    random_processors::{parameter_ranges::random_parameter, utils::{format_float_ms, DistributionRng}},
+};
+
+pub fn random_extreme_stretch(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let stretch_distributions = vec![
+        (rng.gen_range(50.0_f64..100.0), 0.4),
+        (rng.gen_range(100.0..300.0), 0.35),
+        (rng.gen_range(300.0..1000.0), 0.25),
+    ];
+    // Held to the output limit for the audio as planned too, so the reported factor is close
+    let frames = params.samples.len() / params.channels.max(1);
+    let input_seconds = frames as f64 / params.sample_rate.max(1) as f64;
+    let recorded_factor = recorded_attribute(params, "Stretch Factor").and_then(|f| parse_attribute_value(&f));
+    let stretch_factor: f64 = recorded_factor
+        .unwrap_or_else(|| rng.gen_distribution(stretch_distributions))
+        .min(params.permutation.extreme_stretch.unwrap_or(f64::MAX))
+        .min(MAX_OUTPUT_SECONDS / input_seconds.max(f64::EPSILON));
+    // Rounded down so the reported factor stays inside the limit
+    let stretch_factor = ((stretch_factor * 10.0).floor() / 10.0).max(1.0);
+
+    // Longer windows blur more of the original into each moment of the drone
+    let window_ms = random_parameter(params, PermuteNodeName::ExtremeStretch, "Window", &mut rng, |rng| {
+        let window_distributions = vec![
+            (rng.gen_range(120.0..300.0), 0.3),
+            (rng.gen_range(300.0..800.0), 0.45),
+            (rng.gen_range(800.0..2000.0), 0.25),
+        ];
+        rng.gen_distribution(window_distributions)
+    });
+    let window_size = ((window_ms / 1000.0) * params.sample_rate as f64) as usize / 2 * 2;
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Stretch Factor".to_string(),
+            value: format!("{:.1}x", stretch_factor),
+        },
+        ProcessorAttribute {
+            key: "Window".to_string(),
+            value: format_float_ms(window_ms),
+        },
+    ];
+
+    let stretch_params = ExtremeStretchParams {
+        window_size,
+        stretch_factor,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::ExtremeStretch, &params);
+        let new_params = extreme_stretch(params, &stretch_params)?;
+        complete_event!(PermuteNodeName::ExtremeStretch, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::ExtremeStretch, attributes, Box::new(processor))
+}
@@ -3,8 +3,13 @@ use rand::{thread_rng, Rng};
 
 // Internal modules
 use crate::{
+    evolve::recorded_choice,
     processors::filter::{FilterParams, OscillatingFilterParams, LineFilterParams, FilterForm, filter, oscillating_filter, multi_line_filter},
-    random_processors::utils::{format_float, format_hz, format_float_percent},
+    random_processors::{
+        parameter_ranges::{random_override, random_parameter},
+        tempo::{format_sync, random_note_division, sync_to_tempo},
+        utils::{format_float, format_hz, format_float_percent},
+    },
     process::{ProcessorParams, ProcessorPlan, PermuteNodeName, ProcessorAttribute, PermuteNodeEvent, ProcessorClosure},
     permute_files::PermuteUpdate,
     random_process::{start_event, complete_event},
@@ -23,9 +28,12 @@ pub fn random_filter(params: &mut ProcessorParams) -> ProcessorPlan {
         biquad::Type::BandPass,
     ];
 
-    let filter_type = types[rng.gen_range(0..types.len())];
-    let frequency = freqs[rng.gen_range(0..freqs.len())];
-    let q = rng.gen_range(0.15_f64..1.2_f64);
+    let filter_type = recorded_choice(params, "Filter Type", &types, |t| format!("{:?}", t))
+        .unwrap_or_else(|| types[rng.gen_range(0..types.len())]);
+    let frequency = random_parameter(params, PermuteNodeName::Filter, "Frequency", &mut rng, |rng| {
+        freqs[rng.gen_range(0..freqs.len())]
+    });
+    let q = random_parameter(params, PermuteNodeName::Filter, "Q", &mut rng, |rng| rng.gen_range(0.15_f64..1.2_f64));
     let form = FilterForm::Form2;
 
     let attributes = vec![
@@ -82,14 +90,27 @@ pub fn random_oscillating_filter(params: &mut ProcessorParams) -> ProcessorPlan
         biquad::Type::BandPass,
     ];
 
-    let filter_type = types[rng.gen_range(0..types.len())];
-    let frequency = freqs[rng.gen_range(0..freqs.len())];
-    let lfo_rate = lfo_rates[rng.gen_range(0..lfo_rates.len())];
-    let lfo_factor = lfo_factors[rng.gen_range(0..lfo_factors.len())];
-    let q = rng.gen_range(0.5_f64..1.3_f64);
+    let filter_type = recorded_choice(params, "Filter Type", &types, |t| format!("{:?}", t))
+        .unwrap_or_else(|| types[rng.gen_range(0..types.len())]);
+    let frequency = random_parameter(params, PermuteNodeName::OscillatingFilter, "Frequency", &mut rng, |rng| {
+        freqs[rng.gen_range(0..freqs.len())]
+    });
+    // A user's rate range takes over from syncing to the tempo
+    let user_lfo_rate = random_override(params, PermuteNodeName::OscillatingFilter, "LFO Rate", &mut rng);
+    let sync = sync_to_tempo(&mut rng, params.permutation.bpm)
+        .filter(|_| user_lfo_rate.is_none())
+        .and_then(|bpm| random_note_division(&mut rng, bpm, 100.0, 4000.0).map(|division| (division, bpm)));
+    let lfo_rate = match (sync, user_lfo_rate) {
+        (Some((division, bpm)), _) => division.to_hz(bpm),
+        (None, Some(lfo_rate)) => lfo_rate,
+        (None, None) => lfo_rates[rng.gen_range(0..lfo_rates.len())],
+    };
+    let lfo_factor = recorded_choice(params, "LFO Factor", &lfo_factors, |f| format_float_percent(*f))
+        .unwrap_or_else(|| lfo_factors[rng.gen_range(0..lfo_factors.len())]);
+    let q = random_parameter(params, PermuteNodeName::OscillatingFilter, "Q", &mut rng, |rng| rng.gen_range(0.5_f64..1.3_f64));
     let form = FilterForm::Form2;
 
-    let attributes = vec![
+    let mut attributes = vec![
         ProcessorAttribute {
             key: "Filter Type".to_string(),
             value: format!("{:?}", filter_type),
@@ -115,6 +136,12 @@ pub fn random_oscillating_filter(params: &mut ProcessorParams) -> ProcessorPlan
             value: format!("{:?}", form),
         },
     ];
+    if let Some((division, bpm)) = sync {
+        attributes.push(ProcessorAttribute {
+            key: "Sync".to_string(),
+            value: format_sync(division, bpm),
+        });
+    }
 
     let filter_params = OscillatingFilterParams {
         filter_type,
@@ -151,10 +178,15 @@ pub fn random_line_filter(params: &mut ProcessorParams) -> ProcessorPlan {
         biquad::Type::BandPass,
     ];
 
-    let filter_type = types[rng.gen_range(0..types.len())];
-    let hz_from = freqs[rng.gen_range(0..freqs.len())];
-    let hz_to = freqs[rng.gen_range(0..freqs.len())];
-    let q = rng.gen_range(0.5_f64..1.35_f64);
+    let filter_type = recorded_choice(params, "Filter Type", &types, |t| format!("{:?}", t))
+        .unwrap_or_else(|| types[rng.gen_range(0..types.len())]);
+    let hz_from = random_parameter(params, PermuteNodeName::LineFilter, "From", &mut rng, |rng| {
+        freqs[rng.gen_range(0..freqs.len())]
+    });
+    let hz_to = random_parameter(params, PermuteNodeName::LineFilter, "To", &mut rng, |rng| {
+        freqs[rng.gen_range(0..freqs.len())]
+    });
+    let q = random_parameter(params, PermuteNodeName::LineFilter, "Q", &mut rng, |rng| rng.gen_range(0.5_f64..1.35_f64));
     let form = FilterForm::Form2;
 
     let attributes = vec![
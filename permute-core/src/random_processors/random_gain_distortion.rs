@@ -1,20 +1,40 @@
 // External dependencies
-use rand::{thread_rng, Rng};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
 
 // Internal modules
 use crate::{
+    evolve::recorded_choice,
     processors::gain_distortion::{FuzzParams, fuzz, saturate, ceiling, trim_threshold},
-    random_processors::utils::format_float,
+    processors::oversample::Oversampling,
+    random_processors::{
+        parameter_ranges::random_parameter,
+        utils::{format_float, DistributionRng},
+    },
     random_process::{start_event, complete_event},
     process::{ProcessorParams, ProcessorPlan, PermuteNodeName, ProcessorAttribute, PermuteNodeEvent},
     permute_files::PermuteUpdate,
 };
 
-pub fn random_fuzz(_params: &mut ProcessorParams) -> ProcessorPlan {
+// Distortion runs oversampled so harmonics above nyquist don't fold back into the audible range.
+// The user's setting is used when there is one, then a bred node's parent's
+pub fn random_oversampling(params: &ProcessorParams, rng: &mut ThreadRng) -> Oversampling {
+    let options = [Oversampling::None, Oversampling::X2, Oversampling::X4, Oversampling::X8];
+    let recorded = recorded_choice(params, "Oversampling", &options, |o| format!("{}x", o.factor()));
+    params.permutation.oversampling.or(recorded).unwrap_or_else(|| {
+        rng.gen_distribution(vec![
+            (Oversampling::X2, 0.3),
+            (Oversampling::X4, 0.5),
+            (Oversampling::X8, 0.2),
+        ])
+    })
+}
+
+pub fn random_fuzz(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
-    let gain = rng.gen_range(0.5_f64..3.0_f64);
-    let output_gain = rng.gen_range(0.1_f64..1.0_f64);
+    let gain = random_parameter(params, PermuteNodeName::Fuzz, "Gain", &mut rng, |rng| rng.gen_range(0.5_f64..3.0_f64));
+    let output_gain = random_parameter(params, PermuteNodeName::Fuzz, "Output Gain", &mut rng, |rng| rng.gen_range(0.1_f64..1.0_f64));
+    let oversampling = random_oversampling(params, &mut rng);
 
     let attributes = vec![
         ProcessorAttribute {
@@ -25,11 +45,16 @@ pub fn random_fuzz(_params: &mut ProcessorParams) -> ProcessorPlan {
             key: "Output Gain".to_string(),
             value: format_float(output_gain),
         },
+        ProcessorAttribute {
+            key: "Oversampling".to_string(),
+            value: format!("{}x", oversampling.factor()),
+        },
     ];
 
     let fuzz_params = FuzzParams {
         gain,
         output_gain,
+        oversampling,
     };
 
     let processor = move |params: ProcessorParams| {
@@ -42,17 +67,24 @@ pub fn random_fuzz(_params: &mut ProcessorParams) -> ProcessorPlan {
     (PermuteNodeName::Fuzz, attributes, Box::new(processor))
 }
 
-pub fn random_saturate(_params: &mut ProcessorParams) -> ProcessorPlan {
+pub fn random_saturate(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+    let oversampling = random_oversampling(params, &mut rng);
+
     let attributes = vec![
         ProcessorAttribute {
             key: "Algorithm".to_string(),
             value: "Saturate".to_string(),
         },
+        ProcessorAttribute {
+            key: "Oversampling".to_string(),
+            value: format!("{}x", oversampling.factor()),
+        },
     ];
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::Saturate, &params);
-        let new_params = saturate(&params)?;
+        let new_params = saturate(&params, oversampling)?;
         complete_event!(PermuteNodeName::Saturate, new_params);
         Ok(new_params)
     };
@@ -0,0 +1,123 @@
+// External dependencies
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    evolve::{recorded_attribute, recorded_choice},
+    permute_files::PermuteUpdate,
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan},
+    processors::glitch::{format_slice_map, glitch, slice_map_length_factor, GlitchOp, GlitchParams, GlitchSlice, SliceMode},
+    random_process::{complete_event, start_event},
+    random_processors::{
+        parameter_ranges::random_parameter,
+        tempo::{format_sync, random_note_division, sync_to_tempo},
+        utils::{format_float, format_float_ms, DistributionRng},
+    },
+};
+
+fn random_glitch_op(rng: &mut ThreadRng) -> GlitchOp {
+    let repeat_distributions = vec![(2, 0.6), (3, 0.25), (4, 0.15)];
+    let stutter_distributions = vec![(2, 0.3), (3, 0.2), (4, 0.3), (8, 0.2)];
+    let pitch_distributions = vec![(-12.0, 0.25), (-7.0, 0.15), (-5.0, 0.1), (5.0, 0.1), (7.0, 0.15), (12.0, 0.25)];
+    let op_distributions = vec![
+        (GlitchOp::Play, 0.45),
+        (GlitchOp::Repeat(rng.gen_distribution(repeat_distributions)), 0.1),
+        (GlitchOp::Stutter(rng.gen_distribution(stutter_distributions)), 0.12),
+        (GlitchOp::Reverse, 0.12),
+        (GlitchOp::Drop, 0.08),
+        (GlitchOp::Pitch(rng.gen_distribution(pitch_distributions)), 0.13),
+    ];
+    rng.gen_distribution(op_distributions)
+}
+
+pub fn random_glitch(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let mode = recorded_choice(params, "Mode", &[SliceMode::Onset, SliceMode::Grid], |m| format!("{:?}", m))
+        .unwrap_or_else(|| if rng.gen_bool(0.5) { SliceMode::Onset } else { SliceMode::Grid });
+    let slice_distributions = vec![(4, 0.15), (8, 0.35), (16, 0.35), (32, 0.15)];
+    let recorded_slices = recorded_attribute(params, "Slices").and_then(|s| s.parse::<usize>().ok());
+    let mut slices: usize = match recorded_slices {
+        Some(slices) => slices.clamp(2, 64),
+        None => rng.gen_distribution(slice_distributions),
+    };
+
+    // Cut the grid on note divisions when the tempo is known
+    let sync = match mode {
+        SliceMode::Grid => sync_to_tempo(&mut rng, params.permutation.bpm)
+            .and_then(|bpm| random_note_division(&mut rng, bpm, 60.0, 600.0).map(|division| (division, bpm))),
+        SliceMode::Onset => None,
+    };
+    if let Some((division, bpm)) = sync {
+        let duration_ms = (params.samples.len() / params.channels.max(1)) as f64 / params.sample_rate as f64 * 1000.0;
+        slices = ((duration_ms / division.to_ms(bpm)).ceil() as usize).clamp(2, 64);
+    }
+
+    let mut slice_map: Vec<GlitchSlice> = (0..slices)
+        .map(|source| GlitchSlice {
+            source,
+            op: random_glitch_op(&mut rng),
+        })
+        .collect();
+    // Shuffle some neighbouring slices around
+    if rng.gen_bool(0.5) {
+        for i in 0..slices - 1 {
+            if rng.gen_bool(0.3) {
+                slice_map.swap(i, i + 1);
+            }
+        }
+    }
+    // Always keep something to play
+    if slice_map.iter().all(|s| s.op == GlitchOp::Drop) {
+        slice_map[0].op = GlitchOp::Play;
+    }
+    let crossfade_ms = random_parameter(params, PermuteNodeName::Glitch, "Crossfade", &mut rng, |rng| rng.gen_range(2.0..10.0));
+    let length_factor = slice_map_length_factor(&slice_map, slices);
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Mode".to_string(),
+            value: format!("{:?}", mode),
+        },
+        ProcessorAttribute {
+            key: "Slices".to_string(),
+            value: slices.to_string(),
+        },
+        ProcessorAttribute {
+            key: "Slice Map".to_string(),
+            value: format_slice_map(&slice_map),
+        },
+        ProcessorAttribute {
+            key: "Crossfade".to_string(),
+            value: format_float_ms(crossfade_ms),
+        },
+        ProcessorAttribute {
+            key: "Length Factor".to_string(),
+            value: format_float(length_factor),
+        },
+    ];
+    if let Some((division, bpm)) = sync {
+        attributes.push(ProcessorAttribute {
+            key: "Sync".to_string(),
+            value: format_sync(division, bpm),
+        });
+    }
+
+    let glitch_params = GlitchParams {
+        mode,
+        slices,
+        slice_map,
+        crossfade_ms,
+        quality: params.permutation.resample_quality,
+        grid_ms: sync.map(|(division, bpm)| division.to_ms(bpm)),
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Glitch, &params);
+        let new_params = glitch(&params, &glitch_params)?;
+        complete_event!(PermuteNodeName::Glitch, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Glitch, attributes, Box::new(processor))
+}
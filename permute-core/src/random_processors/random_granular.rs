@@ -0,0 +1,249 @@
+// External dependencies
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    evolve::{recorded_choice, recorded_file},
+    permute_files::PermuteUpdate,
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan},
+    processors::granular::{granular_cloud, GrainEnvelope, GrainPointer, GranularCloudParams},
+    random_process::{complete_event, start_event},
+    random_processors::{
+        parameter_ranges::random_parameter,
+        random_cross::select_sidechain_file,
+        utils::{format_float, format_float_ms, format_float_percent, get_filename, DistributionRng},
+    },
+};
+
+// Broad character of the cloud, from a light texture over the original to a dense smear
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CloudCharacter {
+    Subtle,
+    Texture,
+    Shimmer,
+    Cloud,
+    Frozen,
+}
+
+fn random_pointer(rng: &mut ThreadRng, character: CloudCharacter) -> GrainPointer {
+    if character == CloudCharacter::Frozen {
+        return GrainPointer::Freeze(rng.gen_range(0.05..0.8));
+    }
+    let pointer_distributions = vec![
+        (GrainPointer::Follow, 0.6),
+        (GrainPointer::Scan(rng.gen_range(0.0..0.5), rng.gen_range(0.5..1.0)), 0.2),
+        // Backwards through the source
+        (GrainPointer::Scan(rng.gen_range(0.5..1.0), rng.gen_range(0.0..0.5)), 0.1),
+        (GrainPointer::Freeze(rng.gen_range(0.05..0.8)), 0.1),
+    ];
+    rng.gen_distribution(pointer_distributions)
+}
+
+fn format_pointer(pointer: GrainPointer) -> String {
+    match pointer {
+        GrainPointer::Follow => "Follow".to_string(),
+        GrainPointer::Freeze(position) => format!("Freeze at {}", format_float_percent(position)),
+        GrainPointer::Scan(from, to) => format!(
+            "Scan {} to {}",
+            format_float_percent(from),
+            format_float_percent(to)
+        ),
+    }
+}
+
+pub fn random_granular_cloud(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let character_distributions = vec![
+        (CloudCharacter::Subtle, 0.25),
+        (CloudCharacter::Texture, 0.25),
+        (CloudCharacter::Shimmer, 0.15),
+        (CloudCharacter::Cloud, 0.2),
+        (CloudCharacter::Frozen, 0.15),
+    ];
+    let characters: Vec<CloudCharacter> = character_distributions.iter().map(|(c, _)| *c).collect();
+    let character = recorded_choice(params, "Character", &characters, |c| format!("{:?}", c))
+        .unwrap_or_else(|| rng.gen_distribution(character_distributions));
+
+    // (density, grain ms, position jitter ms, pitch scatter, pan scatter, mix)
+    let (density, grain_ms, position_jitter_ms, pitch_scatter, pan_scatter, mix) = match character {
+        CloudCharacter::Subtle => (
+            rng.gen_range(8.0..25.0),
+            rng.gen_range(30.0..90.0),
+            rng.gen_range(5.0..40.0),
+            rng.gen_range(0.0..0.3),
+            rng.gen_range(0.0..0.3),
+            rng.gen_range(0.15..0.35),
+        ),
+        CloudCharacter::Texture => (
+            rng.gen_range(20.0..60.0),
+            rng.gen_range(20.0..120.0),
+            rng.gen_range(20.0..150.0),
+            rng.gen_range(0.2..2.0),
+            rng.gen_range(0.2..0.7),
+            rng.gen_range(0.3..0.6),
+        ),
+        CloudCharacter::Shimmer => (
+            rng.gen_range(20.0..60.0),
+            rng.gen_range(60.0..200.0),
+            rng.gen_range(20.0..200.0),
+            12.0,
+            rng.gen_range(0.4..1.0),
+            rng.gen_range(0.3..0.7),
+        ),
+        CloudCharacter::Cloud => (
+            rng.gen_range(50.0..200.0),
+            rng.gen_range(40.0..300.0),
+            rng.gen_range(100.0..800.0),
+            rng.gen_range(0.0..7.0),
+            rng.gen_range(0.5..1.0),
+            rng.gen_range(0.7..1.0),
+        ),
+        CloudCharacter::Frozen => (
+            rng.gen_range(30.0..120.0),
+            rng.gen_range(60.0..400.0),
+            rng.gen_range(10.0..120.0),
+            rng.gen_range(0.0..0.5),
+            rng.gen_range(0.3..1.0),
+            rng.gen_range(0.8..1.0),
+        ),
+    };
+    // The character sets the defaults, the user's ranges take over from them
+    let density = random_parameter(params, PermuteNodeName::GranularCloud, "Density", &mut rng, |_| density);
+    let grain_ms = random_parameter(params, PermuteNodeName::GranularCloud, "Grain Size", &mut rng, |_| grain_ms);
+    let position_jitter_ms = random_parameter(params, PermuteNodeName::GranularCloud, "Position Jitter", &mut rng, |_| position_jitter_ms);
+    // Grains are only panned in stereo
+    let pan_scatter = match params.channels {
+        2 => random_parameter(params, PermuteNodeName::GranularCloud, "Pan Scatter", &mut rng, |_| pan_scatter),
+        _ => 0.0,
+    };
+    let mix = random_parameter(params, PermuteNodeName::GranularCloud, "Mix", &mut rng, |_| mix);
+    // Shimmer's octaves aren't a scatter in semitones
+    let octave_pitch = character == CloudCharacter::Shimmer;
+    let pitch_scatter = if octave_pitch {
+        pitch_scatter
+    } else {
+        random_parameter(params, PermuteNodeName::GranularCloud, "Pitch Scatter", &mut rng, |_| pitch_scatter)
+    };
+    let grain_size_jitter = random_parameter(params, PermuteNodeName::GranularCloud, "Size Jitter", &mut rng, |rng| rng.gen_range(0.0..0.6));
+    let envelope_distributions = vec![
+        (GrainEnvelope::Hann, 0.4),
+        (GrainEnvelope::Triangle, 0.2),
+        (GrainEnvelope::Trapezoid, 0.2),
+        (GrainEnvelope::Percussive, 0.2),
+    ];
+    let envelopes: Vec<GrainEnvelope> = envelope_distributions.iter().map(|(e, _)| *e).collect();
+    let envelope = recorded_choice(params, "Envelope", &envelopes, |e| format!("{:?}", e))
+        .unwrap_or_else(|| rng.gen_distribution(envelope_distributions));
+    let pointer = random_pointer(&mut rng, character);
+
+    // Sometimes draw the grains from another input file
+    let source_file = match recorded_file(params, "Source File") {
+        Some(file) => Some(file),
+        None if rng.gen_bool(0.3) => select_sidechain_file(&params.permutation.file, &params.permutation.files),
+        None => None,
+    };
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Character".to_string(),
+            value: format!("{:?}", character),
+        },
+        ProcessorAttribute {
+            key: "Density".to_string(),
+            value: format!("{} grains/s", format_float(density)),
+        },
+        ProcessorAttribute {
+            key: "Grain Size".to_string(),
+            value: format_float_ms(grain_ms),
+        },
+        ProcessorAttribute {
+            key: "Size Jitter".to_string(),
+            value: format_float_percent(grain_size_jitter),
+        },
+        ProcessorAttribute {
+            key: "Position Jitter".to_string(),
+            value: format_float_ms(position_jitter_ms),
+        },
+        ProcessorAttribute {
+            key: "Pitch Scatter".to_string(),
+            value: if octave_pitch {
+                "Octaves".to_string()
+            } else {
+                format!("{} semitones", format_float(pitch_scatter))
+            },
+        },
+        ProcessorAttribute {
+            key: "Envelope".to_string(),
+            value: format!("{:?}", envelope),
+        },
+        ProcessorAttribute {
+            key: "Pointer".to_string(),
+            value: format_pointer(pointer),
+        },
+        ProcessorAttribute {
+            key: "Mix".to_string(),
+            value: format_float_percent(mix),
+        },
+    ];
+    if params.channels == 2 {
+        attributes.push(ProcessorAttribute {
+            key: "Pan Scatter".to_string(),
+            value: format_float_percent(pan_scatter),
+        });
+    }
+    if let Some(file) = &source_file {
+        attributes.push(ProcessorAttribute {
+            key: "Source File".to_string(),
+            value: get_filename(file),
+        });
+    }
+
+    let cloud_params = GranularCloudParams {
+        density,
+        grain_ms,
+        grain_size_jitter,
+        position_jitter_ms,
+        pitch_scatter,
+        octave_pitch,
+        pan_scatter,
+        envelope,
+        pointer,
+        source_file,
+        mix,
+        seed: rng.gen(),
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::GranularCloud, &params);
+        let new_params = granular_cloud(&params, &cloud_params)?;
+        complete_event!(PermuteNodeName::GranularCloud, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::GranularCloud, attributes, Box::new(processor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(channels: usize) -> ProcessorPlan {
+        let mut params = ProcessorParams {
+            samples: vec![0.0; 44100 * channels],
+            sample_length: 44100 * channels,
+            channels,
+            sample_rate: 44100,
+            ..ProcessorParams::default()
+        };
+        random_granular_cloud(&mut params)
+    }
+
+    #[test]
+    fn pan_scatter_is_only_recorded_for_stereo() {
+        for _ in 0..20 {
+            assert!(plan(1).1.iter().all(|a| a.key != "Pan Scatter"));
+            assert!(plan(2).1.iter().any(|a| a.key == "Pan Scatter"));
+        }
+    }
+}
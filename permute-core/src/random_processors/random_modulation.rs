@@ -4,11 +4,18 @@ use strum::IntoEnumIterator;
 
 // Internal modules
 use crate::{
+    evolve::{parse_attribute_value, recorded_attribute, recorded_choice, recorded_file},
     process::{PermuteNodeName, ProcessorAttribute, ProcessorPlan, ProcessorParams}, 
     processors::{delay_reverb::{delay_line, DelayLineParams}, gain_distortion::{sum, SampleLine}, modulation::{
-        chorus, phaser, tremolo, tremolo_input_mod, vibrato, ChorusParams, PhaserParams, PhaserStages, TremoloInputModParams, TremoloParams, VibratoParams
+        chorus, frequency_shift, phaser, ring_modulate, tremolo, tremolo_input_mod, vibrato, ChorusParams, FrequencyShifterParams,
+        PhaserParams, PhaserStages, RingCarrier, RingModulatorParams, Sideband, TremoloInputModParams, TremoloParams, VibratoParams
     }}, 
-    random_processors::utils::{format_float, format_float_percent, format_hz, format_samples_as_ms},
+    random_processors::{
+        parameter_ranges::{highest_frequency, random_override, random_parameter},
+        random_cross::select_sidechain_file,
+        tempo::{format_sync, random_note_division, sync_to_tempo},
+        utils::{format_float, format_float_percent, format_hz, format_samples_as_ms, get_filename},
+    },
     random_process::{start_event, complete_event},
     process::PermuteNodeEvent,
     permute_files::PermuteUpdate,
@@ -19,8 +26,8 @@ use super::utils::DistributionRng;
 pub fn random_wow(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
-    let speed_hz = rng.gen_range(0.2_f64..1.6_f64);
-    let depth = rng.gen_range(0.3_f64..0.7_f64);
+    let speed_hz = random_parameter(params, PermuteNodeName::Wow, "Speed", &mut rng, |rng| rng.gen_range(0.2_f64..1.6_f64));
+    let depth = random_parameter(params, PermuteNodeName::Wow, "Depth", &mut rng, |rng| rng.gen_range(0.3_f64..0.7_f64));
 
     let attributes = vec![
         ProcessorAttribute {
@@ -59,10 +66,19 @@ pub fn random_tremolo(params: &mut ProcessorParams) -> ProcessorPlan {
         rng.gen_range(1_f64..10_f64),
         rng.gen_range(8_f64..300_f64),
     ];
-    let speed_hz = factors[rng.gen_range(0..factors.len())];
-    let depth = rng.gen_range(0.3_f64..0.99_f64);
+    // A user's speed range takes over from syncing to the tempo
+    let user_speed_hz = random_override(params, PermuteNodeName::Tremolo, "Speed", &mut rng);
+    let sync = sync_to_tempo(&mut rng, params.permutation.bpm)
+        .filter(|_| user_speed_hz.is_none())
+        .and_then(|bpm| random_note_division(&mut rng, bpm, 50.0, 2000.0).map(|division| (division, bpm)));
+    let speed_hz = match (sync, user_speed_hz) {
+        (Some((division, bpm)), _) => division.to_hz(bpm),
+        (None, Some(speed_hz)) => speed_hz,
+        (None, None) => factors[rng.gen_range(0..factors.len())],
+    };
+    let depth = random_parameter(params, PermuteNodeName::Tremolo, "Depth", &mut rng, |rng| rng.gen_range(0.3_f64..0.99_f64));
 
-    let attributes = vec![
+    let mut attributes = vec![
         ProcessorAttribute {
             key: "Speed".to_string(),
             value: format_hz(speed_hz),
@@ -72,6 +88,12 @@ pub fn random_tremolo(params: &mut ProcessorParams) -> ProcessorPlan {
             value: format_float_percent(depth),
         },
     ];
+    if let Some((division, bpm)) = sync {
+        attributes.push(ProcessorAttribute {
+            key: "Sync".to_string(),
+            value: format_sync(division, bpm),
+        });
+    }
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::Tremolo, &params);
@@ -119,9 +141,11 @@ pub fn random_lazer(params: &mut ProcessorParams) -> ProcessorPlan {
         ),
     ];
     let hz = hz_options[rng.gen_range(0..hz_options.len())];
-    let min_speed_hz = hz.0;
-    let max_speed_hz = hz.1;
-    let depth = rng.gen_range(0.5_f64..0.99_f64);
+    let min_speed_hz = random_parameter(params, PermuteNodeName::Lazer, "Min Speed", &mut rng, |_| hz.0);
+    let max_speed_hz = random_parameter(params, PermuteNodeName::Lazer, "Max Speed", &mut rng, |_| hz.1);
+    // Ranges for both ends can overlap
+    let (min_speed_hz, max_speed_hz) = (min_speed_hz.min(max_speed_hz), min_speed_hz.max(max_speed_hz));
+    let depth = random_parameter(params, PermuteNodeName::Lazer, "Depth", &mut rng, |rng| rng.gen_range(0.5_f64..0.99_f64));
     let frame_ms = 10;
 
     let attributes = vec![
@@ -164,8 +188,10 @@ pub fn random_lazer(params: &mut ProcessorParams) -> ProcessorPlan {
 pub fn random_flutter(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
-    let depth = rng.gen_range(0.1_f64..0.27_f64).powf(2.0); // try and push values towards lower values
-    let speed_hz = rng.gen_range(5_f64..20_f64);
+    let depth = random_parameter(params, PermuteNodeName::Flutter, "Depth", &mut rng, |rng| {
+        rng.gen_range(0.1_f64..0.27_f64).powf(2.0) // try and push values towards lower values
+    });
+    let speed_hz = random_parameter(params, PermuteNodeName::Flutter, "Speed", &mut rng, |rng| rng.gen_range(5_f64..20_f64));
 
     let attributes = vec![
         ProcessorAttribute {
@@ -197,12 +223,11 @@ pub fn random_flutter(params: &mut ProcessorParams) -> ProcessorPlan {
 pub fn random_chorus(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
-    let millis_low = (params.sample_rate as f64 / 1000_f64 * 7_f64) as usize;
-    let millis_high = (params.sample_rate as f64 / 1000_f64 * 20_f64) as usize;
-    let feedback_factor = rng.gen_range(0_f64..0.6_f64);
-    let delay_sample_length = rng.gen_range(millis_low..millis_high);
-    let speed_hz = rng.gen_range(0.1_f64..2.0_f64);
-    let depth = rng.gen_range(0.1_f64..0.3_f64);
+    let feedback_factor = random_parameter(params, PermuteNodeName::Chorus, "Feedback", &mut rng, |rng| rng.gen_range(0_f64..0.6_f64));
+    let delay_ms = random_parameter(params, PermuteNodeName::Chorus, "Delay", &mut rng, |rng| rng.gen_range(7_f64..20_f64));
+    let delay_sample_length = (params.sample_rate as f64 / 1000_f64 * delay_ms) as usize;
+    let speed_hz = random_parameter(params, PermuteNodeName::Chorus, "Speed", &mut rng, |rng| rng.gen_range(0.1_f64..2.0_f64));
+    let depth = random_parameter(params, PermuteNodeName::Chorus, "Depth", &mut rng, |rng| rng.gen_range(0.1_f64..0.3_f64));
     let wet_gain_distributions = vec![
         (0.1, 0.1),
         (0.2, 0.1),
@@ -215,7 +240,9 @@ pub fn random_chorus(params: &mut ProcessorParams) -> ProcessorPlan {
         (0.9, 0.1),
         (1.0, 0.1),
     ];
-    let wet_gain = rng.gen_distribution(wet_gain_distributions);
+    let wet_gain = random_parameter(params, PermuteNodeName::Chorus, "Wet", &mut rng, |rng| {
+        rng.gen_distribution(wet_gain_distributions)
+    });
 
     let attributes = vec![
         ProcessorAttribute {
@@ -266,11 +293,12 @@ pub fn random_chorus(params: &mut ProcessorParams) -> ProcessorPlan {
 
 pub fn random_phaser(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
-    let stages = PhaserStages::iter().choose(&mut rng).unwrap();
-    let base_freq = rng.gen_range(300.0..700.0);
-    let lfo_rate = rng.gen_range(0.2..2.0);
-    let q = rng.gen_range(0.15..0.5);
-    let lfo_depth = rng.gen_range(0.5..0.95);
+    let stages = recorded_choice(params, "Stages", &PhaserStages::iter().collect::<Vec<_>>(), |s| format!("{:?}", s))
+        .unwrap_or_else(|| PhaserStages::iter().choose(&mut rng).unwrap());
+    let base_freq = random_parameter(params, PermuteNodeName::Phaser, "Base Frequency", &mut rng, |rng| rng.gen_range(300.0..700.0));
+    let lfo_rate = random_parameter(params, PermuteNodeName::Phaser, "LFO Rate", &mut rng, |rng| rng.gen_range(0.2..2.0));
+    let q = random_parameter(params, PermuteNodeName::Phaser, "Q", &mut rng, |rng| rng.gen_range(0.15..0.5));
+    let lfo_depth = random_parameter(params, PermuteNodeName::Phaser, "LFO Depth", &mut rng, |rng| rng.gen_range(0.5..0.95));
     let wet_gain_distributions = vec![
         (0.1, 0.1),
         (0.2, 0.1),
@@ -283,7 +311,9 @@ pub fn random_phaser(params: &mut ProcessorParams) -> ProcessorPlan {
         (0.9, 0.1),
         (1.0, 0.1),
     ];
-    let wet_gain = rng.gen_distribution(wet_gain_distributions);
+    let wet_gain = random_parameter(params, PermuteNodeName::Phaser, "Wet", &mut rng, |rng| {
+        rng.gen_distribution(wet_gain_distributions)
+    });
     let phaser_params = PhaserParams {
         base_freq,
         lfo_rate,
@@ -335,10 +365,11 @@ pub fn random_phaser(params: &mut ProcessorParams) -> ProcessorPlan {
 pub fn random_zero_flange(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
 
-    let speed_hz = rng.gen_range(0.01_f64..1.1_f64);
-    let depth = rng.gen_range(0.05_f64..0.2_f64);
-    let delay_sample_length = params.sample_rate as f64 / 1000_f64 * rng.gen_range(1_f64..15_f64);
-    let wet = rng.gen_range(-0.9_f64..-0.4_f64);
+    let speed_hz = random_parameter(params, PermuteNodeName::Flange, "Speed", &mut rng, |rng| rng.gen_range(0.01_f64..1.1_f64));
+    let depth = random_parameter(params, PermuteNodeName::Flange, "Depth", &mut rng, |rng| rng.gen_range(0.05_f64..0.2_f64));
+    let delay_ms = random_parameter(params, PermuteNodeName::Flange, "Delay", &mut rng, |rng| rng.gen_range(1_f64..15_f64));
+    let delay_sample_length = params.sample_rate as f64 / 1000_f64 * delay_ms;
+    let wet = random_parameter(params, PermuteNodeName::Flange, "Wet", &mut rng, |rng| rng.gen_range(-0.9_f64..-0.4_f64));
 
     let attributes = vec![
         ProcessorAttribute {
@@ -399,4 +430,136 @@ pub fn random_zero_flange(params: &mut ProcessorParams) -> ProcessorPlan {
     };
 
     (PermuteNodeName::Flange, attributes, Box::new(processor))
-}
\ No newline at end of file
+}
+
+pub fn random_frequency_shifter(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    // Small shifts give a slow beating/barber pole feel, larger ones get bell-like and inharmonic
+    let shift_hz = random_parameter(params, PermuteNodeName::FrequencyShifter, "Shift", &mut rng, |rng| {
+        let shift_distributions = vec![
+            (rng.gen_range(0.5_f64..8_f64), 0.3),
+            (rng.gen_range(20_f64..200_f64), 0.45),
+            (rng.gen_range(200_f64..1200_f64), 0.25),
+        ];
+        rng.gen_distribution(shift_distributions)
+    });
+    let sideband_distributions = vec![
+        (Sideband::Up, 0.4),
+        (Sideband::Down, 0.4),
+        (Sideband::Both, 0.2),
+    ];
+    let sideband = recorded_choice(params, "Sideband", &[Sideband::Up, Sideband::Down, Sideband::Both], |s| format!("{:?}", s))
+        .unwrap_or_else(|| rng.gen_distribution(sideband_distributions));
+    let feedback = random_parameter(params, PermuteNodeName::FrequencyShifter, "Feedback", &mut rng, |rng| {
+        if rng.gen_bool(0.4) { rng.gen_range(0.2_f64..0.75_f64) } else { 0.0 }
+    });
+    let mix = random_parameter(params, PermuteNodeName::FrequencyShifter, "Mix", &mut rng, |rng| rng.gen_range(0.5_f64..1_f64));
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Shift".to_string(),
+            value: format_hz(shift_hz),
+        },
+        ProcessorAttribute {
+            key: "Sideband".to_string(),
+            value: format!("{:?}", sideband),
+        },
+        ProcessorAttribute {
+            key: "Feedback".to_string(),
+            value: format_float_percent(feedback),
+        },
+        ProcessorAttribute {
+            key: "Mix".to_string(),
+            value: format_float_percent(mix),
+        },
+    ];
+
+    let shift_params = FrequencyShifterParams {
+        shift_hz,
+        sideband,
+        feedback,
+        mix,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::FrequencyShifter, &params);
+        let new_params = frequency_shift(&params, &shift_params)?;
+        complete_event!(PermuteNodeName::FrequencyShifter, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::FrequencyShifter, attributes, Box::new(processor))
+}
+
+pub fn random_ring_modulator(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let sidechain_file = match recorded_file(params, "Carrier File") {
+        Some(file) => Some(file),
+        None if rng.gen_bool(0.25) => select_sidechain_file(&params.permutation.file, &params.permutation.files),
+        None => None,
+    };
+    let carrier = match sidechain_file {
+        Some(file) => {
+            let channels = sndfile::OpenOptions::ReadOnly(sndfile::ReadOptions::Auto)
+                .from_path(&file)
+                .map(|snd| snd.get_channels())
+                .unwrap_or(params.channels);
+            RingCarrier::Sidechain { file, channels }
+        }
+        None => {
+            let start_hz = random_parameter(params, PermuteNodeName::RingModulator, "Carrier", &mut rng, |rng| {
+                let frequency_distributions = vec![
+                    (rng.gen_range(20_f64..100_f64), 0.3),
+                    (rng.gen_range(100_f64..800_f64), 0.45),
+                    (rng.gen_range(800_f64..3000_f64), 0.25),
+                ];
+                rng.gen_distribution(frequency_distributions)
+            });
+            let recorded_end_hz = recorded_attribute(params, "Carrier End").and_then(|end| parse_attribute_value(&end));
+            let end_hz = match recorded_end_hz {
+                Some(end_hz) => end_hz.clamp(1.0, highest_frequency(params.sample_rate)),
+                None if rng.gen_bool(0.3) => start_hz * rng.gen_range(0.25_f64..4_f64),
+                None => start_hz,
+            };
+            RingCarrier::Oscillator { start_hz, end_hz }
+        }
+    };
+    let mix = random_parameter(params, PermuteNodeName::RingModulator, "Mix", &mut rng, |rng| rng.gen_range(0.5_f64..1_f64));
+
+    let mut attributes = match &carrier {
+        RingCarrier::Oscillator { start_hz, end_hz } => {
+            let mut attributes = vec![ProcessorAttribute {
+                key: "Carrier".to_string(),
+                value: format_hz(*start_hz),
+            }];
+            if start_hz != end_hz {
+                attributes.push(ProcessorAttribute {
+                    key: "Carrier End".to_string(),
+                    value: format_hz(*end_hz),
+                });
+            }
+            attributes
+        }
+        RingCarrier::Sidechain { file, .. } => vec![ProcessorAttribute {
+            key: "Carrier File".to_string(),
+            value: get_filename(file),
+        }],
+    };
+    attributes.push(ProcessorAttribute {
+        key: "Mix".to_string(),
+        value: format_float_percent(mix),
+    });
+
+    let ring_params = RingModulatorParams { carrier, mix };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::RingModulator, &params);
+        let new_params = ring_modulate(&params, &ring_params)?;
+        complete_event!(PermuteNodeName::RingModulator, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::RingModulator, attributes, Box::new(processor))
+}
@@ -0,0 +1,145 @@
+// External dependencies
+use rand::{thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    permute_files::PermuteUpdate,
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorClosure, ProcessorParams, ProcessorPlan},
+    evolve::{parse_attribute_value, recorded_attribute, EvolvedChain, EvolvedNode},
+    processors::multiband::multiband,
+    random_process::{complete_event, pick_processor, plan_sub_chain, start_event},
+    random_processors::utils::{format_chain, format_hz, DistributionRng},
+};
+
+// Processors that keep the length and channel count of the audio, so their bands still line up when summed
+const BAND_PROCESSORS: [PermuteNodeName; 23] = [
+    PermuteNodeName::Fuzz,
+    PermuteNodeName::Saturate,
+    PermuteNodeName::Chorus,
+    PermuteNodeName::Phaser,
+    PermuteNodeName::Flutter,
+    PermuteNodeName::Flange,
+    PermuteNodeName::Wow,
+    PermuteNodeName::Tremolo,
+    PermuteNodeName::Lazer,
+    PermuteNodeName::MetallicDelay,
+    PermuteNodeName::RhythmicDelay,
+    PermuteNodeName::OscillatingFilter,
+    PermuteNodeName::Compressor,
+    PermuteNodeName::Limiter,
+    PermuteNodeName::Expander,
+    PermuteNodeName::TransientShaper,
+    PermuteNodeName::FrequencyShifter,
+    PermuteNodeName::RingModulator,
+    PermuteNodeName::AutoWah,
+    PermuteNodeName::EnvelopePitch,
+    PermuteNodeName::EnvelopeDelay,
+    PermuteNodeName::EnvelopeDrive,
+    PermuteNodeName::Reverse,
+];
+
+// Log spaced crossovers, each picked from its own range so bands don't collapse into each other
+fn random_crossovers(bands: usize, rng: &mut impl Rng) -> Vec<f64> {
+    let ranges: Vec<(f64, f64)> = match bands {
+        2 => vec![(150.0, 3000.0)],
+        3 => vec![(100.0, 500.0), (1500.0, 6000.0)],
+        _ => vec![(80.0, 300.0), (600.0, 1500.0), (3000.0, 8000.0)],
+    };
+    ranges
+        .iter()
+        .map(|(low, high): &(f64, f64)| {
+            let log_frequency = rng.gen_range(low.ln()..high.ln());
+            (log_frequency.exp() / 10.0).round() * 10.0
+        })
+        .collect()
+}
+
+// Crossovers recorded as e.g. "150.00 hz, 3000.00 hz", if there's one for each split between the bands
+fn recorded_crossovers(params: &ProcessorParams, bands: usize) -> Option<Vec<f64>> {
+    let crossovers = recorded_attribute(params, "Crossovers")?
+        .split(',')
+        .map(parse_attribute_value)
+        .collect::<Option<Vec<f64>>>()?;
+    let ascending = crossovers.windows(2).all(|w| w[0] < w[1]);
+    (crossovers.len() + 1 == bands && ascending).then_some(crossovers)
+}
+
+fn format_band_range(band: usize, crossovers: &[f64]) -> String {
+    match (band.checked_sub(1).map(|i| crossovers[i]), crossovers.get(band)) {
+        (None, Some(high)) => format!("below {}", format_hz(*high)),
+        (Some(low), None) => format!("above {}", format_hz(low)),
+        (Some(low), Some(high)) => format!("{} - {}", format_hz(low), format_hz(*high)),
+        (None, None) => "full range".to_string(),
+    }
+}
+
+pub fn random_multiband(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    // Stick to the processors the user picked where possible
+    let user_pool: Vec<PermuteNodeName> = BAND_PROCESSORS
+        .iter()
+        .copied()
+        .filter(|p| params.permutation.processor_pool.contains(p))
+        .collect();
+    let pool = if user_pool.is_empty() { BAND_PROCESSORS.to_vec() } else { user_pool };
+
+    // A bred node keeps its parent's bands
+    let recorded_bands: Vec<EvolvedChain> = params
+        .permutation
+        .evolved_node
+        .as_ref()
+        .map_or(vec![], |n| n.branches.clone());
+    let band_count = if (2..=4).contains(&recorded_bands.len()) {
+        recorded_bands.len()
+    } else {
+        let band_distributions = vec![(2, 0.45), (3, 0.4), (4, 0.15)];
+        rng.gen_distribution(band_distributions)
+    };
+    let crossovers =
+        recorded_crossovers(params, band_count).unwrap_or_else(|| random_crossovers(band_count, &mut rng));
+
+    let band_nodes: Vec<EvolvedChain> = if recorded_bands.len() == band_count {
+        recorded_bands
+    } else {
+        // Leaving a band dry, e.g. keeping the lows clean under a mangled top end, is half the point
+        let dry_band = if rng.gen_bool(0.4) { Some(rng.gen_range(0..band_count)) } else { None };
+        (0..band_count)
+            .map(|band| {
+                let chain_length = if dry_band == Some(band) { 0 } else { rng.gen_range(1..=2) };
+                (0..chain_length)
+                    .filter_map(|_| pick_processor(&pool, &params.permutation.processor_weights, &mut rng))
+                    .map(EvolvedNode::new)
+                    .collect()
+            })
+            .collect()
+    };
+
+    let mut attributes = vec![ProcessorAttribute {
+        key: "Crossovers".to_string(),
+        value: crossovers.iter().map(|c| format_hz(*c)).collect::<Vec<String>>().join(", "),
+    }];
+    let mut band_chains: Vec<Vec<ProcessorClosure>> = vec![];
+    let mut branches = vec![];
+    for (band, nodes) in band_nodes.iter().enumerate() {
+        let (chain_closures, chain) = plan_sub_chain(params, nodes);
+        attributes.push(ProcessorAttribute {
+            key: format!("Band {} ({})", band + 1, format_band_range(band, &crossovers)),
+            value: format_chain(&chain),
+        });
+        band_chains.push(chain_closures);
+        branches.push(chain);
+    }
+    if let Some(processor) = params.permutation.processors.get_mut(params.permutation.node_index) {
+        processor.branches = branches;
+    }
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Multiband, &params);
+        let new_params = multiband(&params, &crossovers, band_chains)?;
+        complete_event!(PermuteNodeName::Multiband, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Multiband, attributes, Box::new(processor))
+}
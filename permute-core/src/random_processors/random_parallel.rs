@@ -0,0 +1,110 @@
+// External dependencies
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::collections::HashMap;
+
+// Internal modules
+use crate::{
+    permute_files::PermuteUpdate,
+    process::{
+        PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan, ALL_PROCESSORS,
+    },
+    processors::parallel::{dry_wet, parallel},
+    evolve::{EvolvedChain, EvolvedNode},
+    random_process::{complete_event, pick_processor, plan_sub_chain, start_event},
+    random_processors::{
+        parameter_ranges::random_parameter,
+        utils::{format_chain, format_float_percent},
+    },
+};
+
+// Containers aren't nested inside each other, the branches would only ever record the innermost one.
+// An extreme stretch in one branch would leave the other as a blip at the start
+fn branch_pool(params: &ProcessorParams) -> Vec<PermuteNodeName> {
+    let usable = |p: &PermuteNodeName| {
+        !matches!(p, PermuteNodeName::Parallel | PermuteNodeName::Multiband | PermuteNodeName::ExtremeStretch)
+    };
+    let user_pool: Vec<PermuteNodeName> = params.permutation.processor_pool.iter().copied().filter(usable).collect();
+    if user_pool.is_empty() {
+        ALL_PROCESSORS.iter().copied().filter(usable).collect()
+    } else {
+        user_pool
+    }
+}
+
+fn random_branch(
+    pool: &[PermuteNodeName],
+    weights: &HashMap<PermuteNodeName, f64>,
+    length: usize,
+    rng: &mut ThreadRng,
+) -> EvolvedChain {
+    (0..length).filter_map(|_| pick_processor(pool, weights, rng)).map(EvolvedNode::new).collect()
+}
+
+pub fn random_parallel(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    // A bred node keeps its parent's branches
+    let (nodes_a, nodes_b) = match params.permutation.evolved_node.as_ref().map(|n| n.branches.as_slice()) {
+        Some([a, b]) => (a.clone(), b.clone()),
+        _ => {
+            let pool = branch_pool(params);
+            let length_a = rng.gen_range(1..=2);
+            // An empty branch keeps a clean copy of the input, as in parallel compression
+            let length_b = if rng.gen_bool(0.35) { 0 } else { rng.gen_range(1..=2) };
+            (
+                random_branch(&pool, &params.permutation.processor_weights, length_a, &mut rng),
+                random_branch(&pool, &params.permutation.processor_weights, length_b, &mut rng),
+            )
+        }
+    };
+    let (chain_a, branch_a) = plan_sub_chain(params, &nodes_a);
+    let (chain_b, branch_b) = plan_sub_chain(params, &nodes_b);
+
+    let balance = random_parameter(params, PermuteNodeName::Parallel, "Branch A Gain", &mut rng, |rng| rng.gen_range(0.25..0.75));
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Branch A".to_string(),
+            value: format_chain(&branch_a),
+        },
+        ProcessorAttribute {
+            key: "Branch A Gain".to_string(),
+            value: format_float_percent(balance),
+        },
+        ProcessorAttribute {
+            key: "Branch B".to_string(),
+            value: format_chain(&branch_b),
+        },
+        ProcessorAttribute {
+            key: "Branch B Gain".to_string(),
+            value: format_float_percent(1.0 - balance),
+        },
+    ];
+    if let Some(processor) = params.permutation.processors.get_mut(params.permutation.node_index) {
+        processor.branches = vec![branch_a, branch_b];
+    }
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Parallel, &params);
+        let new_params = parallel(&params, vec![(chain_a, balance), (chain_b, 1.0 - balance)])?;
+        complete_event!(PermuteNodeName::Parallel, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Parallel, attributes, Box::new(processor))
+}
+
+// Blends any processor's output with the unprocessed input
+pub fn with_dry_wet((name, mut attributes, closure): ProcessorPlan, mix: f64) -> ProcessorPlan {
+    attributes.push(ProcessorAttribute {
+        key: "Dry/Wet".to_string(),
+        value: format_float_percent(mix),
+    });
+    let processor = move |params: ProcessorParams| dry_wet(&params, closure, mix);
+
+    (name, attributes, Box::new(processor))
+}
+
+pub fn random_dry_wet(plan: ProcessorPlan) -> ProcessorPlan {
+    let mix = thread_rng().gen_range(0.3..0.9);
+    with_dry_wet(plan, mix)
+}
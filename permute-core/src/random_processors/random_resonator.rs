@@ -0,0 +1,175 @@
+// External dependencies
+use rand::{seq::SliceRandom, thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    evolve::recorded_choice,
+    permute_files::PermuteUpdate,
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan},
+    processors::resonator::{midi_to_hz, resonator_bank, resonator_tail_ms, ResonatorMode, ResonatorParams},
+    random_process::{complete_event, start_event},
+    random_processors::{
+        parameter_ranges::random_parameter,
+        utils::{format_float_ms, format_float_percent, format_midi_note, DistributionRng},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Harmony {
+    Major,
+    Minor,
+    Sus4,
+    Major7,
+    Minor7,
+    Fifths,
+    Octaves,
+    MajorPentatonic,
+    MinorPentatonic,
+    WholeTone,
+}
+
+impl Harmony {
+    fn intervals(&self) -> Vec<usize> {
+        match self {
+            Harmony::Major => vec![0, 4, 7],
+            Harmony::Minor => vec![0, 3, 7],
+            Harmony::Sus4 => vec![0, 5, 7],
+            Harmony::Major7 => vec![0, 4, 7, 11],
+            Harmony::Minor7 => vec![0, 3, 7, 10],
+            Harmony::Fifths => vec![0, 7, 14, 21],
+            Harmony::Octaves => vec![0, 12, 24],
+            Harmony::MajorPentatonic => vec![0, 2, 4, 7, 9],
+            Harmony::MinorPentatonic => vec![0, 3, 5, 7, 10],
+            Harmony::WholeTone => vec![0, 2, 4, 6, 8, 10],
+        }
+    }
+
+    fn is_scale(&self) -> bool {
+        matches!(
+            self,
+            Harmony::MajorPentatonic | Harmony::MinorPentatonic | Harmony::WholeTone
+        )
+    }
+}
+
+fn format_harmony(root: usize, harmony: Harmony) -> String {
+    format!("{} {:?}", format_midi_note(root), harmony)
+}
+
+pub fn random_resonator(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let mode = recorded_choice(params, "Mode", &[ResonatorMode::Comb, ResonatorMode::Bandpass], |m| format!("{:?}", m))
+        .unwrap_or_else(|| if rng.gen_bool(0.5) { ResonatorMode::Comb } else { ResonatorMode::Bandpass });
+    // Combs have harmonics above the root so can sit lower
+    let roots = match mode {
+        ResonatorMode::Comb => 36..=60,
+        ResonatorMode::Bandpass => 48..=72,
+    };
+    let harmony_distributions = vec![
+        (Harmony::Major, 0.12),
+        (Harmony::Minor, 0.12),
+        (Harmony::Sus4, 0.08),
+        (Harmony::Major7, 0.1),
+        (Harmony::Minor7, 0.1),
+        (Harmony::Fifths, 0.1),
+        (Harmony::Octaves, 0.08),
+        (Harmony::MajorPentatonic, 0.1),
+        (Harmony::MinorPentatonic, 0.1),
+        (Harmony::WholeTone, 0.1),
+    ];
+    // A bred node keeps its parent's chord, recorded with its root, e.g. "C3 Minor7"
+    let chords: Vec<(usize, Harmony)> = roots
+        .clone()
+        .flat_map(|root| harmony_distributions.iter().map(move |(harmony, _)| (root, *harmony)))
+        .collect();
+    let (root, harmony) = recorded_choice(params, "Harmony", &chords, |(root, harmony)| format_harmony(*root, *harmony))
+        .unwrap_or_else(|| (rng.gen_range(roots), rng.gen_distribution(harmony_distributions)));
+
+    let mut notes: Vec<usize> = if harmony.is_scale() {
+        // A handful of notes from the scale over two octaves
+        let scale: Vec<usize> = harmony
+            .intervals()
+            .iter()
+            .flat_map(|i| [root + i, root + i + 12])
+            .collect();
+        let count = rng.gen_range(3..=6);
+        let mut notes: Vec<usize> = scale.choose_multiple(&mut rng, count).copied().collect();
+        if !notes.contains(&root) {
+            notes[0] = root;
+        }
+        notes
+    } else {
+        harmony.intervals().iter().map(|i| root + i).collect()
+    };
+    notes.sort_unstable();
+    notes.dedup();
+
+    let decay_ms = random_parameter(params, PermuteNodeName::Resonator, "Decay", &mut rng, |rng| {
+        let decay_distributions = vec![
+            (rng.gen_range(100.0..400.0), 0.3),
+            (rng.gen_range(400.0..1500.0), 0.45),
+            (rng.gen_range(1500.0..4000.0), 0.25),
+        ];
+        rng.gen_distribution(decay_distributions)
+    });
+    let damping = match mode {
+        ResonatorMode::Comb => random_parameter(params, PermuteNodeName::Resonator, "Damping", &mut rng, |rng| rng.gen_range(0.0..0.6)),
+        ResonatorMode::Bandpass => 0.0,
+    };
+    let mix = random_parameter(params, PermuteNodeName::Resonator, "Mix", &mut rng, |rng| rng.gen_range(0.4..1.0));
+
+    let mut attributes = vec![
+        ProcessorAttribute {
+            key: "Mode".to_string(),
+            value: format!("{:?}", mode),
+        },
+        ProcessorAttribute {
+            key: "Harmony".to_string(),
+            value: format_harmony(root, harmony),
+        },
+        ProcessorAttribute {
+            key: "Notes".to_string(),
+            value: notes
+                .iter()
+                .map(|n| format_midi_note(*n))
+                .collect::<Vec<String>>()
+                .join(" "),
+        },
+        ProcessorAttribute {
+            key: "Decay".to_string(),
+            value: format_float_ms(decay_ms),
+        },
+    ];
+    if mode == ResonatorMode::Comb {
+        attributes.push(ProcessorAttribute {
+            key: "Damping".to_string(),
+            value: format_float_percent(damping),
+        });
+    }
+    attributes.push(ProcessorAttribute {
+        key: "Mix".to_string(),
+        value: format_float_percent(mix),
+    });
+    attributes.push(ProcessorAttribute {
+        key: "Tail".to_string(),
+        value: format_float_ms(resonator_tail_ms(decay_ms)),
+    });
+
+    let resonator_params = ResonatorParams {
+        mode,
+        frequencies: notes.iter().map(|n| midi_to_hz(*n as f64)).collect(),
+        decay_ms,
+        damping,
+        mix,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Resonator, &params);
+        let new_params = resonator_bank(&params, &resonator_params)?;
+        complete_event!(PermuteNodeName::Resonator, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Resonator, attributes, Box::new(processor))
+}
@@ -0,0 +1,209 @@
+// External dependencies
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+
+// Internal modules
+use crate::{
+    evolve::recorded_choice,
+    permute_files::PermuteUpdate,
+    process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorParams, ProcessorPlan},
+    processors::stereo::{
+        auto_pan, decorrelate, haas, stereo_width, AutoPanParams, DecorrelateParams, HaasParams, HaasSide,
+        PanWaveform, StereoWidthParams,
+    },
+    random_process::{complete_event, start_event},
+    random_processors::{
+        parameter_ranges::random_parameter,
+        utils::{format_float, format_float_ms, format_float_percent, format_hz, DistributionRng},
+    },
+};
+
+fn random_all_pass_delays(rng: &mut ThreadRng) -> Vec<f64> {
+    let stages = rng.gen_range(2..5);
+    (0..stages).map(|_| rng.gen_range(0.3..8.0)).collect()
+}
+
+fn random_decorrelate_params(
+    params: &ProcessorParams,
+    processor: PermuteNodeName,
+    rng: &mut ThreadRng,
+    amount: f64,
+) -> DecorrelateParams {
+    DecorrelateParams {
+        left_delays_ms: random_all_pass_delays(rng),
+        right_delays_ms: random_all_pass_delays(rng),
+        gain: random_parameter(params, processor, "Diffusion", rng, |rng| rng.gen_range(0.3..0.7)),
+        amount,
+    }
+}
+
+fn format_delays(delays_ms: &[f64]) -> String {
+    delays_ms
+        .iter()
+        .map(|d| format_float_ms(*d))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn random_decorrelate(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let amount = random_parameter(params, PermuteNodeName::Decorrelate, "Amount", &mut rng, |rng| rng.gen_range(0.3..1.0));
+    let decorrelate_params = random_decorrelate_params(params, PermuteNodeName::Decorrelate, &mut rng, amount);
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Amount".to_string(),
+            value: format_float_percent(amount),
+        },
+        ProcessorAttribute {
+            key: "Diffusion".to_string(),
+            value: format_float(decorrelate_params.gain),
+        },
+        ProcessorAttribute {
+            key: "Left Delays".to_string(),
+            value: format_delays(&decorrelate_params.left_delays_ms),
+        },
+        ProcessorAttribute {
+            key: "Right Delays".to_string(),
+            value: format_delays(&decorrelate_params.right_delays_ms),
+        },
+    ];
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Decorrelate, &params);
+        let new_params = decorrelate(&params, &decorrelate_params)?;
+        complete_event!(PermuteNodeName::Decorrelate, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Decorrelate, attributes, Box::new(processor))
+}
+
+pub fn random_auto_pan(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    let rate_hz = random_parameter(params, PermuteNodeName::AutoPan, "Rate", &mut rng, |rng| {
+        let rate_distributions = vec![
+            (rng.gen_range(0.05..0.5), 0.4),
+            (rng.gen_range(0.5..4.0), 0.4),
+            (rng.gen_range(4.0..12.0), 0.2),
+        ];
+        rng.gen_distribution(rate_distributions)
+    });
+    let depth = random_parameter(params, PermuteNodeName::AutoPan, "Depth", &mut rng, |rng| rng.gen_range(0.3..1.0));
+    let waveform = recorded_choice(params, "Waveform", &[PanWaveform::Sine, PanWaveform::Triangle], |w| format!("{:?}", w))
+        .unwrap_or_else(|| if rng.gen_bool(0.6) { PanWaveform::Sine } else { PanWaveform::Triangle });
+    let phase = rng.gen_range(0.0..std::f64::consts::TAU);
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Rate".to_string(),
+            value: format_hz(rate_hz),
+        },
+        ProcessorAttribute {
+            key: "Depth".to_string(),
+            value: format_float_percent(depth),
+        },
+        ProcessorAttribute {
+            key: "Waveform".to_string(),
+            value: format!("{:?}", waveform),
+        },
+    ];
+
+    let pan_params = AutoPanParams {
+        rate_hz,
+        depth,
+        waveform,
+        phase,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::AutoPan, &params);
+        let new_params = auto_pan(&params, &pan_params)?;
+        complete_event!(PermuteNodeName::AutoPan, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::AutoPan, attributes, Box::new(processor))
+}
+
+pub fn random_stereo_width(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    // Mostly widen, sometimes collapse towards mono
+    let width = random_parameter(params, PermuteNodeName::StereoWidth, "Width", &mut rng, |rng| {
+        let width_distributions = vec![
+            (rng.gen_range(1.3..2.5), 0.75),
+            (rng.gen_range(0.0..0.7), 0.25),
+        ];
+        rng.gen_distribution(width_distributions)
+    });
+    let decorrelate = if params.channels == 1 {
+        let amount = rng.gen_range(0.4..0.8);
+        Some(random_decorrelate_params(params, PermuteNodeName::StereoWidth, &mut rng, amount))
+    } else {
+        None
+    };
+
+    let mut attributes = vec![ProcessorAttribute {
+        key: "Width".to_string(),
+        value: format_float_percent(width),
+    }];
+    if let Some(decorrelate_params) = &decorrelate {
+        attributes.push(ProcessorAttribute {
+            key: "Decorrelation".to_string(),
+            value: format_float_percent(decorrelate_params.amount),
+        });
+    }
+
+    let width_params = StereoWidthParams { width, decorrelate };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::StereoWidth, &params);
+        let new_params = stereo_width(&params, &width_params)?;
+        complete_event!(PermuteNodeName::StereoWidth, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::StereoWidth, attributes, Box::new(processor))
+}
+
+pub fn random_haas(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+
+    // Past ~35ms the ear starts hearing a separate echo
+    let delay_ms = random_parameter(params, PermuteNodeName::Haas, "Delay", &mut rng, |rng| rng.gen_range(1.0..35.0));
+    let delayed_side = recorded_choice(params, "Delayed Side", &[HaasSide::Left, HaasSide::Right], |s| format!("{:?}", s))
+        .unwrap_or_else(|| if rng.gen_bool(0.5) { HaasSide::Left } else { HaasSide::Right });
+    let delayed_gain = random_parameter(params, PermuteNodeName::Haas, "Delayed Gain", &mut rng, |rng| rng.gen_range(0.7..1.0));
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Delay".to_string(),
+            value: format_float_ms(delay_ms),
+        },
+        ProcessorAttribute {
+            key: "Delayed Side".to_string(),
+            value: format!("{:?}", delayed_side),
+        },
+        ProcessorAttribute {
+            key: "Delayed Gain".to_string(),
+            value: format_float(delayed_gain),
+        },
+    ];
+
+    let haas_params = HaasParams {
+        delay_ms,
+        delayed_side,
+        delayed_gain,
+    };
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(PermuteNodeName::Haas, &params);
+        let new_params = haas(&params, &haas_params)?;
+        complete_event!(PermuteNodeName::Haas, new_params);
+        Ok(new_params)
+    };
+
+    (PermuteNodeName::Haas, attributes, Box::new(processor))
+}
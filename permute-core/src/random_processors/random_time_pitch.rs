@@ -3,20 +3,25 @@ use rand::{thread_rng, Rng};
 
 // Internal modules
 use crate::{
+    evolve::recorded_choice,
     permute_files::PermuteUpdate, 
     process::{PermuteNodeEvent, PermuteNodeName, ProcessorAttribute, ProcessorPlan, ProcessorClosure, ProcessorParams}, 
     processors::time_pitch::{
-        change_sample_rate, change_speed, reverse, stft_time_stretch, time_stretch_cross, StftTimeStretchParams, TimeStretchParams, WindowType
+        change_sample_rate, change_speed, change_speed_curve, reverse, speed_curve_length_factor, stft_time_stretch,
+        time_stretch_cross, RampShape, SpeedCurve, StftTimeStretchParams, TimeStretchParams, WindowType
     }, 
-    random_process::{complete_event, start_event}, random_processors::utils::{format_factor_to_pitch, DistributionRng}
+    random_process::{complete_event, start_event},
+    random_processors::{parameter_ranges::random_parameter, utils::{format_factor_to_pitch, DistributionRng}},
 };
 
-use super::utils::{format_float_ms, format_hz_usize};
+use super::utils::{format_float, format_float_ms, format_float_percent, format_hz_usize};
 
-pub fn random_pitch(_params: &mut ProcessorParams) -> ProcessorPlan {
+pub fn random_pitch(params: &mut ProcessorParams) -> ProcessorPlan {
     let mut rng = thread_rng();
+    let quality = params.permutation.resample_quality;
     let speeds: [f64; 10] = [-10.0, -8.0, -7.0, -5.0, -2.0, 2.0, 5.0, 7.0, 8.0, 10.0].map(|v| 2_f64.powf(v / 12.0));
-    let speed = speeds[rng.gen_range(0..speeds.len())];
+    let speed = recorded_choice(params, "Pitch", &speeds, |s| format_factor_to_pitch(*s))
+        .unwrap_or_else(|| speeds[rng.gen_range(0..speeds.len())]);
 
     let attributes = vec![
         ProcessorAttribute {
@@ -31,7 +36,7 @@ pub fn random_pitch(_params: &mut ProcessorParams) -> ProcessorPlan {
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::RandomPitch, &params);
-        let new_params: ProcessorParams = change_speed(params, speed);
+        let new_params: ProcessorParams = change_speed(params, speed, quality);
         complete_event!(PermuteNodeName::RandomPitch, new_params);
         Ok(new_params)
     };
@@ -57,8 +62,9 @@ pub fn random_granular_time_stretch(params: &mut ProcessorParams) -> ProcessorPl
         (250.0, 0.1),  // 250ms
         (500.0, 0.1),  // 500ms
     ];
-    let mut grain_ms = rng.gen_distribution(grain_distributions);
-    let grain_samples = ((grain_ms / 1000.0) * params.sample_rate as f64) as usize;
+    let mut grain_ms = random_parameter(params, PermuteNodeName::GranularTimeStretch, "Grain", &mut rng, |rng| {
+        rng.gen_distribution(grain_distributions)
+    });
     
     let stretch_distributions = vec![
         (2, 1.0),
@@ -67,7 +73,9 @@ pub fn random_granular_time_stretch(params: &mut ProcessorParams) -> ProcessorPl
         (5, 0.1),
         (6, 0.1),
     ];
-    let stretch_factor = rng.gen_distribution(stretch_distributions);
+    let stretch_factors: Vec<usize> = stretch_distributions.iter().map(|(f, _)| *f).collect();
+    let stretch_factor = recorded_choice(params, "Stretch Factor", &stretch_factors, |f| f.to_string())
+        .unwrap_or_else(|| rng.gen_distribution(stretch_distributions));
     
     let blend_distributions = vec![
         (0.5, 1.0),
@@ -91,10 +99,13 @@ pub fn random_granular_time_stretch(params: &mut ProcessorParams) -> ProcessorPl
         (90.0, 0.1),
         (100.0, 0.1),
     ];
-    let blend_ms = rng.gen_distribution(blend_distributions);
+    let blend_ms = random_parameter(params, PermuteNodeName::GranularTimeStretch, "Blend", &mut rng, |rng| {
+        rng.gen_distribution(blend_distributions)
+    });
     if blend_ms > grain_ms {
         grain_ms = blend_ms + grain_ms;
     }
+    let grain_samples = ((grain_ms / 1000.0) * params.sample_rate as f64) as usize;
     let blend_samples = ((blend_ms / 1000.0) * params.sample_rate as f64) as usize;
 
     let attributes = vec![
@@ -127,7 +138,8 @@ pub fn random_granular_time_stretch(params: &mut ProcessorParams) -> ProcessorPl
     (PermuteNodeName::GranularTimeStretch, attributes, Box::new(processor))
 }
 
-pub fn half_speed(_params: &mut ProcessorParams) -> ProcessorPlan {
+pub fn half_speed(params: &mut ProcessorParams) -> ProcessorPlan {
+    let quality = params.permutation.resample_quality;
     let attributes = vec![
         ProcessorAttribute {
             key: "Speed".to_string(),
@@ -141,7 +153,7 @@ pub fn half_speed(_params: &mut ProcessorParams) -> ProcessorPlan {
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::HalfSpeed, &params);
-        let new_params = change_speed(params, 0.5_f64);
+        let new_params = change_speed(params, 0.5_f64, quality);
         complete_event!(PermuteNodeName::HalfSpeed, new_params);
         Ok(new_params)
     };
@@ -149,7 +161,8 @@ pub fn half_speed(_params: &mut ProcessorParams) -> ProcessorPlan {
     (PermuteNodeName::HalfSpeed, attributes, Box::new(processor))
 }
 
-pub fn double_speed(_params: &mut ProcessorParams) -> ProcessorPlan {
+pub fn double_speed(params: &mut ProcessorParams) -> ProcessorPlan {
+    let quality = params.permutation.resample_quality;
     let attributes = vec![
         ProcessorAttribute {
             key: "Speed".to_string(),
@@ -163,7 +176,7 @@ pub fn double_speed(_params: &mut ProcessorParams) -> ProcessorPlan {
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::DoubleSpeed, &params);
-        let new_params = change_speed(params, 2_f64);
+        let new_params = change_speed(params, 2_f64, quality);
         complete_event!(PermuteNodeName::DoubleSpeed, new_params);
         Ok(new_params)
     };
@@ -187,10 +200,13 @@ pub fn random_blur_stretch(params: &mut ProcessorParams) -> ProcessorPlan {
         (64000, 0.1),
         (128000, 0.1),
     ];
-    let window_size = rng.gen_distribution(window_distributions);
+    let window_sizes: Vec<usize> = window_distributions.iter().map(|(w, _)| *w).collect();
+    let window_size = recorded_choice(params, "Window Size", &window_sizes, |w| w.to_string())
+        .unwrap_or_else(|| rng.gen_distribution(window_distributions));
     // Randomize hop size between 1/4 and 1/2 of window size
     let hop_options = [window_size/4, window_size/2, window_size/3, window_size/4];
-    let hop_size = hop_options[rng.gen_range(0..hop_options.len())];
+    let hop_size = recorded_choice(params, "Hop Size", &hop_options, |h| h.to_string())
+        .unwrap_or_else(|| hop_options[rng.gen_range(0..hop_options.len())]);
     // Randomize stretch factor between 0.5 and 2.0
     let stretch_distributions = vec![
         (0.25, 0.1),
@@ -204,12 +220,15 @@ pub fn random_blur_stretch(params: &mut ProcessorParams) -> ProcessorPlan {
         (8.0, 0.1),
         (16.0, 0.025),
     ];
-    let stretch_factor = rng.gen_distribution(stretch_distributions);
-    let window_type = match rng.gen_range(0..2) {
-        0 => WindowType::Hamming,
-        1 => WindowType::Blackman,
-        _ => WindowType::Hamming,
-    };
+    let stretch_factors: Vec<f64> = stretch_distributions.iter().map(|(f, _)| *f).collect();
+    let stretch_factor = recorded_choice(params, "Stretch Factor", &stretch_factors, |f| f.to_string())
+        .unwrap_or_else(|| rng.gen_distribution(stretch_distributions));
+    let window_type = recorded_choice(params, "Window Type", &[WindowType::Hamming, WindowType::Blackman], |w| format!("{:?}", w))
+        .unwrap_or_else(|| match rng.gen_range(0..2) {
+            0 => WindowType::Hamming,
+            1 => WindowType::Blackman,
+            _ => WindowType::Hamming,
+        });
 
     let attributes = vec![
         ProcessorAttribute {
@@ -263,22 +282,31 @@ pub fn reverse_with_plan(_params: &mut ProcessorParams) -> ProcessorPlan {
     (PermuteNodeName::Reverse, attributes, Box::new(processor))
 }
 
+pub fn high_sample_rate(sample_rate: usize) -> usize {
+    match sample_rate {
+        0..=48000 => sample_rate * 4,
+        48001..=96000 => sample_rate * 2,
+        _ => sample_rate,
+    }
+}
+
 pub fn change_sample_rate_high(params: &mut ProcessorParams) -> ProcessorPlan {
-    let new_sample_rate = match params.sample_rate {
-        0..=48000 => params.sample_rate * 4,
-        48001..=96000 => params.sample_rate * 2,
-        _ => params.sample_rate,
-    };
+    let new_sample_rate = high_sample_rate(params.sample_rate);
+    let quality = params.permutation.resample_quality;
     let attributes = vec![
         ProcessorAttribute {
             key: "Sample Rate".to_string(),
             value: format_hz_usize(new_sample_rate),
         },
+        ProcessorAttribute {
+            key: "Quality".to_string(),
+            value: format!("{:?}", quality),
+        },
         ];
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::SampleRateConversionHigh, &params);
-        let new_params = change_sample_rate(params, new_sample_rate)?;
+        let new_params = change_sample_rate(params, new_sample_rate, quality)?;
         complete_event!(PermuteNodeName::SampleRateConversionHigh, new_params);
         Ok(new_params)
     };
@@ -290,19 +318,206 @@ pub fn change_sample_rate_original(
     params: &mut ProcessorParams,
 ) -> ProcessorPlan {
     let new_sample_rate = params.permutation.original_sample_rate;
+    let quality = params.permutation.resample_quality;
     let attributes = vec![
         ProcessorAttribute {
             key: "Sample Rate".to_string(),
             value: format_hz_usize(new_sample_rate),
         },
+        ProcessorAttribute {
+            key: "Quality".to_string(),
+            value: format!("{:?}", quality),
+        },
         ];
 
     let processor = move |params: ProcessorParams| {
         start_event!(PermuteNodeName::SampleRateConversionHigh, &params);
-        let new_params = change_sample_rate(params, new_sample_rate)?;
+        let new_params = change_sample_rate(params, new_sample_rate, quality)?;
         complete_event!(PermuteNodeName::SampleRateConversionHigh, new_params);
         Ok(new_params)
     };
 
     (PermuteNodeName::SampleRateConversionOriginal, attributes, Box::new(processor))
-}
\ No newline at end of file
+}
+
+// Shared by the tape processors. Stops, starts and spins take a set time however long the audio
+// is when it gets here, so they report the time they add rather than a factor of the planned
+// length. Both are upper bounds, as a short input stops the curve early
+fn speed_curve_plan(
+    name: PermuteNodeName,
+    params: &ProcessorParams,
+    curve: SpeedCurve,
+    mut attributes: Vec<ProcessorAttribute>,
+) -> ProcessorPlan {
+    let quality = params.permutation.resample_quality;
+    let frames = params.samples.len() / params.channels.max(1);
+    let to_ms = |frames: f64| frames / params.sample_rate.max(1) as f64 * 1000.0;
+    let (length_factor, added_frames) = match &curve {
+        // The ramp plays ramp_frames / (curve + 1) of the input over ramp_frames
+        SpeedCurve::TapeStop { stop_frames: ramp_frames, curve: shape }
+        | SpeedCurve::TapeStart { start_frames: ramp_frames, curve: shape } => (1.0, ramp_frames * shape / (shape + 1.0)),
+        // Plays up to the spin point, then the spin takes at most its own time
+        SpeedCurve::SpinBack { at, spin_frames, .. } => (*at, *spin_frames),
+        // Pitch points are spread through the audio, so the factor holds for any length
+        SpeedCurve::Varispeed { .. } => (speed_curve_length_factor(&curve, frames), 0.0),
+    };
+    attributes.push(ProcessorAttribute {
+        key: "Length Factor".to_string(),
+        value: format!("{:.2}x", length_factor),
+    });
+    if added_frames > 0.0 {
+        attributes.push(ProcessorAttribute {
+            key: "Added Length".to_string(),
+            value: format_float_ms(to_ms(added_frames)),
+        });
+    }
+
+    let processor = move |params: ProcessorParams| {
+        start_event!(name, &params);
+        let new_params = change_speed_curve(params, &curve, quality)?;
+        complete_event!(name, new_params);
+        Ok(new_params)
+    };
+
+    (name, attributes, Box::new(processor))
+}
+
+fn ms_to_frames(ms: f64, sample_rate: usize) -> f64 {
+    ms / 1000.0 * sample_rate as f64
+}
+
+pub fn random_tape_stop(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+    let stop_ms = random_parameter(params, PermuteNodeName::TapeStop, "Stop Time", &mut rng, |rng| {
+        let stop_distributions = vec![
+            (rng.gen_range(150.0..500.0), 0.3),
+            (rng.gen_range(500.0..1500.0), 0.5),
+            (rng.gen_range(1500.0..4000.0), 0.2),
+        ];
+        rng.gen_distribution(stop_distributions)
+    });
+    let curve = random_parameter(params, PermuteNodeName::TapeStop, "Curve", &mut rng, |rng| rng.gen_range(0.5..2.5));
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Stop Time".to_string(),
+            value: format_float_ms(stop_ms),
+        },
+        ProcessorAttribute {
+            key: "Curve".to_string(),
+            value: format_float(curve),
+        },
+    ];
+    let speed_curve = SpeedCurve::TapeStop {
+        stop_frames: ms_to_frames(stop_ms, params.sample_rate),
+        curve,
+    };
+    speed_curve_plan(PermuteNodeName::TapeStop, params, speed_curve, attributes)
+}
+
+pub fn random_tape_start(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+    let start_ms = random_parameter(params, PermuteNodeName::TapeStart, "Start Time", &mut rng, |rng| {
+        let start_distributions = vec![
+            (rng.gen_range(100.0..400.0), 0.4),
+            (rng.gen_range(400.0..1200.0), 0.45),
+            (rng.gen_range(1200.0..3000.0), 0.15),
+        ];
+        rng.gen_distribution(start_distributions)
+    });
+    let curve = random_parameter(params, PermuteNodeName::TapeStart, "Curve", &mut rng, |rng| rng.gen_range(0.5..2.0));
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Start Time".to_string(),
+            value: format_float_ms(start_ms),
+        },
+        ProcessorAttribute {
+            key: "Curve".to_string(),
+            value: format_float(curve),
+        },
+    ];
+    let speed_curve = SpeedCurve::TapeStart {
+        start_frames: ms_to_frames(start_ms, params.sample_rate),
+        curve,
+    };
+    speed_curve_plan(PermuteNodeName::TapeStart, params, speed_curve, attributes)
+}
+
+pub fn random_spin_back(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+    let at = random_parameter(params, PermuteNodeName::SpinBack, "Spin Point", &mut rng, |rng| rng.gen_range(0.5..0.95));
+    let spin_ms = random_parameter(params, PermuteNodeName::SpinBack, "Spin Time", &mut rng, |rng| rng.gen_range(250.0..1200.0));
+    let speed = random_parameter(params, PermuteNodeName::SpinBack, "Spin Speed", &mut rng, |rng| rng.gen_range(2.0..6.0));
+    let curve = random_parameter(params, PermuteNodeName::SpinBack, "Curve", &mut rng, |rng| rng.gen_range(0.7..2.0));
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Spin Point".to_string(),
+            value: format_float_percent(at),
+        },
+        ProcessorAttribute {
+            key: "Spin Time".to_string(),
+            value: format_float_ms(spin_ms),
+        },
+        ProcessorAttribute {
+            key: "Spin Speed".to_string(),
+            value: format!("{:.2}x", speed),
+        },
+        ProcessorAttribute {
+            key: "Curve".to_string(),
+            value: format_float(curve),
+        },
+    ];
+    let speed_curve = SpeedCurve::SpinBack {
+        at,
+        spin_frames: ms_to_frames(spin_ms, params.sample_rate),
+        speed,
+        curve,
+    };
+    speed_curve_plan(PermuteNodeName::SpinBack, params, speed_curve, attributes)
+}
+
+pub fn random_varispeed(params: &mut ProcessorParams) -> ProcessorPlan {
+    let mut rng = thread_rng();
+    let range_distributions = vec![
+        (rng.gen_range(0.3..2.0), 0.4),
+        (rng.gen_range(2.0..7.0), 0.45),
+        (rng.gen_range(7.0..12.0), 0.15),
+    ];
+    let range: f64 = rng.gen_distribution(range_distributions);
+    let points = rng.gen_range(2..=6);
+    let semitones: Vec<f64> = (0..points).map(|_| rng.gen_range(-range..=range)).collect();
+    let shapes: Vec<RampShape> = (1..points)
+        .map(|_| {
+            let shape_distributions = vec![
+                (RampShape::Linear, 0.2),
+                (RampShape::EaseIn, 0.25),
+                (RampShape::EaseOut, 0.25),
+                (RampShape::SCurve, 0.3),
+            ];
+            rng.gen_distribution(shape_distributions)
+        })
+        .collect();
+
+    let attributes = vec![
+        ProcessorAttribute {
+            key: "Pitch Points".to_string(),
+            value: semitones
+                .iter()
+                .map(|s| format!("{:+.2}", s))
+                .collect::<Vec<String>>()
+                .join(" > "),
+        },
+        ProcessorAttribute {
+            key: "Ramps".to_string(),
+            value: shapes
+                .iter()
+                .map(|s| format!("{:?}", s))
+                .collect::<Vec<String>>()
+                .join(", "),
+        },
+    ];
+    let speed_curve = SpeedCurve::Varispeed { semitones, shapes };
+    speed_curve_plan(PermuteNodeName::Varispeed, params, speed_curve, attributes)
+}
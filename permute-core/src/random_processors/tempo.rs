@@ -0,0 +1,150 @@
+use std::fmt;
+
+use rand::{rngs::ThreadRng, seq::SliceRandom, Rng};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteValue {
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteFeel {
+    Straight,
+    Dotted,
+    Triplet,
+}
+
+// A note length relative to the tempo, e.g. dotted 1/8
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteDivision {
+    pub value: NoteValue,
+    pub feel: NoteFeel,
+}
+
+impl NoteDivision {
+    // Length in quarter note beats
+    pub fn beats(&self) -> f64 {
+        let beats = match self.value {
+            NoteValue::Whole => 4.0,
+            NoteValue::Half => 2.0,
+            NoteValue::Quarter => 1.0,
+            NoteValue::Eighth => 0.5,
+            NoteValue::Sixteenth => 0.25,
+            NoteValue::ThirtySecond => 0.125,
+        };
+        match self.feel {
+            NoteFeel::Straight => beats,
+            NoteFeel::Dotted => beats * 1.5,
+            NoteFeel::Triplet => beats * 2.0 / 3.0,
+        }
+    }
+
+    pub fn to_ms(self, bpm: f64) -> f64 {
+        self.beats() * 60_000.0 / bpm
+    }
+
+    // Rate for something that cycles once per division, e.g. an LFO
+    pub fn to_hz(self, bpm: f64) -> f64 {
+        1000.0 / self.to_ms(bpm)
+    }
+}
+
+impl fmt::Display for NoteDivision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self.value {
+            NoteValue::Whole => "1/1",
+            NoteValue::Half => "1/2",
+            NoteValue::Quarter => "1/4",
+            NoteValue::Eighth => "1/8",
+            NoteValue::Sixteenth => "1/16",
+            NoteValue::ThirtySecond => "1/32",
+        };
+        let feel = match self.feel {
+            NoteFeel::Straight => "",
+            NoteFeel::Dotted => ".",
+            NoteFeel::Triplet => "T",
+        };
+        write!(f, "{}{}", value, feel)
+    }
+}
+
+// Random division that lands between min_ms and max_ms at this tempo. Straight divisions are favoured
+pub fn random_note_division(rng: &mut ThreadRng, bpm: f64, min_ms: f64, max_ms: f64) -> Option<NoteDivision> {
+    let values = [
+        NoteValue::Whole,
+        NoteValue::Half,
+        NoteValue::Quarter,
+        NoteValue::Eighth,
+        NoteValue::Sixteenth,
+        NoteValue::ThirtySecond,
+    ];
+    let feels = [(NoteFeel::Straight, 3), (NoteFeel::Dotted, 1), (NoteFeel::Triplet, 1)];
+    let divisions: Vec<(NoteDivision, i32)> = values
+        .iter()
+        .flat_map(|value| feels.iter().map(|(feel, weight)| (NoteDivision { value: *value, feel: *feel }, *weight)))
+        .filter(|(division, _)| {
+            let ms = division.to_ms(bpm);
+            ms >= min_ms && ms <= max_ms
+        })
+        .collect();
+    divisions.choose_weighted(rng, |(_, weight)| *weight).ok().map(|(division, _)| *division)
+}
+
+// Only lock to the tempo most of the time, so synced and free settings both turn up
+pub fn sync_to_tempo(rng: &mut ThreadRng, bpm: Option<f64>) -> Option<f64> {
+    bpm.filter(|_| rng.gen_bool(0.8))
+}
+
+pub fn format_sync(division: NoteDivision, bpm: f64) -> String {
+    format!("{} @ {:.2} bpm", division, bpm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn division(value: NoteValue, feel: NoteFeel) -> NoteDivision {
+        NoteDivision { value, feel }
+    }
+
+    #[test]
+    fn divisions_convert_to_time_at_a_tempo() {
+        assert_eq!(division(NoteValue::Quarter, NoteFeel::Straight).to_ms(120.0), 500.0);
+        assert_eq!(division(NoteValue::Whole, NoteFeel::Straight).to_ms(60.0), 4000.0);
+        assert_eq!(division(NoteValue::Eighth, NoteFeel::Dotted).to_ms(120.0), 375.0);
+        assert!((division(NoteValue::Quarter, NoteFeel::Triplet).to_ms(120.0) - 1000.0 / 3.0).abs() < 1e-9);
+        assert_eq!(division(NoteValue::Sixteenth, NoteFeel::Straight).to_hz(120.0), 8.0);
+    }
+
+    #[test]
+    fn divisions_are_written_as_note_lengths() {
+        assert_eq!(division(NoteValue::Eighth, NoteFeel::Dotted).to_string(), "1/8.");
+        assert_eq!(division(NoteValue::Quarter, NoteFeel::Triplet).to_string(), "1/4T");
+        assert_eq!(division(NoteValue::ThirtySecond, NoteFeel::Straight).to_string(), "1/32");
+        assert_eq!(format_sync(division(NoteValue::Half, NoteFeel::Straight), 97.5), "1/2 @ 97.50 bpm");
+    }
+
+    #[test]
+    fn random_divisions_land_in_range() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let ms = random_note_division(&mut rng, 120.0, 100.0, 600.0).unwrap().to_ms(120.0);
+            assert!((100.0..=600.0).contains(&ms), "{}", ms);
+        }
+        // Nothing between a 1/32 triplet and a dotted whole note fits
+        assert_eq!(random_note_division(&mut rng, 120.0, 10.0, 20.0), None);
+        assert_eq!(random_note_division(&mut rng, 120.0, 5000.0, 9000.0), None);
+    }
+
+    #[test]
+    fn only_syncs_with_a_tempo() {
+        let mut rng = rand::thread_rng();
+        assert!((0..50).all(|_| sync_to_tempo(&mut rng, None).is_none()));
+        assert!((0..200).any(|_| sync_to_tempo(&mut rng, Some(120.0)) == Some(120.0)));
+    }
+}
@@ -1,5 +1,7 @@
 use rand::{rngs::ThreadRng, Rng};
 
+use crate::{display_node::get_processor_display_name, process::PermutationProcessor};
+
 pub trait DistributionRng {
     fn gen_distribution<T: Copy>(&mut self, distribution: Vec<(T, f64)>) -> T;
 }
@@ -35,6 +37,10 @@ pub fn format_float_percent(value: f64) -> String {
     format!("{:.2}%", value * 100.0)
 }
 
+pub fn format_db(value: f64) -> String {
+    format!("{:.2} dB", value)
+}
+
 pub fn format_float_ms(value: f64) -> String {
     format!("{:.2} ms", value)
 }
@@ -50,4 +56,30 @@ pub fn get_filename(path: &str) -> String {
 pub fn format_factor_to_pitch(factor: f64) -> String {
     let pitch = 12.0 * (factor / 2.0).log2();
     format!("{:.2} semitones", pitch)
-}
\ No newline at end of file
+}
+
+// Midi note number as a note name, e.g. 60 -> C4
+pub fn format_midi_note(note: usize) -> String {
+    let names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    format!("{}{}", names[note % 12], (note / 12) as i64 - 1)
+}
+
+// One line description of a sub-chain, e.g. "Fuzz (Gain: 1.20) > Tremolo (Speed: 4.00 hz)"
+pub fn format_chain(chain: &[PermutationProcessor]) -> String {
+    if chain.is_empty() {
+        return "Dry".to_string();
+    }
+    chain
+        .iter()
+        .map(|p| {
+            let details = p
+                .attributes
+                .iter()
+                .map(|a| format!("{}: {}", a.key, a.value))
+                .collect::<Vec<String>>()
+                .join(", ");
+            format!("{} ({})", get_processor_display_name(p.name), details)
+        })
+        .collect::<Vec<String>>()
+        .join(" > ")
+}
@@ -0,0 +1,344 @@
+// External dependencies
+use serde::{Deserialize, Serialize};
+
+// Standard library
+use std::collections::{HashMap, HashSet};
+
+// Internal modules
+use crate::{
+    evolve::{parse_attribute_value, RecordedChain},
+    process::{processor_category, PermutationProcessor, PermuteNodeName, ProcessorCategory},
+    random_processors::parameter_ranges::{range_around, ParameterRange, ParameterRanges},
+};
+
+// Ratings a processor's weight is pulled back towards even by, so a handful of ratings can't swing it
+const PRIOR_RATINGS: f64 = 2.0;
+// Learned weights stay within this factor either side of even
+const MAX_WEIGHT_FACTOR: f64 = 5.0;
+// Kept values a parameter needs before a range is learned for it
+const MIN_KEPT_VALUES: usize = 3;
+// How far past the kept values a learned range reaches, the same as a mutation's amount
+const RANGE_MARGIN: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rating {
+    Kept,
+    Deleted,
+    Starred,
+}
+
+impl Rating {
+    // How much an output with this rating counts for the processors in its chain
+    fn score(&self) -> f64 {
+        match self {
+            Rating::Deleted => 0.0,
+            Rating::Kept => 1.0,
+            Rating::Starred => 2.0,
+        }
+    }
+}
+
+pub fn get_rating_from_name(name: &str) -> Result<Rating, String> {
+    match name.trim().to_lowercase().as_str() {
+        "kept" => Ok(Rating::Kept),
+        "deleted" => Ok(Rating::Deleted),
+        "starred" => Ok(Rating::Starred),
+        _ => Err(format!("{} is not a rating. Try kept, deleted or starred", name)),
+    }
+}
+
+// An output and its rating, e.g. out.wav=starred
+pub fn get_rating_from_arg(arg: &str) -> Result<(String, Rating), String> {
+    let (output, rating) = arg
+        .rsplit_once('=')
+        .ok_or(format!("{} should be an output and rating, e.g. out.wav=starred", arg))?;
+    Ok((output.trim().to_string(), get_rating_from_name(rating)?))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatedOutput {
+    pub output: String,
+    pub rating: Rating,
+    pub chain: RecordedChain,
+}
+
+// Every output that has been rated, kept locally as JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RatingsStore {
+    pub ratings: Vec<RatedOutput>,
+}
+
+// What the ratings say about the processors and parameters that get kept
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LearnedPreferences {
+    // Above 1 for processors in outputs that get kept or starred, below 1 for ones in deleted outputs
+    pub weights: HashMap<PermuteNodeName, f64>,
+    // Ranges around the values of kept and starred outputs
+    pub parameter_ranges: ParameterRanges,
+}
+
+// Processors that were picked, branches included. Utility processors come from settings, not preference
+fn chain_processors<'a>(chain: &'a [PermutationProcessor], processors: &mut Vec<&'a PermutationProcessor>) {
+    for processor in chain {
+        if processor_category(processor.name) != ProcessorCategory::Utility {
+            processors.push(processor);
+        }
+        for branch in processor.branches.iter() {
+            chain_processors(branch, processors);
+        }
+    }
+}
+
+impl RatingsStore {
+    // A new rating for an output replaces any earlier one
+    pub fn record(&mut self, output: String, rating: Rating, chain: RecordedChain) {
+        self.ratings.retain(|r| r.output != output);
+        self.ratings.push(RatedOutput { output, rating, chain });
+    }
+
+    pub fn learn(&self) -> LearnedPreferences {
+        if self.ratings.is_empty() {
+            return LearnedPreferences::default();
+        }
+        // Scores are relative to the average rating, so keeping everything teaches nothing
+        let mean = self.ratings.iter().map(|r| r.rating.score()).sum::<f64>() / self.ratings.len() as f64;
+
+        let mut appearances: HashMap<PermuteNodeName, (f64, f64)> = HashMap::new();
+        let mut kept_values: HashMap<(PermuteNodeName, String), Vec<f64>> = HashMap::new();
+        for rated in self.ratings.iter() {
+            let mut processors = vec![];
+            chain_processors(&rated.chain, &mut processors);
+
+            // An output counts once for each processor, however many times it appears
+            let names: HashSet<PermuteNodeName> = processors.iter().map(|p| p.name).collect();
+            for name in names {
+                let (count, score) = appearances.entry(name).or_default();
+                *count += 1.0;
+                *score += if mean > 0.0 { rated.rating.score() / mean } else { 0.0 };
+            }
+
+            if rated.rating != Rating::Deleted {
+                for processor in processors {
+                    for attribute in processor.attributes.iter() {
+                        if let Some(value) = parse_attribute_value(&attribute.value) {
+                            kept_values.entry((processor.name, attribute.key.clone())).or_default().push(value);
+                        }
+                    }
+                }
+            }
+        }
+
+        let weights = appearances
+            .into_iter()
+            .map(|(name, (count, score))| {
+                let weight = (score + PRIOR_RATINGS) / (count + PRIOR_RATINGS);
+                (name, weight.clamp(1.0 / MAX_WEIGHT_FACTOR, MAX_WEIGHT_FACTOR))
+            })
+            .collect();
+
+        let mut parameter_ranges = ParameterRanges::new();
+        for ((name, parameter), values) in kept_values {
+            if values.len() < MIN_KEPT_VALUES {
+                continue;
+            }
+            let lowest = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let highest = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            // Only parameters with known bounds can be given a range
+            if let (Some(low), Some(high)) = (
+                range_around(name, &parameter, lowest, RANGE_MARGIN),
+                range_around(name, &parameter, highest, RANGE_MARGIN),
+            ) {
+                parameter_ranges.entry(name).or_default().insert(
+                    parameter,
+                    ParameterRange { min: low.min, max: high.max },
+                );
+            }
+        }
+
+        LearnedPreferences {
+            weights,
+            parameter_ranges,
+        }
+    }
+}
+
+impl LearnedPreferences {
+    // Learned weights scale the user's own. Learned ranges only fill in parameters the user hasn't set
+    pub fn personalise(
+        &self,
+        weights: &HashMap<PermuteNodeName, f64>,
+        parameter_ranges: &ParameterRanges,
+    ) -> (HashMap<PermuteNodeName, f64>, ParameterRanges) {
+        let mut personal_weights = weights.clone();
+        for (name, learned) in self.weights.iter() {
+            *personal_weights.entry(*name).or_insert(1.0) *= learned;
+        }
+        let mut personal_ranges = parameter_ranges.clone();
+        for (name, parameters) in self.parameter_ranges.iter() {
+            let ranges = personal_ranges.entry(*name).or_default();
+            for (parameter, range) in parameters {
+                ranges.entry(parameter.clone()).or_insert(*range);
+            }
+        }
+        (personal_weights, personal_ranges)
+    }
+}
+
+// A missing file is an empty store, as nothing has been rated yet
+pub fn load_ratings(path: &str) -> Result<RatingsStore, String> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid ratings in {}: {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(RatingsStore::default()),
+        Err(e) => Err(format!("Could not open {}: {}", path, e)),
+    }
+}
+
+pub fn save_ratings(path: &str, store: &RatingsStore) -> std::io::Result<()> {
+    std::fs::write(path, serde_json::to_string(store)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::ProcessorAttribute;
+    use PermuteNodeName::*;
+
+    fn processor(name: PermuteNodeName, attributes: &[(&str, &str)]) -> PermutationProcessor {
+        PermutationProcessor {
+            name,
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| ProcessorAttribute {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            branches: vec![],
+        }
+    }
+
+    fn store(ratings: Vec<(Rating, RecordedChain)>) -> RatingsStore {
+        let mut store = RatingsStore::default();
+        for (i, (rating, chain)) in ratings.into_iter().enumerate() {
+            store.record(format!("out_{}.wav", i), rating, chain);
+        }
+        store
+    }
+
+    fn decay(value: &str) -> RecordedChain {
+        vec![processor(Reverb, &[("Decay", value)])]
+    }
+
+    #[test]
+    fn keeping_everything_leaves_weights_even() {
+        let learned = store(vec![
+            (Rating::Kept, vec![processor(Reverb, &[]), processor(Filter, &[])]),
+            (Rating::Kept, vec![processor(Reverb, &[])]),
+            (Rating::Kept, vec![processor(Chorus, &[]), processor(Normalise, &[])]),
+        ])
+        .learn();
+        assert_eq!(learned.weights.len(), 3);
+        assert!(learned.weights.values().all(|w| *w == 1.0), "{:?}", learned.weights);
+        assert_eq!(RatingsStore::default().learn(), LearnedPreferences::default());
+    }
+
+    #[test]
+    fn deleted_processors_fall_below_even() {
+        let learned = store(vec![
+            (Rating::Deleted, vec![processor(Reverb, &[])]),
+            (Rating::Deleted, vec![processor(Reverb, &[])]),
+            (Rating::Deleted, vec![processor(Reverb, &[]), processor(Filter, &[])]),
+            (Rating::Kept, vec![processor(Filter, &[])]),
+            (Rating::Starred, vec![processor(Filter, &[])]),
+        ])
+        .learn();
+        let reverb = learned.weights[&Reverb];
+        let filter = learned.weights[&Filter];
+        assert!((1.0 / MAX_WEIGHT_FACTOR..1.0).contains(&reverb), "{}", reverb);
+        assert!(filter > 1.0 && filter <= MAX_WEIGHT_FACTOR, "{}", filter);
+    }
+
+    #[test]
+    fn weights_stay_within_the_max_factor() {
+        let mut ratings = vec![(Rating::Starred, vec![processor(Filter, &[])])];
+        ratings.extend((0..100).map(|_| (Rating::Deleted, vec![processor(Reverb, &[])])));
+        let learned = store(ratings).learn();
+        assert_eq!(learned.weights[&Reverb], 1.0 / MAX_WEIGHT_FACTOR);
+        assert_eq!(learned.weights[&Filter], MAX_WEIGHT_FACTOR);
+    }
+
+    #[test]
+    fn branches_count_and_utilities_do_not() {
+        let mut parallel = processor(Parallel, &[]);
+        parallel.branches = vec![vec![processor(Reverb, &[])], vec![processor(Chorus, &[])]];
+        let learned = store(vec![
+            (Rating::Deleted, vec![parallel, processor(Trim, &[])]),
+            (Rating::Kept, vec![processor(Filter, &[])]),
+        ])
+        .learn();
+        assert!(learned.weights[&Reverb] < 1.0);
+        assert!(learned.weights[&Chorus] < 1.0);
+        assert!(!learned.weights.contains_key(&Trim));
+    }
+
+    #[test]
+    fn ranges_are_learned_around_kept_values() {
+        let learned = store(vec![
+            (Rating::Kept, decay("1000")),
+            (Rating::Starred, decay("2000")),
+            (Rating::Kept, decay("3000")),
+            (Rating::Deleted, decay("15000")),
+        ])
+        .learn();
+        let range = learned.parameter_ranges[&Reverb]["Decay"];
+        assert_eq!(range, ParameterRange { min: 750.0, max: 3750.0 });
+    }
+
+    #[test]
+    fn ranges_need_enough_kept_values() {
+        let learned = store(vec![
+            (Rating::Kept, decay("1000")),
+            (Rating::Kept, decay("2000")),
+            (Rating::Deleted, decay("3000")),
+        ])
+        .learn();
+        assert!(learned.parameter_ranges.is_empty());
+    }
+
+    #[test]
+    fn learned_ranges_never_override_the_users() {
+        let learned = store(vec![
+            (Rating::Kept, vec![processor(Reverb, &[("Decay", "1000")]), processor(Filter, &[("Frequency", "1000")])]),
+            (Rating::Kept, vec![processor(Reverb, &[("Decay", "2000")]), processor(Filter, &[("Frequency", "2000")])]),
+            (Rating::Kept, vec![processor(Reverb, &[("Decay", "3000")]), processor(Filter, &[("Frequency", "3000")])]),
+        ])
+        .learn();
+        let users = ParameterRange { min: 8000.0, max: 9000.0 };
+        let user_ranges = ParameterRanges::from([(Reverb, HashMap::from([(String::from("Decay"), users)]))]);
+        let (_, ranges) = learned.personalise(&HashMap::new(), &user_ranges);
+        assert_eq!(ranges[&Reverb]["Decay"], users);
+        assert_eq!(ranges[&Filter]["Frequency"], learned.parameter_ranges[&Filter]["Frequency"]);
+    }
+
+    #[test]
+    fn learned_weights_scale_the_users() {
+        let learned = LearnedPreferences {
+            weights: HashMap::from([(Reverb, 0.5), (Filter, 2.0)]),
+            parameter_ranges: ParameterRanges::new(),
+        };
+        let (weights, _) = learned.personalise(&HashMap::from([(Reverb, 3.0), (Chorus, 0.0)]), &ParameterRanges::new());
+        assert_eq!(weights, HashMap::from([(Reverb, 1.5), (Filter, 2.0), (Chorus, 0.0)]));
+    }
+
+    #[test]
+    fn a_new_rating_replaces_the_old_one() {
+        let mut store = RatingsStore::default();
+        store.record(String::from("out.wav"), Rating::Kept, vec![]);
+        store.record(String::from("out.wav"), Rating::Deleted, vec![]);
+        assert_eq!(store.ratings.len(), 1);
+        assert_eq!(store.ratings[0].rating, Rating::Deleted);
+        assert_eq!(get_rating_from_arg("a=b.wav = Starred").unwrap(), (String::from("a=b.wav"), Rating::Starred));
+        assert!(get_rating_from_arg("out.wav").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use audio_info::{fingerprint, Fingerprint};
+
+use crate::process::{processor_category, PermutationProcessor, PermuteNodeName, ProcessorCategory};
+
+// Chain structure counts for this much of the similarity between outputs, the audio for the rest
+const CHAIN_WEIGHT: f64 = 0.4;
+
+// What an output is compared on when looking for near duplicates
+#[derive(Debug, Clone)]
+pub struct OutputFingerprint {
+    pub output: String,
+    pub chain: Vec<PermuteNodeName>,
+    pub audio: Fingerprint,
+}
+
+impl OutputFingerprint {
+    pub fn new(output: String, processors: &[PermutationProcessor], samples: &[f64], channels: usize, sample_rate: usize) -> Self {
+        let mut chain = vec![];
+        flatten_chain(processors, &mut chain);
+        Self {
+            output,
+            chain,
+            audio: fingerprint(samples, channels, sample_rate),
+        }
+    }
+
+    // How alike two outputs are, from 0 for nothing alike to 1 for the same
+    pub fn similarity(&self, other: &OutputFingerprint) -> f64 {
+        CHAIN_WEIGHT * chain_similarity(&self.chain, &other.chain) + (1.0 - CHAIN_WEIGHT) * self.audio.similarity(&other.audio)
+    }
+}
+
+// Processors in the order they run, branches included. Sample rate conversion, normalising
+// and trimming are left out as nearly every chain has them
+fn flatten_chain(processors: &[PermutationProcessor], chain: &mut Vec<PermuteNodeName>) {
+    for processor in processors {
+        if processor_category(processor.name) != ProcessorCategory::Utility {
+            chain.push(processor.name);
+        }
+        for branch in processor.branches.iter() {
+            flatten_chain(branch, chain);
+        }
+    }
+}
+
+// One minus the edit distance between the chains, relative to the longer chain
+pub fn chain_similarity(a: &[PermuteNodeName], b: &[PermuteNodeName]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + (x != y) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+// The most similar earlier output, and how similar it is
+pub fn most_similar<'a>(fingerprint: &OutputFingerprint, earlier: &'a [OutputFingerprint]) -> Option<(&'a OutputFingerprint, f64)> {
+    earlier
+        .iter()
+        .map(|e| (e, fingerprint.similarity(e)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+// How different a run's outputs are from each other, from 0 for all the same to 1. Outputs are
+// only compared with outputs of the same source file. None when there is nothing to compare
+pub fn diversity(groups: &[Vec<OutputFingerprint>]) -> Option<f64> {
+    let similarities: Vec<f64> = groups
+        .iter()
+        .flat_map(|group| {
+            group
+                .iter()
+                .enumerate()
+                .flat_map(move |(i, a)| group[i + 1..].iter().map(move |b| a.similarity(b)))
+        })
+        .collect();
+    if similarities.is_empty() {
+        return None;
+    }
+    Some(1.0 - similarities.iter().sum::<f64>() / similarities.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+    use PermuteNodeName::*;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn sine(hz: f64) -> Vec<f64> {
+        (0..SAMPLE_RATE)
+            .map(|i| (2.0 * PI * hz * i as f64 / SAMPLE_RATE as f64).sin() * 0.5)
+            .collect()
+    }
+
+    fn processor(name: PermuteNodeName, branches: Vec<Vec<PermutationProcessor>>) -> PermutationProcessor {
+        PermutationProcessor {
+            name,
+            attributes: vec![],
+            branches,
+        }
+    }
+
+    fn output(chain: &[PermuteNodeName], samples: &[f64]) -> OutputFingerprint {
+        let processors: Vec<PermutationProcessor> = chain.iter().map(|n| processor(*n, vec![])).collect();
+        OutputFingerprint::new(String::from("output.wav"), &processors, samples, 1, SAMPLE_RATE)
+    }
+
+    #[test]
+    fn identical_chains_are_the_same() {
+        assert_eq!(chain_similarity(&[Reverb, Filter, Reverse], &[Reverb, Filter, Reverse]), 1.0);
+        assert_eq!(chain_similarity(&[], &[]), 1.0);
+    }
+
+    #[test]
+    fn disjoint_chains_have_nothing_alike() {
+        assert_eq!(chain_similarity(&[Reverb, Filter], &[Reverse, Chorus]), 0.0);
+        assert_eq!(chain_similarity(&[Reverb, Filter], &[]), 0.0);
+    }
+
+    #[test]
+    fn chain_similarity_is_the_edit_distance_over_the_longer_chain() {
+        // One substitution
+        assert_eq!(chain_similarity(&[Reverb, Filter, Reverse, Chorus], &[Reverb, Flange, Reverse, Chorus]), 0.75);
+        // One insertion
+        assert_eq!(chain_similarity(&[Reverb, Reverse], &[Reverb, Filter, Reverse, Chorus]), 0.5);
+        // Order counts
+        assert_eq!(chain_similarity(&[Reverb, Filter], &[Filter, Reverb]), 0.0);
+    }
+
+    #[test]
+    fn chains_include_branches_and_leave_out_utilities() {
+        let processors = vec![
+            processor(SampleRateConversionHigh, vec![]),
+            processor(Parallel, vec![vec![processor(Reverb, vec![])], vec![processor(Filter, vec![])]]),
+            processor(Normalise, vec![]),
+            processor(Trim, vec![]),
+        ];
+        let print = OutputFingerprint::new(String::from("output.wav"), &processors, &sine(440.0), 1, SAMPLE_RATE);
+        assert_eq!(print.chain, vec![Parallel, Reverb, Filter]);
+    }
+
+    #[test]
+    fn identical_outputs_are_the_same() {
+        let a = output(&[Reverb, Filter], &sine(440.0));
+        let b = output(&[Reverb, Filter], &sine(440.0));
+        assert!(a.similarity(&b) >= 0.99, "{}", a.similarity(&b));
+    }
+
+    #[test]
+    fn most_similar_finds_the_closest_earlier_output() {
+        let earlier = vec![output(&[Reverse, Chorus], &sine(110.0)), output(&[Reverb, Filter], &sine(440.0))];
+        let (closest, similarity) = most_similar(&output(&[Reverb, Filter], &sine(440.0)), &earlier).unwrap();
+        assert_eq!(closest.chain, vec![Reverb, Filter]);
+        assert!(similarity >= 0.99);
+        assert!(most_similar(&earlier[0], &[]).is_none());
+    }
+
+    #[test]
+    fn diversity_is_zero_for_identical_outputs() {
+        let group = vec![output(&[Reverb], &sine(440.0)), output(&[Reverb], &sine(440.0)), output(&[Reverb], &sine(440.0))];
+        assert!(diversity(&[group]).unwrap() <= 0.01);
+    }
+
+    #[test]
+    fn diversity_rises_as_outputs_differ() {
+        let same = vec![output(&[Reverb, Filter], &sine(440.0)), output(&[Reverb, Filter], &sine(440.0))];
+        let different = vec![output(&[Reverb, Filter], &sine(440.0)), output(&[Reverse, Chorus], &sine(3000.0))];
+        let same = diversity(&[same]).unwrap();
+        let different = diversity(&[different]).unwrap();
+        assert!(different > same + 0.4, "{} {}", different, same);
+        assert!(different <= 1.0);
+    }
+
+    #[test]
+    fn diversity_only_compares_outputs_of_the_same_file() {
+        // Each file has one output, so there is nothing to compare
+        let groups = vec![vec![output(&[Reverb], &sine(440.0))], vec![output(&[Reverse], &sine(3000.0))]];
+        assert_eq!(diversity(&groups), None);
+        assert_eq!(diversity(&[]), None);
+    }
+}
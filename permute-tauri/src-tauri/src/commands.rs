@@ -2,7 +2,7 @@ use std::{sync::Arc, thread};
 
 use tauri::{AppHandle, Emitter, State};
 
-use crate::state::{AppState, PermuteProgressEvent, PermuteStateDto};
+use crate::state::{AppState, LearnedPreferencesDto, PermuteProgressEvent, PermuteStateDto};
 
 // ─── State query ─────────────────────────────────────────────────────────────
 
@@ -27,6 +27,20 @@ pub fn run_processor(
     Ok(())
 }
 
+/// Breeds a new generation from an output row. With a partner the two chains are crossed, otherwise the output is mutated
+#[tauri::command]
+pub fn evolve_output(
+    state: State<'_, AppState>,
+    on_event: tauri::ipc::Channel<PermuteProgressEvent>,
+    output: String,
+    partner: Option<String>,
+    amount: f64,
+) -> Result<(), String> {
+    *state.active_channel.lock().unwrap() = Some(on_event);
+    state.shared.lock().unwrap().run_evolution(output, partner, amount)?;
+    Ok(())
+}
+
 #[tauri::command]
 pub fn reverse_file(
     state: State<'_, AppState>,
@@ -120,6 +134,38 @@ pub fn delete_all_output_files(state: State<'_, AppState>) -> Result<(), String>
         .map_err(|e| e.to_string())
 }
 
+// ─── Ratings ──────────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn star_output(state: State<'_, AppState>, file: String) -> Result<(), String> {
+    state.shared.lock().unwrap().star_output(file)
+}
+
+#[tauri::command]
+pub fn set_record_ratings(state: State<'_, AppState>, record_ratings: bool) {
+    state.shared.lock().unwrap().set_record_ratings(record_ratings);
+}
+
+#[tauri::command]
+pub fn set_personalised(state: State<'_, AppState>, personalised: bool) {
+    state.shared.lock().unwrap().set_personalised(personalised);
+}
+
+#[tauri::command]
+pub fn reset_ratings(state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .shared
+        .lock()
+        .unwrap()
+        .reset_ratings()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_learned_preferences(state: State<'_, AppState>) -> LearnedPreferencesDto {
+    state.shared.lock().unwrap().to_learned_preferences_dto()
+}
+
 // ─── Processor pool ───────────────────────────────────────────────────────────
 
 #[tauri::command]
@@ -132,6 +178,31 @@ pub fn remove_processor(state: State<'_, AppState>, name: String) {
     state.shared.lock().unwrap().remove_processor(name);
 }
 
+#[tauri::command]
+pub fn set_processor_weight(state: State<'_, AppState>, name: String, weight: f64) -> Result<(), String> {
+    state.shared.lock().unwrap().set_processor_weight(name, weight)
+}
+
+#[tauri::command]
+pub fn set_parameter_ranges(state: State<'_, AppState>, json: String) -> Result<(), String> {
+    state.shared.lock().unwrap().set_parameter_ranges(json)
+}
+
+#[tauri::command]
+pub fn set_chain_rules(state: State<'_, AppState>, json: String) -> Result<(), String> {
+    state.shared.lock().unwrap().set_chain_rules(json)
+}
+
+#[tauri::command]
+pub fn set_template(state: State<'_, AppState>, template: Option<Vec<String>>) -> Result<(), String> {
+    state.shared.lock().unwrap().set_template(template)
+}
+
+#[tauri::command]
+pub fn set_complexity(state: State<'_, AppState>, json: Option<String>) -> Result<(), String> {
+    state.shared.lock().unwrap().set_complexity(json)
+}
+
 #[tauri::command]
 pub fn select_all_processors(state: State<'_, AppState>) {
     state.shared.lock().unwrap().select_all_processors();
@@ -184,6 +255,46 @@ pub fn set_max_stretch(state: State<'_, AppState>, max_stretch: f64) {
     state.shared.lock().unwrap().set_max_stretch(max_stretch);
 }
 
+#[tauri::command]
+pub fn set_extreme_stretch(state: State<'_, AppState>, extreme_stretch: Option<f64>) {
+    state.shared.lock().unwrap().set_extreme_stretch(extreme_stretch);
+}
+
+#[tauri::command]
+pub fn set_descriptor_targets(state: State<'_, AppState>, json: String) -> Result<(), String> {
+    state.shared.lock().unwrap().set_descriptor_targets(json)
+}
+
+#[tauri::command]
+pub fn set_quality_checks(state: State<'_, AppState>, json: String) -> Result<(), String> {
+    state.shared.lock().unwrap().set_quality_checks(json)
+}
+
+#[tauri::command]
+pub fn set_max_similarity(state: State<'_, AppState>, max_similarity: Option<f64>) {
+    state.shared.lock().unwrap().set_max_similarity(max_similarity);
+}
+
+#[tauri::command]
+pub fn set_max_attempts(state: State<'_, AppState>, max_attempts: Option<usize>) {
+    state.shared.lock().unwrap().set_max_attempts(max_attempts);
+}
+
+#[tauri::command]
+pub fn set_resample_quality(state: State<'_, AppState>, quality: String) -> Result<(), String> {
+    state.shared.lock().unwrap().set_resample_quality(quality)
+}
+
+#[tauri::command]
+pub fn set_oversampling(state: State<'_, AppState>, oversampling: String) -> Result<(), String> {
+    state.shared.lock().unwrap().set_oversampling(oversampling)
+}
+
+#[tauri::command]
+pub fn set_bpm(state: State<'_, AppState>, bpm: Option<f64>) {
+    state.shared.lock().unwrap().set_bpm(bpm);
+}
+
 #[tauri::command]
 pub fn set_create_subdirectories(state: State<'_, AppState>, create: bool) {
     state
@@ -18,11 +18,12 @@ fn main() {
             let app_handle = app.handle().clone();
 
             // Config path in platform userData directory
-            let config_path = app
+            let app_data_dir = app
                 .path()
                 .app_data_dir()
-                .expect("failed to resolve app data dir")
-                .join("config.json");
+                .expect("failed to resolve app data dir");
+            let config_path = app_data_dir.join("config.json");
+            let ratings_path = app_data_dir.join("ratings.json");
 
             // Create the update channel
             let (permute_tx, permute_rx) =
@@ -32,6 +33,8 @@ fn main() {
 
             // Load saved settings (ignore errors on first launch)
             let _ = initial_state.read_from_json(config_path.to_string_lossy().into_owned());
+            // Ratings are kept alongside the config (ignore errors, ratings just aren't saved)
+            let _ = initial_state.set_ratings_path(ratings_path.to_string_lossy().into_owned());
 
             let shared = Arc::new(std::sync::Mutex::new(initial_state));
             let shared_for_update = Arc::clone(&shared);
@@ -51,7 +54,7 @@ fn main() {
                     while let Ok(message) = permute_rx.recv() {
                         let is_complete = matches!(
                             message,
-                            PermuteUpdate::ProcessComplete(_) | PermuteUpdate::Error(_)
+                            PermuteUpdate::ProcessComplete(_, _) | PermuteUpdate::Error(_)
                         );
                         let has_channel = active_channel_for_thread.lock().unwrap().is_some();
 
@@ -111,6 +114,7 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             get_state,
             run_processor,
+            evolve_output,
             reverse_file,
             trim_file,
             cancel,
@@ -119,8 +123,18 @@ fn main() {
             clear_all_files,
             delete_output_file,
             delete_all_output_files,
+            star_output,
+            set_record_ratings,
+            set_personalised,
+            reset_ratings,
+            get_learned_preferences,
             add_processor,
             remove_processor,
+            set_processor_weight,
+            set_parameter_ranges,
+            set_chain_rules,
+            set_template,
+            set_complexity,
             select_all_processors,
             deselect_all_processors,
             set_output,
@@ -131,6 +145,14 @@ fn main() {
             set_input_trail,
             set_output_trail,
             set_max_stretch,
+            set_extreme_stretch,
+            set_descriptor_targets,
+            set_quality_checks,
+            set_max_similarity,
+            set_max_attempts,
+            set_resample_quality,
+            set_oversampling,
+            set_bpm,
             set_create_subdirectories,
             set_viewed_welcome,
             open_output_dialog,
@@ -1,12 +1,27 @@
-use audio_info::{AudioFileError, AudioInfo};
+use audio_info::{
+    descriptors::parse_descriptor_targets, quality::parse_quality_checks, AudioFileError, AudioInfo, DescriptorTargets,
+    QualityChecks, QualityFlag,
+};
 use crossbeam_channel::Sender;
 use tauri::ipc::Channel;
 use permute::{
     audio_cache::AUDIO_CACHE,
+    chain_rules::{
+        get_template_slot_display_name, get_template_slot_from_display_name, parse_chain_rules, ChainRules,
+        ChainRulesConfig, ChainTemplate,
+    },
+    complexity::{parse_complexity, ComplexityConfig, ComplexityModel, COMPLEXITY_PRESETS},
     display_node::{get_processor_display_name, get_processor_from_display_name},
+    evolve::Evolution,
     permute_error::PermuteError,
     permute_files::{permute_files, process_file, PermuteFilesParams, PermuteUpdate},
-    process::{Permutation, PermuteNodeName, ProcessorAttribute, ALL_PROCESSORS},
+    process::{Permutation, PermutationProcessor, PermuteNodeName, ProcessorAttribute, ALL_PROCESSORS},
+    processors::{
+        oversample::{get_oversampling_from_name, Oversampling},
+        resample::{get_resample_quality_from_name, ResampleQuality},
+    },
+    random_processors::parameter_ranges::{parse_parameter_ranges, validate_parameter_ranges, ParameterRange, ParameterRanges},
+    ratings::{load_ratings, save_ratings, Rating, RatingsStore},
     rms_cache::clear_file_from_rms_cache,
 };
 use serde::{Deserialize, Serialize};
@@ -50,8 +65,17 @@ pub enum PermuteProgressEvent {
         name: String,
         image: String,
         duration_sec: f64,
+        quality_flags: Vec<QualityFlag>,
+    },
+    #[serde(rename_all = "camelCase")]
+    OutputRejected {
+        path: String,
+        reasons: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Finished {
+        diversity: Option<f64>,
     },
-    Finished,
     #[serde(rename_all = "camelCase")]
     Error {
         message: String,
@@ -73,16 +97,42 @@ pub struct PermuteStateDto {
     pub permutation_depth: u32,
     pub processor_count: u32,
     pub processor_pool: Vec<String>,
+    pub processor_weights: HashMap<String, f64>,
+    pub parameter_ranges: HashMap<String, HashMap<String, ParameterRange>>,
+    pub chain_rules: ChainRulesConfig,
+    pub template: Option<Vec<String>>,
+    pub complexity: Option<ComplexityConfig>,
+    pub complexity_presets: Vec<String>,
+    pub descriptor_targets: DescriptorTargets,
+    pub quality_checks: QualityChecks,
+    pub max_similarity: Option<f64>,
+    pub max_attempts: Option<usize>,
     pub all_processors: Vec<String>,
     pub normalise_at_end: bool,
     pub trim_all: bool,
     pub create_subdirectories: bool,
     pub viewed_welcome: bool,
+    pub record_ratings: bool,
+    pub personalised: bool,
+    pub rated_outputs: usize,
     pub max_stretch: f64,
+    pub extreme_stretch: Option<f64>,
+    pub resample_quality: String,
+    pub oversampling: String,
+    pub bpm: Option<f64>,
     pub files: Vec<PermutationInputDto>,
     pub permutation_outputs: Vec<PermutationOutputDto>,
 }
 
+// What has been learned from ratings, strongest preference first
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LearnedPreferencesDto {
+    pub rated_outputs: usize,
+    pub weights: Vec<(String, f64)>,
+    pub parameter_ranges: HashMap<String, HashMap<String, ParameterRange>>,
+}
+
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PermutationInputDto {
@@ -90,6 +140,7 @@ pub struct PermutationInputDto {
     pub name: String,
     pub duration_sec: f64,
     pub image: String,
+    pub bpm: Option<f64>,
 }
 
 #[derive(Serialize, Clone)]
@@ -102,12 +153,35 @@ pub struct PermutationOutputDto {
     pub duration_sec: f64,
     pub deleted: bool,
     pub processors: Vec<ProcessorDto>,
+    pub quality_flags: Vec<QualityFlag>,
 }
 
 #[derive(Serialize, Clone)]
 pub struct ProcessorDto {
     pub name: String,
     pub attributes: Vec<ProcessorAttributeDto>,
+    pub branches: Vec<Vec<ProcessorDto>>,
+}
+
+impl From<&PermutationProcessor> for ProcessorDto {
+    fn from(processor: &PermutationProcessor) -> Self {
+        ProcessorDto {
+            name: get_processor_display_name(processor.name).to_string(),
+            attributes: processor
+                .attributes
+                .iter()
+                .map(|a| ProcessorAttributeDto {
+                    key: a.key.clone(),
+                    value: a.value.clone(),
+                })
+                .collect(),
+            branches: processor
+                .branches
+                .iter()
+                .map(|branch| branch.iter().map(ProcessorDto::from).collect())
+                .collect(),
+        }
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -127,6 +201,18 @@ pub struct SharedState {
     pub permutations: usize,
     pub permutation_depth: usize,
     pub processor_pool: Vec<PermuteNodeName>,
+    pub processor_weights: HashMap<PermuteNodeName, f64>,
+    pub parameter_ranges: ParameterRanges,
+    pub chain_rules: ChainRules,
+    pub template: Option<ChainTemplate>,
+    // Replaces depth and processor count when set
+    pub complexity: Option<ComplexityModel>,
+    // Empty targets keep every output
+    pub descriptor_targets: DescriptorTargets,
+    pub quality_checks: QualityChecks,
+    // None keeps near duplicate outputs
+    pub max_similarity: Option<f64>,
+    pub max_attempts: Option<usize>,
     pub all_processors: Vec<PermuteNodeName>,
     pub normalise_at_end: bool,
     pub trim_all: bool,
@@ -135,7 +221,19 @@ pub struct SharedState {
     pub constrain_length: bool,
     pub create_subdirectories: bool,
     pub viewed_welcome: bool,
+    // Opt-in, outputs are only rated once this is on
+    pub record_ratings: bool,
+    // Shift weights and ranges towards what was kept when running
+    pub personalised: bool,
+    pub ratings: RatingsStore,
+    // Where ratings are saved, empty to not save them
+    pub ratings_path: String,
     pub max_stretch: f64,
+    pub extreme_stretch: Option<f64>,
+    pub resample_quality: ResampleQuality,
+    // None picks oversampling at random for each distortion
+    pub oversampling: Option<Oversampling>,
+    pub bpm: Option<f64>,
     pub update_sender: Arc<Sender<PermuteUpdate>>,
     pub processing: bool,
     outputs: HashMap<(usize, usize), OutputProgress>,
@@ -163,6 +261,15 @@ impl SharedState {
             ),
             update_sender: Arc::new(update_sender),
             processor_pool: ALL_PROCESSORS.to_vec(),
+            processor_weights: HashMap::new(),
+            parameter_ranges: ParameterRanges::new(),
+            chain_rules: ChainRules::default(),
+            template: None,
+            complexity: None,
+            descriptor_targets: DescriptorTargets::default(),
+            quality_checks: QualityChecks::default(),
+            max_similarity: None,
+            max_attempts: None,
             all_processors: ALL_PROCESSORS.to_vec(),
             processing: false,
             outputs: HashMap::new(),
@@ -171,7 +278,15 @@ impl SharedState {
             constrain_length: true,
             create_subdirectories: true,
             viewed_welcome: false,
+            record_ratings: false,
+            personalised: false,
+            ratings: RatingsStore::default(),
+            ratings_path: String::new(),
             max_stretch: 17.0,
+            extreme_stretch: None,
+            resample_quality: ResampleQuality::default(),
+            oversampling: None,
+            bpm: None,
         }
     }
 
@@ -184,6 +299,7 @@ impl SharedState {
                 name: f.name.clone(),
                 duration_sec: f.duration_sec,
                 image: f.image.clone(),
+                bpm: f.bpm,
             })
             .collect();
 
@@ -193,6 +309,18 @@ impl SharedState {
             .map(|p| get_processor_display_name(*p).to_string())
             .collect();
 
+        let processor_weights = self
+            .processor_weights
+            .iter()
+            .map(|(p, w)| (get_processor_display_name(*p).to_string(), *w))
+            .collect();
+
+        let parameter_ranges = self
+            .parameter_ranges
+            .iter()
+            .map(|(p, ranges)| (get_processor_display_name(*p).to_string(), ranges.clone()))
+            .collect();
+
         let all_processors = self
             .all_processors
             .iter()
@@ -203,22 +331,7 @@ impl SharedState {
             .get_ordered_outputs()
             .into_iter()
             .map(|o| {
-                let processors = o
-                    .permutation
-                    .processors
-                    .iter()
-                    .map(|p| ProcessorDto {
-                        name: get_processor_display_name(p.name).to_string(),
-                        attributes: p
-                            .attributes
-                            .iter()
-                            .map(|a| ProcessorAttributeDto {
-                                key: a.key.clone(),
-                                value: a.value.clone(),
-                            })
-                            .collect(),
-                    })
-                    .collect();
+                let processors = o.permutation.processors.iter().map(ProcessorDto::from).collect();
                 PermutationOutputDto {
                     path: o.output.clone(),
                     name: o.audio_info.name.clone(),
@@ -227,6 +340,7 @@ impl SharedState {
                     duration_sec: o.audio_info.duration_sec,
                     deleted: o.deleted,
                     processors,
+                    quality_flags: o.audio_info.quality_flags.clone(),
                 }
             })
             .collect();
@@ -242,12 +356,32 @@ impl SharedState {
             permutation_depth: self.permutation_depth as u32,
             processor_count: self.processor_count.unwrap_or(0) as u32,
             processor_pool,
+            processor_weights,
+            parameter_ranges,
+            chain_rules: self.chain_rules.to_config(),
+            template: self
+                .template
+                .as_ref()
+                .map(|t| t.iter().map(|s| get_template_slot_display_name(*s)).collect()),
+            complexity: self.complexity.as_ref().map(|c| c.to_config()),
+            complexity_presets: COMPLEXITY_PRESETS.iter().map(|p| p.to_string()).collect(),
+            descriptor_targets: self.descriptor_targets.clone(),
+            quality_checks: self.quality_checks.clone(),
+            max_similarity: self.max_similarity,
+            max_attempts: self.max_attempts,
             all_processors,
             normalise_at_end: self.normalise_at_end,
             trim_all: self.trim_all,
             create_subdirectories: self.create_subdirectories,
             viewed_welcome: self.viewed_welcome,
+            record_ratings: self.record_ratings,
+            personalised: self.personalised,
+            rated_outputs: self.ratings.ratings.len(),
             max_stretch: self.max_stretch,
+            extreme_stretch: self.extreme_stretch,
+            resample_quality: format!("{:?}", self.resample_quality),
+            oversampling: get_oversampling_name(self.oversampling),
+            bpm: self.bpm,
             files,
             permutation_outputs,
         }
@@ -264,6 +398,10 @@ impl SharedState {
     fn to_permute_params(&mut self) -> PermuteFilesParams {
         let (cancel_sender, cancel_receiver) = crossbeam_channel::bounded(1);
         self.cancel_sender = cancel_sender;
+        let (processor_weights, parameter_ranges) = match self.personalised {
+            true => self.ratings.learn().personalise(&self.processor_weights, &self.parameter_ranges),
+            false => (self.processor_weights.clone(), self.parameter_ranges.clone()),
+        };
         PermuteFilesParams {
             files: self.files.iter().map(|ai| ai.path.clone()).collect(),
             constrain_length: self.constrain_length,
@@ -277,11 +415,25 @@ impl SharedState {
             permutations: self.permutations,
             processor_count: self.processor_count,
             processor_pool: self.processor_pool.clone(),
+            processor_weights,
+            parameter_ranges,
+            chain_rules: self.chain_rules.clone(),
+            template: self.template.clone(),
+            complexity: self.complexity.clone(),
+            evolution: None,
+            descriptor_targets: Some(self.descriptor_targets.clone()).filter(|t| !t.is_empty()),
+            quality_checks: self.quality_checks.clone(),
+            max_similarity: self.max_similarity,
+            max_attempts: self.max_attempts,
             output_file_as_wav: true,
             update_sender: self.update_sender.clone(),
             create_subdirectories: self.create_subdirectories,
             cancel_receiver: Arc::new(cancel_receiver),
             max_stretch: self.max_stretch,
+            extreme_stretch: self.extreme_stretch,
+            resample_quality: self.resample_quality,
+            oversampling: self.oversampling,
+            bpm: self.bpm,
         }
     }
 
@@ -304,10 +456,16 @@ impl SharedState {
                         permutation_index: i,
                         output: String::new(),
                         processor_pool: vec![],
+                        processor_weights: HashMap::new(),
+                        parameter_ranges: Arc::new(ParameterRanges::new()),
                         processors: vec![],
                         original_sample_rate: 0,
                         node_index: 0,
                         files: vec![],
+                        resample_quality: self.resample_quality,
+                        oversampling: self.oversampling,
+                        bpm: self.bpm.or(audio_info.bpm),
+                        extreme_stretch: self.extreme_stretch,
                     },
                     processors: vec![],
                     audio_info: AudioInfo::default(),
@@ -347,6 +505,79 @@ impl SharedState {
         }
     }
 
+    // A weight of 1 is the same as not weighting the processor at all
+    pub fn set_processor_weight(&mut self, name: String, weight: f64) -> Result<(), String> {
+        self.clear_error();
+        let processor = get_processor_from_display_name(&name)?;
+        if weight == 1.0 {
+            self.processor_weights.remove(&processor);
+        } else {
+            self.processor_weights.insert(processor, weight.max(0.0));
+        }
+        Ok(())
+    }
+
+    // Ranges are JSON keyed by processor display name, the same as the CLI's parameter ranges file
+    pub fn set_parameter_ranges(&mut self, json: String) -> Result<(), String> {
+        self.clear_error();
+        self.parameter_ranges = parse_parameter_ranges(&json)?;
+        Ok(())
+    }
+
+    // Rules are JSON with processors and categories by display name, the same as the CLI's chain rules file
+    pub fn set_chain_rules(&mut self, json: String) -> Result<(), String> {
+        self.clear_error();
+        self.chain_rules = parse_chain_rules(&json)?;
+        Ok(())
+    }
+
+    // Targets are JSON ranges by descriptor, the same as the CLI's targets file
+    pub fn set_descriptor_targets(&mut self, json: String) -> Result<(), String> {
+        self.clear_error();
+        self.descriptor_targets = parse_descriptor_targets(&json)?;
+        Ok(())
+    }
+
+    // Checks are JSON by check name, the same as the CLI's quality checks file. Checks left out keep their defaults
+    pub fn set_quality_checks(&mut self, json: String) -> Result<(), String> {
+        self.clear_error();
+        self.quality_checks = parse_quality_checks(&json)?;
+        Ok(())
+    }
+
+    // Similarity is 0 - 1. None keeps every output however alike
+    pub fn set_max_similarity(&mut self, max_similarity: Option<f64>) {
+        self.max_similarity = max_similarity.map(|s| s.clamp(0.0, 1.0));
+    }
+
+    // None goes back to the default number of attempts per output
+    pub fn set_max_attempts(&mut self, max_attempts: Option<usize>) {
+        self.max_attempts = max_attempts.filter(|a| *a > 0);
+    }
+
+    // Complexity is JSON with a preset and categories by display name, the same as the CLI's complexity
+    // file, e.g. { "preset": "dense" }. None goes back to depth and processor count
+    pub fn set_complexity(&mut self, json: Option<String>) -> Result<(), String> {
+        self.clear_error();
+        self.complexity = json.map(|j| parse_complexity(&j)).transpose()?;
+        Ok(())
+    }
+
+    // Template is display names in order with * for any processor. None goes back to random chains
+    pub fn set_template(&mut self, template: Option<Vec<String>>) -> Result<(), String> {
+        self.clear_error();
+        self.template = match template {
+            Some(names) if !names.is_empty() => Some(
+                names
+                    .iter()
+                    .map(|n| get_template_slot_from_display_name(n))
+                    .collect::<Result<ChainTemplate, String>>()?,
+            ),
+            _ => None,
+        };
+        Ok(())
+    }
+
     pub fn set_output(&mut self, output: String) {
         self.clear_error();
         self.output = output;
@@ -390,6 +621,13 @@ impl SharedState {
         }
     }
 
+    // A candidate that missed the descriptor targets. The next candidate takes its place
+    pub fn remove_output_progress(&mut self, permutation: &Permutation) {
+        if let Some(file_index) = self.files.iter().position(|f| f.path == permutation.file) {
+            self.outputs.remove(&(file_index, permutation.permutation_index));
+        }
+    }
+
     pub fn update_output_progress(&mut self, permutation: Permutation) {
         let percentage = ((permutation.node_index as f64 + 1.0)
             / permutation.processors.len() as f64)
@@ -426,6 +664,30 @@ impl SharedState {
         self.max_stretch = max_stretch;
     }
 
+    // None turns the extreme stretch off
+    pub fn set_extreme_stretch(&mut self, extreme_stretch: Option<f64>) {
+        self.extreme_stretch = extreme_stretch.filter(|s| *s > 1.0);
+    }
+
+    pub fn set_resample_quality(&mut self, quality: String) -> Result<(), String> {
+        self.resample_quality = get_resample_quality_from_name(&quality)?;
+        Ok(())
+    }
+
+    // Random picks oversampling for each distortion
+    pub fn set_oversampling(&mut self, oversampling: String) -> Result<(), String> {
+        self.oversampling = match oversampling.to_lowercase().as_str() {
+            "random" => None,
+            name => Some(get_oversampling_from_name(name)?),
+        };
+        Ok(())
+    }
+
+    // None goes back to detecting the tempo of each file
+    pub fn set_bpm(&mut self, bpm: Option<f64>) {
+        self.bpm = bpm.filter(|b| *b > 0.0);
+    }
+
     pub fn set_input_trail(&mut self, trail: f64) {
         self.input_trail = trail;
     }
@@ -483,9 +745,48 @@ impl SharedState {
         if self.processing {
             return thread::spawn(|| {});
         }
+        let params = self.to_permute_params();
+        self.start_run(params)
+    }
+
+    // Breeds a new generation from an output, mutating it or crossing it with a partner output.
+    // The generation is run on the output's source file
+    pub fn run_evolution(&mut self, output: String, partner: Option<String>, amount: f64) -> Result<JoinHandle<()>, String> {
+        self.clear_error();
+        if self.processing {
+            return Ok(thread::spawn(|| {}));
+        }
+        let find = |path: &String| {
+            self.outputs
+                .values()
+                .find(|o| o.output == *path && !o.deleted)
+                .cloned()
+                .ok_or(format!("Output {} not found", path))
+        };
+        let parent = find(&output)?;
+        let evolution = match partner {
+            Some(partner) => Evolution::Crossover {
+                parents: (parent.permutation.processors.clone(), find(&partner)?.permutation.processors),
+                amount,
+            },
+            None => Evolution::Mutate {
+                parent: parent.permutation.processors.clone(),
+                amount,
+            },
+        };
+        let mut params = self.to_permute_params();
+        params.files = vec![parent.source_file.clone()];
+        params.evolution = Some(evolution);
+        Ok(self.start_run(params))
+    }
+
+    fn start_run(&mut self, params: PermuteFilesParams) -> JoinHandle<()> {
         self.processing = true;
+        // Outputs still around when the next run starts were kept
+        if let Err(e) = self.record_kept_outputs() {
+            self.set_error(format!("Could not save ratings: {}", e));
+        }
         self.outputs.clear();
-        let params = self.to_permute_params();
         let state_clone = Arc::new(std::sync::Mutex::new(self.clone()));
         thread::spawn(move || {
             let handle = permute_files(params);
@@ -513,9 +814,100 @@ impl SharedState {
                 break;
             }
         }
+        self.rate_output(&file, Rating::Deleted)
+    }
+
+    // Records a rating with the output's chain, if ratings are on
+    fn rate_output(&mut self, file: &str, rating: Rating) -> std::io::Result<()> {
+        if !self.record_ratings {
+            return Ok(());
+        }
+        if let Some(output) = self.outputs.values().find(|o| o.output == file) {
+            self.ratings.record(file.to_string(), rating, output.permutation.processors.clone());
+            self.save_ratings()?;
+        }
+        Ok(())
+    }
+
+    fn record_kept_outputs(&mut self) -> std::io::Result<()> {
+        if !self.record_ratings {
+            return Ok(());
+        }
+        let kept: Vec<(String, Vec<PermutationProcessor>)> = self
+            .outputs
+            .values()
+            .filter(|o| !o.deleted && !o.audio_info.path.is_empty())
+            .filter(|o| self.ratings.ratings.iter().all(|r| r.output != o.output))
+            .map(|o| (o.output.clone(), o.permutation.processors.clone()))
+            .collect();
+        if kept.is_empty() {
+            return Ok(());
+        }
+        for (output, chain) in kept {
+            self.ratings.record(output, Rating::Kept, chain);
+        }
+        self.save_ratings()
+    }
+
+    fn save_ratings(&self) -> std::io::Result<()> {
+        if self.ratings_path.is_empty() {
+            return Ok(());
+        }
+        if let Some(dir) = Path::new(&self.ratings_path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        save_ratings(&self.ratings_path, &self.ratings)
+    }
+
+    pub fn star_output(&mut self, file: String) -> Result<(), String> {
+        self.clear_error();
+        if !self.record_ratings {
+            return Err(String::from("Turn on ratings to star outputs"));
+        }
+        self.rate_output(&file, Rating::Starred).map_err(|e| e.to_string())
+    }
+
+    // Ratings are loaded from here and saved back as outputs are rated
+    pub fn set_ratings_path(&mut self, path: String) -> Result<(), String> {
+        self.ratings = load_ratings(&path)?;
+        self.ratings_path = path;
         Ok(())
     }
 
+    pub fn set_record_ratings(&mut self, record_ratings: bool) {
+        self.record_ratings = record_ratings;
+    }
+
+    pub fn set_personalised(&mut self, personalised: bool) {
+        self.personalised = personalised;
+    }
+
+    // Forgets every rating, so personalised runs go back to the user's own weights and ranges
+    pub fn reset_ratings(&mut self) -> std::io::Result<()> {
+        self.clear_error();
+        self.ratings = RatingsStore::default();
+        self.save_ratings()
+    }
+
+    pub fn to_learned_preferences_dto(&self) -> LearnedPreferencesDto {
+        let learned = self.ratings.learn();
+        let mut weights: Vec<(String, f64)> = learned
+            .weights
+            .iter()
+            .map(|(p, w)| (get_processor_display_name(*p).to_string(), *w))
+            .collect();
+        weights.sort_by(|a, b| b.1.total_cmp(&a.1));
+        LearnedPreferencesDto {
+            rated_outputs: self.ratings.ratings.len(),
+            weights,
+            parameter_ranges: learned
+                .parameter_ranges
+                .iter()
+                .map(|(p, ranges)| (get_processor_display_name(*p).to_string(), ranges.clone()))
+                .collect(),
+        }
+    }
+
     pub fn delete_all_output_files(&mut self) -> Result<(), std::io::Error> {
         let mut last_output_path = String::new();
         for output in self.outputs.values_mut() {
@@ -575,9 +967,24 @@ impl SharedState {
             permutations: self.permutations,
             processor_count: self.processor_count,
             processor_pool: self.processor_pool.clone(),
+            processor_weights: self.processor_weights.clone(),
+            parameter_ranges: self.parameter_ranges.clone(),
+            chain_rules: self.chain_rules.clone(),
+            template: self.template.clone(),
+            complexity: self.complexity.clone(),
+            descriptor_targets: self.descriptor_targets.clone(),
+            quality_checks: self.quality_checks.clone(),
+            max_similarity: self.max_similarity,
+            max_attempts: self.max_attempts,
             create_subdirectories: self.create_subdirectories,
             viewed_welcome: self.viewed_welcome,
+            record_ratings: self.record_ratings,
+            personalised: self.personalised,
             max_stretch: self.max_stretch,
+            extreme_stretch: self.extreme_stretch,
+            resample_quality: self.resample_quality,
+            oversampling: self.oversampling,
+            bpm: self.bpm,
         };
         let json = serde_json::to_string(&data)?;
         let mut file = File::create(path)?;
@@ -600,6 +1007,11 @@ impl SharedState {
                 format!("Invalid scene file format: {}", e),
             )
         })?;
+        validate_parameter_ranges(&data.parameter_ranges)
+            .and_then(|_| data.chain_rules.validate())
+            .and_then(|_| data.complexity.as_ref().map_or(Ok(()), |c| c.validate()))
+            .and_then(|_| data.descriptor_targets.validate())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
         self.files = data.files;
         self.high_sample_rate = data.high_sample_rate;
         self.input_trail = data.input_trail;
@@ -611,13 +1023,37 @@ impl SharedState {
         self.permutations = data.permutations;
         self.processor_count = data.processor_count;
         self.processor_pool = data.processor_pool;
+        self.processor_weights = data.processor_weights;
+        self.parameter_ranges = data.parameter_ranges;
+        self.chain_rules = data.chain_rules;
+        self.template = data.template;
+        self.complexity = data.complexity;
+        self.descriptor_targets = data.descriptor_targets;
+        self.quality_checks = data.quality_checks;
+        self.max_similarity = data.max_similarity;
+        self.max_attempts = data.max_attempts;
         self.create_subdirectories = data.create_subdirectories;
         self.viewed_welcome = data.viewed_welcome;
+        self.record_ratings = data.record_ratings;
+        self.personalised = data.personalised;
         self.max_stretch = data.max_stretch;
+        self.extreme_stretch = data.extreme_stretch;
+        self.resample_quality = data.resample_quality;
+        self.oversampling = data.oversampling;
+        self.bpm = data.bpm;
         Ok(())
     }
 }
 
+// None is the setting that picks oversampling at random for each processor
+fn get_oversampling_name(oversampling: Option<Oversampling>) -> String {
+    match oversampling {
+        None => String::from("Random"),
+        Some(Oversampling::None) => String::from("Off"),
+        Some(o) => format!("{}x", o.factor()),
+    }
+}
+
 // ─── Serialisable snapshot for JSON persistence ───────────────────────────────
 
 #[derive(Serialize, Deserialize)]
@@ -636,6 +1072,24 @@ pub struct SharedStateSerializable {
     pub permutation_depth: usize,
     #[serde(default = "default_processor_pool")]
     pub processor_pool: Vec<PermuteNodeName>,
+    #[serde(default)]
+    pub processor_weights: HashMap<PermuteNodeName, f64>,
+    #[serde(default)]
+    pub parameter_ranges: ParameterRanges,
+    #[serde(default)]
+    pub chain_rules: ChainRules,
+    #[serde(default)]
+    pub template: Option<ChainTemplate>,
+    #[serde(default)]
+    pub complexity: Option<ComplexityModel>,
+    #[serde(default)]
+    pub descriptor_targets: DescriptorTargets,
+    #[serde(default)]
+    pub quality_checks: QualityChecks,
+    #[serde(default)]
+    pub max_similarity: Option<f64>,
+    #[serde(default)]
+    pub max_attempts: Option<usize>,
     #[serde(default = "default_normalise_at_end")]
     pub normalise_at_end: bool,
     #[serde(default)]
@@ -648,8 +1102,20 @@ pub struct SharedStateSerializable {
     pub create_subdirectories: bool,
     #[serde(default)]
     pub viewed_welcome: bool,
+    #[serde(default)]
+    pub record_ratings: bool,
+    #[serde(default)]
+    pub personalised: bool,
     #[serde(default = "default_max_stretch")]
     pub max_stretch: f64,
+    #[serde(default)]
+    pub extreme_stretch: Option<f64>,
+    #[serde(default)]
+    pub resample_quality: ResampleQuality,
+    #[serde(default)]
+    pub oversampling: Option<Oversampling>,
+    #[serde(default)]
+    pub bpm: Option<f64>,
 }
 
 fn default_input_trail() -> f64 { 0.0 }
@@ -696,13 +1162,16 @@ pub fn update_state_from_message(s: &mut SharedState, message: &PermuteUpdate) {
         PermuteUpdate::AudioInfoGenerated(file, info) => {
             s.update_output_audioinfo(file.clone(), info.clone());
         }
-        PermuteUpdate::ProcessComplete(_) => {
+        PermuteUpdate::ProcessComplete(_, _) => {
             let _ = s.set_finished();
         }
         PermuteUpdate::Error(err) => {
             let _ = s.set_finished();
             s.set_error(err.clone());
         }
+        PermuteUpdate::CandidateRejected(perm, _) => {
+            s.remove_output_progress(perm);
+        }
         PermuteUpdate::UpdatePermuteNodeStarted(_, _, _) => {}
     }
 }
@@ -711,20 +1180,9 @@ pub fn update_state_from_message(s: &mut SharedState, message: &PermuteUpdate) {
 /// Returns `None` for events that don't need forwarding to the frontend.
 pub fn build_progress_event(message: &PermuteUpdate) -> Option<PermuteProgressEvent> {
     match message {
-        PermuteUpdate::UpdateSetProcessors(perm, procs) => {
-            let processors = procs
-                .iter()
-                .map(|(name, attrs)| ProcessorDto {
-                    name: get_processor_display_name(*name).to_string(),
-                    attributes: attrs
-                        .iter()
-                        .map(|a| ProcessorAttributeDto {
-                            key: a.key.clone(),
-                            value: a.value.clone(),
-                        })
-                        .collect(),
-                })
-                .collect();
+        // The permutation's processors carry the full tree, including anything run in branches
+        PermuteUpdate::UpdateSetProcessors(perm, _) => {
+            let processors = perm.processors.iter().map(ProcessorDto::from).collect();
             Some(PermuteProgressEvent::OutputAdded {
                 path: perm.output.clone(),
                 processors,
@@ -747,9 +1205,16 @@ pub fn build_progress_event(message: &PermuteUpdate) -> Option<PermuteProgressEv
                 name: info.name.clone(),
                 image: info.image.clone(),
                 duration_sec: info.duration_sec,
+                quality_flags: info.quality_flags.clone(),
             })
         }
-        PermuteUpdate::ProcessComplete(_) => Some(PermuteProgressEvent::Finished),
+        PermuteUpdate::CandidateRejected(perm, reasons) => Some(PermuteProgressEvent::OutputRejected {
+            path: perm.output.clone(),
+            reasons: reasons.clone(),
+        }),
+        PermuteUpdate::ProcessComplete(_, diversity) => Some(PermuteProgressEvent::Finished {
+            diversity: *diversity,
+        }),
         PermuteUpdate::Error(err) => Some(PermuteProgressEvent::Error {
             message: err.clone(),
         }),
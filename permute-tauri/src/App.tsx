@@ -29,6 +29,13 @@ export interface IAppState {
   permuteState: IPermuteState;
 }
 
+// How far a mutated generation strays from its parent, 0 - 1
+const MUTATE_AMOUNT = 0.3;
+
+// How different a run's outputs are from each other, null when there was nothing to compare
+const displayDiversity = (diversity?: number | null) =>
+  diversity == null ? '' : ` Diversity ${Math.round(diversity * 100)}%`;
+
 const defaultAppState: IAppState = {
   permuteState: {
     allProcessors: [],
@@ -126,6 +133,49 @@ const Content = () => {
 
   // ─── Processing ─────────────────────────────────────────────────────────────
 
+  // Outputs stream back from the backend as lightweight delta events
+  const onProgressEvent = useCallback((event: PermuteProgressEvent) => {
+    setState(prev => {
+      const outputs = prev.permuteState.permutationOutputs;
+      switch (event.type) {
+        case 'outputAdded':
+          return {
+            permuteState: {
+              ...prev.permuteState,
+              permutationOutputs: [
+                ...outputs,
+                { path: event.path, name: '', progress: 0, processors: event.processors, image: '', durationSec: 0, deleted: false, qualityFlags: [] },
+              ],
+            },
+          };
+        case 'outputProgress': {
+          const idx = outputs.findIndex(o => o.path === event.path);
+          if (idx < 0) return prev;
+          const updated = [...outputs];
+          updated[idx] = { ...updated[idx], progress: event.progress };
+          return { permuteState: { ...prev.permuteState, permutationOutputs: updated } };
+        }
+        case 'outputCompleted': {
+          const idx = outputs.findIndex(o => o.path === event.path);
+          if (idx < 0) return prev;
+          const updated = [...outputs];
+          updated[idx] = { ...updated[idx], name: event.name, image: event.image, durationSec: event.durationSec, qualityFlags: event.qualityFlags, progress: 100 };
+          return { permuteState: { ...prev.permuteState, permutationOutputs: updated } };
+        }
+        // A candidate that missed the descriptor targets or failed a quality check, the next attempt takes its place
+        case 'outputRejected':
+          return {
+            permuteState: {
+              ...prev.permuteState,
+              permutationOutputs: outputs.filter(o => o.path !== event.path),
+            },
+          };
+        default:
+          return prev;
+      }
+    });
+  }, []);
+
   const runProcessor = useCallback(() => {
     const expectedCount = files.length * (permutations ?? 0);
     // Clear outputs immediately — backend will stream them back as OutputAdded events.
@@ -133,49 +183,38 @@ const Content = () => {
       permuteState: { ...prev.permuteState, processing: true, permutationOutputs: [] },
     }));
     bridge.runProcessor(
-      (event: PermuteProgressEvent) => {
-        setState(prev => {
-          const outputs = prev.permuteState.permutationOutputs;
-          switch (event.type) {
-            case 'outputAdded':
-              return {
-                permuteState: {
-                  ...prev.permuteState,
-                  permutationOutputs: [
-                    ...outputs,
-                    { path: event.path, name: '', progress: 0, processors: event.processors, image: '', durationSec: 0, deleted: false },
-                  ],
-                },
-              };
-            case 'outputProgress': {
-              const idx = outputs.findIndex(o => o.path === event.path);
-              if (idx < 0) return prev;
-              const updated = [...outputs];
-              updated[idx] = { ...updated[idx], progress: event.progress };
-              return { permuteState: { ...prev.permuteState, permutationOutputs: updated } };
-            }
-            case 'outputCompleted': {
-              const idx = outputs.findIndex(o => o.path === event.path);
-              if (idx < 0) return prev;
-              const updated = [...outputs];
-              updated[idx] = { ...updated[idx], name: event.name, image: event.image, durationSec: event.durationSec, progress: 100 };
-              return { permuteState: { ...prev.permuteState, permutationOutputs: updated } };
-            }
-            default:
-              return prev;
-          }
-        });
+      onProgressEvent,
+      (success, error, diversity) => {
+        if (!success && error) {
+          toast({ description: error, status: 'error', duration: 5000, isClosable: true });
+        } else {
+          toast({ description: `${expectedCount} files permuted!${displayDiversity(diversity)}`, status: 'success', duration: 5000, isClosable: true });
+        }
+        setState(prev => ({ permuteState: { ...prev.permuteState, processing: false } }));
       },
-      (success, error) => {
+    );
+  }, [toast, files, permutations, onProgressEvent]);
+
+  // A new generation replaces the outputs, bred from the chosen output's chain
+  const mutateOutputFile = useCallback((file: string) => {
+    setState(prev => ({
+      permuteState: { ...prev.permuteState, processing: true, permutationOutputs: [] },
+    }));
+    bridge.evolveOutput(
+      file,
+      null,
+      MUTATE_AMOUNT,
+      onProgressEvent,
+      (success, error, diversity) => {
         if (!success && error) {
           toast({ description: error, status: 'error', duration: 5000, isClosable: true });
         } else {
-          toast({ description: `${expectedCount} files permuted!`, status: 'success', duration: 5000, isClosable: true });
+          toast({ description: `${permutations ?? 0} files bred!${displayDiversity(diversity)}`, status: 'success', duration: 5000, isClosable: true });
         }
         setState(prev => ({ permuteState: { ...prev.permuteState, processing: false } }));
       },
     );
-  }, [toast, files, permutations]);
+  }, [toast, permutations, onProgressEvent]);
 
   const reverseFile = useCallback((file: string) => {
     setState(prev => ({ permuteState: { ...prev.permuteState, processing: true } }));
@@ -292,6 +331,17 @@ const Content = () => {
     setState({ permuteState });
   }, []);
 
+  const starOutputFile = useCallback(async (file: string) => {
+    try {
+      await bridge.starOutput(file);
+      toast({ description: 'Starred', status: 'success', duration: 2000, isClosable: true });
+    } catch (e) {
+      toast({ description: String(e), status: 'error', duration: 5000, isClosable: true });
+    }
+    const permuteState = await bridge.getState();
+    setState({ permuteState });
+  }, [toast]);
+
   const deleteAllOutputFiles = useCallback(async () => {
     await bridge.deleteAllOutputFiles();
     const permuteState = await bridge.getState();
@@ -357,6 +407,8 @@ const Content = () => {
         permutationOutputs={permutationOutputs}
         reverseFile={reverseFile}
         trimFile={trimFile}
+        mutateOutputFile={mutateOutputFile}
+        starOutputFile={starOutputFile}
         deleteOutputFile={deleteOutputFile}
         deleteAllOutputFiles={deleteAllOutputFiles}
         processing={processing}
@@ -28,6 +28,7 @@ const defaultFile: IPermutationInput = {
   name: '',
   image: '',
   durationSec: 0,
+  bpm: null,
 };
 
 export const AudioContext = React.createContext<IAudioContext>({} as any);
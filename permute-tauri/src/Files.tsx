@@ -156,7 +156,7 @@ const FileBox = memo(
             fontSize="sm"
             lineHeight={1}
           >
-            {displayTime(file.durationSec)}
+            {file.bpm ? `${Math.round(file.bpm)} bpm · ` : ''}{displayTime(file.durationSec)}
           </Text>
         </Box>
       </Box>
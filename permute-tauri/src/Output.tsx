@@ -11,8 +11,8 @@ import {
   useColorMode,
   Link,
 } from "@chakra-ui/react";
-import { ViewIcon, DeleteIcon } from "@chakra-ui/icons";
-import type { IPermutationOutput } from "./types";
+import { ViewIcon, DeleteIcon, RepeatIcon, StarIcon, WarningIcon } from "@chakra-ui/icons";
+import type { IPermutationOutput, QualityFlag } from "./types";
 import { useContext, useCallback, memo, useMemo } from "react";
 import { PlayIcon } from "./icons/PlayIcon";
 import { AudioContext } from "./AudioContext";
@@ -32,6 +32,8 @@ export interface IOutputProps {
   showFile: (file: string) => void;
   reverseFile: (file: string) => void;
   trimFile: (file: string) => void;
+  mutateOutputFile: (file: string) => void;
+  starOutputFile: (file: string) => void;
   deleteOutputFile: (file: string) => void;
   deleteAllOutputFiles: () => void;
   processing: boolean;
@@ -47,9 +49,20 @@ type OutputFileProps = {
   onShow: (path: string) => void;
   onReverse: (path: string) => void;
   onTrim: (path: string) => void;
+  onMutate: (path: string) => void;
+  onStar: (path: string) => void;
   onPlay: (file: IPermutationOutput) => void;
 };
 
+const QUALITY_FLAG_LABELS: Record<QualityFlag, string> = {
+  Silent: "Silent",
+  NonFinite: "NaN or infinite samples",
+  DcOffset: "DC offset",
+  Clipping: "Clipping",
+  TooShort: "Too short",
+  TooLong: "Too long",
+};
+
 // Custom comparator: getState() returns fresh objects every call, so default
 // reference equality would re-render all output files when any one changes.
 // We compare the fields that actually affect rendering.
@@ -59,14 +72,17 @@ const outputFileEqual = (prev: OutputFileProps, next: OutputFileProps) =>
   prev.file.deleted === next.file.deleted &&
   prev.file.image === next.file.image &&
   prev.file.durationSec === next.file.durationSec &&
+  prev.file.qualityFlags.join() === next.file.qualityFlags.join() &&
   prev.onDelete === next.onDelete &&
   prev.onShow === next.onShow &&
   prev.onReverse === next.onReverse &&
   prev.onTrim === next.onTrim &&
+  prev.onMutate === next.onMutate &&
+  prev.onStar === next.onStar &&
   prev.onPlay === next.onPlay;
 
 const OutputFile = memo(
-  ({ file, onDelete, onShow, onReverse, onTrim, onPlay }: OutputFileProps) => {
+  ({ file, onDelete, onShow, onReverse, onTrim, onMutate, onStar, onPlay }: OutputFileProps) => {
     const { colorMode } = useColorMode();
     const props: PropsOf<typeof Box> = {
       borderBottom: "1px solid",
@@ -98,6 +114,14 @@ const OutputFile = memo(
               {file.name}
             </Heading>
           </Tooltip>
+          {file.qualityFlags.length > 0 && (
+            <Tooltip
+              openDelay={OUTPUT_TOOLTIP_DELAY}
+              label={file.qualityFlags.map((f) => QUALITY_FLAG_LABELS[f]).join(", ")}
+            >
+              <WarningIcon color="orange.400" boxSize="12px" marginRight={1} />
+            </Tooltip>
+          )}
           <Tooltip openDelay={OUTPUT_TOOLTIP_DELAY} label="Delete file">
             <IconButton
               aria-label="delete"
@@ -181,6 +205,32 @@ const OutputFile = memo(
               _hover={{ bg: "brand.50" }}
             />
           </Tooltip>
+          <Tooltip openDelay={OUTPUT_TOOLTIP_DELAY} label="Mutate into a new generation">
+            <IconButton
+              aria-label="show"
+              variant="ghost"
+              rounded="full"
+              alignSelf="center"
+              size="xs"
+              icon={<RepeatIcon />}
+              onClick={() => onMutate(file.path)}
+              color="brand.5600"
+              _hover={{ bg: "brand.50" }}
+            />
+          </Tooltip>
+          <Tooltip openDelay={OUTPUT_TOOLTIP_DELAY} label="Star to rate higher than kept">
+            <IconButton
+              aria-label="star"
+              variant="ghost"
+              rounded="full"
+              alignSelf="center"
+              size="xs"
+              icon={<StarIcon />}
+              onClick={() => onStar(file.path)}
+              color="brand.5600"
+              _hover={{ bg: "brand.50" }}
+            />
+          </Tooltip>
           <ProcessorSummary
             processors={file.processors}
             tooltipDelay={OUTPUT_TOOLTIP_DELAY}
@@ -209,6 +259,8 @@ export const Output = memo(
     reverseFile,
     setOutput,
     trimFile,
+    mutateOutputFile,
+    starOutputFile,
     permutationOutputs,
     deleteOutputFile,
     deleteAllOutputFiles,
@@ -244,6 +296,20 @@ export const Output = memo(
       [trimFile],
     );
 
+    const handleMutate = useCallback(
+      (path: string) => {
+        mutateOutputFile(path);
+      },
+      [mutateOutputFile],
+    );
+
+    const handleStar = useCallback(
+      (path: string) => {
+        starOutputFile(path);
+      },
+      [starOutputFile],
+    );
+
     const handlePlay = useCallback(
       (file: IPermutationOutput) => {
         playFile(file);
@@ -267,6 +333,8 @@ export const Output = memo(
         onShow={handleShow}
         onReverse={handleReverse}
         onTrim={handleTrim}
+        onMutate={handleMutate}
+        onStar={handleStar}
         onPlay={handlePlay}
       />
     ));
@@ -20,6 +20,31 @@ import type { IProcessor } from './types';
 
 const PROCESSOR_SUMMARY_TOOLTIP_DELAY = 2000;
 
+// Processors run inside a parallel or multiband split, indented under their branch
+const BranchSummary = ({ branches }: { branches: IProcessor[][] }) => (
+  <>
+    {branches.map((branch, b) => (
+      <Box key={`branch${b}`} pl={2} py={1} borderBottom="1px" borderColor="brand.150">
+        <Box color="brand.5600" fontWeight="medium">
+          Branch {b + 1}
+        </Box>
+        {branch.length === 0 ? (
+          <Box pl={2} color="brand.5600">
+            Dry
+          </Box>
+        ) : (
+          branch.map((p, i) => (
+            <Box key={`${p.name}${i}`} pl={2} color="brand.5600">
+              {i + 1}: {p.name}
+              {p.branches.length > 0 && <BranchSummary branches={p.branches} />}
+            </Box>
+          ))
+        )}
+      </Box>
+    ))}
+  </>
+);
+
 interface ProcessorSummaryProps {
   processors: IProcessor[];
   tooltipDelay?: number;
@@ -94,6 +119,7 @@ export const ProcessorSummary = ({ processors, tooltipDelay = PROCESSOR_SUMMARY_
                               </Box>
                             </Box>
                           ))}
+                          {p.branches.length > 0 && <BranchSummary branches={p.branches} />}
                         </Box>
                       </AccordionPanel>
                     </AccordionItem>
@@ -6,15 +6,16 @@
  */
 import { invoke, Channel } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import type { IPermuteState, GetStateCallback, IProcessor } from './types';
+import type { IPermuteState, GetStateCallback, ILearnedPreferences, IProcessor, Oversampling, QualityFlag, ResampleQuality } from './types';
 
 // ─── Channel event types ──────────────────────────────────────────────────────
 
 export type PermuteProgressEvent =
   | { type: 'outputAdded'; path: string; processors: IProcessor[] }
   | { type: 'outputProgress'; path: string; progress: number }
-  | { type: 'outputCompleted'; path: string; name: string; image: string; durationSec: number }
-  | { type: 'finished' }
+  | { type: 'outputCompleted'; path: string; name: string; image: string; durationSec: number; qualityFlags: QualityFlag[] }
+  | { type: 'outputRejected'; path: string; reasons: string[] }
+  | { type: 'finished'; diversity: number | null }
   | { type: 'error'; message: string };
 
 // ─── State ───────────────────────────────────────────────────────────────────
@@ -30,19 +31,44 @@ export const getState = (): Promise<IPermuteState> =>
  */
 export function runProcessor(
   onProgress: (event: PermuteProgressEvent) => void,
-  onFinished: (success: boolean, error?: string) => void,
+  onFinished: (success: boolean, error?: string, diversity?: number | null) => void,
 ): void {
+  const channel = progressChannel(onProgress, onFinished);
+  invoke('run_processor', { onEvent: channel }).catch(console.error);
+}
+
+/**
+ * Breeds a new generation from an output. With a partner the two outputs'
+ * chains are crossed, otherwise the output is mutated. amount is 0 - 1.
+ */
+export function evolveOutput(
+  output: string,
+  partner: string | null,
+  amount: number,
+  onProgress: (event: PermuteProgressEvent) => void,
+  onFinished: (success: boolean, error?: string, diversity?: number | null) => void,
+): void {
+  const channel = progressChannel(onProgress, onFinished);
+  invoke('evolve_output', { onEvent: channel, output, partner, amount }).catch((e) =>
+    onFinished(false, String(e)),
+  );
+}
+
+function progressChannel(
+  onProgress: (event: PermuteProgressEvent) => void,
+  onFinished: (success: boolean, error?: string, diversity?: number | null) => void,
+): Channel<PermuteProgressEvent> {
   const channel = new Channel<PermuteProgressEvent>();
   channel.onmessage = (event) => {
     if (event.type === 'finished') {
-      onFinished(true);
+      onFinished(true, undefined, event.diversity);
     } else if (event.type === 'error') {
       onFinished(false, event.message);
     } else {
       onProgress(event);
     }
   };
-  invoke('run_processor', { onEvent: channel }).catch(console.error);
+  return channel;
 }
 
 let activeReverseUnlisten: (() => void) | undefined;
@@ -101,6 +127,21 @@ export const deleteOutputFile = (file: string): Promise<void> =>
 export const deleteAllOutputFiles = (): Promise<void> =>
   invoke('delete_all_output_files');
 
+export const starOutput = (file: string): Promise<void> =>
+  invoke('star_output', { file });
+
+export const setRecordRatings = (recordRatings: boolean): Promise<void> =>
+  invoke('set_record_ratings', { recordRatings });
+
+export const setPersonalised = (personalised: boolean): Promise<void> =>
+  invoke('set_personalised', { personalised });
+
+export const resetRatings = (): Promise<void> =>
+  invoke('reset_ratings');
+
+export const getLearnedPreferences = (): Promise<ILearnedPreferences> =>
+  invoke('get_learned_preferences');
+
 export const showFile = (file: string): Promise<void> =>
   invoke('show_in_folder', { file });
 
@@ -112,6 +153,22 @@ export const addProcessor = (name: string): Promise<void> =>
 export const removeProcessor = (name: string): Promise<void> =>
   invoke('remove_processor', { name });
 
+export const setProcessorWeight = (name: string, weight: number): Promise<void> =>
+  invoke('set_processor_weight', { name, weight });
+
+export const setParameterRanges = (json: string): Promise<void> =>
+  invoke('set_parameter_ranges', { json });
+
+export const setChainRules = (json: string): Promise<void> =>
+  invoke('set_chain_rules', { json });
+
+export const setTemplate = (template: string[] | null): Promise<void> =>
+  invoke('set_template', { template });
+
+// json is a preset and overrides, e.g. '{"preset": "dense"}'. null goes back to depth and processor count
+export const setComplexity = (json: string | null): Promise<void> =>
+  invoke('set_complexity', { json });
+
 export const selectAllProcessors = (): Promise<void> =>
   invoke('select_all_processors');
 
@@ -144,6 +201,30 @@ export const setOutputTrail = (trail: number): Promise<void> =>
 export const setMaxStretch = (maxStretch: number): Promise<void> =>
   invoke('set_max_stretch', { maxStretch });
 
+export const setExtremeStretch = (extremeStretch: number | null): Promise<void> =>
+  invoke('set_extreme_stretch', { extremeStretch });
+
+export const setDescriptorTargets = (json: string): Promise<void> =>
+  invoke('set_descriptor_targets', { json });
+
+export const setQualityChecks = (json: string): Promise<void> =>
+  invoke('set_quality_checks', { json });
+
+export const setMaxSimilarity = (maxSimilarity: number | null): Promise<void> =>
+  invoke('set_max_similarity', { maxSimilarity });
+
+export const setMaxAttempts = (maxAttempts: number | null): Promise<void> =>
+  invoke('set_max_attempts', { maxAttempts });
+
+export const setResampleQuality = (quality: ResampleQuality): Promise<void> =>
+  invoke('set_resample_quality', { quality });
+
+export const setOversampling = (oversampling: Oversampling): Promise<void> =>
+  invoke('set_oversampling', { oversampling });
+
+export const setBpm = (bpm: number | null): Promise<void> =>
+  invoke('set_bpm', { bpm });
+
 export const setCreateSubdirectories = (create: boolean): Promise<void> =>
   invoke('set_create_subdirectories', { create });
 
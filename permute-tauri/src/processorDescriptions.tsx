@@ -1,9 +1,12 @@
 export const processorCategories = {
-  'Time/Pitch': ['Half Speed', 'Double Speed', 'Random Pitch', 'Granular Stretch', 'Blur Stretch', 'Reverse'],
-  'Modulation': ['Wow', 'Flutter', 'Chorus', 'Flange', 'Phaser', 'Tremolo', 'Lazer'],
-  'Filter/Drive': ['Fuzz', 'Saturate', 'LFO Filter', 'Line Filter', 'Filter'],
-  'Delay/Reverb': ['Metallic Delay', 'Rhythmic Delay', 'Reverb'],
-  'Cross Processing': ['Cross Gain', 'Cross Filter', 'Cross Distort', 'Cross Mix', 'Cross Grain']
+  'Time/Pitch': ['Half Speed', 'Double Speed', 'Random Pitch', 'Granular Stretch', 'Blur Stretch', 'Reverse', 'Glitch', 'Granular Cloud', 'Envelope Pitch', 'Tape Stop', 'Tape Start', 'Spin Back', 'Varispeed', 'Extreme Stretch'],
+  'Modulation': ['Wow', 'Flutter', 'Chorus', 'Flange', 'Phaser', 'Tremolo', 'Lazer', 'Frequency Shifter', 'Ring Modulator'],
+  'Filter/Drive': ['Fuzz', 'Saturate', 'LFO Filter', 'Line Filter', 'Filter', 'Resonator', 'Auto Wah', 'Envelope Drive'],
+  'Delay/Reverb': ['Metallic Delay', 'Rhythmic Delay', 'Reverb', 'Envelope Delay'],
+  'Stereo': ['Stereo Decorrelate', 'Auto Pan', 'Stereo Width', 'Haas'],
+  'Dynamics': ['Compressor', 'Limiter', 'Gate/Expander', 'Transient Shaper'],
+  'Cross Processing': ['Cross Gain', 'Cross Filter', 'Cross Distort', 'Cross Mix', 'Cross Grain'],
+  'Routing': ['Multiband', 'Parallel']
 };
 
 export const processorDescriptions: Record<string, React.ReactNode> = {
@@ -16,13 +19,21 @@ export const processorDescriptions: Record<string, React.ReactNode> = {
   'Blur Stretch': `A time stretch that uses a blurring algorithm to stretch the audio. Be careful with high depths, it can dramatically increase the length of the audio`,
   'Metallic Delay': `A delay with low duration (less than 100ms) and high feedback to create metallic sounds.`,
   'Rhythmic Delay': `A delay between 100ms and 1 second.`,
+  'Envelope Delay': `A delay whose time follows the loudness of the audio. As the delay time moves the repeats bend in pitch, like a tape delay being pushed around`,
   'Filter': `Random Lo-pass, hi-pass, band-pass filters.`,
-  Reverb: `A very 80s sounding reverb. Reverb length and mix are randomised`,
+  Reverb: `Picks from several reverbs: the original very 80s reverb, a hall, a plate, an octave-up shimmer or a reverse reverb that swells into the sound.
+  Decay, size and mix are randomised. The audio is extended so the tail rings out`,
   'Half-Speed': `Converts the audio to half speed, lowering the pitch by an octave. The duration of the audio will change. There are no randomised parameters`,
   'Double Speed':
     'Converts the audio to double speed, increasing the pitch by an octave. The duration of the audio will change. There are no randomised parameters',
   'Random Pitch':
     'Shifts the pitch of the audio by a random interval. The duration of the audio will change',
+  'Tape Stop': `The audio slows to a halt as it ends, like a tape machine or turntable losing power. Stop time and how suddenly it drops away are randomised`,
+  'Tape Start': `The audio runs up to speed from a standstill, like a turntable being switched on`,
+  'Spin Back': `Plays forwards then whips backwards at speed and slows to a stop, like a DJ spinning a record back. The rest of the audio is cut`,
+  Varispeed: `Smoothly ramps the tape speed up and down through the audio, bending pitch and timing together. The duration of the audio will change`,
+  'Extreme Stretch': `Stretches the audio 50 to 1000 times into a slowly shifting drone. Only used when an extreme stretch limit is set, and will make very long files`,
+  'Envelope Pitch': `Bends the pitch up or down as the audio gets louder, from slight wobbles on each note to octave swoops. The duration of the audio does not change`,
   Wow: 'A low speed vibrato, high depth effect similar to a warped record. Depth, speed and mix levels are randomised',
   Flutter: `A high speed vibrato effect, giving a warbley or fluttery sound.
   Can sound similar to tremolo. Depth, speed and mix levels are randomised`,
@@ -33,8 +44,22 @@ export const processorDescriptions: Record<string, React.ReactNode> = {
   Tremolo: `Low-ish frequency amplitude modulation. Weirdly good for percussive sounds`,
   Lazer: `Amplitude modulation where the frequency is determined by strength of signal. 
   Good for robot sounds and adding high frequency elements to a sound`,
+  'Frequency Shifter': `Moves every frequency up or down by the same amount. Unlike pitch shifting this breaks the harmonics apart,
+  from slow phasing with small shifts to metallic, bell-like tones. Optional feedback shifts the sound again and again`,
+  'Ring Modulator': `Classic ring modulation, multiplying the audio by a carrier oscillator or another audio file.
+  Carrier frequency may glide over the length of the audio`,
   'LFO Filter': `A low-frequency oscillator that controls the cutoff frequency of a filter.`,
   'Line Filter': `Filter frequency increases (or decreases) over the length of the audio.`,
+  'Auto Wah': `A filter whose cutoff follows the loudness of the audio, opening up on loud notes and closing as they fade.
+  Sometimes inverted so the filter closes on loud notes. Filter range, Q and the follower's attack and release are randomised`,
+  Multiband: `Splits the audio into 2–4 frequency bands with Linkwitz-Riley crossovers and runs a different random chain of effects on each band before summing them back together.
+  A band is sometimes left dry, e.g. keeping the lows clean under a mangled top end. The effects used on each band are listed in its attributes`,
+  Parallel: `Splits the audio in two and runs a different random chain of effects on each side, then mixes them back together.
+  One side is sometimes left clean, as in parallel compression. Separately, any processor may be blended with its unprocessed input, shown by a Dry/Wet attribute`,
+  'Envelope Drive': `Distortion that gets heavier as the audio gets louder, so attacks break up while tails stay clean. 
+  Sometimes inverted to distort the tails instead`,
+  Resonator: `A bank of tuned resonators set to a random chord or scale. Comb mode rings like plucked strings, 
+  bandpass mode gives purer tones. Great for turning noisy or percussive sounds into pitched, metallic ones`,
   'Cross Gain': `Modulates the gain of the audio based on the RMS energy of another audio file. 
     Modulating Audio file is stretched to the length of the original audio.
     Cross effects run quite slow. Be careful with high depth values or large numbers of files`,
@@ -49,6 +74,23 @@ export const processorDescriptions: Record<string, React.ReactNode> = {
   The sidechain file cycles from the beginning if shorter than the current file, so each run produces a different interleaving.
   Output length is approximately double the current audio length. Crossfades are applied at each grain boundary to avoid clicks.
   Cross effects run quite slow. Be careful with high depth values or large numbers of files`,
+  Compressor: `A feed-forward compressor. Threshold, ratio, knee, attack and release are randomised.
+  Sometimes runs as an upward compressor, lifting quieter parts towards the threshold and bringing up room sound and tails.
+  With more than one input file it can occasionally key off another file, ducking the audio in time with it`,
+  Limiter: `A brickwall lookahead limiter. The audio is driven into a ceiling, flattening peaks.
+  Drive, ceiling and release are randomised`,
+  'Gate/Expander': `Turns down audio below a threshold. As a gate it cuts quiet parts and tails off abruptly, as an expander it gently increases the dynamic range.
+  Threshold, ratio, hold and release are randomised`,
+  'Transient Shaper': `Boosts or softens the attack of sounds, and brings out or tightens their tails, independently of level`,
+  'Stereo Decorrelate': `Runs the left and right channels through different all-pass chains so they stop being identical. Turns mono audio into a wide stereo image. Mono inputs become stereo`,
+  'Auto Pan': `Moves the audio between the left and right speakers with a sine or triangle LFO. Rate and depth are randomised. Mono inputs become stereo`,
+  'Stereo Width': `Mid/side widening or narrowing of the stereo image. Mono audio is decorrelated first so there is something to widen. Mono inputs become stereo`,
+  Haas: `Delays one side by 1–35ms. The ear fuses the two channels and the sound is placed towards the earlier side. Mono inputs become stereo`,
+  Glitch: `Cuts the audio into slices, either at detected onsets or on a grid snapped to zero crossings, then repeats, stutters, reverses, drops, re-pitches or swaps individual slices.
+  The slice map shows what happened to each slice in order: "3" plays slice 3, "3x2" repeats it, "3s4" stutters its start 4 times, "3r" reverses it, "-" drops it and "3p+7" shifts it by 7 semitones`,
+  'Granular Cloud': `Scatters many short, enveloped grains of sound with randomised timing, size, position, pitch and pan.
+  Ranges from a subtle texture under the original to a dense cloud, octave shimmer or a frozen moment of the audio. The read position can follow the audio, freeze or scan through it.
+  With more than one input file the grains are sometimes taken from another file`,
   'Cross Mix': `Sums the audio with another input file at a random offset and mix ratio.
   The output length extends to fit both files in full. The offset is randomised each run, so even with the same two files the result will differ.
   Cross effects run quite slow. Be careful with high depth values or large numbers of files`,
@@ -12,20 +12,122 @@ export interface IPermuteState {
   permutationDepth: number;
   processorCount: number;
   processorPool: string[];
+  // Relative chance of each processor being picked, by display name. Missing processors are 1, 0 excludes them
+  processorWeights: Record<string, number>;
+  // User ranges for processor parameters, by processor display name then parameter
+  parameterRanges: Record<string, Record<string, IParameterRange>>;
+  // Required, forbidden and repeated processors for every chain, by display name
+  chainRules: IChainRules;
+  // Fixed chain by display name, with * for any processor. null picks random chains
+  template: string[] | null;
+  // Chain lengths, category budgets and intensity. null uses depth and processor count
+  complexity: IComplexity | null;
+  complexityPresets: string[];
+  // Ranges outputs have to match, by descriptor. Candidates that miss are rendered again
+  descriptorTargets: IDescriptorTargets;
+  // Silence, NaN, DC offset, clipping and duration checks that reject or flag each output
+  qualityChecks: IQualityChecks;
+  // Outputs more similar than this, 0 - 1, to an earlier output of the same file are rendered again. null keeps them
+  maxSimilarity: number | null;
+  // Most candidates to render per file. null is 10 per output
+  maxAttempts: number | null;
   allProcessors: string[];
   normaliseAtEnd: boolean;
   trimAll: boolean;
   createSubdirectories: boolean;
   permutationOutputs: IPermutationOutput[];
   viewedWelcome: boolean;
+  // Opt-in. Kept, deleted and starred outputs are recorded with their chains
+  recordRatings: boolean;
+  // Shift processor weights and parameter ranges towards what has been kept
+  personalised: boolean;
+  ratedOutputs: number;
   maxStretch: number;
+  // Limit for the extreme stretch. null leaves it out
+  extremeStretch: number | null;
+  resampleQuality: ResampleQuality;
+  // Oversampling for distortion processors. Random picks one for each processor
+  oversampling: Oversampling;
+  // User set tempo. null detects the tempo of each file
+  bpm: number | null;
 }
 
+export interface IParameterRange {
+  min: number;
+  max: number;
+}
+
+export interface IChainRules {
+  required: string[];
+  forbidden_pairs: [string, string][];
+  max_occurrences: Record<string, number>;
+  max_category_occurrences: Record<string, number>;
+  category_quotas: Record<string, number>;
+  first: string | null;
+  last: string | null;
+}
+
+export interface ILearnedPreferences {
+  ratedOutputs: number;
+  // Processor display name and learned weight, strongest first
+  weights: [string, number][];
+  // Ranges around kept values, by processor display name then parameter
+  parameterRanges: Record<string, Record<string, IParameterRange>>;
+}
+
+export interface IComplexity {
+  // The preset this matches, if any
+  preset: string | null;
+  // Relative chance of each chain length
+  node_counts: Record<string, number> | null;
+  // Most processors from each category, by category display name
+  category_budgets: Record<string, number> | null;
+  // 0 - 1, how far parameter ranges open up with each processor down the chain
+  intensity: number | null;
+}
+
+export interface IDescriptorRange {
+  min: number | null;
+  max: number | null;
+}
+
+export interface IDescriptorTargets {
+  duration_sec: IDescriptorRange | null;
+  rms_db: IDescriptorRange | null;
+  peak_db: IDescriptorRange | null;
+  crest_db: IDescriptorRange | null;
+  centroid_hz: IDescriptorRange | null;
+  silence_ratio: IDescriptorRange | null;
+}
+
+export type QualityAction = 'Off' | 'Flag' | 'Reject';
+
+export type QualityFlag = 'Silent' | 'NonFinite' | 'DcOffset' | 'Clipping' | 'TooShort' | 'TooLong';
+
+export interface IQualityCheck {
+  action: QualityAction;
+  threshold: number;
+}
+
+export interface IQualityChecks {
+  silence: IQualityCheck;
+  non_finite: IQualityCheck;
+  dc_offset: IQualityCheck;
+  clipping: IQualityCheck;
+  min_duration_sec: IQualityCheck;
+  max_duration_sec: IQualityCheck;
+}
+
+export type ResampleQuality = 'Low' | 'Medium' | 'High';
+
+export type Oversampling = 'Random' | 'Off' | '2x' | '4x' | '8x';
+
 export interface IPermutationInput {
   path: string;
   name: string;
   durationSec: number;
   image: string;
+  bpm: number | null;
 }
 
 export interface IProcessorAttribute {
@@ -36,6 +138,8 @@ export interface IProcessorAttribute {
 export interface IProcessor {
   name: string;
   attributes: IProcessorAttribute[];
+  // Sub-chains run inside this processor, e.g. the two sides of a parallel split
+  branches: IProcessor[][];
 }
 
 export interface IPermutationOutput {
@@ -46,6 +150,8 @@ export interface IPermutationOutput {
   name: string;
   durationSec: number;
   deleted: boolean;
+  // Quality checks the output failed but was kept anyway
+  qualityFlags: QualityFlag[];
 }
 
 export type GetStateCallback = (state: IPermuteState) => void;